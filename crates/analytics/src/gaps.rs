@@ -0,0 +1,65 @@
+use chrono::DateTime;
+use sqlx::SqlitePool;
+
+/// A run of missing candles: `after` and `before` are the start times of
+/// the stored candles on either side of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gap {
+    pub symbol: String,
+    pub after: i64,
+    pub before: i64,
+    pub missing: i64,
+}
+
+impl std::fmt::Display for Gap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = |ms| {
+            DateTime::from_timestamp_millis(ms).map_or_else(
+                || ms.to_string(),
+                |t| t.format("%Y-%m-%d %H:%M:%S").to_string(),
+            )
+        };
+        write!(
+            f,
+            "{}: {} missing between {} and {}",
+            self.symbol,
+            self.missing,
+            time(self.after),
+            time(self.before)
+        )
+    }
+}
+
+/// Every gap in the `interval` candles of each symbol, `step_ms` being the
+/// interval's length. Gaps before a symbol's first candle or after its last
+/// can't be seen.
+pub async fn kline_gaps(
+    pool: &SqlitePool,
+    interval: &str,
+    step_ms: i64,
+) -> Result<Vec<Gap>, sqlx::Error> {
+    let rows: Vec<(String, i64, i64)> = sqlx::query_as(
+        "SELECT s.ticker, k.previous, k.start_time FROM (
+             SELECT symbol_id, start_time, LAG(start_time) OVER (
+                 PARTITION BY symbol_id ORDER BY start_time
+             ) AS previous
+             FROM klines_all WHERE interval = ?1
+         ) AS k
+         JOIN symbols s ON s.id = k.symbol_id
+         WHERE k.start_time - k.previous > ?2
+         ORDER BY s.ticker, k.previous",
+    )
+    .bind(interval)
+    .bind(step_ms)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(symbol, after, before)| Gap {
+            symbol,
+            after,
+            before,
+            missing: (before - after) / step_ms - 1,
+        })
+        .collect())
+}
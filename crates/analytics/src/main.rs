@@ -0,0 +1,393 @@
+//! Read-only analysis of recorded weekly files:
+//!
+//! ```text
+//! analytics stats    (--period YYYY_WW | --db FILE)
+//! analytics gaps     (--period YYYY_WW | --db FILE) [--interval 1m]
+//! analytics export   (--period YYYY_WW | --db FILE) --table TABLE [--out FILE]
+//! analytics export   (--period YYYY_WW | --db FILE) --table agg_trades --symbol SYMBOL [--start S] [--end S] [--out FILE]
+//! analytics resample (--period YYYY_WW | --db FILE) --symbol SYMBOL --to 15m [--from 1m] [--out FILE]
+//! ```
+//!
+//! `--period` looks the file up under `WORKDIR`, like `bot export`. Files are
+//! opened with `storage::db::open_reader`, so running this against the file
+//! the recorder is writing can't take its write lock.
+
+mod gaps;
+mod resample;
+
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+use anyhow::Context;
+use common::logger;
+use common::paths::Paths;
+use common::symbols::{DEFAULT_SYMBOL_ALIASES, SymbolAliases};
+use dotenvy::dotenv;
+use sqlx::SqlitePool;
+use storage::data_manager::TABLES;
+use storage::db::{self, ReaderOptions};
+use storage::maintenance::interval_ms;
+use tracing::info;
+
+const USAGE: &str = "usage: analytics (stats | gaps | export | resample) \
+    (--period YYYY_WW | --db FILE) [--interval I] [--table T] [--symbol S] \
+    [--from I] [--to I] [--start S] [--end S] [--out FILE]";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    logger::setup_logger();
+    dotenv().ok();
+
+    let mut args = env::args().skip(1);
+    let command = args.next().context(USAGE)?;
+    let options = options(args.collect())?;
+    let (db_path, label) = db_path(&options)?;
+    anyhow::ensure!(Path::new(&db_path).exists(), "No database at {}", db_path);
+    let reader_options =
+        ReaderOptions::from_env().map_err(|e| anyhow::anyhow!("Invalid reader options: {}", e))?;
+    let pool = db::open_reader(&db_path, &reader_options)
+        .await
+        .with_context(|| format!("Failed to open {}", db_path))?;
+
+    match command.as_str() {
+        "stats" => stats(&pool, &db_path).await,
+        "gaps" => gaps(&pool, &options).await,
+        "export" => export(&pool, &options, &label).await,
+        "resample" => resample(&pool, &options, &label).await,
+        other => anyhow::bail!("Unknown command '{}'\n{}", other, USAGE),
+    }
+}
+
+/// `--flag value` pairs, without the dashes.
+fn options(args: Vec<String>) -> anyhow::Result<HashMap<String, String>> {
+    let mut options = HashMap::new();
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let name = flag
+            .strip_prefix("--")
+            .with_context(|| format!("Unexpected argument '{}'\n{}", flag, USAGE))?;
+        let value = args
+            .next()
+            .with_context(|| format!("{} needs a value\n{}", flag, USAGE))?;
+        options.insert(name.to_string(), value);
+    }
+    Ok(options)
+}
+
+/// The file `--db` names, or the weekly file of `--period` under `WORKDIR`
+/// and `DATA_DIR`, with a label for default output names.
+fn db_path(options: &HashMap<String, String>) -> anyhow::Result<(String, String)> {
+    if let Some(path) = options.get("db") {
+        let label = Path::new(path)
+            .file_stem()
+            .map_or_else(|| "db".to_string(), |s| s.to_string_lossy().into_owned());
+        return Ok((path.clone(), label));
+    }
+    let period = options.get("period").context(USAGE)?;
+    let paths = Paths::from_env().map_err(|e| anyhow::anyhow!("--period needs WORKDIR: {}", e))?;
+    let db_path = db::db_path_for_period(&paths, period).map_err(anyhow::Error::msg)?;
+    Ok((db_path, period.clone()))
+}
+
+fn required<'a>(options: &'a HashMap<String, String>, name: &str) -> anyhow::Result<&'a str> {
+    options
+        .get(name)
+        .map(String::as_str)
+        .with_context(|| format!("--{} is required\n{}", name, USAGE))
+}
+
+fn step_ms(interval: &str) -> anyhow::Result<i64> {
+    interval_ms(interval).with_context(|| format!("Unknown kline interval '{}'", interval))
+}
+
+async fn stats(pool: &SqlitePool, db_path: &str) -> anyhow::Result<()> {
+    let file_bytes: i64 = sqlx::query_scalar(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+    )
+    .fetch_one(pool)
+    .await?;
+    println!("{}: {:.1} MiB", db_path, file_bytes as f64 / 1_048_576.0);
+    for (table, rows) in row_counts(pool).await? {
+        println!("  {:<20} {:>12}", table, rows);
+    }
+    Ok(())
+}
+
+/// Rows per table of `TABLES`, skipping those the file doesn't have: a file
+/// written before a table's migration, opened read-only, never gets it.
+async fn row_counts(pool: &SqlitePool) -> anyhow::Result<Vec<(&'static str, i64)>> {
+    let present: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .fetch_all(pool)
+            .await?;
+    let mut counts = Vec::new();
+    for &table in TABLES {
+        if !present.iter().any(|name| name == table) {
+            continue;
+        }
+        let rows: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(pool)
+            .await?;
+        counts.push((table, rows));
+    }
+    Ok(counts)
+}
+
+async fn gaps(pool: &SqlitePool, options: &HashMap<String, String>) -> anyhow::Result<()> {
+    let interval = options.get("interval").map_or("1m", String::as_str);
+    let gaps = gaps::kline_gaps(pool, interval, step_ms(interval)?).await?;
+    for gap in &gaps {
+        println!("{}", gap);
+    }
+    println!(
+        "{} gaps, {} {} candles missing",
+        gaps.len(),
+        gaps.iter().map(|g| g.missing).sum::<i64>(),
+        interval
+    );
+    Ok(())
+}
+
+/// `SYMBOL_ALIASES`, as the recorder reads it, so a renamed pair is found
+/// under the name its rows are stored under.
+fn symbol_aliases() -> anyhow::Result<SymbolAliases> {
+    let spec = env::var("SYMBOL_ALIASES").unwrap_or_else(|_| DEFAULT_SYMBOL_ALIASES.to_string());
+    SymbolAliases::parse(&spec).map_err(|e| anyhow::anyhow!("Invalid SYMBOL_ALIASES: {}", e))
+}
+
+/// `--name`, in epoch seconds, or `default` when it isn't given.
+fn seconds(options: &HashMap<String, String>, name: &str, default: f64) -> anyhow::Result<f64> {
+    match options.get(name) {
+        Some(value) => value
+            .parse()
+            .with_context(|| format!("--{} must be epoch seconds, got '{}'", name, value)),
+        None => Ok(default),
+    }
+}
+
+async fn export(
+    pool: &SqlitePool,
+    options: &HashMap<String, String>,
+    label: &str,
+) -> anyhow::Result<()> {
+    let table = required(options, "table")?;
+    if let Some(symbol) = options.get("symbol") {
+        anyhow::ensure!(
+            table == "agg_trades",
+            "--symbol only applies to --table agg_trades"
+        );
+        let out = options
+            .get("out")
+            .cloned()
+            .unwrap_or_else(|| format!("{}_{}_{}.csv", table, symbol.to_uppercase(), label));
+        let rows = storage::export::trades_to_csv(
+            pool,
+            &symbol.to_uppercase(),
+            &symbol_aliases()?,
+            seconds(options, "start", 0.0)?,
+            seconds(options, "end", f64::MAX)?,
+            Path::new(&out),
+        )
+        .await
+        .with_context(|| format!("Failed to export {} trades", symbol))?;
+        info!(
+            "Exported {} trades of {} to {}",
+            rows,
+            symbol.to_uppercase(),
+            out
+        );
+        return Ok(());
+    }
+    let out = options
+        .get("out")
+        .cloned()
+        .unwrap_or_else(|| format!("{}_{}.csv", table, label));
+    let rows = storage::export::to_csv(pool, table, Path::new(&out))
+        .await
+        .with_context(|| format!("Failed to export {}", table))?;
+    info!("Exported {} rows of {} to {}", rows, table, out);
+    Ok(())
+}
+
+async fn resample(
+    pool: &SqlitePool,
+    options: &HashMap<String, String>,
+    label: &str,
+) -> anyhow::Result<()> {
+    let symbol = required(options, "symbol")?;
+    let target = required(options, "to")?;
+    let source = options.get("from").map_or("1m", String::as_str);
+    let target_ms = step_ms(target)?;
+    let source_ms = step_ms(source)?;
+    anyhow::ensure!(
+        target_ms > source_ms && target_ms % source_ms == 0,
+        "Cannot resample {} into {}",
+        source,
+        target
+    );
+    let candles = resample::resample(pool, symbol, source, target_ms).await?;
+    let out = options
+        .get("out")
+        .cloned()
+        .unwrap_or_else(|| format!("{}_{}_{}.csv", symbol.to_uppercase(), target, label));
+    resample::write_csv(&candles, Path::new(&out)).await?;
+    info!(
+        "Wrote {} {} candles of {} to {}",
+        candles.len(),
+        target,
+        symbol.to_uppercase(),
+        out
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::models::{AggTradeInsert, KlineInsert};
+    use storage::db::StorageConfig;
+    use storage::repositories::{AggTradeRepository, KlinesRepository};
+    use storage::test_support::{TempDir, manager, trade};
+
+    fn minute(start_time: i64, open: f32, close: f32) -> KlineInsert {
+        KlineInsert {
+            symbol: "BTCUSDT".to_string(),
+            start_time,
+            close_time: start_time + 59_999,
+            interval: "1m".to_string(),
+            open_price: open,
+            close_price: close,
+            high_price: open.max(close),
+            low_price: open.min(close),
+            volume: 1.0,
+            no_of_trades: 2,
+            taker_buy_vol: 0.5,
+            seq: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gaps_and_resample_on_a_reader() {
+        let dir = TempDir::new("analytics");
+        let manager = manager(&dir, StorageConfig::default()).await;
+        // Minutes 0, 1, 2 and 5: 3 and 4 are missing.
+        let rows = [
+            minute(0, 10.0, 11.0),
+            minute(60_000, 11.0, 9.0),
+            minute(120_000, 9.0, 12.0),
+            minute(300_000, 12.0, 13.0),
+        ];
+        KlinesRepository::insert_batch(&manager, &rows)
+            .await
+            .unwrap();
+        let pool = db::open_reader(
+            &manager.pool_rotator.current_db_path(),
+            &ReaderOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let gaps = gaps::kline_gaps(&pool, "1m", 60_000).await.unwrap();
+        assert_eq!(
+            gaps,
+            vec![gaps::Gap {
+                symbol: "BTCUSDT".to_string(),
+                after: 120_000,
+                before: 300_000,
+                missing: 2,
+            }]
+        );
+
+        let candles = resample::resample(&pool, "btcusdt", "1m", 180_000)
+            .await
+            .unwrap();
+        let summary: Vec<_> = candles
+            .iter()
+            .map(|c| {
+                (
+                    c.start_time,
+                    c.open_price,
+                    c.high_price,
+                    c.low_price,
+                    c.close_price,
+                    c.no_of_trades,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 10.0, 12.0, 9.0, 12.0, 6),
+                (180_000, 12.0, 13.0, 12.0, 13.0, 2),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exports_one_symbols_trades_under_its_current_name() {
+        let dir = TempDir::new("analytics");
+        let manager = manager(&dir, StorageConfig::default()).await;
+        let trade = |agg_trade_id, time, symbol| AggTradeInsert {
+            quantity: 10.0,
+            is_buyer_maker: true,
+            ..trade(agg_trade_id, time, symbol, 0.5)
+        };
+        // Stored as POLUSDT under the default aliases.
+        let trades = [
+            trade(1, 10.0, "MATICUSDT"),
+            trade(2, 20.0, "MATICUSDT"),
+            trade(3, 20.0, "BTCUSDT"),
+        ];
+        AggTradeRepository::insert_batch(&manager, &trades)
+            .await
+            .unwrap();
+        let pool = db::open_reader(
+            &manager.pool_rotator.current_db_path(),
+            &ReaderOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let out = dir.path().join("trades.csv");
+        let options = HashMap::from([
+            ("table".to_string(), "agg_trades".to_string()),
+            ("symbol".to_string(), "maticusdt".to_string()),
+            ("start".to_string(), "15".to_string()),
+            ("out".to_string(), out.to_str().unwrap().to_string()),
+        ]);
+        export(&pool, &options, "test").await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "agg_trade_id,time,trade_time,price,quantity,is_buyer_maker\n2,20,20,0.5,10,1\n"
+        );
+
+        let options = HashMap::from([
+            ("table".to_string(), "klines".to_string()),
+            ("symbol".to_string(), "btcusdt".to_string()),
+        ]);
+        assert!(export(&pool, &options, "test").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stats_skips_tables_the_file_lacks() {
+        let dir = TempDir::new("analytics");
+        let path = dir.path().join("stats.db");
+        let options = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::query("CREATE TABLE klines (id INTEGER PRIMARY KEY)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO klines DEFAULT VALUES")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(row_counts(&pool).await.unwrap(), vec![("klines", 1)]);
+
+        pool.close().await;
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use sqlx::SqlitePool;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+/// One candle built from several stored ones.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Candle {
+    pub start_time: i64,
+    pub close_time: i64,
+    pub open_price: f64,
+    pub high_price: f64,
+    pub low_price: f64,
+    pub close_price: f64,
+    pub volume: f64,
+    pub no_of_trades: i64,
+    pub taker_buy_vol: f64,
+}
+
+/// The `source` candles of `symbol` rolled up into candles of `target_ms`,
+/// oldest first, aggregated the way kline compaction does it. A bucket with
+/// missing source candles is still returned, built from the ones present.
+pub async fn resample(
+    pool: &SqlitePool,
+    symbol: &str,
+    source: &str,
+    target_ms: i64,
+) -> Result<Vec<Candle>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+            SELECT bucket AS start_time, bucket + ?3 - 1 AS close_time,
+                   MIN(first_open) AS open_price, MAX(high_price) AS high_price,
+                   MIN(low_price) AS low_price, MIN(last_close) AS close_price,
+                   SUM(volume) AS volume, SUM(no_of_trades) AS no_of_trades,
+                   SUM(taker_buy_vol) AS taker_buy_vol
+            FROM (
+                SELECT start_time - start_time % ?3 AS bucket,
+                       high_price, low_price, volume, no_of_trades, taker_buy_vol,
+                       FIRST_VALUE(open_price) OVER (
+                           PARTITION BY start_time - start_time % ?3 ORDER BY start_time
+                       ) AS first_open,
+                       FIRST_VALUE(close_price) OVER (
+                           PARTITION BY start_time - start_time % ?3 ORDER BY start_time DESC
+                       ) AS last_close
+                FROM klines_all
+                WHERE interval = ?2
+                  AND symbol_id = (SELECT id FROM symbols WHERE ticker = ?1)
+            )
+            GROUP BY bucket
+            ORDER BY bucket
+        "#,
+    )
+    .bind(symbol.to_uppercase())
+    .bind(source)
+    .bind(target_ms)
+    .fetch_all(pool)
+    .await
+}
+
+/// Writes `candles` to `out_path` as CSV, header first.
+pub async fn write_csv(candles: &[Candle], out_path: &Path) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(out_path).await?);
+    out.write_all(
+        b"start_time,close_time,open_price,high_price,low_price,close_price,volume,no_of_trades,taker_buy_vol\n",
+    )
+    .await?;
+    for c in candles {
+        let line = format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            c.start_time,
+            c.close_time,
+            c.open_price,
+            c.high_price,
+            c.low_price,
+            c.close_price,
+            c.volume,
+            c.no_of_trades,
+            c.taker_buy_vol
+        );
+        out.write_all(line.as_bytes()).await?;
+    }
+    out.flush().await
+}
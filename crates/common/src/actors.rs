@@ -1,9 +1,46 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
 use async_trait::async_trait;
-use tokio::{sync::mpsc, task::JoinHandle};
+use tokio::{sync::mpsc, task::JoinHandle, time::Instant};
 use uuid::Uuid;
 
+/// How often an actor's heartbeat task reports in. The supervisor restarts
+/// actors silent for 3s.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often `Actor::spawn_heartbeat_with_metrics` sends its `Throughput`.
+pub const METRICS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What a service has done since its last `ControlMessage::Metrics`, counted
+/// by its event loop and DB writer and sent by its heartbeat task.
+#[derive(Debug, Default)]
+pub struct Throughput {
+    rows_written: AtomicU64,
+    lag_events: AtomicU64,
+}
+
+impl Throughput {
+    /// Rows committed to the database.
+    pub fn wrote(&self, rows: u64) {
+        self.rows_written.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    /// Broadcast events the service fell too far behind to receive.
+    pub fn lagged(&self, events: u64) {
+        self.lag_events.fetch_add(events, Ordering::Relaxed);
+    }
+
+    /// `(rows_written, lag_events)` since the last call.
+    pub fn take(&self) -> (u64, u64) {
+        (
+            self.rows_written.swap(0, Ordering::Relaxed),
+            self.lag_events.swap(0, Ordering::Relaxed),
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ActorType {
     AggTradeActor,
@@ -13,6 +50,15 @@ pub enum ActorType {
     MarkPriceActor,
     ForceOrderActor,
     OpenInterestActor,
+    TradeFlowActor,
+    LiveCacheActor,
+    AggTradeCandleActor,
+    BalanceSnapshotActor,
+    MaintenanceActor,
+    OrderBookFeatureActor,
+    Ticker24hActor,
+    FundingActor,
+    EventRouterActor,
     Dynamic,
 }
 
@@ -22,6 +68,22 @@ pub enum ControlMessage {
     Heartbeat(Uuid),
     Shutdown(Uuid),
     Error(Uuid, String),
+    /// Rows written and events lagged since the actor's previous report.
+    Metrics(Uuid, u64, u64),
+    /// Stop writing and trading `symbol`; its events keep streaming.
+    DisableSymbol(String),
+    /// Undo `DisableSymbol`.
+    EnableSymbol(String),
+    /// Subscribe the gateway's sockets to `symbol`'s streams.
+    AddSymbol(String),
+    /// Unsubscribe the gateway's sockets from `symbol`'s streams.
+    RemoveSymbol(String),
+    /// Stop every actor of this type and don't restart it until `StartType`.
+    StopType(ActorType),
+    /// Undo `StopType`: spawn the type's actor again.
+    StartType(ActorType),
+    /// Stop every actor and return from `Supervisor::start`.
+    ShutdownAll,
 }
 
 impl std::fmt::Debug for ControlMessage {
@@ -31,6 +93,16 @@ impl std::fmt::Debug for ControlMessage {
             Self::Heartbeat(actor_type) => write!(f, "Heartbeat({:?})", actor_type),
             Self::Shutdown(actor_type) => write!(f, "Shutdown({:?})", actor_type),
             Self::Error(actor_type, err) => write!(f, "Error({:?}, {})", actor_type, err),
+            Self::Metrics(actor_type, rows, lag) => {
+                write!(f, "Metrics({:?}, {}, {})", actor_type, rows, lag)
+            }
+            Self::DisableSymbol(symbol) => write!(f, "DisableSymbol({})", symbol),
+            Self::EnableSymbol(symbol) => write!(f, "EnableSymbol({})", symbol),
+            Self::AddSymbol(symbol) => write!(f, "AddSymbol({})", symbol),
+            Self::RemoveSymbol(symbol) => write!(f, "RemoveSymbol({})", symbol),
+            Self::StopType(actor_type) => write!(f, "StopType({:?})", actor_type),
+            Self::StartType(actor_type) => write!(f, "StartType({:?})", actor_type),
+            Self::ShutdownAll => write!(f, "ShutdownAll"),
         }
     }
 }
@@ -47,19 +119,73 @@ pub trait Actor: Send + Sync {
     /// It must periodically send `ControlMessage::Heartbeat` to the supervisor.
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()>;
 
-    fn spawn_heartbeat(&self, supervisor_tx: mpsc::Sender<ControlMessage>) -> JoinHandle<()> {
-        let id = self.id();
-        tokio::spawn(async move {
-            loop {
-                if supervisor_tx
-                    .send(ControlMessage::Heartbeat(id))
-                    .await
-                    .is_err()
-                {
-                    break;
-                }
-                tokio::time::sleep(Duration::from_millis(500)).await;
+    fn spawn_heartbeat(&self, supervisor_tx: mpsc::Sender<ControlMessage>) -> HeartbeatHandle {
+        HeartbeatHandle(tokio::spawn(heartbeat(self.id(), supervisor_tx, None)))
+    }
+
+    /// `spawn_heartbeat` that also sends `throughput` as a
+    /// `ControlMessage::Metrics` every `METRICS_INTERVAL`.
+    fn spawn_heartbeat_with_metrics(
+        &self,
+        supervisor_tx: mpsc::Sender<ControlMessage>,
+        throughput: Arc<Throughput>,
+    ) -> HeartbeatHandle {
+        HeartbeatHandle(tokio::spawn(heartbeat(
+            self.id(),
+            supervisor_tx,
+            Some(throughput),
+        )))
+    }
+}
+
+/// An actor's heartbeat task. It stops when the handle is dropped, so an
+/// actor the supervisor aborts doesn't leave its heartbeat running.
+pub struct HeartbeatHandle(JoinHandle<()>);
+
+impl HeartbeatHandle {
+    pub fn abort(&self) {
+        self.0.abort();
+    }
+}
+
+impl Drop for HeartbeatHandle {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+async fn heartbeat(
+    id: Uuid,
+    supervisor_tx: mpsc::Sender<ControlMessage>,
+    throughput: Option<Arc<Throughput>>,
+) {
+    // Actors start together, so without an offset every heartbeat would
+    // land in the supervisor's channel in the same instant. The id is a
+    // random v4 UUID, which spreads them across the interval.
+    let phase = Duration::from_millis((id.as_u128() % HEARTBEAT_INTERVAL.as_millis()) as u64);
+    tokio::time::sleep(phase).await;
+    let mut last_report = Instant::now();
+    loop {
+        if supervisor_tx
+            .send(ControlMessage::Heartbeat(id))
+            .await
+            .is_err()
+        {
+            break;
+        }
+        if let Some(throughput) = &throughput
+            && last_report.elapsed() >= METRICS_INTERVAL
+        {
+            last_report = Instant::now();
+            let (rows, lag) = throughput.take();
+            if supervisor_tx
+                .send(ControlMessage::Metrics(id, rows, lag))
+                .await
+                .is_err()
+            {
+                break;
             }
-        })
+        }
+        tokio::time::sleep(HEARTBEAT_INTERVAL).await;
     }
 }
@@ -0,0 +1,800 @@
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::models::kline::KLINE_INTERVALS;
+use crate::paths::{DEFAULT_DATA_DIR, Paths};
+use crate::symbols::{DEFAULT_SYMBOL_ALIASES, SymbolAliases};
+
+pub const DEFAULT_REST_URL: &str = "https://api.binance.com";
+pub const DEFAULT_WS_URL: &str = "wss://stream.binance.com:9443/stream?streams=";
+pub const DEFAULT_FUTURES_WS_URL: &str = "wss://fstream.binance.com/stream?streams=";
+pub const DEFAULT_FUTURES_REST_URL: &str = "https://fapi.binance.com";
+pub const DEFAULT_MODEL_PATH: &str = "models/strategy.onnx";
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+pub const DEFAULT_HTTP_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+pub const DEFAULT_HTTP_POOL_MAX_IDLE: usize = 4;
+
+/// Default slots in the gateway -> router broadcast channel and in each of the
+/// router's per-kind channels.
+///
+/// A channel is one ring buffer shared by every subscriber: each slot holds an
+/// `Arc<MarketEvent>`, so adding services does not add memory, it only means the
+/// slowest of them decides how full the ring gets. A subscriber more than
+/// `capacity` events behind loses the oldest ones (`RecvError::Lagged`); under
+/// the `block` backpressure policy the gateway waits instead. An event sits in
+/// the gateway's ring and its kind's at once, so worst-case memory is roughly
+/// 2 x `capacity` x the largest event (a depth20 snapshot, ~400 bytes), i.e.
+/// about 8 MB at the default. Override with `MARKET_CHANNEL_CAPACITY`.
+pub const DEFAULT_MARKET_CHANNEL_CAPACITY: usize = 10_000;
+
+/// Pairs streamed unless `SYMBOLS` lists others.
+pub const DEFAULT_SYMBOLS: &[&str] = &[
+    // Core (7)
+    "btcusdt",
+    "ethusdt",
+    "bnbusdt",
+    "solusdt",
+    "avaxusdt",
+    "nearusdt",
+    "maticusdt",
+    // Alpha (5)
+    "dogeusdt",
+    "shibusdt",
+    "pepeusdt",
+    "wifusdt",
+    "bonkusdt",
+    // Macro (3)
+    "xrpusdt",
+    "adausdt",
+    "dotusdt",
+];
+
+/// Deployment settings: paths, exchange endpoints and credentials, the
+/// symbols, where alerts go, and the knobs `main` tunes the services with.
+///
+/// Loaded once at startup and shared as an `Arc<Config>`, so a missing or
+/// malformed value stops the bot before anything is spawned rather than
+/// panicking inside an actor later. Settings a library reads for itself
+/// (storage, writer limits, batch windows, ...) keep their own `from_env`
+/// next to the code they tune.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// `WORKDIR`, relative or absolute: everything the bot writes goes
+    /// under it (see `Paths`).
+    pub workdir: String,
+    /// `DATA_DIR` (`sqlitedata`): the weekly databases' directory, under
+    /// `WORKDIR` unless absolute.
+    pub data_dir: String,
+    /// `UTILS`: directory holding `dump_db.sh`, run after each rotation.
+    pub utils_dir: String,
+    /// `MODEL_PATH` (`models/strategy.onnx`).
+    pub model_path: String,
+    /// `SYMBOL_ALIASES` (`maticusdt=polusdt`; empty for none): renamed
+    /// pairs, applied to the symbol list, the stored rows and the strategy's
+    /// groups alike.
+    pub symbol_aliases: SymbolAliases,
+    /// `SYMBOLS` (comma-separated, `DEFAULT_SYMBOLS` when unset): the pairs
+    /// streamed and recorded, lowercase, before `symbol_aliases` is applied.
+    pub symbols: Vec<String>,
+    /// `INVALID_SYMBOLS` is `drop` (default), leaving symbols Binance doesn't
+    /// list as trading out, or `fail`, refusing to start.
+    pub fail_on_invalid_symbols: bool,
+    /// `QUOTE_ASSETS` (e.g. `USDT,FDUSD,BTC`) lists the quote assets symbols
+    /// may trade in; unset allows every one.
+    pub quote_assets: Option<HashSet<String>>,
+    pub binance: BinanceConfig,
+    pub notifier: NotifierConfig,
+    pub tuning: TuningConfig,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinanceConfig {
+    /// `BINANCE_BASE_URL`: spot REST API.
+    pub rest_url: String,
+    /// `BINANCE_WS_URL`: spot combined-stream prefix.
+    pub ws_url: String,
+    /// `BINANCE_FUTURES_WS_URL`: futures combined-stream prefix.
+    pub futures_ws_url: String,
+    /// `BINANCE_FUTURES_BASE_URL`: futures REST API, polled for open interest.
+    pub futures_rest_url: String,
+    /// `BINANCE_API_KEY` and `BINANCE_SECRET_KEY`. Without them only public
+    /// market data is recorded.
+    pub credentials: Option<ApiCredentials>,
+    /// Timeouts and connection reuse of every REST client.
+    pub http: HttpConfig,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpConfig {
+    /// `BINANCE_HTTP_TIMEOUT_MS`: whole request, from connect to last byte.
+    pub timeout: Duration,
+    /// `BINANCE_HTTP_CONNECT_TIMEOUT_MS`: TCP and TLS handshake.
+    pub connect_timeout: Duration,
+    /// `BINANCE_HTTP_POOL_IDLE_SECS`: how long an unused connection is kept.
+    pub pool_idle_timeout: Duration,
+    /// `BINANCE_HTTP_POOL_MAX_IDLE`: idle connections kept per host.
+    pub pool_max_idle: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_HTTP_TIMEOUT,
+            connect_timeout: DEFAULT_HTTP_CONNECT_TIMEOUT,
+            pool_idle_timeout: DEFAULT_HTTP_POOL_IDLE_TIMEOUT,
+            pool_max_idle: DEFAULT_HTTP_POOL_MAX_IDLE,
+        }
+    }
+}
+
+/// How `main` sets up the services. `None` keeps the default of the code
+/// being tuned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningConfig {
+    /// `MARKET_CHANNEL_CAPACITY` (see `DEFAULT_MARKET_CHANNEL_CAPACITY`).
+    pub market_channel_capacity: usize,
+    /// `STORAGE_MODE` is `full` (default) or `klines`. The latter records
+    /// klines and trades only, leaving `order_books` empty, for long-horizon
+    /// research DBs.
+    pub klines_only: bool,
+    /// `KLINE_FLUSH_ON_CLOSE` lists the intervals, e.g. `1m,1h`, whose closed
+    /// candles are written immediately; empty batches every interval.
+    pub kline_flush_on_close: Option<Vec<String>>,
+    /// `OBI_SAMPLE_MS` sets the spacing of stored order book imbalance
+    /// samples.
+    pub obi_sample_ms: Option<u64>,
+    /// `ORDERBOOK_DEDUP_MS` skips an order book identical to its symbol's
+    /// last stored one within that window; unset or 0 stores every book.
+    pub orderbook_dedup: Option<Duration>,
+    /// `SYMBOL_MAX_WRITES_PER_SEC` caps the trades and order books each
+    /// symbol may send to its DB writer per second; unset or 0 writes
+    /// everything.
+    pub symbol_max_writes_per_sec: Option<u32>,
+    /// `WS_ROTATE_AFTER_SECS` is the age at which each gateway socket is
+    /// replaced; zero never rotates.
+    pub ws_rotate_after: Option<Duration>,
+    /// `DROP_INVALID_BOOKS` (`false`) discards unsorted or crossed depth
+    /// snapshots instead of only counting them.
+    pub drop_invalid_books: bool,
+    /// `LIVE_CACHE_TRADES` sets how many recent trades per symbol stay in
+    /// memory.
+    pub live_cache_trades: Option<usize>,
+    /// `BALANCE_SNAPSHOT_MINUTES` sets how often account balances are
+    /// recorded.
+    pub balance_snapshot_period: Option<Duration>,
+    /// `TICKER_24H_SECONDS` sets how often the 24h ticker statistics are
+    /// polled.
+    pub ticker_24h_period: Option<Duration>,
+    /// `ACCOUNT_REFRESH_SECS` sets how often `ExecutionService` re-fetches
+    /// the account once a fetch has succeeded.
+    pub account_refresh: Option<Duration>,
+    /// `RUN_DURATION_SECS` turns the bot into a fixed-length recording: run
+    /// for that long, flush every writer and report how many rows landed in
+    /// each table.
+    pub run_duration: Option<Duration>,
+    /// `STRATEGY_ENABLED` (`false`) runs the strategy on the live streams.
+    pub strategy_enabled: bool,
+    /// `MAX_EVENT_AGE_MS` is how far behind the clock, by exchange time, a
+    /// trade may be and still drive signals; zero turns the check off.
+    pub max_event_age: Option<Duration>,
+    /// `MODEL_TEMPERATURE` divides the model's logits before the softmax, to
+    /// calibrate its confidence against the group thresholds without
+    /// retraining.
+    pub model_temperature: Option<f32>,
+    /// `MODEL_SCORE_THRESHOLD` is the score at or above which a
+    /// single-output model buys; it sells at or below `1 - threshold`.
+    pub model_score_threshold: Option<f32>,
+    /// `STRATEGY_OBI_LEVELS` is how many of the best book levels per side
+    /// the strategy's OBI feature is computed over.
+    pub strategy_obi_levels: Option<usize>,
+    /// `STRATEGY_FEATURE_COUNT` is 4 for the base features, or 6 to append
+    /// the open interest change and funding rate for models trained with
+    /// them; `StrategyService::with_feature_count` refuses anything else.
+    pub strategy_feature_count: Option<usize>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ApiCredentials {
+    pub api_key: String,
+    pub secret_key: String,
+}
+
+impl fmt::Debug for ApiCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiCredentials").finish_non_exhaustive()
+    }
+}
+
+/// Where human-facing alerts are sent, picked by `NOTIFIER`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotifierConfig {
+    /// `TELEGRAM_BOT_TOKEN` and `TELEGRAM_CHAT_ID`.
+    Telegram {
+        bot_token: Secret,
+        chat_id: i64,
+    },
+    /// `NOTIFIER_WEBHOOK_URL`.
+    Webhook(String),
+    Stdout,
+}
+
+/// A value kept out of `Debug` output.
+#[derive(Clone, PartialEq)]
+pub struct Secret(pub String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+impl Config {
+    /// Reads and validates every setting. The error lists all problems found,
+    /// one per line.
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Like `from_env`, reading variables through `var`.
+    pub fn from_vars(raw: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let var = |name: &str| {
+            raw(name)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let mut problems = Vec::new();
+
+        let mut required = |name: &str| {
+            var(name).unwrap_or_else(|| {
+                problems.push(format!("{} must be set", name));
+                String::new()
+            })
+        };
+        let workdir = required("WORKDIR");
+        let utils_dir = required("UTILS");
+
+        let mut number = |name: &str, default: u64| match var(name) {
+            Some(value) => parse_whole(name, &value).unwrap_or_else(|problem| {
+                problems.push(problem);
+                default
+            }),
+            None => default,
+        };
+        let http = HttpConfig {
+            timeout: Duration::from_millis(number(
+                "BINANCE_HTTP_TIMEOUT_MS",
+                DEFAULT_HTTP_TIMEOUT.as_millis() as u64,
+            )),
+            connect_timeout: Duration::from_millis(number(
+                "BINANCE_HTTP_CONNECT_TIMEOUT_MS",
+                DEFAULT_HTTP_CONNECT_TIMEOUT.as_millis() as u64,
+            )),
+            pool_idle_timeout: Duration::from_secs(number(
+                "BINANCE_HTTP_POOL_IDLE_SECS",
+                DEFAULT_HTTP_POOL_IDLE_TIMEOUT.as_secs(),
+            )),
+            pool_max_idle: number(
+                "BINANCE_HTTP_POOL_MAX_IDLE",
+                DEFAULT_HTTP_POOL_MAX_IDLE as u64,
+            ) as usize,
+        };
+        if http.timeout.is_zero() || http.connect_timeout.is_zero() {
+            problems.push(
+                "BINANCE_HTTP_TIMEOUT_MS and BINANCE_HTTP_CONNECT_TIMEOUT_MS must be above 0"
+                    .to_string(),
+            );
+        }
+
+        let mut url = |name: &str, default: &str, schemes: &[&str]| {
+            let url = var(name).unwrap_or_else(|| default.to_string());
+            if !schemes.iter().any(|scheme| url.starts_with(scheme)) {
+                problems.push(format!(
+                    "{} must start with {}, got '{}'",
+                    name,
+                    schemes.join(" or "),
+                    url
+                ));
+            }
+            url
+        };
+        let binance = BinanceConfig {
+            rest_url: url(
+                "BINANCE_BASE_URL",
+                DEFAULT_REST_URL,
+                &["https://", "http://"],
+            ),
+            ws_url: url("BINANCE_WS_URL", DEFAULT_WS_URL, &["wss://", "ws://"]),
+            futures_ws_url: url(
+                "BINANCE_FUTURES_WS_URL",
+                DEFAULT_FUTURES_WS_URL,
+                &["wss://", "ws://"],
+            ),
+            futures_rest_url: url(
+                "BINANCE_FUTURES_BASE_URL",
+                DEFAULT_FUTURES_REST_URL,
+                &["https://", "http://"],
+            ),
+            credentials: match (var("BINANCE_API_KEY"), var("BINANCE_SECRET_KEY")) {
+                (Some(api_key), Some(secret_key)) => Some(ApiCredentials {
+                    api_key,
+                    secret_key,
+                }),
+                (None, None) => None,
+                _ => {
+                    problems.push(
+                        "BINANCE_API_KEY and BINANCE_SECRET_KEY must be set together".to_string(),
+                    );
+                    None
+                }
+            },
+            http,
+        };
+
+        let telegram = match (var("TELEGRAM_BOT_TOKEN"), var("TELEGRAM_CHAT_ID")) {
+            (Some(token), Some(chat_id)) => match chat_id.parse::<i64>() {
+                Ok(chat_id) => Some(NotifierConfig::Telegram {
+                    bot_token: Secret(token),
+                    chat_id,
+                }),
+                Err(_) => {
+                    problems.push(format!(
+                        "TELEGRAM_CHAT_ID must be a number, got '{}'",
+                        chat_id
+                    ));
+                    None
+                }
+            },
+            (None, None) => None,
+            _ => {
+                problems.push(
+                    "TELEGRAM_BOT_TOKEN and TELEGRAM_CHAT_ID must be set together".to_string(),
+                );
+                None
+            }
+        };
+        let notifier = match var("NOTIFIER").map(|kind| kind.to_lowercase()).as_deref() {
+            // Unset: Telegram when it's configured, otherwise just log.
+            None => telegram.unwrap_or(NotifierConfig::Stdout),
+            Some("telegram") => telegram.unwrap_or_else(|| {
+                problems.push(
+                    "NOTIFIER=telegram needs TELEGRAM_BOT_TOKEN and TELEGRAM_CHAT_ID".to_string(),
+                );
+                NotifierConfig::Stdout
+            }),
+            Some("webhook") => match var("NOTIFIER_WEBHOOK_URL") {
+                Some(url) => NotifierConfig::Webhook(url),
+                None => {
+                    problems.push("NOTIFIER=webhook needs NOTIFIER_WEBHOOK_URL".to_string());
+                    NotifierConfig::Stdout
+                }
+            },
+            Some("stdout") => NotifierConfig::Stdout,
+            Some(other) => {
+                problems.push(format!(
+                    "NOTIFIER must be telegram, webhook or stdout, got '{}'",
+                    other
+                ));
+                NotifierConfig::Stdout
+            }
+        };
+
+        // Unlike the others an empty value means something: no aliases.
+        let symbol_aliases = SymbolAliases::parse(
+            &raw("SYMBOL_ALIASES").unwrap_or_else(|| DEFAULT_SYMBOL_ALIASES.to_string()),
+        )
+        .unwrap_or_else(|e| {
+            problems.push(format!("Invalid SYMBOL_ALIASES: {}", e));
+            SymbolAliases::default()
+        });
+
+        let symbols: Vec<String> = match var("SYMBOLS") {
+            Some(list) => list
+                .split(',')
+                .map(|symbol| symbol.trim().to_lowercase())
+                .filter(|symbol| !symbol.is_empty())
+                .collect(),
+            None => DEFAULT_SYMBOLS.iter().map(|s| s.to_string()).collect(),
+        };
+        if symbols.is_empty() {
+            problems.push("SYMBOLS must list at least one symbol".to_string());
+        }
+        if let Some(bad) = symbols
+            .iter()
+            .find(|s| !s.chars().all(|c| c.is_ascii_alphanumeric()))
+        {
+            problems.push(format!("SYMBOLS: '{}' is not a symbol", bad));
+        }
+
+        let fail_on_invalid_symbols = match var("INVALID_SYMBOLS")
+            .map(|mode| mode.to_lowercase())
+            .as_deref()
+        {
+            None | Some("drop") => false,
+            Some("fail") => true,
+            Some(other) => {
+                problems.push(format!(
+                    "INVALID_SYMBOLS must be 'drop' or 'fail', got '{}'",
+                    other
+                ));
+                false
+            }
+        };
+        // Set but empty is an error, not "every quote".
+        let quote_assets = raw("QUOTE_ASSETS").map(|value| {
+            let quotes: HashSet<String> = value
+                .split(',')
+                .map(|q| q.trim().to_uppercase())
+                .filter(|q| !q.is_empty())
+                .collect();
+            if quotes.is_empty() {
+                problems.push(format!(
+                    "QUOTE_ASSETS must list at least one asset, got '{}'",
+                    value
+                ));
+            }
+            if let Some(bad) = quotes
+                .iter()
+                .find(|q| !q.chars().all(|c| c.is_ascii_alphanumeric()))
+            {
+                problems.push(format!("QUOTE_ASSETS: '{}' is not an asset name", bad));
+            }
+            quotes
+        });
+
+        let tuning = TuningConfig::from_vars(&var, &raw, &mut problems);
+
+        if !problems.is_empty() {
+            return Err(problems.join("\n"));
+        }
+        Ok(Self {
+            workdir,
+            data_dir: var("DATA_DIR").unwrap_or_else(|| DEFAULT_DATA_DIR.to_string()),
+            utils_dir,
+            model_path: var("MODEL_PATH").unwrap_or_else(|| DEFAULT_MODEL_PATH.to_string()),
+            symbol_aliases,
+            symbols,
+            fail_on_invalid_symbols,
+            quote_assets,
+            binance,
+            notifier,
+            tuning,
+        })
+    }
+
+    /// The file layout under `workdir`, with `data_dir` and `model_path`
+    /// applied.
+    pub fn paths(&self) -> Paths {
+        Paths::new(&self.workdir)
+            .with_data_dir(&self.data_dir)
+            .with_model(&self.model_path)
+    }
+}
+
+impl TuningConfig {
+    /// Reads every knob through `var` (trimmed, empty as unset), or `raw`
+    /// where an empty value means something, adding what is malformed to
+    /// `problems`.
+    fn from_vars(
+        var: &dyn Fn(&str) -> Option<String>,
+        raw: &dyn Fn(&str) -> Option<String>,
+        problems: &mut Vec<String>,
+    ) -> Self {
+        let mut vars = Vars { var, problems };
+        let positive = |n: &u64| *n > 0;
+        let nonzero = |d: &Duration| !d.is_zero();
+
+        let klines_only = vars
+            .get("STORAGE_MODE", |name, value| {
+                match value.to_lowercase().as_str() {
+                    "full" => Ok(false),
+                    "klines" => Ok(true),
+                    _ => Err(format!(
+                        "{} must be 'full' or 'klines', got '{}'",
+                        name, value
+                    )),
+                }
+            })
+            .unwrap_or(false);
+        let kline_flush_on_close = raw("KLINE_FLUSH_ON_CLOSE").map(|value| {
+            let intervals: Vec<String> = value
+                .split(',')
+                .map(str::trim)
+                .filter(|interval| !interval.is_empty())
+                .map(str::to_string)
+                .collect();
+            for interval in &intervals {
+                if !KLINE_INTERVALS.contains(&interval.as_str()) {
+                    vars.problems.push(format!(
+                        "KLINE_FLUSH_ON_CLOSE: unknown interval '{}', expected one of {:?}",
+                        interval, KLINE_INTERVALS
+                    ));
+                }
+            }
+            intervals
+        });
+
+        Self {
+            market_channel_capacity: vars
+                .checked(
+                    "MARKET_CHANNEL_CAPACITY",
+                    parse_whole,
+                    |n: &usize| *n > 0,
+                    "greater than 0",
+                )
+                .unwrap_or(DEFAULT_MARKET_CHANNEL_CAPACITY),
+            klines_only,
+            kline_flush_on_close,
+            obi_sample_ms: vars.get("OBI_SAMPLE_MS", parse_whole),
+            orderbook_dedup: vars.get("ORDERBOOK_DEDUP_MS", parse_millis).filter(nonzero),
+            symbol_max_writes_per_sec: vars
+                .get("SYMBOL_MAX_WRITES_PER_SEC", parse_whole)
+                .filter(|n: &u32| *n > 0),
+            ws_rotate_after: vars.get("WS_ROTATE_AFTER_SECS", parse_secs),
+            drop_invalid_books: vars.get("DROP_INVALID_BOOKS", parse_bool).unwrap_or(false),
+            live_cache_trades: vars.get("LIVE_CACHE_TRADES", parse_whole),
+            balance_snapshot_period: vars
+                .checked(
+                    "BALANCE_SNAPSHOT_MINUTES",
+                    parse_whole,
+                    positive,
+                    "greater than 0",
+                )
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+            ticker_24h_period: vars.checked(
+                "TICKER_24H_SECONDS",
+                parse_secs,
+                nonzero,
+                "greater than 0",
+            ),
+            account_refresh: vars.checked(
+                "ACCOUNT_REFRESH_SECS",
+                parse_secs,
+                nonzero,
+                "greater than 0",
+            ),
+            run_duration: vars.get("RUN_DURATION_SECS", parse_secs),
+            strategy_enabled: vars.get("STRATEGY_ENABLED", parse_bool).unwrap_or(false),
+            max_event_age: vars.get("MAX_EVENT_AGE_MS", parse_millis),
+            model_temperature: vars.checked(
+                "MODEL_TEMPERATURE",
+                parse_number,
+                |t: &f32| t.is_finite() && *t > 0.0,
+                "positive",
+            ),
+            model_score_threshold: vars.checked(
+                "MODEL_SCORE_THRESHOLD",
+                parse_number,
+                |t: &f32| (0.5..=1.0).contains(t),
+                "between 0.5 and 1",
+            ),
+            strategy_obi_levels: vars.checked(
+                "STRATEGY_OBI_LEVELS",
+                parse_whole,
+                |n: &usize| (1..=20).contains(n),
+                "between 1 and 20",
+            ),
+            strategy_feature_count: vars.get("STRATEGY_FEATURE_COUNT", parse_whole),
+        }
+    }
+}
+
+/// Variables looked up through `var`, with every malformed one noted in
+/// `problems` so they can all be reported together.
+struct Vars<'a> {
+    var: &'a dyn Fn(&str) -> Option<String>,
+    problems: &'a mut Vec<String>,
+}
+
+impl Vars<'_> {
+    /// `name` read with `parse`; `None` if unset or malformed.
+    fn get<T>(&mut self, name: &str, parse: impl Fn(&str, &str) -> Result<T, String>) -> Option<T> {
+        let value = (self.var)(name)?;
+        parse(name, &value)
+            .map_err(|problem| self.problems.push(problem))
+            .ok()
+    }
+
+    /// `get` for a value that must also pass `valid`, described by
+    /// `expected` when it doesn't.
+    fn checked<T>(
+        &mut self,
+        name: &str,
+        parse: impl Fn(&str, &str) -> Result<T, String>,
+        valid: impl Fn(&T) -> bool,
+        expected: &str,
+    ) -> Option<T> {
+        let value = self.get(name, parse)?;
+        if valid(&value) {
+            return Some(value);
+        }
+        let raw = (self.var)(name).unwrap_or_default();
+        self.problems
+            .push(format!("{} must be {}, got '{}'", name, expected, raw));
+        None
+    }
+}
+
+/// `value` of the variable `name` as `true`/`1` or `false`/`0`, in any case.
+pub fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(format!("{} must be true or false, got '{}'", name, value)),
+    }
+}
+
+/// `value` of the variable `name` as whole seconds.
+pub fn parse_secs(name: &str, value: &str) -> Result<Duration, String> {
+    value
+        .trim()
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|_| format!("{} must be whole seconds, got '{}'", name, value))
+}
+
+/// `value` of the variable `name` as whole milliseconds.
+pub fn parse_millis(name: &str, value: &str) -> Result<Duration, String> {
+    value
+        .trim()
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("{} must be whole milliseconds, got '{}'", name, value))
+}
+
+/// `value` of the variable `name` as a whole number.
+pub fn parse_whole<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} must be a whole number, got '{}'", name, value))
+}
+
+/// `value` of the variable `name` as a number.
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} must be a number, got '{}'", name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Config, String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_defaults_and_all_problems_reported_together() {
+        let config = load(&[("WORKDIR", "/data"), ("UTILS", "/utils")]).unwrap();
+        assert_eq!(config.model_path, DEFAULT_MODEL_PATH);
+        assert_eq!(config.binance.ws_url, DEFAULT_WS_URL);
+        assert_eq!(config.binance.credentials, None);
+        assert_eq!(config.binance.http, HttpConfig::default());
+        assert_eq!(config.notifier, NotifierConfig::Stdout);
+        assert_eq!(config.symbol_aliases.canonical("maticusdt"), "polusdt");
+        assert_eq!(config.symbols, DEFAULT_SYMBOLS);
+        let config = load(&[
+            ("WORKDIR", "/data"),
+            ("UTILS", "/utils"),
+            ("SYMBOL_ALIASES", ""),
+            ("SYMBOLS", " BTCUSDT, ethusdt ,"),
+        ])
+        .unwrap();
+        assert_eq!(config.symbol_aliases, SymbolAliases::default());
+        assert_eq!(config.symbols, vec!["btcusdt", "ethusdt"]);
+
+        let config = load(&[
+            ("WORKDIR", "/data"),
+            ("UTILS", "/utils"),
+            ("TELEGRAM_BOT_TOKEN", "token"),
+            ("TELEGRAM_CHAT_ID", "-42"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.notifier,
+            NotifierConfig::Telegram {
+                bot_token: Secret("token".to_string()),
+                chat_id: -42
+            }
+        );
+
+        let problems = load(&[
+            ("UTILS", " "),
+            ("BINANCE_API_KEY", "key"),
+            ("BINANCE_WS_URL", "https://wrong"),
+            ("BINANCE_HTTP_TIMEOUT_MS", "5s"),
+            ("NOTIFIER", "webhook"),
+            ("SYMBOL_ALIASES", "maticusdt"),
+            ("SYMBOLS", "btcusdt,eth-usdt"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            problems.lines().collect::<Vec<_>>(),
+            vec![
+                "WORKDIR must be set",
+                "UTILS must be set",
+                "BINANCE_HTTP_TIMEOUT_MS must be a whole number, got '5s'",
+                "BINANCE_WS_URL must start with wss:// or ws://, got 'https://wrong'",
+                "BINANCE_API_KEY and BINANCE_SECRET_KEY must be set together",
+                "NOTIFIER=webhook needs NOTIFIER_WEBHOOK_URL",
+                "Invalid SYMBOL_ALIASES: expected 'old=new', got 'maticusdt'",
+                "SYMBOLS: 'eth-usdt' is not a symbol",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuning_defaults_and_problems() {
+        let config = load(&[("WORKDIR", "/data"), ("UTILS", "/utils")]).unwrap();
+        let tuning = &config.tuning;
+        assert_eq!(
+            tuning.market_channel_capacity,
+            DEFAULT_MARKET_CHANNEL_CAPACITY
+        );
+        assert!(!tuning.klines_only && !tuning.strategy_enabled && !tuning.drop_invalid_books);
+        assert_eq!(tuning.kline_flush_on_close, None);
+        assert_eq!(tuning.ws_rotate_after, None);
+        assert!(!config.fail_on_invalid_symbols);
+        assert_eq!(config.quote_assets, None);
+
+        let config = load(&[
+            ("WORKDIR", "/data"),
+            ("UTILS", "/utils"),
+            ("STRATEGY_ENABLED", "TRUE"),
+            ("DROP_INVALID_BOOKS", "1"),
+            ("KLINE_FLUSH_ON_CLOSE", ""),
+            ("ORDERBOOK_DEDUP_MS", "0"),
+            ("WS_ROTATE_AFTER_SECS", "0"),
+            ("QUOTE_ASSETS", "usdt, btc"),
+        ])
+        .unwrap();
+        assert!(config.tuning.strategy_enabled && config.tuning.drop_invalid_books);
+        assert_eq!(config.tuning.kline_flush_on_close, Some(vec![]));
+        assert_eq!(config.tuning.orderbook_dedup, None);
+        assert_eq!(config.tuning.ws_rotate_after, Some(Duration::ZERO));
+        assert_eq!(
+            config.quote_assets,
+            Some(HashSet::from(["USDT".to_string(), "BTC".to_string()]))
+        );
+
+        let problems = load(&[
+            ("WORKDIR", "/data"),
+            ("UTILS", "/utils"),
+            ("INVALID_SYMBOLS", "skip"),
+            ("QUOTE_ASSETS", " , "),
+            ("STORAGE_MODE", "lite"),
+            ("KLINE_FLUSH_ON_CLOSE", "1m,5m"),
+            ("MARKET_CHANNEL_CAPACITY", "0"),
+            ("STRATEGY_ENABLED", "yes"),
+            ("TICKER_24H_SECONDS", "0"),
+            ("MODEL_SCORE_THRESHOLD", "0.3"),
+            ("MAX_EVENT_AGE_MS", "1.5"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            problems.lines().collect::<Vec<_>>(),
+            vec![
+                "INVALID_SYMBOLS must be 'drop' or 'fail', got 'skip'",
+                "QUOTE_ASSETS must list at least one asset, got ' , '",
+                "STORAGE_MODE must be 'full' or 'klines', got 'lite'",
+                "KLINE_FLUSH_ON_CLOSE: unknown interval '5m', expected one of [\"1h\", \"1m\", \"1s\"]",
+                "MARKET_CHANNEL_CAPACITY must be greater than 0, got '0'",
+                "TICKER_24H_SECONDS must be greater than 0, got '0'",
+                "STRATEGY_ENABLED must be true or false, got 'yes'",
+                "MAX_EVENT_AGE_MS must be whole milliseconds, got '1.5'",
+                "MODEL_SCORE_THRESHOLD must be between 0.5 and 1, got '0.3'",
+            ]
+        );
+    }
+}
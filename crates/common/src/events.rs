@@ -0,0 +1,245 @@
+//! What the market gateway publishes. Lives here rather than in
+//! `market_data` so consumers such as the strategy can take the events
+//! without linking the websocket code.
+
+use std::pin::pin;
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, SendError, TryRecvError},
+};
+
+use crate::models::{
+    AggTradeInsert, DepthDiff, ForceOrderInsert, KlineInsert, MarkPriceInsert, OpenInterestInsert,
+    OrderBookInsert,
+};
+
+pub enum MarketEvent {
+    AggTrade(AggTradeInsert),
+    OrderBook(OrderBookInsert),
+    /// Only produced for symbols subscribed to the `@depth` diff stream.
+    DepthDiff(DepthDiff),
+    Kline((KlineInsert, bool)),
+    MarkPrice(MarkPriceInsert),
+    ForceOrder(ForceOrderInsert),
+    OpenInterest(OpenInterestInsert),
+}
+
+/// The variant of a `MarketEvent`, without its payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    AggTrade,
+    OrderBook,
+    DepthDiff,
+    Kline,
+    MarkPrice,
+    ForceOrder,
+    OpenInterest,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 7] = [
+        Self::AggTrade,
+        Self::OrderBook,
+        Self::DepthDiff,
+        Self::Kline,
+        Self::MarkPrice,
+        Self::ForceOrder,
+        Self::OpenInterest,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AggTrade => "AggTrade",
+            Self::OrderBook => "OrderBook",
+            Self::DepthDiff => "DepthDiff",
+            Self::Kline => "Kline",
+            Self::MarkPrice => "MarkPrice",
+            Self::ForceOrder => "ForceOrder",
+            Self::OpenInterest => "OpenInterest",
+        }
+    }
+}
+
+impl MarketEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::AggTrade(_) => EventKind::AggTrade,
+            Self::OrderBook(_) => EventKind::OrderBook,
+            Self::DepthDiff(_) => EventKind::DepthDiff,
+            Self::Kline(_) => EventKind::Kline,
+            Self::MarkPrice(_) => EventKind::MarkPrice,
+            Self::ForceOrder(_) => EventKind::ForceOrder,
+            Self::OpenInterest(_) => EventKind::OpenInterest,
+        }
+    }
+
+    /// Position in the gateway's publish order: 1 for the first event after
+    /// startup, then one more for each event of any kind, so a receiver that
+    /// sees it jump knows exactly which events it missed.
+    pub fn seq(&self) -> Option<i64> {
+        match self {
+            Self::AggTrade(trade) => trade.seq,
+            Self::OrderBook(book) => book.seq,
+            Self::DepthDiff(diff) => diff.seq,
+            Self::Kline((kline, _)) => kline.seq,
+            Self::MarkPrice(price) => price.seq,
+            Self::ForceOrder(order) => order.seq,
+            Self::OpenInterest(interest) => interest.seq,
+        }
+    }
+
+    /// Stamps the gateway sequence number; only the gateway publishes events.
+    pub fn set_seq(&mut self, seq: i64) {
+        let slot = match self {
+            Self::AggTrade(trade) => &mut trade.seq,
+            Self::OrderBook(book) => &mut book.seq,
+            Self::DepthDiff(diff) => &mut diff.seq,
+            Self::Kline((kline, _)) => &mut kline.seq,
+            Self::MarkPrice(price) => &mut price.seq,
+            Self::ForceOrder(order) => &mut order.seq,
+            Self::OpenInterest(interest) => &mut interest.seq,
+        };
+        *slot = Some(seq);
+    }
+}
+
+/// A broadcast channel of `capacity` market events whose sender can wait
+/// for room, which `tokio::sync::broadcast` alone can't: every read wakes a
+/// sender waiting in `EventSender::reserve`.
+pub fn event_channel(capacity: usize) -> (EventSender, EventReceiver) {
+    let (tx, rx) = broadcast::channel(capacity);
+    let room = Arc::new(Notify::new());
+    let receiver = EventReceiver {
+        rx,
+        room: room.clone(),
+    };
+    (EventSender { tx, room, capacity }, receiver)
+}
+
+#[derive(Clone)]
+pub struct EventSender {
+    tx: broadcast::Sender<Arc<MarketEvent>>,
+    room: Arc<Notify>,
+    capacity: usize,
+}
+
+impl EventSender {
+    pub fn subscribe(&self) -> EventReceiver {
+        EventReceiver {
+            rx: self.tx.subscribe(),
+            room: self.room.clone(),
+        }
+    }
+
+    /// Sends without waiting. On a full channel the oldest event is
+    /// overwritten and receivers that hadn't read it get `Lagged`.
+    pub fn send(&self, event: Arc<MarketEvent>) -> Result<usize, SendError<Arc<MarketEvent>>> {
+        self.tx.send(event)
+    }
+
+    /// Waits until the slowest receiver has read far enough for the next
+    /// `send` not to overwrite anything.
+    pub async fn reserve(&self) {
+        loop {
+            let mut read = pin!(self.room.notified());
+            // Registered before the check, so a read in between still wakes us.
+            read.as_mut().enable();
+            if self.tx.len() < self.capacity {
+                return;
+            }
+            read.await;
+        }
+    }
+
+    /// Events some receiver hasn't read yet.
+    pub fn len(&self) -> usize {
+        self.tx.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tx.is_empty()
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+pub struct EventReceiver {
+    rx: broadcast::Receiver<Arc<MarketEvent>>,
+    room: Arc<Notify>,
+}
+
+impl EventReceiver {
+    pub async fn recv(&mut self) -> Result<Arc<MarketEvent>, RecvError> {
+        let result = self.rx.recv().await;
+        self.room.notify_waiters();
+        result
+    }
+
+    pub fn try_recv(&mut self) -> Result<Arc<MarketEvent>, TryRecvError> {
+        let result = self.rx.try_recv();
+        self.room.notify_waiters();
+        result
+    }
+}
+
+impl Drop for EventReceiver {
+    /// A receiver that goes away no longer holds back the events it hadn't read.
+    fn drop(&mut self) {
+        self.room.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::OpenInterestInsert;
+    use std::time::Duration;
+
+    fn event() -> Arc<MarketEvent> {
+        Arc::new(MarketEvent::OpenInterest(OpenInterestInsert {
+            time: 1.0,
+            symbol: "BTCUSDT".to_string(),
+            oi_value: 1.0,
+            seq: None,
+        }))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reserve_waits_for_the_slowest_receiver() {
+        let (tx, mut fast) = event_channel(2);
+        let mut slow = tx.subscribe();
+        tx.send(event()).ok();
+        tx.send(event()).ok();
+        fast.recv().await.unwrap();
+        fast.recv().await.unwrap();
+
+        let waiting = tokio::spawn({
+            let tx = tx.clone();
+            async move { tx.reserve().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished(), "slow hasn't read anything yet");
+
+        slow.recv().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("a read makes room")
+            .unwrap();
+
+        // Dropping the last laggard frees its slots as well.
+        tx.send(event()).ok();
+        let waiting = tokio::spawn(async move { tx.reserve().await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        drop(slow);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("a dropped receiver makes room")
+            .unwrap();
+    }
+}
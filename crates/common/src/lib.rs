@@ -1,3 +1,8 @@
-pub mod models;
+pub mod actors;
+pub mod clock;
+pub mod config;
+pub mod events;
 pub mod logger;
-pub mod actors;
\ No newline at end of file
+pub mod models;
+pub mod paths;
+pub mod symbols;
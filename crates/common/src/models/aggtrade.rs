@@ -1,9 +1,12 @@
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct AggTrade {
-    pub id: i32,
+    pub id: i64,
+    /// `None` in rows written before the column was added.
+    pub agg_trade_id: Option<i64>,
     pub time: f64,
-    pub symbol_id: u64,
+    pub trade_time: Option<f64>,
+    pub symbol_id: i64,
     pub price: f64,
     pub quantity: f64,
     pub is_buyer_maker: bool,
@@ -11,9 +14,17 @@ pub struct AggTrade {
 
 #[derive(Debug, Clone)]
 pub struct AggTradeInsert {
+    /// Binance's aggregate trade id, unique per symbol.
+    pub agg_trade_id: i64,
+    /// Local receipt time, in seconds.
     pub time: f64,
+    /// Exchange trade time (`T`), in seconds.
+    pub trade_time: f64,
     pub symbol: String,
     pub price: f64,
     pub quantity: f64,
     pub is_buyer_maker: bool,
+    /// Position in the gateway's publish order, `None` for rows that did not
+    /// come off the market stream.
+    pub seq: Option<i64>,
 }
@@ -0,0 +1,17 @@
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct Balance {
+    pub id: i32,
+    pub time: f64,
+    pub asset: String,
+    pub free: f64,
+    pub locked: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceInsert {
+    pub time: f64,
+    pub asset: String,
+    pub free: f64,
+    pub locked: f64,
+}
@@ -16,4 +16,6 @@ pub struct ForceOrderInsert {
     pub side: String,
     pub price: f64,
     pub quantity: f64,
+    /// Gateway sequence number.
+    pub seq: Option<i64>,
 }
@@ -1,10 +1,15 @@
+/// Kline intervals the gateway subscribes to. Events carrying any other
+/// interval are rejected so `(symbol_id, interval, start_time)` keeps meaning
+/// one row per candle.
+pub const KLINE_INTERVALS: &[&str] = &["1h", "1m", "1s"];
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct Kline {
     pub id: i32,
     pub symbol: String,
-    pub start_time: i32,
-    pub close_time: i32,
+    pub start_time: i64,
+    pub close_time: i64,
     pub interval: String,
     pub open_price: f32,
     pub close_price: f32,
@@ -18,8 +23,8 @@ pub struct Kline {
 #[derive(Debug, Clone)]
 pub struct KlineInsert {
     pub symbol: String,
-    pub start_time: i32,
-    pub close_time: i32,
+    pub start_time: i64,
+    pub close_time: i64,
     pub interval: String,
     pub open_price: f32,
     pub close_price: f32,
@@ -28,4 +33,6 @@ pub struct KlineInsert {
     pub volume: f64,
     pub no_of_trades: i32,
     pub taker_buy_vol: f32,
+    /// Gateway sequence number, `None` for REST backfills.
+    pub seq: Option<i64>,
 }
@@ -16,4 +16,19 @@ pub struct MarkPriceInsert {
     pub mark_price: f64,
     pub index_price: f64,
     pub funding_rate: f64,
+    /// When `funding_rate` is next settled, ms since the epoch.
+    pub next_funding_time: Option<i64>,
+    /// Gateway sequence number.
+    pub seq: Option<i64>,
+}
+
+/// A funding settlement: the rate and mark price a symbol last streamed
+/// before `funding_time`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingEventInsert {
+    /// Settlement time, ms since the epoch.
+    pub funding_time: i64,
+    pub symbol: String,
+    pub rate: f64,
+    pub mark_price: f64,
 }
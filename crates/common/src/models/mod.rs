@@ -1,15 +1,23 @@
 pub mod aggtrade;
+pub mod balance;
 pub mod force_order;
 pub mod kline;
 pub mod markprice;
 pub mod open_interest;
+pub mod order;
 pub mod orderbook;
 pub mod signal;
+pub mod ticker;
+pub mod trade_flow;
 
 pub use aggtrade::{AggTrade, AggTradeInsert};
+pub use balance::{Balance, BalanceInsert};
 pub use force_order::{ForceOrder, ForceOrderInsert};
 pub use kline::{Kline, KlineInsert};
-pub use markprice::{MarkPrice, MarkPriceInsert};
+pub use markprice::{FundingEventInsert, MarkPrice, MarkPriceInsert};
 pub use open_interest::{OpenInterest, OpenInterestInsert};
-pub use orderbook::{OrderBook, OrderBookInsert};
-pub use signal::TradeSignal;
+pub use order::OrderAuditInsert;
+pub use orderbook::{BookViolation, DepthDiff, OrderBook, OrderBookFeatureInsert, OrderBookInsert};
+pub use signal::{Side, TradeSignal};
+pub use ticker::{Ticker24h, Ticker24hInsert};
+pub use trade_flow::{TradeFlow, TradeFlowInsert};
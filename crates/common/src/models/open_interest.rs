@@ -12,4 +12,6 @@ pub struct OpenInterestInsert {
     pub time: f64,
     pub symbol: String,
     pub oi_value: f64,
+    /// Gateway sequence number.
+    pub seq: Option<i64>,
 }
@@ -0,0 +1,19 @@
+/// One order the bot tried to place and what came of it, kept for
+/// reconciliation against the exchange.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderAuditInsert {
+    /// When the order was sent, in seconds.
+    pub client_ts: f64,
+    pub symbol: String,
+    pub side: String,
+    pub quantity: f64,
+    pub order_type: String,
+    /// Exchange order id; `None` if the order never got one.
+    pub order_id: Option<i64>,
+    /// Exchange status (`FILLED`, `EXPIRED`, ...) or `FAILED` when the request
+    /// itself failed.
+    pub status: String,
+    pub executed_qty: Option<f64>,
+    pub cummulative_quote_qty: Option<f64>,
+    pub error: Option<String>,
+}
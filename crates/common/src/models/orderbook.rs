@@ -8,10 +8,196 @@ pub struct OrderBook {
     pub asks: Vec<u8>,
 }
 
+impl OrderBook {
+    /// `{"time", "symbol", "bids": [[price, qty], ...], "asks": [...]}`, best
+    /// level first, for handing a stored book to clients that can't read
+    /// the packed blobs. Prices and quantities are written as the shortest
+    /// decimal that reads back as the stored `f32`, so a stored 0.1 is `0.1`
+    /// rather than `0.10000000149011612`.
+    pub fn to_json_levels(&self) -> serde_json::Value {
+        let levels = |packed: &[u8]| {
+            unpack_levels(packed)
+                .map(|(price, qty)| serde_json::json!([shortest(price), shortest(qty)]))
+                .collect::<Vec<_>>()
+        };
+        serde_json::json!({
+            "time": self.time,
+            "symbol": self.symbol,
+            "bids": levels(&self.bids),
+            "asks": levels(&self.asks),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OrderBookInsert {
     pub time: f64,
     pub symbol: String,
     pub bids: Vec<u8>,
     pub asks: Vec<u8>,
+    /// Gateway sequence number.
+    pub seq: Option<i64>,
+}
+
+impl OrderBookInsert {
+    pub fn bid_volume(&self) -> f64 {
+        packed_volume(&self.bids)
+    }
+
+    pub fn ask_volume(&self) -> f64 {
+        packed_volume(&self.asks)
+    }
+
+    /// Order book imbalance `(bid - ask) / (bid + ask)`, in [-1, 1]. `None`
+    /// for an empty book.
+    pub fn imbalance(&self) -> Option<f64> {
+        imbalance(self.bid_volume(), self.ask_volume())
+    }
+
+    /// Imbalance of only the best `levels` levels on each side, which reacts
+    /// to pressure at the touch that the full book averages away. `None` when
+    /// they hold no volume.
+    pub fn depth_imbalance(&self, levels: usize) -> Option<f64> {
+        imbalance(
+            top_volume(&self.bids, levels),
+            top_volume(&self.asks, levels),
+        )
+    }
+
+    /// Checks the book is shaped like one: bid prices non-increasing, ask
+    /// prices non-decreasing, and the best bid below the best ask. Either
+    /// side may be empty.
+    pub fn validate(&self) -> Result<(), BookViolation> {
+        let sorted = |levels: &[u8], descending: bool| {
+            let prices: Vec<f32> = unpack_levels(levels).map(|(price, _)| price).collect();
+            prices.windows(2).all(|pair| {
+                if descending {
+                    pair[0] >= pair[1]
+                } else {
+                    pair[0] <= pair[1]
+                }
+            })
+        };
+        if !sorted(&self.bids, true) {
+            return Err(BookViolation::UnsortedBids);
+        }
+        if !sorted(&self.asks, false) {
+            return Err(BookViolation::UnsortedAsks);
+        }
+        if let (Some((best_bid, _)), Some((best_ask, _))) = (
+            unpack_levels(&self.bids).next(),
+            unpack_levels(&self.asks).next(),
+        ) && best_bid >= best_ask
+        {
+            return Err(BookViolation::Crossed { best_bid, best_ask });
+        }
+        Ok(())
+    }
+}
+
+/// Why `OrderBookInsert::validate` rejected a book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BookViolation {
+    UnsortedBids,
+    UnsortedAsks,
+    Crossed { best_bid: f32, best_ask: f32 },
+}
+
+impl std::fmt::Display for BookViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsortedBids => write!(f, "bid prices not descending"),
+            Self::UnsortedAsks => write!(f, "ask prices not ascending"),
+            Self::Crossed { best_bid, best_ask } => {
+                write!(
+                    f,
+                    "crossed book, best bid {} >= best ask {}",
+                    best_bid, best_ask
+                )
+            }
+        }
+    }
+}
+
+/// Total quantity of levels packed as little-endian `[price: f32, qty: f32]`.
+pub fn packed_volume(levels: &[u8]) -> f64 {
+    levels
+        .chunks_exact(8)
+        .map(|level| f32::from_le_bytes([level[4], level[5], level[6], level[7]]) as f64)
+        .sum()
+}
+
+/// Total quantity of the first `n` packed levels, best first.
+pub fn top_volume(levels: &[u8], n: usize) -> f64 {
+    unpack_levels(levels)
+        .take(n)
+        .map(|(_, qty)| qty as f64)
+        .sum()
+}
+
+/// `(price, qty)` of each level packed as little-endian `[price: f32, qty: f32]`.
+/// `value` as the `f64` with the same shortest decimal form, since JSON
+/// numbers go through `f64`.
+fn shortest(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(f64::from(value))
+}
+
+pub fn unpack_levels(levels: &[u8]) -> impl Iterator<Item = (f32, f32)> + '_ {
+    levels.chunks_exact(8).map(|level| {
+        (
+            f32::from_le_bytes([level[0], level[1], level[2], level[3]]),
+            f32::from_le_bytes([level[4], level[5], level[6], level[7]]),
+        )
+    })
+}
+
+/// `(bid_vol - ask_vol) / (bid_vol + ask_vol)`, `None` when both are zero.
+pub fn imbalance(bid_vol: f64, ask_vol: f64) -> Option<f64> {
+    let total = bid_vol + ask_vol;
+    (total > 0.0).then(|| (bid_vol - ask_vol) / total)
+}
+
+/// Order book imbalance sampled from a depth snapshot, for offline features.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderBookFeatureInsert {
+    pub time: f64,
+    pub symbol: String,
+    /// Over every level of the snapshot (20 for `depth20`).
+    pub obi: f64,
+    /// Over the best level only.
+    pub obi_1: Option<f64>,
+    /// Over the best 5 levels.
+    pub obi_5: Option<f64>,
+    pub bid_vol: f64,
+    pub ask_vol: f64,
+}
+
+impl OrderBookFeatureInsert {
+    /// Features of `book`, or `None` when it has no volume on either side.
+    pub fn from_book(book: &OrderBookInsert) -> Option<Self> {
+        let bid_vol = book.bid_volume();
+        let ask_vol = book.ask_volume();
+        Some(Self {
+            time: book.time,
+            symbol: book.symbol.clone(),
+            obi: imbalance(bid_vol, ask_vol)?,
+            obi_1: book.depth_imbalance(1),
+            obi_5: book.depth_imbalance(5),
+            bid_vol,
+            ask_vol,
+        })
+    }
+}
+
+/// One `@depth` diff: every level that changed between update ids
+/// `first_update_id` and `final_update_id`. A quantity of 0 removes the level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthDiff {
+    pub symbol: String,
+    pub first_update_id: u64,
+    pub final_update_id: u64,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+    /// Gateway sequence number.
+    pub seq: Option<i64>,
 }
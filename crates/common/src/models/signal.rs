@@ -1,9 +1,57 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+/// Direction of a market order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    /// The `side` parameter Binance expects.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Buy => "BUY",
+            Self::Sell => "SELL",
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradeSignal {
     pub symbol: String,
-    pub side: String, // "BUY" or "SELL"
+    pub side: Side,
     pub quantity: f64,
-    pub reason: String, // "AI_CONFIDENCE_0.85"
+    /// The model's probability for `side`, 0 to 1.
+    pub confidence: f32,
+}
+
+impl TradeSignal {
+    /// Why the signal was sent, as logged and audited: `AI_CONFIDENCE_0.85`.
+    pub fn reason(&self) -> String {
+        format!("AI_CONFIDENCE_{:.2}", self.confidence)
+    }
+}
+
+/// `BUY 0.5 BTCUSDT (AI_CONFIDENCE_0.85)`.
+impl fmt::Display for TradeSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ({})",
+            self.side,
+            self.quantity,
+            self.symbol,
+            self.reason()
+        )
+    }
 }
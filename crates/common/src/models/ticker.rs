@@ -0,0 +1,33 @@
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct Ticker24h {
+    pub id: i32,
+    pub time: f64,
+    pub symbol_id: i32,
+    pub price_change: f64,
+    pub price_change_pct: f64,
+    pub last_price: f64,
+    pub open_price: f64,
+    pub high_price: f64,
+    pub low_price: f64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    pub trade_count: i64,
+}
+
+/// Binance's rolling 24h statistics of one symbol, as of `time` (the window's
+/// close, in seconds).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ticker24hInsert {
+    pub time: f64,
+    pub symbol: String,
+    pub price_change: f64,
+    pub price_change_pct: f64,
+    pub last_price: f64,
+    pub open_price: f64,
+    pub high_price: f64,
+    pub low_price: f64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    pub trade_count: i64,
+}
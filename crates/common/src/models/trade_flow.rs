@@ -0,0 +1,19 @@
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct TradeFlow {
+    pub id: i32,
+    pub symbol_id: i32,
+    pub bucket_start: i64,
+    pub buy_vol: f64,
+    pub sell_vol: f64,
+    pub trade_count: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeFlowInsert {
+    pub symbol: String,
+    pub bucket_start: i64,
+    pub buy_vol: f64,
+    pub sell_vol: f64,
+    pub trade_count: i32,
+}
@@ -0,0 +1,176 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Default `DATA_DIR`, under `WORKDIR`.
+pub const DEFAULT_DATA_DIR: &str = "sqlitedata";
+
+/// Where the bot keeps its files, built once from `WORKDIR` so no other code
+/// joins path strings by hand.
+///
+/// ```text
+/// <workdir>/
+///   <data_dir>/          DATA_DIR, `sqlitedata` by default
+///     current/           crypto_<year>_<week>.db, the file being written
+///     archived/          <stem>.sql.zst dumps left by `dump_db.sh`
+///     .backup/           weekly files `dump_db.sh` has moved aside
+///   arrowdata/           per-symbol trade streams with AGG_TRADE_SINK=arrow
+/// ```
+///
+/// `current`, `archived` and `.backup` are the names `utils/dump_db.sh`
+/// expects inside the directory it is given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paths {
+    workdir: PathBuf,
+    data_dir: PathBuf,
+    model: PathBuf,
+}
+
+impl Paths {
+    /// `workdir` is made absolute against the current directory, so a
+    /// relative one keeps pointing at the same place.
+    pub fn new(workdir: impl AsRef<Path>) -> Self {
+        let workdir = absolute(workdir.as_ref());
+        Self {
+            data_dir: workdir.join(DEFAULT_DATA_DIR),
+            model: absolute(Path::new(crate::config::DEFAULT_MODEL_PATH)),
+            workdir,
+        }
+    }
+
+    /// `WORKDIR` with `DATA_DIR` applied, for commands that only read the
+    /// databases and shouldn't need the rest of `Config`.
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name| {
+            env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let workdir = var("WORKDIR").ok_or("WORKDIR must be set")?;
+        let data_dir = var("DATA_DIR").unwrap_or_else(|| DEFAULT_DATA_DIR.to_string());
+        Ok(Self::new(workdir).with_data_dir(data_dir))
+    }
+
+    /// Puts the databases in `dir` instead of `<workdir>/sqlitedata`. A
+    /// relative `dir` is taken as under the workdir.
+    pub fn with_data_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.data_dir = self.workdir.join(dir);
+        self
+    }
+
+    /// The ONNX model; a relative `path` is taken from the current directory.
+    pub fn with_model(mut self, path: impl AsRef<Path>) -> Self {
+        self.model = absolute(path.as_ref());
+        self
+    }
+
+    pub fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+
+    /// The directory handed to `dump_db.sh`.
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    pub fn current_dir(&self) -> PathBuf {
+        self.data_dir.join("current")
+    }
+
+    pub fn archive_dir(&self) -> PathBuf {
+        self.data_dir.join("archived")
+    }
+
+    pub fn backup_dir(&self) -> PathBuf {
+        self.data_dir.join(".backup")
+    }
+
+    pub fn arrow_dir(&self) -> PathBuf {
+        self.workdir.join("arrowdata")
+    }
+
+    pub fn model(&self) -> &Path {
+        &self.model
+    }
+
+    /// The database file of `year`/`week`, whether or not it exists.
+    pub fn weekly_db(&self, year: i32, week: u32) -> PathBuf {
+        self.current_dir()
+            .join(format!("{}.db", weekly_stem(year, week)))
+    }
+
+    /// Where `dump_db.sh` leaves the compressed dump of `year`/`week`.
+    pub fn archived_dump(&self, year: i32, week: u32) -> PathBuf {
+        self.archive_dir()
+            .join(format!("{}.sql.zst", weekly_stem(year, week)))
+    }
+
+    /// Creates every directory of the layout and checks the bot can write
+    /// to them, so a wrong or read-only `WORKDIR` fails at startup rather
+    /// than at the first flush.
+    pub fn prepare(&self) -> io::Result<()> {
+        for dir in [self.current_dir(), self.archive_dir(), self.backup_dir()] {
+            fs::create_dir_all(&dir).map_err(|e| in_dir(&dir, "can't be created", e))?;
+            let probe = dir.join(format!(".write_test_{}", std::process::id()));
+            fs::write(&probe, b"").map_err(|e| in_dir(&dir, "is not writable", e))?;
+            fs::remove_file(&probe).map_err(|e| in_dir(&dir, "is not writable", e))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<String> for Paths {
+    fn from(workdir: String) -> Self {
+        Self::new(workdir)
+    }
+}
+
+impl From<&str> for Paths {
+    fn from(workdir: &str) -> Self {
+        Self::new(workdir)
+    }
+}
+
+/// `crypto_<year>_<week>`, the name of a weekly file without extension.
+pub fn weekly_stem(year: i32, week: u32) -> String {
+    format!("crypto_{}_{:02}", year, week)
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn in_dir(dir: &Path, problem: &str, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{} {}: {}", dir.display(), problem, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_and_prepare() {
+        let paths = Paths::new("relative/work");
+        assert!(paths.workdir().is_absolute());
+        assert!(paths.data_dir().ends_with("relative/work/sqlitedata"));
+
+        let root = std::env::temp_dir().join(format!("paths_{}", std::process::id()));
+        let paths = Paths::new(&root).with_data_dir("db");
+        assert_eq!(
+            paths.weekly_db(2026, 3),
+            root.join("db/current/crypto_2026_03.db")
+        );
+        assert_eq!(
+            Paths::new(&root).with_data_dir("/mnt/fast").current_dir(),
+            PathBuf::from("/mnt/fast/current")
+        );
+
+        paths.prepare().unwrap();
+        assert!(paths.archive_dir().is_dir() && paths.backup_dir().is_dir());
+        assert_eq!(fs::read_dir(paths.current_dir()).unwrap().count(), 0);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,133 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Renames applied unless `SYMBOL_ALIASES` says otherwise: MATIC became POL
+/// in September 2024.
+pub const DEFAULT_SYMBOL_ALIASES: &str = "maticusdt=polusdt";
+
+/// Symbols paused at runtime: their events still stream through the market
+/// broadcast, but nothing is written to the database and no orders are sent.
+///
+/// Cloning shares the same set. Symbols are compared case-insensitively.
+#[derive(Debug, Clone, Default)]
+pub struct DisabledSymbols(Arc<RwLock<HashSet<String>>>);
+
+impl DisabledSymbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pauses `symbol`. Returns `false` if it was already paused.
+    pub fn disable(&self, symbol: &str) -> bool {
+        self.0
+            .write()
+            .expect("disabled symbols poisoned")
+            .insert(symbol.to_lowercase())
+    }
+
+    /// Resumes `symbol`. Returns `false` if it wasn't paused.
+    pub fn enable(&self, symbol: &str) -> bool {
+        self.0
+            .write()
+            .expect("disabled symbols poisoned")
+            .remove(&symbol.to_lowercase())
+    }
+
+    pub fn is_disabled(&self, symbol: &str) -> bool {
+        let set = self.0.read().expect("disabled symbols poisoned");
+        // Skip the lowercase allocation on the hot path when nothing is paused.
+        !set.is_empty() && set.contains(&symbol.to_lowercase())
+    }
+
+    pub fn snapshot(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self
+            .0
+            .read()
+            .expect("disabled symbols poisoned")
+            .iter()
+            .cloned()
+            .collect();
+        symbols.sort();
+        symbols
+    }
+}
+
+/// Base and quote asset of a pair as `exchangeInfo` lists them, e.g. `ETH`
+/// and `BTC` for `ETHBTC`. Uppercase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolAssets {
+    pub base: String,
+    pub quote: String,
+}
+
+/// Old symbol -> current symbol, for pairs Binance has renamed. Streams are
+/// subscribed and rows stored under the current name, whichever one the
+/// configuration uses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolAliases(HashMap<String, String>);
+
+impl SymbolAliases {
+    /// Parses `old=new,old=new`. `Config` reads it from `SYMBOL_ALIASES`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut aliases = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (old, new) = entry
+                .split_once('=')
+                .map(|(old, new)| (old.trim().to_lowercase(), new.trim().to_lowercase()))
+                .filter(|(old, new)| !old.is_empty() && !new.is_empty())
+                .ok_or_else(|| format!("expected 'old=new', got '{}'", entry))?;
+            aliases.insert(old, new);
+        }
+        Ok(Self(aliases))
+    }
+
+    /// `symbol` under its current name, in the same case (all uppercase
+    /// input gives uppercase output).
+    pub fn canonical<'a>(&self, symbol: &'a str) -> Cow<'a, str> {
+        if self.0.is_empty() {
+            return Cow::Borrowed(symbol);
+        }
+        match self.0.get(&symbol.to_lowercase()) {
+            Some(new) if symbol.bytes().any(|b| b.is_ascii_lowercase()) => Cow::Owned(new.clone()),
+            Some(new) => Cow::Owned(new.to_uppercase()),
+            None => Cow::Borrowed(symbol),
+        }
+    }
+
+    /// Every `(old, new)` pair, lowercase.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(old, new)| (old.as_str(), new.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_is_shared_and_case_insensitive() {
+        let disabled = DisabledSymbols::new();
+        let shared = disabled.clone();
+
+        assert!(disabled.disable("BTCUSDT"));
+        assert!(!disabled.disable("btcusdt"));
+        assert!(shared.is_disabled("btcUSDT"));
+        assert!(!shared.is_disabled("ETHUSDT"));
+        assert_eq!(shared.snapshot(), vec!["btcusdt".to_string()]);
+
+        assert!(shared.enable("BtcUsdt"));
+        assert!(!disabled.is_disabled("BTCUSDT"));
+    }
+
+    #[test]
+    fn test_aliases_keep_case_and_pass_others_through() {
+        let aliases = SymbolAliases::parse(DEFAULT_SYMBOL_ALIASES).unwrap();
+        assert_eq!(aliases.canonical("maticusdt"), "polusdt");
+        assert_eq!(aliases.canonical("MATICUSDT"), "POLUSDT");
+        assert_eq!(aliases.canonical("BTCUSDT"), "BTCUSDT");
+
+        assert_eq!(SymbolAliases::parse(""), Ok(SymbolAliases::default()));
+        assert!(SymbolAliases::parse("maticusdt").is_err());
+    }
+}
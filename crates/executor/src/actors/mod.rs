@@ -1,4 +1,4 @@
 pub mod supervisor;
 
 // Re-export from common
-pub use common::actors::{Actor, ActorType, ControlMessage};
\ No newline at end of file
+pub use common::actors::{Actor, ActorType, ControlMessage};
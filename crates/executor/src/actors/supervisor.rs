@@ -1,6 +1,8 @@
 use std::{collections::HashMap, time::Duration};
 use tracing::{error, info, warn};
 
+use common::symbols::DisabledSymbols;
+use market_data::services::market_gateway::GatewayCommand;
 use tokio::{
     sync::mpsc,
     task::JoinHandle,
@@ -10,13 +12,26 @@ use uuid::Uuid;
 
 use crate::actors::{Actor, ActorType, ControlMessage};
 
+/// How often the supervisor logs the throughput its services reported.
+const METRICS_SUMMARY_PERIOD: Duration = Duration::from_secs(60);
+
+type ActorFactory = Box<dyn Fn() -> Box<dyn Actor> + Send + Sync>;
+
 pub struct Supervisor {
-    actor_factories: HashMap<ActorType, Box<dyn Fn() -> Box<dyn Actor> + Send + Sync>>,
+    actor_factories: HashMap<ActorType, ActorFactory>,
+    /// Factories of the types paused with `StopType`, kept for `StartType`.
+    stopped_factories: HashMap<ActorType, ActorFactory>,
     pulses: HashMap<Uuid, Instant>,
     handles: HashMap<Uuid, JoinHandle<()>>,
     actor_types: HashMap<Uuid, ActorType>,
     tx: mpsc::Sender<ControlMessage>,
     rx: Option<mpsc::Receiver<ControlMessage>>,
+    disabled_symbols: DisabledSymbols,
+    /// Where `AddSymbol`/`RemoveSymbol` are forwarded, if a gateway is
+    /// registered.
+    gateway_commands: Option<mpsc::Sender<GatewayCommand>>,
+    /// `(rows_written, lag_events)` per actor type since the last summary.
+    throughput: HashMap<ActorType, (u64, u64)>,
 }
 
 impl Supervisor {
@@ -24,41 +39,117 @@ impl Supervisor {
         let (tx, rx) = mpsc::channel(512);
         Self {
             actor_factories: HashMap::new(),
+            stopped_factories: HashMap::new(),
             pulses: HashMap::new(),
             handles: HashMap::new(),
             actor_types: HashMap::new(),
             tx,
             rx: Some(rx),
+            disabled_symbols: DisabledSymbols::new(),
+            gateway_commands: None,
+            throughput: HashMap::new(),
         }
     }
 
+    /// The set `DisableSymbol`/`EnableSymbol` toggle, shared with the services
+    /// that consult it.
+    pub fn with_disabled_symbols(mut self, disabled_symbols: DisabledSymbols) -> Self {
+        self.disabled_symbols = disabled_symbols;
+        self
+    }
+
+    /// The gateway command queue `AddSymbol`/`RemoveSymbol` are forwarded to.
+    pub fn with_gateway_commands(mut self, commands: mpsc::Sender<GatewayCommand>) -> Self {
+        self.gateway_commands = Some(commands);
+        self
+    }
+
     pub fn sender(&self) -> mpsc::Sender<ControlMessage> {
         self.tx.clone()
     }
 
-    pub fn register_actor(
-        &mut self,
-        actor_type: ActorType,
-        factory: Box<dyn Fn() -> Box<dyn Actor> + Send + Sync>,
-    ) {
+    pub fn register_actor(&mut self, actor_type: ActorType, factory: ActorFactory) {
         self.actor_factories.insert(actor_type, factory);
     }
 
-    fn initialize_actors(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) {
-        let actors: Vec<(ActorType, Box<dyn Actor>)> = self
-            .actor_factories
+    /// Aborts the running actors of `actor_type` and sets its factory aside,
+    /// so it is not restarted. Returns how many actors were stopped.
+    fn stop_type(&mut self, actor_type: ActorType) -> usize {
+        if let Some(factory) = self.actor_factories.remove(&actor_type) {
+            self.stopped_factories.insert(actor_type, factory);
+        }
+        let ids: Vec<Uuid> = self
+            .actor_types
             .iter()
-            .map(|f| (f.0.clone(), f.1()))
+            .filter(|(_, t)| **t == actor_type)
+            .map(|(id, _)| *id)
             .collect();
+        for id in &ids {
+            self.pulses.remove(id);
+            self.actor_types.remove(id);
+            if let Some(handle) = self.handles.remove(id) {
+                handle.abort();
+            }
+        }
+        ids.len()
+    }
+
+    /// Registers a type stopped with `stop_type` again and spawns its actor.
+    /// False if it wasn't stopped.
+    fn start_type(&mut self, actor_type: ActorType, tx: mpsc::Sender<ControlMessage>) -> bool {
+        let Some(factory) = self.stopped_factories.remove(&actor_type) else {
+            return false;
+        };
+        let actor = factory();
+        self.actor_factories.insert(actor_type, factory);
+        self.spawn_actor(actor, actor_type, tx);
+        true
+    }
+
+    /// Queues `command` for the gateway without waiting for it, so a gateway
+    /// that is restarting can't stall the supervisor.
+    fn command_gateway(&self, command: GatewayCommand) {
+        match &self.gateway_commands {
+            Some(commands) => {
+                if let Err(e) = commands.try_send(command) {
+                    error!("Failed to queue gateway command: {}", e);
+                }
+            }
+            None => warn!("No gateway registered, ignoring {:?}", command),
+        }
+    }
+
+    fn initialize_actors(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) {
+        let actors: Vec<(ActorType, Box<dyn Actor>)> =
+            self.actor_factories.iter().map(|f| (*f.0, f.1())).collect();
 
         actors.into_iter().for_each(|(actor, factory)| {
             self.spawn_actor(factory, actor, supervisor_tx.clone());
         });
     }
 
+    /// Sends `ShutdownAll` on SIGINT (Ctrl-C), so a signal takes the same
+    /// path out of `start` as a shutdown requested from code.
+    pub fn stop_on_ctrl_c(&self) {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            match tokio::signal::ctrl_c().await {
+                Ok(()) => {
+                    info!("SIGINT received, shutting down.");
+                    let _ = tx.send(ControlMessage::ShutdownAll).await;
+                }
+                Err(e) => error!("Failed to listen for SIGINT: {}", e),
+            }
+        });
+    }
+
+    /// Runs the actors until a `ControlMessage::ShutdownAll` arrives on
+    /// `sender()`, then stops them all (see `shutdown`) and returns.
     pub async fn start(&mut self) {
         let mut check_interval = time::interval(Duration::from_secs(1));
         let timeout_duration = Duration::from_secs(3);
+        let mut summary_interval = time::interval(METRICS_SUMMARY_PERIOD);
+        summary_interval.reset();
 
         let supervisor_tx = self.tx.clone();
         let mut supervisor_rx = self.rx.take().expect("Supervisor started twice");
@@ -74,7 +165,11 @@ impl Supervisor {
                             self.spawn_actor(actor, ActorType::Dynamic, tx);
                         },
                         ControlMessage::Heartbeat(actor_id) => {
-                            self.pulses.insert(actor_id, Instant::now());
+                            // A stopped or replaced actor's late heartbeat
+                            // must not bring its id back.
+                            if self.actor_types.contains_key(&actor_id) {
+                                self.pulses.insert(actor_id, Instant::now());
+                            }
                         }
                         ControlMessage::Shutdown(actor_id) => {
                             warn!("{:?} is shutting down gracefully.", actor_id);
@@ -86,9 +181,56 @@ impl Supervisor {
                         },
                         ControlMessage::Error(actor_id, error_msg) => {
                             error!("Actor {:?} reported error: {}", actor_id, error_msg);
-                            self.pulses.insert(actor_id, Instant::now());
+                            if self.actor_types.contains_key(&actor_id) {
+                                self.pulses.insert(actor_id, Instant::now());
+                            }
+                        },
+                        ControlMessage::Metrics(actor_id, rows, lag) => {
+                            if let Some(&actor_type) = self.actor_types.get(&actor_id) {
+                                let total = self.throughput.entry(actor_type).or_default();
+                                total.0 += rows;
+                                total.1 += lag;
+                            }
+                        },
+                        ControlMessage::DisableSymbol(symbol) => {
+                            if self.disabled_symbols.disable(&symbol) {
+                                warn!("{} disabled: still streaming, no DB writes or orders.", symbol);
+                            }
+                        },
+                        ControlMessage::EnableSymbol(symbol) => {
+                            if self.disabled_symbols.enable(&symbol) {
+                                info!("{} re-enabled.", symbol);
+                            }
+                        },
+                        ControlMessage::AddSymbol(symbol) => {
+                            self.command_gateway(GatewayCommand::AddSymbol(symbol));
+                        },
+                        ControlMessage::RemoveSymbol(symbol) => {
+                            self.command_gateway(GatewayCommand::RemoveSymbol(symbol));
+                        },
+                        ControlMessage::StopType(actor_type) => {
+                            let stopped = self.stop_type(actor_type);
+                            warn!("{:?} stopped ({} running), not restarted until started again.", actor_type, stopped);
+                        },
+                        ControlMessage::StartType(actor_type) => {
+                            if self.start_type(actor_type, supervisor_tx.clone()) {
+                                info!("{:?} started again.", actor_type);
+                            } else {
+                                warn!("{:?} was not stopped, nothing to start.", actor_type);
+                            }
                         },
+                        ControlMessage::ShutdownAll => {
+                            info!("Shutdown requested.");
+                            break;
+                        },
+                    }
+                }
+
+                _ = summary_interval.tick() => {
+                    if let Some(summary) = throughput_summary(&self.throughput) {
+                        info!("Throughput over the last {:?}: {}", METRICS_SUMMARY_PERIOD, summary);
                     }
+                    self.throughput.clear();
                 }
 
                 _ = check_interval.tick() => {
@@ -99,7 +241,7 @@ impl Supervisor {
                     for (key, &value) in self.pulses.iter() {
                         if value < dead_timeout {
                             warn!("{:?} is unresponsive!", key);
-                            dead_actors.push(key.clone());
+                            dead_actors.push(*key);
                             if let Some(handle) = self.handles.get(key) {
                                 handle.abort();
                             }
@@ -122,6 +264,24 @@ impl Supervisor {
                 }
             }
         }
+
+        self.shutdown().await;
+    }
+
+    /// Aborts every running actor and waits for its task to finish.
+    ///
+    /// Dropping an actor's task closes its DB writer channel, which makes the
+    /// writer flush whatever it still has buffered.
+    pub async fn shutdown(&mut self) {
+        info!("Stopping {} actors", self.handles.len());
+        for handle in self.handles.values() {
+            handle.abort();
+        }
+        for (_, handle) in self.handles.drain() {
+            let _ = handle.await;
+        }
+        self.pulses.clear();
+        self.actor_types.clear();
     }
 
     fn spawn_actor(
@@ -141,3 +301,200 @@ impl Supervisor {
         self.pulses.insert(actor_id, Instant::now());
     }
 }
+
+/// One `<actor>: <rows> rows` entry per actor type, with its lagged events
+/// when there were any, or `None` if nothing reported.
+fn throughput_summary(throughput: &HashMap<ActorType, (u64, u64)>) -> Option<String> {
+    let mut entries: Vec<String> = throughput
+        .iter()
+        .map(|(actor_type, &(rows, lag))| match lag {
+            0 => format!("{:?}: {} rows", actor_type, rows),
+            lag => format!("{:?}: {} rows, {} events lagged", actor_type, rows, lag),
+        })
+        .collect();
+    if entries.is_empty() {
+        return None;
+    }
+    entries.sort();
+    Some(entries.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use common::actors::HEARTBEAT_INTERVAL;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct IdleActor(Uuid);
+
+    #[async_trait]
+    impl Actor for IdleActor {
+        fn name(&self) -> ActorType {
+            ActorType::LiveCacheActor
+        }
+
+        fn id(&self) -> Uuid {
+            self.0
+        }
+
+        async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+            let _heartbeat = self.spawn_heartbeat(supervisor_tx);
+            std::future::pending().await
+        }
+    }
+
+    /// Crashes on its first run, by panicking or by returning an error, and
+    /// idles with a heartbeat on every later one. `runs` counts the runs.
+    struct FlakyActor {
+        id: Uuid,
+        runs: Arc<AtomicUsize>,
+        panics: bool,
+    }
+
+    #[async_trait]
+    impl Actor for FlakyActor {
+        fn name(&self) -> ActorType {
+            ActorType::TradeFlowActor
+        }
+
+        fn id(&self) -> Uuid {
+            self.id
+        }
+
+        async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+            if self.runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                if self.panics {
+                    panic!("first run panics");
+                }
+                anyhow::bail!("first run fails");
+            }
+            let _heartbeat = self.spawn_heartbeat(supervisor_tx);
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restarts_actors_that_crash_or_fail() {
+        let mut supervisor = Supervisor::new();
+        let panicking = Arc::new(AtomicUsize::new(0));
+        let failing = Arc::new(AtomicUsize::new(0));
+        for (actor_type, runs, panics) in [
+            (ActorType::TradeFlowActor, &panicking, true),
+            (ActorType::LiveCacheActor, &failing, false),
+        ] {
+            let runs = runs.clone();
+            supervisor.register_actor(
+                actor_type,
+                Box::new(move || {
+                    Box::new(FlakyActor {
+                        id: Uuid::new_v4(),
+                        runs: runs.clone(),
+                        panics,
+                    })
+                }),
+            );
+        }
+
+        // Neither crashed actor sends heartbeats, so both are restarted once
+        // their last pulse is older than the 3s dead timeout.
+        let tx = supervisor.sender();
+        let (watched_panicking, watched_failing) = (panicking.clone(), failing.clone());
+        tokio::spawn(async move {
+            let restarted = || {
+                watched_panicking.load(Ordering::SeqCst) >= 2
+                    && watched_failing.load(Ordering::SeqCst) >= 2
+            };
+            let _ = time::timeout(Duration::from_secs(10), async {
+                while !restarted() {
+                    time::sleep(Duration::from_millis(50)).await;
+                }
+            })
+            .await;
+            tx.send(ControlMessage::ShutdownAll).await.unwrap();
+        });
+
+        time::timeout(Duration::from_secs(15), supervisor.start())
+            .await
+            .expect("start should return after ShutdownAll");
+        assert_eq!(panicking.load(Ordering::SeqCst), 2);
+        assert_eq!(failing.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_throughput_summary_lists_lag_only_where_it_happened() {
+        assert_eq!(throughput_summary(&HashMap::new()), None);
+        let throughput = HashMap::from([
+            (ActorType::OrderBookActor, (600, 0)),
+            (ActorType::AggTradeActor, (12_000, 35)),
+        ]);
+        assert_eq!(
+            throughput_summary(&throughput).unwrap(),
+            "AggTradeActor: 12000 rows, 35 events lagged; OrderBookActor: 600 rows"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_type_pauses_until_started() {
+        let mut supervisor = Supervisor::new();
+        supervisor.register_actor(
+            ActorType::LiveCacheActor,
+            Box::new(|| Box::new(IdleActor(Uuid::new_v4()))),
+        );
+        let tx = supervisor.sender();
+        supervisor.initialize_actors(tx.clone());
+
+        assert_eq!(supervisor.stop_type(ActorType::LiveCacheActor), 1);
+        assert!(supervisor.handles.is_empty() && supervisor.pulses.is_empty());
+        assert!(supervisor.actor_factories.is_empty(), "not restarted");
+        assert!(!supervisor.start_type(ActorType::OrderBookActor, tx.clone()));
+
+        assert!(supervisor.start_type(ActorType::LiveCacheActor, tx));
+        assert_eq!(supervisor.handles.len(), 1);
+        assert!(
+            supervisor
+                .actor_factories
+                .contains_key(&ActorType::LiveCacheActor)
+        );
+        supervisor.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_stopped_actor_heartbeat_stops() {
+        let mut supervisor = Supervisor::new();
+        supervisor.register_actor(
+            ActorType::LiveCacheActor,
+            Box::new(|| Box::new(IdleActor(Uuid::new_v4()))),
+        );
+        let mut rx = supervisor.rx.take().unwrap();
+        supervisor.initialize_actors(supervisor.sender());
+        time::sleep(HEARTBEAT_INTERVAL).await;
+
+        supervisor.stop_type(ActorType::LiveCacheActor);
+        tokio::task::yield_now().await;
+        while rx.try_recv().is_ok() {}
+        time::sleep(HEARTBEAT_INTERVAL * 2).await;
+        assert!(rx.try_recv().is_err(), "no heartbeat after stop");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_all_stops_actors_and_returns() {
+        let mut supervisor = Supervisor::new();
+        supervisor.register_actor(
+            ActorType::LiveCacheActor,
+            Box::new(|| Box::new(IdleActor(Uuid::new_v4()))),
+        );
+
+        let tx = supervisor.sender();
+        tokio::spawn(async move {
+            time::sleep(Duration::from_millis(100)).await;
+            tx.send(ControlMessage::ShutdownAll).await.unwrap();
+        });
+
+        time::timeout(Duration::from_secs(5), supervisor.start())
+            .await
+            .expect("start should return after ShutdownAll");
+        assert!(supervisor.handles.is_empty());
+    }
+}
@@ -1,44 +1,84 @@
+use anyhow::Context;
 use dotenvy::dotenv;
+use market_data::remote::exchange_info::{ExchangeInfoClient, partition_symbols};
+use market_data::remote::exchange_status::ExchangeStatus;
+use market_data::remote::kline_rest::KlineRestClient;
+use market_data::remote::{BinanceClient, Ticker24hClient};
 use market_data::services::forceorder_service::ForceOrderService;
+use market_data::services::funding_service::FundingService;
 use market_data::services::markprice_service::MarkPriceService;
 use market_data::services::openinterest_service::OpenInterestService;
-use std::{env, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::Arc,
+    time::Duration,
+};
 use storage::data_manager::DataManager;
-use tokio::sync::broadcast;
-use tracing::debug;
+use storage::db::StorageConfig;
+use storage::maintenance::{CompactionConfig, MaintenanceActor};
+use storage::repositories::AggTradeRepository;
+use strategy::config::{SharedStrategyConfig, StrategyConfig};
+use strategy::inference::{DEFAULT_SCORE_THRESHOLD, DEFAULT_TEMPERATURE};
+use strategy::services::inference_worker::FEATURE_COUNT;
+use strategy::services::strategy_service::{
+    DEFAULT_MAX_EVENT_AGE, DEFAULT_OBI_LEVELS, StrategyFeeds, StrategyService,
+};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::{
+    sync::{broadcast, mpsc},
+    time,
+};
+use tracing::{debug, error, info, warn};
 
-use common::actors::ActorType;
+use common::actors::{ActorType, ControlMessage};
+use common::clock::{Clock, SystemClock};
+use common::config::{BinanceConfig, Config};
 use common::logger;
-use market_data::services::aggtrade_service::AggTradeService;
-use market_data::services::klines_service::KlinesService;
-use market_data::services::market_gateway::{MarketEvent, MarketGateway};
-use market_data::services::orderbook_service::OrderBookService;
+use common::models::TradeSignal;
+use common::paths::Paths;
+use common::symbols::{SymbolAliases, SymbolAssets};
+use market_data::backpressure::{BackpressurePolicy, WriterLimits};
+use market_data::connection_stats::ReconnectWarning;
+use market_data::raw_capture::{RawCapture, RawCaptureConfig, RawCaptureWriter};
+use market_data::services::aggtrade_service::{AggTradeService, DEFAULT_TRADE_BATCH};
+use market_data::services::balance_snapshot::{
+    BalanceSnapshotActor, DEFAULT_BALANCE_SNAPSHOT_MINUTES,
+};
+use market_data::services::candle_service::AggTradeCandleService;
+use market_data::services::event_router::{EventRouter, EventRouterActor};
+use market_data::services::klines_service::{DEFAULT_FLUSH_ON_CLOSE, KlinesService};
+use market_data::services::live_cache::{DEFAULT_LIVE_CACHE_TRADES, LiveCache, LiveCacheService};
+use market_data::services::market_gateway::{
+    DEFAULT_ROTATE_AFTER, EventKind, EventSender, GatewayCommand, GatewayControl, MarketGateway,
+    event_channel, spawn_queue_monitor,
+};
+use market_data::services::orderbook_feature_service::{
+    DEFAULT_OBI_SAMPLE_MS, OrderBookFeatureService,
+};
+use market_data::services::orderbook_service::{DEFAULT_ORDERBOOK_BATCH, OrderBookService};
+use market_data::services::ticker_service::{DEFAULT_TICKER_24H_SECONDS, Ticker24hActor};
+use market_data::services::trade_flow_service::TradeFlowService;
+use market_data::streams::StreamSelection;
 
 use crate::actors::supervisor::Supervisor;
+use crate::services::circuit_breaker::BreakerConfig;
+use crate::services::execution_service::{DEFAULT_ACCOUNT_REFRESH, ExecutionService};
+use crate::services::notifier::{AlertThrottle, NotificationService, notifier_from_config};
 
 mod actors;
 mod services;
 
-const SYMBOLS: &[&str; 15] = &[
-    // Core (7)
-    "btcusdt",
-    "ethusdt",
-    "bnbusdt",
-    "solusdt",
-    "avaxusdt",
-    "nearusdt",
-    "maticusdt",
-    // Alpha (5)
-    "dogeusdt",
-    "shibusdt",
-    "pepeusdt",
-    "wifiusdt",
-    "bonkusdt",
-    // Macro (3)
-    "xrpusdt",
-    "adausdt",
-    "dotusdt",
-];
+/// How often the queue depth of the market channel is logged.
+const MARKET_CHANNEL_MONITOR_PERIOD: Duration = Duration::from_secs(30);
+
+/// How often row counts and file sizes of the active database are logged.
+const DB_STATS_PERIOD: Duration = Duration::from_secs(300);
+
+/// Groups of `DEFAULT_SYMBOLS` used for per-group risk, unless `SYMBOL_GROUPS` overrides it.
+const DEFAULT_SYMBOL_GROUPS: &str = "core=btcusdt,ethusdt,bnbusdt,solusdt,avaxusdt,nearusdt,maticusdt;\
+    alpha=dogeusdt,shibusdt,pepeusdt,wifusdt,bonkusdt;\
+    macro=xrpusdt,adausdt,dotusdt";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -46,105 +86,939 @@ async fn main() -> anyhow::Result<()> {
     dotenv().ok();
     debug!("System starting up...");
 
+    // Exporting only reads a database, so it needs no more than its path.
+    if env::args().nth(1).as_deref() == Some("export") {
+        let paths =
+            Paths::from_env().map_err(|e| anyhow::anyhow!("Invalid configuration:\n{}", e))?;
+        return export(&paths, env::args().skip(2).collect()).await;
+    }
+
+    // Paths, endpoints and credentials, validated before anything is spawned.
+    let config =
+        Arc::new(Config::from_env().map_err(|e| anyhow::anyhow!("Invalid configuration:\n{}", e))?);
+
+    let paths = config.paths();
+    paths.prepare().context("WORKDIR/DATA_DIR can't be used")?;
+    info!("Databases in {}", paths.data_dir().display());
+
     let mut supervisor = Supervisor::new();
     let supervisor_tx = supervisor.sender();
 
-    let data_folder = env::var("WORKDIR")?;
-    let data_manager = DataManager::new(data_folder, supervisor_tx).await?;
+    let storage_config = StorageConfig {
+        symbol_aliases: config.symbol_aliases.clone(),
+        ..StorageConfig::from_env().map_err(|e| anyhow::anyhow!("Invalid storage config: {}", e))?
+    };
+    let data_manager = DataManager::new(paths.clone(), supervisor_tx, storage_config).await?;
+    data_manager.set_backup_scripts(config.utils_dir.clone());
+    // `DisableSymbol`/`EnableSymbol` toggle the set the services check before writing.
+    supervisor = supervisor.with_disabled_symbols(data_manager.disabled_symbols().clone());
 
-    let (market_tx, _) = broadcast::channel::<Arc<MarketEvent>>(10_000);
+    // Human-facing alerts (weekly backup reports for now) go out through `NOTIFIER`.
+    let (notify_tx, _) = broadcast::channel::<String>(64);
+    data_manager.set_notifier(notify_tx.clone());
+    let throttle =
+        AlertThrottle::from_env().map_err(|e| anyhow::anyhow!("Invalid alert throttle: {}", e))?;
+    tokio::spawn(
+        NotificationService::new(notifier_from_config(&config.notifier))
+            .with_throttle(throttle)
+            .start(notify_tx.subscribe()),
+    );
 
-    let tx_for_gateway = market_tx.clone();
-    supervisor.register_actor(
-        ActorType::GatewayActor,
-        Box::new(move || Box::new(MarketGateway::new(SYMBOLS, tx_for_gateway.clone()))),
+    // Don't launch every actor into its reconnect loop while Binance is down.
+    let tuning = &config.tuning;
+    let run_duration = tuning.run_duration;
+    let exchange = ExchangeStatus::new(config.binance.rest_url.clone());
+    match run_duration {
+        Some(duration) => time::timeout(duration, exchange.wait_until_reachable())
+            .await
+            .context("Binance stayed unreachable for the whole run")?,
+        None => exchange.wait_until_reachable().await,
+    }
+    // A symbol Binance doesn't list would subscribe to a stream that never speaks.
+    // Renamed pairs (MATIC -> POL) are subscribed under their current name.
+    let configured = canonical_symbols(&config.symbols, &config.symbol_aliases);
+    let configured: Vec<&str> = configured.iter().map(String::as_str).collect();
+    let (symbols, symbol_assets) = validated_symbols(
+        &config.binance,
+        &configured,
+        config.fail_on_invalid_symbols,
+        config.quote_assets.as_ref(),
+    )
+    .await?;
+    data_manager.set_symbol_assets(symbol_assets.clone());
+
+    // Services subscribe lazily from their factories so a restarted actor never
+    // leaves an unread receiver pinning the queue at capacity.
+    let market_capacity = tuning.market_channel_capacity;
+    // Whether the gateway, the router and every DB writer wait for room or drop.
+    let policy = BackpressurePolicy::from_env()
+        .map_err(|e| anyhow::anyhow!("Invalid backpressure policy: {}", e))?;
+    let (market_tx, _) = event_channel(market_capacity);
+    spawn_queue_monitor(
+        "Market",
+        market_tx.clone(),
+        market_capacity,
+        MARKET_CHANNEL_MONITOR_PERIOD,
     );
 
-    let pool_for_agg = data_manager.clone();
-    let tx_for_agg = market_tx.subscribe();
+    // The router is the gateway channel's only reader; services subscribe to
+    // the channel of the one event kind they record.
+    let router = EventRouter::new(market_capacity);
+    let router_for_actor = router.clone();
+    let tx_for_router = market_tx.clone();
     supervisor.register_actor(
-        ActorType::AggTradeActor,
+        ActorType::EventRouterActor,
         Box::new(move || {
-            Box::new(AggTradeService::new(
-                pool_for_agg.clone(),
-                tx_for_agg.resubscribe(),
-            ))
+            Box::new(
+                EventRouterActor::new(router_for_actor.clone(), tx_for_router.subscribe())
+                    .with_policy(policy),
+            )
         }),
     );
+    for kind in EventKind::ALL {
+        spawn_queue_monitor(
+            kind.name(),
+            router.sender(kind),
+            market_capacity,
+            MARKET_CHANNEL_MONITOR_PERIOD,
+        );
+    }
+    let router_stats = router.stats();
+
+    let mut stream_selection = StreamSelection::from_env()
+        .map_err(|e| anyhow::anyhow!("Invalid SYMBOL_STREAMS/DEPTH_UPDATE_SPEED: {}", e))?;
+    let klines_only = tuning.klines_only;
+    if klines_only {
+        info!("STORAGE_MODE=klines: no depth streams, order books are not recorded.");
+        stream_selection = stream_selection.without_depth();
+    }
+    let reconnect_warning = ReconnectWarning::from_env()
+        .map_err(|e| anyhow::anyhow!("Invalid reconnect warning: {}", e))?;
+    let (gateway_control, gateway_commands) = GatewayControl::new(&symbols, stream_selection);
+    let gateway_control = gateway_control
+        .with_endpoints(&config.binance.ws_url, &config.binance.futures_ws_url)
+        .with_open_interest_url(&config.binance.futures_rest_url)
+        .with_reconnect_warning(reconnect_warning)
+        .with_rotate_after(match tuning.ws_rotate_after {
+            None => Some(DEFAULT_ROTATE_AFTER),
+            Some(after) => (!after.is_zero()).then_some(after),
+        })
+        .with_drop_invalid_books(tuning.drop_invalid_books);
+    let gateway_stats = gateway_control.stats();
+    let stats_manager = data_manager.clone();
+    tokio::spawn(async move {
+        let mut ticker = time::interval(DB_STATS_PERIOD);
+        loop {
+            ticker.tick().await;
+            match stats_manager.stats().await {
+                Ok(stats) => info!(
+                    "DB stats: {}, {} malformed fields rejected, {} invalid order books, {} repeated order books skipped, {} events rate limited",
+                    stats,
+                    market_data::parse_errors(),
+                    market_data::invalid_books(),
+                    market_data::repeated_books(),
+                    market_data::rate_limited()
+                ),
+                Err(e) => warn!("Failed to read DB stats: {}", e),
+            }
+            info!("Gateway: {}", gateway_stats);
+            info!("Event router: {}", router_stats);
 
-    let pool_for_order = data_manager.clone();
-    let tx_for_order = market_tx.subscribe();
+            if !stats_manager.trades_in_sqlite() {
+                continue;
+            }
+            let since = SystemClock.now_millis() as f64 / 1000.0 - DB_STATS_PERIOD.as_secs_f64();
+            match AggTradeRepository::latency_since(&stats_manager, since).await {
+                Ok(latency) => {
+                    for symbol in latency {
+                        info!("Ingestion latency: {}", symbol);
+                    }
+                }
+                Err(e) => warn!("Failed to read ingestion latency: {}", e),
+            }
+        }
+    });
+
+    let (raw_capture, raw_capture_writer) = raw_capture()?.unzip();
+    supervisor = register_gateway(
+        supervisor,
+        gateway_control.clone(),
+        gateway_commands,
+        market_tx.clone(),
+        policy,
+        raw_capture,
+    );
+    // `SYMBOLS` edits in `.env` reach the gateway on SIGHUP.
+    reload_symbols_on_sighup(
+        config.clone(),
+        gateway_control,
+        data_manager.clone(),
+        supervisor.sender(),
+    );
+
+    // Every DB writer buffers and retries failed flushes within these limits.
+    let writer_limits =
+        WriterLimits::from_env().map_err(|e| anyhow::anyhow!("Invalid DB writer limits: {}", e))?;
+
+    // Per-symbol cap on trade and order book writes, off unless set.
+    let rate_limit = tuning.symbol_max_writes_per_sec;
+    let trade_batch = DEFAULT_TRADE_BATCH
+        .from_env("TRADE")
+        .map_err(|e| anyhow::anyhow!("Invalid trade batch window: {}", e))?;
+    let pool_for_agg = data_manager.clone();
+    let tx_for_agg = router.sender(EventKind::AggTrade);
     supervisor.register_actor(
-        ActorType::OrderBookActor,
+        ActorType::AggTradeActor,
         Box::new(move || {
-            Box::new(OrderBookService::new(
-                pool_for_order.clone(),
-                tx_for_order.resubscribe(),
-            ))
+            Box::new(
+                AggTradeService::new(pool_for_agg.clone(), tx_for_agg.subscribe())
+                    .with_rate_limit(rate_limit)
+                    .with_batch_window(trade_batch)
+                    .with_policy(policy)
+                    .with_writer_limits(writer_limits),
+            )
         }),
     );
 
+    if !klines_only {
+        let orderbook_dedup = tuning.orderbook_dedup;
+        let orderbook_batch = DEFAULT_ORDERBOOK_BATCH
+            .from_env("ORDERBOOK")
+            .map_err(|e| anyhow::anyhow!("Invalid order book batch window: {}", e))?;
+        let pool_for_order = data_manager.clone();
+        let tx_for_order = router.sender(EventKind::OrderBook);
+        supervisor.register_actor(
+            ActorType::OrderBookActor,
+            Box::new(move || {
+                Box::new(
+                    OrderBookService::new(pool_for_order.clone(), tx_for_order.subscribe())
+                        .with_rate_limit(rate_limit)
+                        .with_dedup(orderbook_dedup)
+                        .with_batch_window(orderbook_batch)
+                        .with_policy(policy)
+                        .with_writer_limits(writer_limits),
+                )
+            }),
+        );
+
+        // Imbalance samples for offline training, at most one per symbol per `OBI_SAMPLE_MS`.
+        let obi_sample_ms = tuning.obi_sample_ms.unwrap_or(DEFAULT_OBI_SAMPLE_MS);
+        let pool_for_features = data_manager.clone();
+        let tx_for_features = router.sender(EventKind::OrderBook);
+        supervisor.register_actor(
+            ActorType::OrderBookFeatureActor,
+            Box::new(move || {
+                Box::new(
+                    OrderBookFeatureService::new(
+                        pool_for_features.clone(),
+                        tx_for_features.subscribe(),
+                        obi_sample_ms,
+                    )
+                    .with_policy(policy)
+                    .with_writer_limits(writer_limits),
+                )
+            }),
+        );
+    }
+
+    // Closed candles of `KLINE_FLUSH_ON_CLOSE` intervals skip the batching.
+    let flush_on_close = tuning.kline_flush_on_close.clone().unwrap_or_else(|| {
+        DEFAULT_FLUSH_ON_CLOSE
+            .iter()
+            .map(|i| i.to_string())
+            .collect()
+    });
     let pool_for_klines = data_manager.clone();
-    let tx_for_klines = market_tx.subscribe();
+    let tx_for_klines = router.sender(EventKind::Kline);
+    let rest_for_klines =
+        KlineRestClient::new(config.binance.rest_url.clone(), &config.binance.http);
     supervisor.register_actor(
         ActorType::KlinesActor,
         Box::new(move || {
-            Box::new(KlinesService::new(
-                pool_for_klines.clone(),
-                tx_for_klines.resubscribe(),
-            ))
+            Box::new(
+                KlinesService::new(
+                    pool_for_klines.clone(),
+                    tx_for_klines.subscribe(),
+                    rest_for_klines.clone(),
+                )
+                .with_flush_on_close(&flush_on_close)
+                .with_policy(policy)
+                .with_writer_limits(writer_limits),
+            )
         }),
     );
 
     let pool_for_mark_prices = data_manager.clone();
-    let tx_for_mark_prices = market_tx.subscribe();
+    let tx_for_mark_prices = router.sender(EventKind::MarkPrice);
     supervisor.register_actor(
         ActorType::MarkPriceActor,
         Box::new(move || {
-            Box::new(MarkPriceService::new(
-                pool_for_mark_prices.clone(),
-                tx_for_mark_prices.resubscribe(),
+            Box::new(
+                MarkPriceService::new(pool_for_mark_prices.clone(), tx_for_mark_prices.subscribe())
+                    .with_policy(policy)
+                    .with_writer_limits(writer_limits),
+            )
+        }),
+    );
+
+    let pool_for_funding = data_manager.clone();
+    let tx_for_funding = router.sender(EventKind::MarkPrice);
+    supervisor.register_actor(
+        ActorType::FundingActor,
+        Box::new(move || {
+            Box::new(FundingService::new(
+                pool_for_funding.clone(),
+                tx_for_funding.subscribe(),
             ))
         }),
     );
 
     let pool_for_force_order = data_manager.clone();
-    let tx_for_force_order = market_tx.subscribe();
+    let tx_for_force_order = router.sender(EventKind::ForceOrder);
     supervisor.register_actor(
         ActorType::ForceOrderActor,
         Box::new(move || {
-            Box::new(ForceOrderService::new(
-                pool_for_force_order.clone(),
-                tx_for_force_order.resubscribe(),
-            ))
+            Box::new(
+                ForceOrderService::new(
+                    pool_for_force_order.clone(),
+                    tx_for_force_order.subscribe(),
+                )
+                .with_policy(policy)
+                .with_writer_limits(writer_limits),
+            )
         }),
     );
 
     let pool_for_open_interest = data_manager.clone();
-    let tx_for_open_interest = market_tx.subscribe();
+    let tx_for_open_interest = router.sender(EventKind::OpenInterest);
     supervisor.register_actor(
         ActorType::OpenInterestActor,
         Box::new(move || {
-            Box::new(OpenInterestService::new(
-                pool_for_open_interest.clone(),
-                tx_for_open_interest.resubscribe(),
+            Box::new(
+                OpenInterestService::new(
+                    pool_for_open_interest.clone(),
+                    tx_for_open_interest.subscribe(),
+                )
+                .with_policy(policy)
+                .with_writer_limits(writer_limits),
+            )
+        }),
+    );
+
+    let pool_for_trade_flow = data_manager.clone();
+    let tx_for_trade_flow = router.sender(EventKind::AggTrade);
+    supervisor.register_actor(
+        ActorType::TradeFlowActor,
+        Box::new(move || {
+            Box::new(
+                TradeFlowService::new(pool_for_trade_flow.clone(), tx_for_trade_flow.subscribe())
+                    .with_policy(policy)
+                    .with_writer_limits(writer_limits),
+            )
+        }),
+    );
+
+    // 1m candles built from aggTrades, for symbols/endpoints without kline streams.
+    let pool_for_candles = data_manager.clone();
+    let tx_for_candles = router.sender(EventKind::AggTrade);
+    supervisor.register_actor(
+        ActorType::AggTradeCandleActor,
+        Box::new(move || {
+            Box::new(
+                AggTradeCandleService::new(pool_for_candles.clone(), tx_for_candles.subscribe())
+                    .with_policy(policy)
+                    .with_writer_limits(writer_limits),
+            )
+        }),
+    );
+
+    // Exchange-computed rolling 24h stats every `TICKER_24H_SECONDS`, for dashboards.
+    let ticker_period = tuning
+        .ticker_24h_period
+        .unwrap_or(Duration::from_secs(DEFAULT_TICKER_24H_SECONDS));
+    let pool_for_tickers = data_manager.clone();
+    let ticker_symbols: Vec<String> = symbols.iter().map(|s| s.to_string()).collect();
+    let client_for_tickers =
+        Ticker24hClient::new(config.binance.rest_url.clone(), &config.binance.http);
+    supervisor.register_actor(
+        ActorType::Ticker24hActor,
+        Box::new(move || {
+            Box::new(Ticker24hActor::new(
+                pool_for_tickers.clone(),
+                client_for_tickers.clone(),
+                ticker_symbols.clone(),
+                ticker_period,
+            ))
+        }),
+    );
+
+    // Equity curve: account balances every `BALANCE_SNAPSHOT_MINUTES`, when
+    // API credentials are configured.
+    if let Some(client_for_balances) = BinanceClient::from_config(&config.binance) {
+        let balance_period = tuning
+            .balance_snapshot_period
+            .unwrap_or(Duration::from_secs(DEFAULT_BALANCE_SNAPSHOT_MINUTES * 60));
+        let pool_for_balances = data_manager.clone();
+        supervisor.register_actor(
+            ActorType::BalanceSnapshotActor,
+            Box::new(move || {
+                Box::new(BalanceSnapshotActor::new(
+                    pool_for_balances.clone(),
+                    client_for_balances.clone(),
+                    balance_period,
+                ))
+            }),
+        );
+    } else {
+        info!("No Binance API credentials, balance snapshots disabled.");
+    }
+
+    // Rolls aged 1s klines up into 1m and vacuums the freed pages.
+    let compaction = CompactionConfig::from_env()
+        .map_err(|e| anyhow::anyhow!("Invalid kline compaction config: {}", e))?;
+    let pool_for_maintenance = data_manager.clone();
+    supervisor.register_actor(
+        ActorType::MaintenanceActor,
+        Box::new(move || {
+            Box::new(MaintenanceActor::new(
+                pool_for_maintenance.clone(),
+                compaction.clone(),
             ))
         }),
     );
 
-    // let telegram_svc = services::telegram_service::TelegramService::new();
-    // let execution_svc = services::execution_service::ExecutionService::new();
+    // Recent trades per symbol, readable without touching sqlite.
+    let live_cache = LiveCache::new(
+        tuning
+            .live_cache_trades
+            .unwrap_or(DEFAULT_LIVE_CACHE_TRADES),
+    );
+    let cache_for_live = live_cache.clone();
+    let tx_for_live = router.sender(EventKind::AggTrade);
+    supervisor.register_actor(
+        ActorType::LiveCacheActor,
+        Box::new(move || {
+            Box::new(LiveCacheService::new(
+                cache_for_live.clone(),
+                tx_for_live.subscribe(),
+            ))
+        }),
+    );
+
+    // The strategy is opt-in: with `STRATEGY_ENABLED=true` it runs the model
+    // on the live streams; otherwise the bot only records.
+    if tuning.strategy_enabled {
+        // Every symbol must sit in at most one group; ungrouped ones trade on defaults.
+        let strategy_config =
+            StrategyConfig::from_env(DEFAULT_SYMBOL_GROUPS, &config.symbol_aliases)
+                .map_err(|e| anyhow::anyhow!("Invalid strategy config: {}", e))?;
+        for symbol in strategy_config.groups.ungrouped(&symbols) {
+            warn!("{} is not in any symbol group, using default risk.", symbol);
+        }
+        debug!(
+            "Signal cooldown {:?}, single position per symbol: {}",
+            strategy_config.cooldown, strategy_config.single_position
+        );
+        let strategy_config = SharedStrategyConfig::new(strategy_config);
+        reload_strategy_config_on_sighup(strategy_config.clone(), config.symbol_aliases.clone());
 
-    // Configurable Model Path
-    let model_path = env::var("MODEL_PATH").unwrap_or_else(|_| "models/strategy.onnx".to_string());
-    debug!("Using AI Model: {}", model_path);
+        debug!("Using AI Model: {}", paths.model().display());
+        let max_event_age = match tuning.max_event_age {
+            None => Some(DEFAULT_MAX_EVENT_AGE),
+            Some(age) => (!age.is_zero()).then_some(age),
+        };
+        debug!("Strategy ignores trades older than {:?}", max_event_age);
+        let model_temperature = tuning.model_temperature.unwrap_or(DEFAULT_TEMPERATURE);
+        debug!("Model softmax temperature {}", model_temperature);
+        let score_threshold = tuning
+            .model_score_threshold
+            .unwrap_or(DEFAULT_SCORE_THRESHOLD);
+        let obi_levels = tuning.strategy_obi_levels.unwrap_or(DEFAULT_OBI_LEVELS);
+        debug!("OBI feature over the best {} levels", obi_levels);
+        let feature_count = tuning.strategy_feature_count.unwrap_or(FEATURE_COUNT);
+        debug!(
+            "Strategy feeds the model {} features per tick",
+            feature_count
+        );
 
-    // Initialize Strategy Service (Process Phase)
-    // Tracks all 15 symbols with a window size of 100
-    // let strategy_svc = strategy::services::strategy_service::StrategyService::new(SYMBOLS, 100, &model_path)
-    //     .with_notifier(notify_tx.clone())
-    //     .with_executor(exec_tx.clone());
+        // Every symbol, with a window of 100 ticks for sequence models.
+        let strategy = StrategyService::new(
+            &symbols,
+            100,
+            &paths.model().to_string_lossy(),
+            model_temperature,
+        )
+        .with_notifier(notify_tx.clone())
+        .with_config(strategy_config)
+        .with_max_event_age(max_event_age)
+        .with_score_threshold(score_threshold)
+        .with_obi_levels(obi_levels)
+        .with_feature_count(feature_count)
+        .context("Invalid STRATEGY_FEATURE_COUNT")?
+        .with_disabled_symbols(data_manager.disabled_symbols().clone())
+        .with_symbol_assets(&symbol_assets);
+        // With API credentials its signals become orders; without, they are
+        // only announced.
+        let strategy = match BinanceClient::from_config(&config.binance) {
+            Some(client) => {
+                let breaker = BreakerConfig::from_env()
+                    .map_err(|e| anyhow::anyhow!("Invalid order circuit breaker: {}", e))?;
+                debug!("Order circuit breaker: {:?}", breaker);
+                let (exec_tx, exec_rx) = broadcast::channel::<TradeSignal>(64);
+                tokio::spawn(
+                    ExecutionService::new(client)
+                        .with_audit(data_manager.clone())
+                        .with_circuit_breaker(breaker)
+                        .with_notifier(notify_tx.clone())
+                        .with_account_refresh(
+                            tuning.account_refresh.unwrap_or(DEFAULT_ACCOUNT_REFRESH),
+                        )
+                        .start(exec_rx),
+                );
+                strategy.with_executor(exec_tx)
+            }
+            None => {
+                info!("No Binance API credentials, signals are not executed.");
+                strategy
+            }
+        };
+        tokio::spawn(strategy.start(StrategyFeeds {
+            trades: router.sender(EventKind::AggTrade).subscribe(),
+            books: router.sender(EventKind::OrderBook).subscribe(),
+            open_interest: router.sender(EventKind::OpenInterest).subscribe(),
+            mark_prices: router.sender(EventKind::MarkPrice).subscribe(),
+        }));
+    } else {
+        info!("STRATEGY_ENABLED is off: recording only, no signals.");
+    }
+
+    // Ctrl-C and the end of a timed run both send `ShutdownAll`.
+    supervisor.stop_on_ctrl_c();
+    let before = match run_duration {
+        Some(duration) => {
+            let before = data_manager.row_counts().await?;
+            info!("Recording for {}s, then exiting.", duration.as_secs());
+            let tx = supervisor.sender();
+            tokio::spawn(async move {
+                time::sleep(duration).await;
+                let _ = tx.send(ControlMessage::ShutdownAll).await;
+            });
+            Some(before)
+        }
+        None => None,
+    };
 
     supervisor.start().await;
+    data_manager.flush_writers().await;
+    if let Some(writer) = raw_capture_writer {
+        tokio::task::spawn_blocking(move || writer.finish()).await?;
+    }
+
+    if let Some(before) = before {
+        let after = data_manager.row_counts().await?;
+        for ((table, start), (_, end)) in before.iter().zip(after.iter()) {
+            info!("{:<14} {:>8} rows written", table, end - start);
+        }
+    }
     Ok(())
 }
+
+/// `bot export --period 2026_01 --table agg_trades [--out file.csv]` writes
+/// one table of a weekly database to CSV, `<table>_<period>.csv` by default.
+async fn export(paths: &Paths, args: Vec<String>) -> anyhow::Result<()> {
+    const USAGE: &str = "usage: bot export --period YYYY_WW --table TABLE [--out FILE]";
+    let mut period = None;
+    let mut table = None;
+    let mut out = None;
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("{} needs a value\n{}", flag, USAGE))?;
+        match flag.as_str() {
+            "--period" => period = Some(value),
+            "--table" => table = Some(value),
+            "--out" => out = Some(value),
+            other => anyhow::bail!("Unknown option '{}'\n{}", other, USAGE),
+        }
+    }
+    let (Some(period), Some(table)) = (period, table) else {
+        anyhow::bail!(USAGE);
+    };
+
+    let db_path = storage::db::db_path_for_period(paths, &period).map_err(anyhow::Error::msg)?;
+    anyhow::ensure!(
+        std::path::Path::new(&db_path).exists(),
+        "No database for {} at {}",
+        period,
+        db_path
+    );
+    let reader_options = storage::db::ReaderOptions::from_env()
+        .map_err(|e| anyhow::anyhow!("Invalid reader options: {}", e))?;
+    let pool = storage::db::open_reader(&db_path, &reader_options)
+        .await
+        .with_context(|| format!("Failed to open {}", db_path))?;
+    let out = out.unwrap_or_else(|| format!("{}_{}.csv", table, period));
+    let rows = storage::export::to_csv(&pool, &table, std::path::Path::new(&out))
+        .await
+        .with_context(|| format!("Failed to export {} from {}", table, db_path))?;
+    info!("Exported {} rows of {} to {}", rows, table, out);
+    Ok(())
+}
+
+/// `RAW_CAPTURE_DIR` turns on the raw frame log. The writer thread lives
+/// across gateway restarts until its `RawCaptureWriter` is finished on
+/// shutdown.
+fn raw_capture() -> anyhow::Result<Option<(RawCapture, RawCaptureWriter)>> {
+    let Some(config) =
+        RawCaptureConfig::from_env().map_err(|e| anyhow::anyhow!("Invalid raw capture: {}", e))?
+    else {
+        return Ok(None);
+    };
+    info!(
+        "Capturing raw frames to {} (1 in {}).",
+        config.dir.display(),
+        config.sample_every
+    );
+    let dir = config.dir.clone();
+    let capture = RawCapture::spawn(config)
+        .with_context(|| format!("Failed to start raw capture in {}", dir.display()))?;
+    Ok(Some(capture))
+}
+
+/// `symbols` under their current names, logging each one `aliases` renames.
+fn canonical_symbols(symbols: &[String], aliases: &SymbolAliases) -> Vec<String> {
+    symbols
+        .iter()
+        .map(|symbol| {
+            let canonical = aliases.canonical(symbol);
+            if canonical != symbol.as_str() {
+                warn!(
+                    "{} was renamed to {}, using the new name.",
+                    symbol, canonical
+                );
+            }
+            canonical.into_owned()
+        })
+        .collect()
+}
+
+/// Keeps the `symbols` Binance lists as trading, returning them with their
+/// base and quote assets. The others are logged as an error and left out, or
+/// stop startup when `fail`. Pairs quoted in anything but `quotes`, when
+/// given, are left out too. If `exchangeInfo` can't be fetched every symbol
+/// is kept and no assets are known.
+async fn validated_symbols<'a>(
+    binance: &BinanceConfig,
+    symbols: &[&'a str],
+    fail: bool,
+    quotes: Option<&HashSet<String>>,
+) -> anyhow::Result<(Vec<&'a str>, HashMap<String, SymbolAssets>)> {
+    let trading = match ExchangeInfoClient::new(binance.rest_url.clone(), &binance.http)
+        .trading_symbols()
+        .await
+    {
+        Ok(trading) => trading,
+        Err(e) => {
+            warn!("Could not check symbols against exchangeInfo: {:#}", e);
+            return Ok((symbols.to_vec(), HashMap::new()));
+        }
+    };
+    let (valid, invalid) = partition_symbols(symbols, &trading);
+    if !invalid.is_empty() {
+        anyhow::ensure!(
+            !fail,
+            "Not trading on Binance: {} (INVALID_SYMBOLS=fail)",
+            invalid.join(", ")
+        );
+        error!(
+            "Not trading on Binance, skipping: {}. Fix the symbol list.",
+            invalid.join(", ")
+        );
+    }
+
+    let mut kept = Vec::with_capacity(valid.len());
+    let mut assets = HashMap::new();
+    for symbol in valid {
+        let ticker = symbol.to_uppercase();
+        let pair = trading[&ticker].clone();
+        if let Some(quotes) = quotes
+            && !quotes.contains(&pair.quote)
+        {
+            warn!(
+                "{} is quoted in {}, not in QUOTE_ASSETS, skipping.",
+                symbol, pair.quote
+            );
+            continue;
+        }
+        kept.push(symbol);
+        assets.insert(ticker, pair);
+    }
+    Ok((kept, assets))
+}
+
+/// Read once at startup; a new value in `.env` needs a restart.
+const RESTART_ONLY_VARS: &[&str] = &["MODEL_PATH", "MODEL_TEMPERATURE", "SYMBOL_ALIASES"];
+
+/// On every SIGHUP, re-reads `.env` and swaps in the strategy thresholds,
+/// position caps and disabled symbols it sets, logging what changed. Keys
+/// missing from the file keep their value from the process environment. A
+/// config that doesn't parse is logged and the running one kept.
+fn reload_strategy_config_on_sighup(strategy_config: SharedStrategyConfig, aliases: SymbolAliases) {
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                error!("Failed to listen for SIGHUP: {}", e);
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            let file = match env_file() {
+                Ok(file) => file,
+                Err(e) => {
+                    error!(
+                        "SIGHUP: can't read .env, keeping the strategy config: {}",
+                        e
+                    );
+                    continue;
+                }
+            };
+            let var = |key: &str| file.get(key).cloned().or_else(|| env::var(key).ok());
+            for key in RESTART_ONLY_VARS {
+                if var(key) != env::var(key).ok() {
+                    warn!("SIGHUP: {} changed, restart to apply it.", key);
+                }
+            }
+            match StrategyConfig::from_vars(DEFAULT_SYMBOL_GROUPS, &aliases, var) {
+                Ok(new_config) => {
+                    let changes = strategy_config.store(new_config);
+                    if changes.is_empty() {
+                        info!("SIGHUP: strategy config unchanged.");
+                    }
+                    for change in changes {
+                        info!("SIGHUP: strategy config {}", change);
+                    }
+                }
+                Err(e) => error!(
+                    "SIGHUP: invalid strategy config, keeping the old one: {}",
+                    e
+                ),
+            }
+        }
+    });
+}
+
+/// The variables `.env` sets now, for a SIGHUP reload.
+fn env_file() -> dotenvy::Result<HashMap<String, String>> {
+    dotenvy::dotenv_iter().and_then(|vars| vars.collect())
+}
+
+/// Registers the gateway and hands the supervisor its command queue, so
+/// `AddSymbol`/`RemoveSymbol` reach the live sockets.
+fn register_gateway(
+    supervisor: Supervisor,
+    control: GatewayControl,
+    commands: mpsc::Sender<GatewayCommand>,
+    market_tx: EventSender,
+    policy: BackpressurePolicy,
+    raw_capture: Option<RawCapture>,
+) -> Supervisor {
+    let mut supervisor = supervisor.with_gateway_commands(commands);
+    supervisor.register_actor(
+        ActorType::GatewayActor,
+        Box::new(move || {
+            let gateway =
+                MarketGateway::new(control.clone(), market_tx.clone()).with_policy(policy);
+            match &raw_capture {
+                Some(capture) => Box::new(gateway.with_raw_capture(capture.clone())),
+                None => Box::new(gateway),
+            }
+        }),
+    );
+    supervisor
+}
+
+/// On every SIGHUP, re-reads `SYMBOLS` from `.env`, checks it against
+/// `exchangeInfo` like at startup and has the supervisor subscribe the
+/// gateway to the symbols added and unsubscribe it from the ones removed,
+/// without dropping the sockets. A list that doesn't parse, or of which no
+/// symbol is trading, is logged and the streamed set kept.
+fn reload_symbols_on_sighup(
+    config: Arc<Config>,
+    control: GatewayControl,
+    data_manager: Arc<DataManager>,
+    supervisor_tx: mpsc::Sender<ControlMessage>,
+) {
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                error!("Failed to listen for SIGHUP: {}", e);
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            let file = match env_file() {
+                Ok(file) => file,
+                Err(e) => {
+                    error!("SIGHUP: can't read .env, keeping the symbol list: {}", e);
+                    continue;
+                }
+            };
+            let var = |key: &str| file.get(key).cloned().or_else(|| env::var(key).ok());
+            let listed = match Config::from_vars(var) {
+                Ok(new_config) => canonical_symbols(&new_config.symbols, &config.symbol_aliases),
+                Err(e) => {
+                    error!("SIGHUP: invalid config, keeping the symbol list:\n{}", e);
+                    continue;
+                }
+            };
+            let listed: Vec<&str> = listed.iter().map(String::as_str).collect();
+            let (wanted, assets) = match validated_symbols(
+                &config.binance,
+                &listed,
+                false,
+                config.quote_assets.as_ref(),
+            )
+            .await
+            {
+                Ok(validated) => validated,
+                Err(e) => {
+                    error!("SIGHUP: can't check the symbol list, keeping it: {:#}", e);
+                    continue;
+                }
+            };
+            if wanted.is_empty() {
+                error!("SIGHUP: no symbol in SYMBOLS is trading, keeping the symbol list.");
+                continue;
+            }
+            if !assets.is_empty() {
+                data_manager.set_symbol_assets(assets);
+            }
+            if sync_gateway_symbols(&control, &wanted, &supervisor_tx).await == 0 {
+                info!("SIGHUP: symbol list unchanged.");
+            }
+        }
+    });
+}
+
+/// Asks the supervisor to add the `wanted` symbols the gateway doesn't stream
+/// and remove the streamed ones not `wanted`. Returns how many it asked for.
+async fn sync_gateway_symbols(
+    control: &GatewayControl,
+    wanted: &[&str],
+    supervisor_tx: &mpsc::Sender<ControlMessage>,
+) -> usize {
+    let streamed = control.symbols();
+    let added = wanted
+        .iter()
+        .filter(|symbol| !streamed.iter().any(|s| s.eq_ignore_ascii_case(symbol)))
+        .map(|symbol| ControlMessage::AddSymbol(symbol.to_string()));
+    let removed = streamed
+        .iter()
+        .filter(|symbol| !wanted.iter().any(|s| s.eq_ignore_ascii_case(symbol)))
+        .map(|symbol| ControlMessage::RemoveSymbol(symbol.clone()));
+    let changes: Vec<ControlMessage> = added.chain(removed).collect();
+    let count = changes.len();
+    for change in changes {
+        info!("SIGHUP: {:?}", change);
+        let _ = supervisor_tx.send(change).await;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_reloaded_symbol_is_subscribed_on_the_live_socket() {
+        let spot = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let futures = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        // Takes the open interest requests and never answers them.
+        let open_interest = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = |listener: &TcpListener| {
+            format!("ws://{}/stream?streams=", listener.local_addr().unwrap())
+        };
+        let (spot_url, futures_url) = (ws_url(&spot), ws_url(&futures));
+        let open_interest_url = format!("http://{}", open_interest.local_addr().unwrap());
+
+        let (connected_tx, connected) = tokio::sync::oneshot::channel();
+        let (frames_tx, mut frames) = mpsc::unbounded_channel();
+        let spot_server = tokio::spawn(async move {
+            let (tcp, _) = spot.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            connected_tx.send(()).unwrap();
+            while let Some(Ok(msg)) = socket.next().await {
+                if msg.is_text() {
+                    let _ = frames_tx.send(msg.into_text().unwrap().to_string());
+                }
+            }
+        });
+        let futures_server = tokio::spawn(async move {
+            let (tcp, _) = futures.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            while socket.next().await.is_some() {}
+        });
+
+        let (control, commands) = GatewayControl::new(&["btcusdt"], StreamSelection::default());
+        let control = control
+            .with_endpoints(&spot_url, &futures_url)
+            .with_open_interest_url(&open_interest_url)
+            .with_rotate_after(None);
+        let (market_tx, _) = event_channel(16);
+        let mut supervisor = register_gateway(
+            Supervisor::new(),
+            control.clone(),
+            commands,
+            market_tx,
+            BackpressurePolicy::default(),
+            None,
+        );
+        let supervisor_tx = supervisor.sender();
+        let running = tokio::spawn(async move { supervisor.start().await });
+
+        time::timeout(Duration::from_secs(5), connected)
+            .await
+            .expect("gateway never connected")
+            .unwrap();
+        let wanted = ["btcusdt", "ethusdt"];
+        assert_eq!(
+            sync_gateway_symbols(&control, &wanted, &supervisor_tx).await,
+            1
+        );
+
+        let frame = time::timeout(Duration::from_secs(5), frames.recv())
+            .await
+            .expect("no frame sent on the live socket")
+            .unwrap();
+        let frame: serde_json::Value = serde_json::from_str(&frame).unwrap();
+        assert_eq!(frame["method"], "SUBSCRIBE");
+        assert!(
+            frame["params"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|stream| stream == "ethusdt@aggTrade")
+        );
+        assert_eq!(control.symbols(), wanted);
+        assert_eq!(
+            sync_gateway_symbols(&control, &wanted, &supervisor_tx).await,
+            0
+        );
+
+        supervisor_tx
+            .send(ControlMessage::ShutdownAll)
+            .await
+            .unwrap();
+        time::timeout(Duration::from_secs(5), running)
+            .await
+            .expect("supervisor did not stop")
+            .unwrap();
+        spot_server.abort();
+        futures_server.abort();
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+use std::env;
+use std::time::{Duration, Instant};
+
+/// When the order circuit breaker trips and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerConfig {
+    /// Consecutive failed orders that open the breaker...
+    pub max_failures: usize,
+    /// ...when all of them fall within this window.
+    pub window: Duration,
+    /// How long it stays open before one trial order is let through.
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            window: Duration::from_secs(300),
+            cooldown: Duration::from_secs(600),
+        }
+    }
+}
+
+impl BreakerConfig {
+    /// Reads `ORDER_BREAKER_FAILURES` (5), `ORDER_BREAKER_WINDOW_SECS` (300)
+    /// and `ORDER_BREAKER_COOLDOWN_SECS` (600).
+    pub fn from_env() -> Result<Self, String> {
+        fn var(name: &str) -> Result<Option<u64>, String> {
+            match env::var(name) {
+                Ok(value) => match value.trim().parse::<u64>() {
+                    Ok(n) if n > 0 => Ok(Some(n)),
+                    _ => Err(format!(
+                        "{} must be a positive whole number, got '{}'",
+                        name, value
+                    )),
+                },
+                Err(_) => Ok(None),
+            }
+        }
+
+        let defaults = Self::default();
+        Ok(Self {
+            max_failures: var("ORDER_BREAKER_FAILURES")?
+                .map_or(defaults.max_failures, |n| n as usize),
+            window: var("ORDER_BREAKER_WINDOW_SECS")?.map_or(defaults.window, Duration::from_secs),
+            cooldown: var("ORDER_BREAKER_COOLDOWN_SECS")?
+                .map_or(defaults.cooldown, Duration::from_secs),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed,
+    Open {
+        until: Instant,
+    },
+    /// The cooldown is over and one trial order has been let through.
+    HalfOpen,
+}
+
+/// What a recorded result did to the breaker, for the caller to log or alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// Too many failures: orders stop for the cooldown.
+    Opened,
+    /// The trial order failed too: another cooldown.
+    Reopened,
+    /// The trial order went through: back to normal.
+    Closed,
+}
+
+/// Stops placing orders after `max_failures` consecutive failures within
+/// `window`, so bad keys or a symbol filter don't turn every signal into a
+/// failed request. After `cooldown` a single trial order decides whether it
+/// closes again or stays open for another cooldown.
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    state: State,
+    /// Times of the current run of failures, oldest first.
+    failures: VecDeque<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            state: State::Closed,
+            failures: VecDeque::new(),
+        }
+    }
+
+    /// Whether an order may be sent at `now`. Once the cooldown has passed
+    /// this lets exactly one through, whose result must then be recorded.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        match self.state {
+            State::Closed => true,
+            State::Open { until } if now >= until => {
+                self.state = State::HalfOpen;
+                true
+            }
+            State::Open { .. } | State::HalfOpen => false,
+        }
+    }
+
+    /// Records the result of an order sent at `now`.
+    pub fn record(&mut self, now: Instant, ok: bool) -> Option<Transition> {
+        if ok {
+            self.failures.clear();
+            let was_half_open = self.state == State::HalfOpen;
+            self.state = State::Closed;
+            return was_half_open.then_some(Transition::Closed);
+        }
+
+        let until = now + self.config.cooldown;
+        if self.state == State::HalfOpen {
+            self.state = State::Open { until };
+            return Some(Transition::Reopened);
+        }
+        self.failures.push_back(now);
+        while self
+            .failures
+            .front()
+            .is_some_and(|&first| now.duration_since(first) > self.config.window)
+        {
+            self.failures.pop_front();
+        }
+        if self.failures.len() >= self.config.max_failures {
+            self.failures.clear();
+            self.state = State::Open { until };
+            return Some(Transition::Opened);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_failures_and_half_opens_after_cooldown() {
+        let mut breaker = CircuitBreaker::new(BreakerConfig {
+            max_failures: 3,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(600),
+        });
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Failures spread wider than the window don't trip it.
+        assert_eq!(breaker.record(at(0), false), None);
+        assert_eq!(breaker.record(at(50), false), None);
+        assert_eq!(breaker.record(at(100), false), None);
+        // A success resets the run.
+        assert_eq!(breaker.record(at(101), true), None);
+        assert_eq!(breaker.record(at(102), false), None);
+        assert_eq!(breaker.record(at(103), false), None);
+        assert_eq!(breaker.record(at(104), false), Some(Transition::Opened));
+
+        assert!(!breaker.allow(at(600)));
+        assert!(breaker.allow(at(704)));
+        // Only one trial at a time.
+        assert!(!breaker.allow(at(705)));
+        assert_eq!(breaker.record(at(705), false), Some(Transition::Reopened));
+        assert!(!breaker.allow(at(1000)));
+
+        assert!(breaker.allow(at(1305)));
+        assert_eq!(breaker.record(at(1306), true), Some(Transition::Closed));
+        assert!(breaker.allow(at(1307)));
+    }
+}
@@ -1,62 +1,137 @@
-use common::models::TradeSignal;
-use market_data::remote::BinanceClient;
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::broadcast;
-use tracing::{error, info, warn};
+use std::time::{Duration, Instant};
+
+use common::clock::{Clock, SystemClock};
+use common::models::{OrderAuditInsert, TradeSignal};
+use market_data::remote::BinanceClient;
+use market_data::remote::binance_client::AccountInformation;
+use market_data::remote::binance_client::OrderResponse;
+use storage::data_manager::DataManager;
+use storage::repositories::OrderRepository;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+
+use crate::services::circuit_breaker::{BreakerConfig, CircuitBreaker, Transition};
+
+/// `BinanceClient::post_order` only places market orders.
+const ORDER_TYPE: &str = "MARKET";
+
+/// Default time between account fetches once one has succeeded.
+pub const DEFAULT_ACCOUNT_REFRESH: Duration = Duration::from_secs(300);
+
+/// Wait after a failed account fetch; it doubles with each further failure
+/// in a row, up to `MAX_ACCOUNT_BACKOFF`.
+const ACCOUNT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_ACCOUNT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Audited error of a signal the open circuit breaker kept from the exchange.
+const BREAKER_OPEN: &str = "refused: circuit breaker open";
 
 pub struct ExecutionService {
     client: BinanceClient,
+    audit: Option<Arc<DataManager>>,
+    account_refresh: Duration,
+    breaker: BreakerConfig,
+    notification_tx: Option<broadcast::Sender<String>>,
 }
 
 impl ExecutionService {
-    pub fn new() -> Self {
+    pub fn new(client: BinanceClient) -> Self {
         Self {
-            client: BinanceClient::new(),
+            client,
+            audit: None,
+            account_refresh: DEFAULT_ACCOUNT_REFRESH,
+            breaker: BreakerConfig::default(),
+            notification_tx: None,
         }
     }
 
+    /// When repeated order failures stop orders, instead of
+    /// `BreakerConfig::default()`.
+    pub fn with_circuit_breaker(mut self, config: BreakerConfig) -> Self {
+        self.breaker = config;
+        self
+    }
+
+    /// Alerts when the circuit breaker opens and when it closes again.
+    pub fn with_notifier(mut self, tx: broadcast::Sender<String>) -> Self {
+        self.notification_tx = Some(tx);
+        self
+    }
+
+    /// Re-fetches the account every `period` instead of
+    /// `DEFAULT_ACCOUNT_REFRESH`.
+    pub fn with_account_refresh(mut self, period: Duration) -> Self {
+        self.account_refresh = period;
+        self
+    }
+
+    /// Records every order attempt, filled or failed, in the `orders` table.
+    pub fn with_audit(mut self, data_manager: Arc<DataManager>) -> Self {
+        self.audit = Some(data_manager);
+        self
+    }
+
+    /// Executes signals until the channel closes. Orders are only placed
+    /// while the last account fetch said the account can trade; anything
+    /// else is refused and audited as failed.
     pub async fn start(self, mut rx: broadcast::Receiver<TradeSignal>) {
         info!("Starting Execution Service (Binance Connected)");
 
-        // Log Initial Balance
-        match self.client.get_account().await {
-            Ok(info) => {
-                info!("Binance Account Connected. Can Trade: {}", info.can_trade);
-                for b in info
-                    .balances
-                    .iter()
-                    .filter(|b| b.free.parse::<f64>().unwrap_or(0.0) > 0.0)
-                {
-                    info!("Balance: {} Free={} Locked={}", b.asset, b.free, b.locked);
-                }
-            }
-            Err(e) => error!("Failed to fetch account info: {}", e),
-        }
+        let (account_tx, account) = watch::channel(None);
+        // Stops with this future, whether it returns or is dropped.
+        let _refresher = AbortOnDrop(tokio::spawn(Self::refresh_account(
+            self.client.clone(),
+            self.account_refresh,
+            account_tx,
+        )));
 
+        let mut breaker = CircuitBreaker::new(self.breaker);
         loop {
             match rx.recv().await {
                 Ok(signal) => {
-                    info!("RECEIVED SIGNAL: {:?} - Executing...", signal);
+                    info!("RECEIVED SIGNAL: {} - Executing...", signal);
 
                     // EXECUTE ORDER
                     // For safety in this phase, we might want to hardcode a small quantity or use the one from signal.
                     // Let's assume the signal provides a safe quantity.
 
-                    match self
-                        .client
-                        .post_order(&signal.symbol, &signal.side, signal.quantity)
-                        .await
-                    {
+                    let client_ts = SystemClock.now_millis() as f64 / 1000.0;
+                    let refusal = refusal(account.borrow().as_ref());
+                    let result = match refusal {
+                        Some(reason) => Err(reason.to_string()),
+                        None if !breaker.allow(Instant::now()) => Err(BREAKER_OPEN.to_string()),
+                        None => {
+                            let result = self
+                                .client
+                                .post_order(&signal.symbol, signal.side.as_str(), signal.quantity)
+                                .await
+                                .map_err(|e| e.to_string());
+                            if let Some(transition) = breaker.record(Instant::now(), result.is_ok())
+                            {
+                                self.report(transition);
+                            }
+                            result
+                        }
+                    };
+                    match &result {
                         Ok(order) => {
                             info!(
                                 "ORDER EXECUTED: ID={}, Status={}",
                                 order.order_id, order.status
                             );
                         }
+                        Err(e) if e == BREAKER_OPEN => {
+                            debug!("Circuit breaker open, not sending {}", signal);
+                        }
                         Err(e) => {
                             error!("ORDER FAILED: {}", e);
                         }
                     }
+                    self.record(audit_row(&signal, client_ts, &result)).await;
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
                     warn!("Execution service lagged: missed {} signals", n);
@@ -68,4 +143,263 @@ impl ExecutionService {
             }
         }
     }
+
+    /// Fetches the account and publishes it on `account`: every `period`
+    /// after a success, with backoff after a failure. A failed fetch keeps
+    /// the last answer.
+    async fn refresh_account(
+        client: BinanceClient,
+        period: Duration,
+        account: watch::Sender<Option<AccountState>>,
+    ) {
+        let mut backoff = ACCOUNT_BACKOFF;
+        loop {
+            let wait = match client.get_account().await {
+                Ok(info) => {
+                    let state = AccountState::from(&info);
+                    log_account(&state, account.borrow().as_ref());
+                    account.send_replace(Some(state));
+                    backoff = ACCOUNT_BACKOFF;
+                    period
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to fetch account info: {}, retrying in {}s",
+                        e,
+                        backoff.as_secs()
+                    );
+                    let wait = backoff;
+                    backoff = (backoff * 2).min(MAX_ACCOUNT_BACKOFF);
+                    wait
+                }
+            };
+            sleep(wait).await;
+        }
+    }
+
+    /// Logs a circuit breaker change; opening and closing also go out as alerts.
+    fn report(&self, transition: Transition) {
+        let cooldown = self.breaker.cooldown.as_secs();
+        let alert = match transition {
+            Transition::Opened => {
+                let msg = format!(
+                    "Order circuit breaker OPEN after {} failed orders: no orders for {}s.",
+                    self.breaker.max_failures, cooldown
+                );
+                error!("{}", msg);
+                msg
+            }
+            Transition::Reopened => {
+                warn!(
+                    "Trial order failed, circuit breaker stays open for another {}s.",
+                    cooldown
+                );
+                return;
+            }
+            Transition::Closed => {
+                let msg = "Trial order went through, circuit breaker closed.".to_string();
+                info!("{}", msg);
+                msg
+            }
+        };
+        if let Some(tx) = &self.notification_tx {
+            let _ = tx.send(alert);
+        }
+    }
+
+    async fn record(&self, row: OrderAuditInsert) {
+        let Some(ref data_manager) = self.audit else {
+            return;
+        };
+        if let Err(e) = OrderRepository::insert(data_manager, &row).await {
+            // Keep the row in the log so the order can still be reconciled.
+            error!("Failed to record order {:?}: {}", row, e);
+        }
+    }
+}
+
+/// Aborts the task when dropped.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// What the last successful account fetch said.
+#[derive(Debug, Clone, PartialEq)]
+struct AccountState {
+    can_trade: bool,
+    /// Asset -> (free, locked), for the assets holding any.
+    balances: BTreeMap<String, (f64, f64)>,
+}
+
+impl From<&AccountInformation> for AccountState {
+    fn from(info: &AccountInformation) -> Self {
+        Self {
+            can_trade: info.can_trade,
+            balances: info
+                .non_zero_balances(0.0)
+                .into_iter()
+                .map(|b| (b.asset, (b.free, b.locked)))
+                .collect(),
+        }
+    }
+}
+
+/// Why no order may be placed, given the last known account.
+fn refusal(account: Option<&AccountState>) -> Option<&'static str> {
+    match account.map(|account| account.can_trade) {
+        Some(true) => None,
+        Some(false) => Some("refused: account cannot trade"),
+        None => Some("refused: account not fetched yet"),
+    }
+}
+
+/// Logs `state` on the first successful fetch, a `can_trade` change, and
+/// every balance that differs from `before`; an unchanged refresh only at
+/// debug level.
+fn log_account(state: &AccountState, before: Option<&AccountState>) {
+    if before == Some(state) {
+        debug!("Account refreshed, can trade: {}", state.can_trade);
+        return;
+    }
+    if before.map(|b| b.can_trade) != Some(state.can_trade) {
+        if state.can_trade {
+            info!("Binance Account Connected. Can Trade: true");
+        } else {
+            warn!("Binance account cannot trade: refusing orders until it can.");
+        }
+    }
+    for (asset, (free, locked)) in &state.balances {
+        let old = before.and_then(|b| b.balances.get(asset));
+        if old != Some(&(*free, *locked)) {
+            info!("Balance: {} Free={} Locked={}", asset, free, locked);
+        }
+    }
+    if let Some(before) = before {
+        for asset in before.balances.keys() {
+            if !state.balances.contains_key(asset) {
+                info!("Balance: {} Free=0 Locked=0", asset);
+            }
+        }
+    }
+}
+
+/// The `orders` row for `signal`, sent at `client_ts`, given the exchange's
+/// answer or the reason the request failed.
+fn audit_row(
+    signal: &TradeSignal,
+    client_ts: f64,
+    result: &Result<OrderResponse, String>,
+) -> OrderAuditInsert {
+    let mut row = OrderAuditInsert {
+        client_ts,
+        symbol: signal.symbol.to_uppercase(),
+        side: signal.side.to_string(),
+        quantity: signal.quantity,
+        order_type: ORDER_TYPE.to_string(),
+        order_id: None,
+        status: "FAILED".to_string(),
+        executed_qty: None,
+        cummulative_quote_qty: None,
+        error: None,
+    };
+    match result {
+        Ok(order) => {
+            row.order_id = Some(order.order_id as i64);
+            row.status = order.status.clone();
+            row.executed_qty = order.executed_qty.parse().ok();
+            row.cummulative_quote_qty = order.cummulative_quote_qty.parse().ok();
+        }
+        Err(e) => row.error = Some(e.clone()),
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::models::Side;
+
+    #[test]
+    fn test_refuses_orders_unless_account_can_trade() {
+        let info: AccountInformation = serde_json::from_str(
+            r#"{"canTrade":true,"balances":[
+                {"asset":"USDT","free":"25.5","locked":"0.0"},
+                {"asset":"BTC","free":"0.0","locked":"0.0"}]}"#,
+        )
+        .unwrap();
+        let mut account = AccountState::from(&info);
+        assert_eq!(
+            account.balances,
+            BTreeMap::from([("USDT".to_string(), (25.5, 0.0))])
+        );
+
+        assert_eq!(refusal(Some(&account)), None);
+        account.can_trade = false;
+        assert!(refusal(Some(&account)).is_some());
+        assert!(refusal(None).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_refresher_stops_when_dropped() {
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let guard = AbortOnDrop(tokio::spawn(async move {
+            let _tx = tx;
+            std::future::pending::<()>().await;
+        }));
+        drop(guard);
+        assert!(
+            rx.await.is_err(),
+            "the task was aborted, dropping its sender"
+        );
+    }
+
+    #[test]
+    fn test_audit_row_for_fill_and_failure() {
+        let signal = TradeSignal {
+            symbol: "btcusdt".to_string(),
+            side: Side::Buy,
+            quantity: 0.5,
+            confidence: 0.85,
+        };
+        assert_eq!(signal.to_string(), "BUY 0.5 btcusdt (AI_CONFIDENCE_0.85)");
+        let order: OrderResponse = serde_json::from_str(
+            r#"{"orderId":42,"symbol":"BTCUSDT","status":"FILLED",
+                "executedQty":"0.50000000","cummulativeQuoteQty":"15000.25"}"#,
+        )
+        .unwrap();
+
+        let filled = audit_row(&signal, 1.5, &Ok(order));
+        assert_eq!(
+            (
+                filled.symbol.as_str(),
+                filled.order_id,
+                filled.status.as_str()
+            ),
+            ("BTCUSDT", Some(42), "FILLED")
+        );
+        assert_eq!(filled.side, "BUY");
+        assert_eq!(
+            (
+                filled.executed_qty,
+                filled.cummulative_quote_qty,
+                filled.error
+            ),
+            (Some(0.5), Some(15000.25), None)
+        );
+
+        let failed = audit_row(&signal, 1.5, &Err("insufficient balance".to_string()));
+        assert_eq!(
+            (
+                failed.order_id,
+                failed.status.as_str(),
+                failed.order_type.as_str()
+            ),
+            (None, "FAILED", "MARKET")
+        );
+        assert_eq!(failed.error.as_deref(), Some("insufficient balance"));
+    }
 }
@@ -1,2 +1,4 @@
+pub mod circuit_breaker;
 pub mod execution_service;
+pub mod notifier;
 pub mod telegram_service;
@@ -0,0 +1,343 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::config::{NotifierConfig, parse_bool, parse_secs, parse_whole};
+use tokio::sync::broadcast;
+use tokio::time::{self, Instant};
+use tracing::{error, info, warn};
+
+use crate::services::telegram_service::TelegramService;
+
+/// A sink for the human-facing alerts the strategy emits.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Delivers `msg`. Failures are logged by the sink and never crash the caller.
+    async fn notify(&self, msg: &str);
+}
+
+/// Writes alerts to the log. Used when nothing else is configured.
+pub struct StdoutNotifier;
+
+#[async_trait]
+impl Notifier for StdoutNotifier {
+    async fn notify(&self, msg: &str) {
+        info!("[notify] {}", msg);
+    }
+}
+
+/// POSTs `{"text": "<msg>"}` to a URL (Slack/Discord/Mattermost-style hooks).
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build HTTP client."),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, msg: &str) {
+        let result = self
+            .client
+            .post(&self.url)
+            .json(&serde_json::json!({ "text": msg }))
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+
+        if let Err(e) = result {
+            error!("Failed to send webhook notification: {}", e);
+        }
+    }
+}
+
+/// Builds the sink chosen in `Config::notifier`.
+pub fn notifier_from_config(config: &NotifierConfig) -> Box<dyn Notifier> {
+    match config {
+        NotifierConfig::Telegram { bot_token, chat_id } => {
+            Box::new(TelegramService::new(&bot_token.0, *chat_id))
+        }
+        NotifierConfig::Webhook(url) => Box::new(WebhookNotifier::new(url.clone())),
+        NotifierConfig::Stdout => Box::new(StdoutNotifier),
+    }
+}
+
+/// Identical alerts closer together than this are collapsed into one.
+const DEFAULT_DEDUP_WINDOW: Duration = Duration::from_secs(60);
+
+/// Telegram allows roughly 20 messages per minute into a group chat.
+const DEFAULT_MAX_PER_MINUTE: usize = 20;
+
+/// Alerts waiting for a send slot; the oldest is dropped beyond this.
+const MAX_QUEUED: usize = 200;
+
+/// Telegram's limit on the text of one message, in characters. Batches stop
+/// short of it and a single longer alert is cut.
+const MAX_MESSAGE_CHARS: usize = 4096;
+
+/// Between two alerts of a batch.
+const BATCH_SEPARATOR: &str = "\n\n";
+
+/// Dedup, rate limit and batching in front of a `Notifier`, so a burst of
+/// strategy alerts can't get the bot rate-limited or banned.
+pub struct AlertThrottle {
+    dedup_window: Duration,
+    max_per_minute: usize,
+    batch: bool,
+    last_seen: HashMap<String, Instant>,
+    sent_at: VecDeque<Instant>,
+    queue: VecDeque<String>,
+    duplicates: u64,
+    dropped: u64,
+}
+
+impl AlertThrottle {
+    pub fn new(dedup_window: Duration, max_per_minute: usize, batch: bool) -> Self {
+        Self {
+            dedup_window,
+            max_per_minute: max_per_minute.max(1),
+            batch,
+            last_seen: HashMap::new(),
+            sent_at: VecDeque::new(),
+            queue: VecDeque::new(),
+            duplicates: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Reads `NOTIFY_DEDUP_SECS` (60), `NOTIFY_MAX_PER_MIN` (20, at least 1)
+    /// and `NOTIFY_BATCH` (true).
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| env::var(name).ok();
+        let dedup_window = match var("NOTIFY_DEDUP_SECS") {
+            Some(value) => parse_secs("NOTIFY_DEDUP_SECS", &value)?,
+            None => DEFAULT_DEDUP_WINDOW,
+        };
+        let max_per_minute = match var("NOTIFY_MAX_PER_MIN") {
+            Some(value) => parse_whole("NOTIFY_MAX_PER_MIN", &value)?,
+            None => DEFAULT_MAX_PER_MINUTE,
+        };
+        if max_per_minute == 0 {
+            return Err("NOTIFY_MAX_PER_MIN must be greater than 0".to_string());
+        }
+        let batch = match var("NOTIFY_BATCH") {
+            Some(value) => parse_bool("NOTIFY_BATCH", &value)?,
+            None => true,
+        };
+        Ok(Self::new(dedup_window, max_per_minute, batch))
+    }
+
+    /// Queues `msg` unless the same text was accepted within the dedup window.
+    pub fn push(&mut self, msg: String, now: Instant) {
+        if let Some(&seen) = self.last_seen.get(&msg)
+            && now.duration_since(seen) < self.dedup_window
+        {
+            self.duplicates += 1;
+            if self.duplicates % 10 == 1 {
+                info!("Suppressed {} duplicate alerts so far", self.duplicates);
+            }
+            return;
+        }
+        let window = self.dedup_window;
+        self.last_seen
+            .retain(|_, seen| now.duration_since(*seen) < window);
+        self.last_seen.insert(msg.clone(), now);
+
+        if self.queue.len() >= MAX_QUEUED {
+            self.queue.pop_front();
+            self.dropped += 1;
+            warn!(
+                "Alert queue full, dropped oldest alert ({} dropped so far)",
+                self.dropped
+            );
+        }
+        self.queue.push_back(msg);
+    }
+
+    /// The next message to send now, if any is queued and the rate limit allows.
+    /// With batching on, as many queued alerts as fit in `MAX_MESSAGE_CHARS`
+    /// go out as a single message; the rest wait for the next slot.
+    pub fn pop_ready(&mut self, now: Instant) -> Option<String> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        while let Some(&sent) = self.sent_at.front()
+            && now.duration_since(sent) >= Duration::from_secs(60)
+        {
+            self.sent_at.pop_front();
+        }
+        if self.sent_at.len() >= self.max_per_minute {
+            return None;
+        }
+        self.sent_at.push_back(now);
+
+        let mut msg = self.queue.pop_front()?;
+        if self.batch {
+            let mut chars = msg.chars().count();
+            let mut alerts = 1;
+            while let Some(next) = self.queue.front() {
+                let next_chars = BATCH_SEPARATOR.len() + next.chars().count();
+                if chars + next_chars > MAX_MESSAGE_CHARS {
+                    break;
+                }
+                msg.push_str(BATCH_SEPARATOR);
+                msg.push_str(next);
+                chars += next_chars;
+                alerts += 1;
+                self.queue.pop_front();
+            }
+            if alerts > 1 {
+                info!("Batching {} alerts into one message", alerts);
+            }
+        }
+        Some(fit_message(msg))
+    }
+
+    /// When a send slot frees up for the queued alerts, if they're waiting on one.
+    pub fn next_slot(&self) -> Option<Instant> {
+        if self.queue.is_empty() || self.sent_at.len() < self.max_per_minute {
+            return None;
+        }
+        self.sent_at
+            .front()
+            .map(|sent| *sent + Duration::from_secs(60))
+    }
+}
+
+/// `msg`, cut to `MAX_MESSAGE_CHARS` with an ellipsis if it is longer.
+fn fit_message(msg: String) -> String {
+    if msg.chars().count() <= MAX_MESSAGE_CHARS {
+        return msg;
+    }
+    warn!(
+        "Alert longer than {} characters, cutting it",
+        MAX_MESSAGE_CHARS
+    );
+    let mut cut: String = msg.chars().take(MAX_MESSAGE_CHARS - 1).collect();
+    cut.push('…');
+    cut
+}
+
+/// Forwards every message from the strategy's `notification_tx` to a `Notifier`,
+/// through an `AlertThrottle`.
+pub struct NotificationService {
+    notifier: Box<dyn Notifier>,
+    throttle: AlertThrottle,
+}
+
+impl NotificationService {
+    pub fn new(notifier: Box<dyn Notifier>) -> Self {
+        Self {
+            notifier,
+            throttle: AlertThrottle::new(DEFAULT_DEDUP_WINDOW, DEFAULT_MAX_PER_MINUTE, true),
+        }
+    }
+
+    /// Dedup window, rate limit and batching of the alerts.
+    pub fn with_throttle(mut self, throttle: AlertThrottle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    pub async fn start(mut self, mut rx: broadcast::Receiver<String>) {
+        info!("Starting Notification Service");
+
+        loop {
+            while let Some(msg) = self.throttle.pop_ready(Instant::now()) {
+                self.notifier.notify(&msg).await;
+            }
+            // Far-future deadline when nothing is waiting on the rate limit.
+            let wake_at = self
+                .throttle
+                .next_slot()
+                .unwrap_or_else(|| Instant::now() + Duration::from_secs(3600));
+
+            tokio::select! {
+                result = rx.recv() => match result {
+                    Ok(msg) => self.throttle.push(msg, Instant::now()),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        error!("Notification service lagged behind. Missed {} messages.", n);
+                    }
+                    Err(_) => {
+                        info!("Notification channel closed. Stopping service.");
+                        break;
+                    }
+                },
+                _ = time::sleep_until(wake_at) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicates_within_window_are_collapsed() {
+        let start = Instant::now();
+        let mut throttle = AlertThrottle::new(Duration::from_secs(60), 20, false);
+
+        throttle.push("BUY BTC".to_string(), start);
+        throttle.push("BUY BTC".to_string(), start + Duration::from_secs(10));
+        assert_eq!(throttle.pop_ready(start), Some("BUY BTC".to_string()));
+        assert_eq!(throttle.pop_ready(start), None);
+        assert_eq!(throttle.duplicates, 1);
+
+        throttle.push("BUY BTC".to_string(), start + Duration::from_secs(61));
+        assert_eq!(
+            throttle.pop_ready(start + Duration::from_secs(61)),
+            Some("BUY BTC".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_queues_then_batches() {
+        let start = Instant::now();
+        let mut throttle = AlertThrottle::new(Duration::from_secs(60), 1, true);
+
+        throttle.push("a".to_string(), start);
+        assert_eq!(throttle.pop_ready(start), Some("a".to_string()));
+
+        throttle.push("b".to_string(), start);
+        throttle.push("c".to_string(), start);
+        assert_eq!(throttle.pop_ready(start), None);
+        assert_eq!(throttle.next_slot(), Some(start + Duration::from_secs(60)));
+
+        let later = start + Duration::from_secs(60);
+        assert_eq!(throttle.pop_ready(later), Some("b\n\nc".to_string()));
+        assert_eq!(throttle.next_slot(), None);
+    }
+
+    #[test]
+    fn test_batches_stop_at_the_message_limit() {
+        let start = Instant::now();
+        let mut throttle = AlertThrottle::new(Duration::from_secs(60), 20, true);
+        let alert = |c: char| c.to_string().repeat(2000);
+
+        for c in ['a', 'b', 'c'] {
+            throttle.push(alert(c), start);
+        }
+        assert_eq!(
+            throttle.pop_ready(start),
+            Some(format!("{}\n\n{}", alert('a'), alert('b')))
+        );
+        assert_eq!(throttle.pop_ready(start), Some(alert('c')));
+
+        throttle.push("é".repeat(5000), start);
+        let cut = throttle.pop_ready(start).unwrap();
+        assert_eq!(cut.chars().count(), MAX_MESSAGE_CHARS);
+        assert!(cut.ends_with('…'));
+    }
+}
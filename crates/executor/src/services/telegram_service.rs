@@ -1,7 +1,8 @@
+use async_trait::async_trait;
 use teloxide::prelude::*;
-use tokio::sync::broadcast;
-use std::env;
-use tracing::{info, error};
+use tracing::error;
+
+use crate::services::notifier::Notifier;
 
 pub struct TelegramService {
     bot: Bot,
@@ -9,39 +10,20 @@ pub struct TelegramService {
 }
 
 impl TelegramService {
-    pub fn new() -> Self {
-        // We expect these to be present. If not, the service will panic at startup, which is fine for critical config.
-        let token = env::var("TELEGRAM_BOT_TOKEN").expect("TELEGRAM_BOT_TOKEN not set in .env");
-        let chat_id_str = env::var("TELEGRAM_CHAT_ID").expect("TELEGRAM_CHAT_ID not set in .env");
-        let chat_id = chat_id_str.parse::<i64>().expect("TELEGRAM_CHAT_ID must be a number");
-        
-        let bot = Bot::new(token);
-        
+    pub fn new(token: &str, chat_id: i64) -> Self {
         Self {
-            bot,
+            bot: Bot::new(token),
             chat_id: ChatId(chat_id),
         }
     }
+}
 
-    pub async fn start(self, mut rx: broadcast::Receiver<String>) {
-        info!("Starting Telegram Notification Service");
-        
-        loop {
-            match rx.recv().await {
-                Ok(msg) => {
-                    // Send message and log error if it fails, but don't crash
-                    if let Err(e) = self.bot.send_message(self.chat_id, msg).await {
-                        error!("Failed to send Telegram message: {}", e);
-                    }
-                }
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    error!("Telegram service lagged behind. Missed {} messages.", n);
-                }
-                Err(_) => {
-                    info!("Telegram notification channel closed. Stopping service.");
-                    break;
-                }
-            }
+#[async_trait]
+impl Notifier for TelegramService {
+    async fn notify(&self, msg: &str) {
+        // Log and carry on: a failed alert must not take the bot down.
+        if let Err(e) = self.bot.send_message(self.chat_id, msg).await {
+            error!("Failed to send Telegram message: {}", e);
         }
     }
 }
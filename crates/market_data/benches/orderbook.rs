@@ -0,0 +1,38 @@
+//! Packing a depth20 side into the order book blob and reading it back.
+//!
+//! Run with `cargo bench -p market_data --bench orderbook`.
+
+use std::hint::black_box;
+
+use common::models::orderbook::unpack_levels;
+use criterion::{Criterion, criterion_group, criterion_main};
+use market_data::remote::orderbook_response::OrderBookCombinedEvent;
+
+/// One side of a `depth20` payload, prices and quantities as Binance sends them.
+fn depth20_side() -> Vec<[String; 2]> {
+    (0..20)
+        .map(|i| {
+            [
+                format!("{:.8}", 65_000.0 - i as f64 * 0.01),
+                format!("{:.8}", 0.125 + i as f64 * 0.037),
+            ]
+        })
+        .collect()
+}
+
+fn pack_level(c: &mut Criterion) {
+    let side = depth20_side();
+    c.bench_function("depth20_pack_level", |b| {
+        b.iter(|| OrderBookCombinedEvent::pack_level(black_box(&side)).unwrap())
+    });
+}
+
+fn unpack(c: &mut Criterion) {
+    let packed = OrderBookCombinedEvent::pack_level(&depth20_side()).unwrap();
+    c.bench_function("depth20_unpack_levels", |b| {
+        b.iter(|| unpack_levels(black_box(&packed)).collect::<Vec<_>>())
+    });
+}
+
+criterion_group!(benches, pack_level, unpack);
+criterion_main!(benches);
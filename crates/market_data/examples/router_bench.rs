@@ -0,0 +1,170 @@
+//! Service wakeups and delivery time with every service on the gateway's
+//! channel against the same services behind the `EventRouter`.
+//!
+//! Receivers are laid out as in `bot`: four trade services, two order book
+//! services, klines, two mark price services, liquidations and open
+//! interest. The event mix is per symbol and second, defaulting to depth20
+//! at 100ms (10 books), the 1s/1m/1h klines (about 2), one mark price and
+//! `trades` aggTrades. Run with
+//! `cargo run --release -p market_data --example router_bench [trades] [seconds]`.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use common::models::{AggTradeInsert, KlineInsert, MarkPriceInsert, OrderBookInsert};
+use market_data::backpressure::BackpressurePolicy;
+use market_data::services::event_router::EventRouter;
+use market_data::services::market_gateway::{EventKind, EventReceiver, MarketEvent, event_channel};
+use tokio::task::JoinSet;
+
+const SYMBOLS: usize = 15;
+const BOOKS: usize = 10;
+const KLINES: usize = 2;
+const MARKS: usize = 1;
+
+/// Subscribers per kind in `bot`, order books recorded.
+const RECEIVERS: &[(EventKind, usize)] = &[
+    (EventKind::AggTrade, 4),
+    (EventKind::OrderBook, 2),
+    (EventKind::Kline, 1),
+    (EventKind::MarkPrice, 2),
+    (EventKind::ForceOrder, 1),
+    (EventKind::OpenInterest, 1),
+];
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let trades: usize = args
+        .next()
+        .map(|n| n.parse().expect("trades must be a number"))
+        .unwrap_or(10);
+    let seconds: usize = args
+        .next()
+        .map(|n| n.parse().expect("seconds must be a number"))
+        .unwrap_or(600);
+    let events = events(trades, seconds);
+    println!(
+        "{} events: {} trades, {} books, {} klines, {} mark prices per symbol and second",
+        events.len(),
+        trades,
+        BOOKS,
+        KLINES,
+        MARKS
+    );
+    for routed in [false, true] {
+        run(routed, &events).await;
+    }
+}
+
+async fn run(routed: bool, events: &[Arc<MarketEvent>]) {
+    let capacity = events.len();
+    let (market_tx, mut market_rx) = event_channel(capacity);
+    let router = EventRouter::new(capacity);
+    let mut receivers = JoinSet::new();
+    for &(kind, count) in RECEIVERS {
+        for _ in 0..count {
+            let rx = if routed {
+                router.sender(kind).subscribe()
+            } else {
+                market_tx.subscribe()
+            };
+            receivers.spawn(wakeups(rx));
+        }
+    }
+
+    let started = Instant::now();
+    for event in events {
+        let _ = market_tx.send(event.clone());
+    }
+    drop(market_tx);
+    if routed {
+        router.run(&mut market_rx, BackpressurePolicy::Block).await;
+    }
+    drop(market_rx);
+    drop(router);
+    let mut total = 0;
+    while let Some(woken) = receivers.join_next().await {
+        total += woken.unwrap();
+    }
+    let elapsed = started.elapsed();
+
+    println!(
+        "routed={}: {} wakeups ({:.2} per event), delivered in {:.2?}",
+        routed,
+        total,
+        total as f64 / events.len() as f64,
+        elapsed
+    );
+}
+
+/// Events a receiver woke up for until its channel closed.
+async fn wakeups(mut rx: EventReceiver) -> u64 {
+    let mut woken = 0;
+    while rx.recv().await.is_ok() {
+        woken += 1;
+    }
+    woken
+}
+
+fn events(trades: usize, seconds: usize) -> Vec<Arc<MarketEvent>> {
+    let mut events = Vec::new();
+    for second in 0..seconds {
+        for symbol in 0..SYMBOLS {
+            let symbol = format!("SYM{}USDT", symbol);
+            let time = second as f64;
+            for _ in 0..trades {
+                events.push(MarketEvent::AggTrade(AggTradeInsert {
+                    agg_trade_id: events.len() as i64,
+                    time,
+                    trade_time: time,
+                    symbol: symbol.clone(),
+                    price: 100.0,
+                    quantity: 1.0,
+                    is_buyer_maker: false,
+                    seq: None,
+                }));
+            }
+            for _ in 0..BOOKS {
+                events.push(MarketEvent::OrderBook(OrderBookInsert {
+                    time,
+                    symbol: symbol.clone(),
+                    bids: vec![0; 160],
+                    asks: vec![0; 160],
+                    seq: None,
+                }));
+            }
+            for _ in 0..KLINES {
+                events.push(MarketEvent::Kline((
+                    KlineInsert {
+                        symbol: symbol.clone(),
+                        start_time: second as i64 * 1000,
+                        close_time: second as i64 * 1000 + 999,
+                        interval: "1s".to_string(),
+                        open_price: 1.0,
+                        close_price: 1.0,
+                        high_price: 1.0,
+                        low_price: 1.0,
+                        volume: 1.0,
+                        no_of_trades: 1,
+                        taker_buy_vol: 1.0,
+                        seq: None,
+                    },
+                    false,
+                )));
+            }
+            for _ in 0..MARKS {
+                events.push(MarketEvent::MarkPrice(MarkPriceInsert {
+                    time,
+                    symbol: symbol.clone(),
+                    mark_price: 100.0,
+                    index_price: 100.0,
+                    funding_rate: 0.0001,
+                    next_funding_time: None,
+                    seq: None,
+                }));
+            }
+        }
+    }
+    events.into_iter().map(Arc::new).collect()
+}
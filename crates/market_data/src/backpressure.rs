@@ -0,0 +1,822 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use common::actors::{ControlMessage, Throughput};
+use storage::StorageError;
+use tokio::sync::mpsc::{
+    self,
+    error::{SendError, TrySendError},
+};
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How often (in dropped messages) a `DropCounter` reports its running total.
+const DROP_REPORT_EVERY: u64 = 1000;
+
+/// Default cap on rows a DB writer holds while its flushes fail.
+pub const DEFAULT_MAX_PENDING_ROWS: usize = 100_000;
+
+/// Default number of failed flushes in a row after which a DB writer gives up.
+pub const DEFAULT_MAX_FLUSH_RETRIES: u32 = 10;
+
+/// Wait after the first retryable flush failure; it doubles with each
+/// further failure in a row, up to `MAX_RETRY_PAUSE`.
+const RETRY_PAUSE: Duration = Duration::from_secs(1);
+const MAX_RETRY_PAUSE: Duration = Duration::from_secs(60);
+
+/// Attempts at writing what is left when a DB writer stops, and the pause
+/// between them. Kept short so they fit in `WRITER_DRAIN_TIMEOUT`.
+const FINAL_FLUSH_ATTEMPTS: u32 = 3;
+const FINAL_FLUSH_PAUSE: Duration = Duration::from_millis(500);
+
+/// What a producer does when the channel in front of it is full.
+///
+/// Selected once per deployment via `BACKPRESSURE_POLICY` (`block` | `drop`)
+/// and handed to every service with its `with_policy`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Wait for room. A slow DB writer stalls its service, which stalls the
+    /// gateway, which stops reading the socket.
+    #[default]
+    Block,
+    /// Never wait. Messages that don't fit are discarded and counted.
+    Drop,
+}
+
+impl BackpressurePolicy {
+    /// `BACKPRESSURE_POLICY`, `Block` when unset.
+    pub fn from_env() -> Result<Self, String> {
+        match env::var("BACKPRESSURE_POLICY") {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Forwards `item` to `tx` according to the policy, returning whether
+    /// it was delivered.
+    ///
+    /// Only a closed channel is reported as an error; with `Drop` a full
+    /// channel is recorded on `dropped` and returns `Ok(false)`.
+    pub async fn forward<T>(
+        &self,
+        tx: &mpsc::Sender<T>,
+        item: T,
+        dropped: &DropCounter,
+    ) -> Result<bool, SendError<T>> {
+        match self {
+            Self::Block => tx.send(item).await.map(|()| true),
+            Self::Drop => match tx.try_send(item) {
+                Ok(()) => Ok(true),
+                Err(TrySendError::Full(_)) => {
+                    dropped.record(1);
+                    Ok(false)
+                }
+                Err(TrySendError::Closed(item)) => Err(SendError(item)),
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for BackpressurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "block" => Ok(Self::Block),
+            "drop" => Ok(Self::Drop),
+            _ => Err(format!(
+                "BACKPRESSURE_POLICY must be 'block' or 'drop', got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+/// Running count of messages discarded under `BackpressurePolicy::Drop`.
+pub struct DropCounter {
+    label: &'static str,
+    dropped: AtomicU64,
+}
+
+impl DropCounter {
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds `n` to the total, warning on the first drop and every
+    /// `DROP_REPORT_EVERY` drops after that.
+    pub fn record(&self, n: u64) {
+        let before = self.dropped.fetch_add(n, Ordering::Relaxed);
+        let after = before + n;
+        if before == 0 || before / DROP_REPORT_EVERY != after / DROP_REPORT_EVERY {
+            warn!(
+                "{}: channel full, {} messages dropped so far",
+                self.label, after
+            );
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Turns a subscriber's `RecvError::Lagged` into the exact run of gateway
+/// sequence numbers it lost, which is only known once the next event arrives.
+pub struct LagTracker {
+    label: &'static str,
+    last_seq: Option<i64>,
+    missed: u64,
+    throughput: Option<Arc<Throughput>>,
+}
+
+impl LagTracker {
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            last_seq: None,
+            missed: 0,
+            throughput: None,
+        }
+    }
+
+    /// Also counts skipped events in `throughput`.
+    pub fn with_throughput(mut self, throughput: Arc<Throughput>) -> Self {
+        self.throughput = Some(throughput);
+        self
+    }
+
+    /// Notes that the receiver skipped `n` events.
+    pub fn lagged(&mut self, n: u64) {
+        self.missed += n;
+        if let Some(throughput) = &self.throughput {
+            throughput.lagged(n);
+        }
+    }
+
+    /// Notes a received event. After a lag, warns with the sequence numbers
+    /// that were skipped and returns them as an inclusive range.
+    pub fn observe(&mut self, seq: Option<i64>) -> Option<(i64, i64)> {
+        let seq = seq?;
+        let last = self.last_seq.replace(seq);
+        if self.missed == 0 {
+            return None;
+        }
+        let missed = std::mem::take(&mut self.missed);
+        let gap = (last.map_or(seq - missed as i64, |last| last + 1), seq - 1);
+        warn!(
+            "{} lagged: missed {} events, seq {} to {}",
+            self.label, missed, gap.0, gap.1
+        );
+        Some(gap)
+    }
+}
+
+/// When a DB writer flushes: once `rows` are buffered or `max_age` after
+/// its last flush, whichever comes first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchWindow {
+    pub rows: usize,
+    pub max_age: Duration,
+}
+
+impl BatchWindow {
+    pub const fn new(rows: usize, max_age: Duration) -> Self {
+        Self { rows, max_age }
+    }
+
+    /// Overrides the window with `<prefix>_BATCH_ROWS` and `<prefix>_BATCH_MS`
+    /// where set. Larger windows mean fewer, bigger transactions. Both must
+    /// be whole numbers above 0.
+    pub fn from_env(self, prefix: &str) -> Result<Self, String> {
+        fn var(name: String, default: u64) -> Result<u64, String> {
+            let Ok(value) = env::var(&name) else {
+                return Ok(default);
+            };
+            match value.trim().parse::<u64>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!(
+                    "{} must be a whole number above 0, got '{}'",
+                    name, value
+                )),
+            }
+        }
+        Ok(Self {
+            rows: var(format!("{}_BATCH_ROWS", prefix), self.rows as u64)? as usize,
+            max_age: Duration::from_millis(var(
+                format!("{}_BATCH_MS", prefix),
+                self.max_age.as_millis() as u64,
+            )?),
+        })
+    }
+}
+
+/// Runs a service's DB writer until `rx` closes: rows are buffered and
+/// written with `insert` once `window` fills or expires, then whatever is
+/// left is drained. Stops early if `buffer` gives up.
+pub async fn run_db_writer<C, T>(
+    ctx: Arc<C>,
+    rx: mpsc::Receiver<T>,
+    window: BatchWindow,
+    buffer: PendingBuffer<T>,
+    insert: InsertFn<C, T>,
+) where
+    C: ?Sized + Sync,
+{
+    run_db_writer_urgent(ctx, rx, window, buffer, insert, |_| false).await
+}
+
+/// `run_db_writer`, also writing at once, along with everything buffered,
+/// when a row that is `urgent` arrives.
+pub async fn run_db_writer_urgent<C, T>(
+    ctx: Arc<C>,
+    mut rx: mpsc::Receiver<T>,
+    window: BatchWindow,
+    mut buffer: PendingBuffer<T>,
+    insert: InsertFn<C, T>,
+    urgent: impl Fn(&T) -> bool,
+) where
+    C: ?Sized + Sync,
+{
+    let mut last_flush = Instant::now();
+    loop {
+        let flush = tokio::select! {
+            row = rx.recv() => match row {
+                Some(row) => {
+                    let urgent = urgent(&row);
+                    buffer.push(row);
+                    buffer.should_flush(window.rows, urgent || last_flush.elapsed() >= window.max_age)
+                }
+                None => {
+                    info!("{}: channel closed, flushing remaining buffer.", buffer.label);
+                    buffer.drain(&*ctx, insert).await;
+                    return;
+                }
+            },
+            _ = tokio::time::sleep_until(last_flush + window.max_age) => {
+                // Restarts the timer even when a retry pause holds the rows.
+                last_flush = Instant::now();
+                buffer.should_flush(0, true)
+            }
+        };
+        if flush {
+            let result = insert(&ctx, buffer.as_slice()).await;
+            buffer.settle(result);
+            if buffer.gave_up() {
+                return;
+            }
+            last_flush = Instant::now();
+        }
+    }
+}
+
+static RATE_LIMITED: AtomicU64 = AtomicU64::new(0);
+
+/// Events not written because their symbol was over its write rate.
+pub fn rate_limited() -> u64 {
+    RATE_LIMITED.load(Ordering::Relaxed)
+}
+
+/// Caps how many events per second each symbol may send to a DB writer, with
+/// a token bucket per symbol that holds up to one second of writes. Events
+/// over the cap are dropped and counted, so one symbol streaming far more
+/// than usual can't fill the writer's channel at the others' expense.
+pub struct SymbolRateLimiter {
+    label: &'static str,
+    per_sec: f64,
+    /// Symbol -> tokens left and when they were last topped up.
+    buckets: HashMap<String, (f64, Instant)>,
+}
+
+impl SymbolRateLimiter {
+    pub fn new(label: &'static str, per_sec: u32) -> Self {
+        Self {
+            label,
+            per_sec: per_sec.max(1) as f64,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes a token from `symbol`'s bucket, or counts the event as dropped
+    /// and returns false if it is empty.
+    pub fn allow(&mut self, symbol: &str) -> bool {
+        self.allow_at(symbol, Instant::now())
+    }
+
+    fn allow_at(&mut self, symbol: &str, now: Instant) -> bool {
+        let per_sec = self.per_sec;
+        let (tokens, last) = self
+            .buckets
+            .entry(symbol.to_string())
+            .or_insert((per_sec, now));
+        let elapsed = now.saturating_duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * per_sec).min(per_sec);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            return true;
+        }
+        let total = RATE_LIMITED.fetch_add(1, Ordering::Relaxed) + 1;
+        if total == 1 || total.is_multiple_of(DROP_REPORT_EVERY) {
+            warn!(
+                "{}: {} over {} writes/s, {} events rate limited so far",
+                self.label, symbol, per_sec, total
+            );
+        }
+        false
+    }
+}
+
+/// How a DB writer writes one batch, given what it writes through.
+pub type InsertFn<C, T> =
+    for<'a> fn(
+        &'a C,
+        &'a [T],
+    ) -> Pin<Box<dyn Future<Output = Result<(), StorageError>> + Send + 'a>>;
+
+/// How much a DB writer buffers while its flushes fail, and how many
+/// failures in a row it takes before giving up. See `PendingBuffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriterLimits {
+    pub max_pending: usize,
+    pub max_retries: u32,
+}
+
+impl Default for WriterLimits {
+    fn default() -> Self {
+        Self {
+            max_pending: DEFAULT_MAX_PENDING_ROWS,
+            max_retries: DEFAULT_MAX_FLUSH_RETRIES,
+        }
+    }
+}
+
+impl WriterLimits {
+    /// Reads `DB_MAX_PENDING_ROWS` (100000) and `DB_MAX_FLUSH_RETRIES` (10),
+    /// both whole numbers above 0.
+    pub fn from_env() -> Result<Self, String> {
+        fn var<T: std::str::FromStr + PartialOrd + Default>(
+            name: &str,
+            default: T,
+        ) -> Result<T, String> {
+            let Ok(value) = env::var(name) else {
+                return Ok(default);
+            };
+            match value.trim().parse::<T>() {
+                Ok(n) if n > T::default() => Ok(n),
+                _ => Err(format!(
+                    "{} must be a whole number above 0, got '{}'",
+                    name, value
+                )),
+            }
+        }
+        Ok(Self {
+            max_pending: var("DB_MAX_PENDING_ROWS", DEFAULT_MAX_PENDING_ROWS)?,
+            max_retries: var("DB_MAX_FLUSH_RETRIES", DEFAULT_MAX_FLUSH_RETRIES)?,
+        })
+    }
+}
+
+/// Rows a DB writer has accepted but not yet written.
+///
+/// A batch that failed on a locked or busy database is kept and retried with
+/// exponential backoff instead of being thrown away. While the disk stalls
+/// the buffer grows up to `max_pending` rows; past that the oldest rows are
+/// dropped, counted, and reported to the supervisor once per stall, so memory
+/// stays bounded. After `max_retries` failures in a row the buffer gives up
+/// (see `gave_up`).
+pub struct PendingBuffer<T> {
+    label: &'static str,
+    items: VecDeque<T>,
+    max_pending: usize,
+    dropped: DropCounter,
+    paused_until: Option<Instant>,
+    overflowing: bool,
+    failures: u32,
+    /// Retries made by `settle_final`.
+    final_attempts: u32,
+    max_retries: u32,
+    reporter: Option<(Uuid, mpsc::Sender<ControlMessage>)>,
+    throughput: Option<Arc<Throughput>>,
+}
+
+impl<T> PendingBuffer<T> {
+    pub fn new(label: &'static str, max_pending: usize) -> Self {
+        Self {
+            label,
+            items: VecDeque::new(),
+            max_pending: max_pending.max(1),
+            dropped: DropCounter::new(label),
+            paused_until: None,
+            overflowing: false,
+            failures: 0,
+            final_attempts: 0,
+            max_retries: DEFAULT_MAX_FLUSH_RETRIES,
+            reporter: None,
+            throughput: None,
+        }
+    }
+
+    /// Failed flushes in a row before giving up, at least 1.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries.max(1);
+        self
+    }
+
+    /// A buffer with `limits` applied.
+    pub fn with_limits(label: &'static str, limits: WriterLimits) -> Self {
+        Self::new(label, limits.max_pending).with_max_retries(limits.max_retries)
+    }
+
+    /// Reports overflow to the supervisor as `ControlMessage::Error(id, ..)`.
+    pub fn with_reporter(mut self, id: Uuid, supervisor_tx: mpsc::Sender<ControlMessage>) -> Self {
+        self.reporter = Some((id, supervisor_tx));
+        self
+    }
+
+    /// Counts the rows each successful flush writes in `throughput`.
+    pub fn with_throughput(mut self, throughput: Arc<Throughput>) -> Self {
+        self.throughput = Some(throughput);
+        self
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() >= self.max_pending {
+            self.items.pop_front();
+            self.dropped.record(1);
+            if !self.overflowing {
+                self.overflowing = true;
+                self.report_overflow();
+            }
+        }
+        self.items.push_back(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Whether to flush now: `batch_size` rows are waiting or the writer's
+    /// timer says the rows are `stale`, and no retry pause is running.
+    pub fn should_flush(&self, batch_size: usize, stale: bool) -> bool {
+        !self.items.is_empty()
+            && self
+                .paused_until
+                .is_none_or(|until| Instant::now() >= until)
+            && (self.items.len() >= batch_size || stale)
+    }
+
+    /// The pending rows, oldest first, as one batch.
+    pub fn as_slice(&mut self) -> &[T] {
+        self.items.make_contiguous()
+    }
+
+    /// Applies the outcome of flushing `as_slice()`: written or permanently
+    /// failed rows are discarded, a retryable failure keeps them until the
+    /// retries run out.
+    pub fn settle(&mut self, result: Result<(), StorageError>) {
+        match result {
+            Err(e) if e.is_retryable() => {
+                self.failures += 1;
+                if self.gave_up() {
+                    let msg = format!(
+                        "{}: {} flushes failed in a row ({}), dropping {} rows and stopping",
+                        self.label,
+                        self.failures,
+                        e,
+                        self.items.len()
+                    );
+                    error!("{}", msg);
+                    self.dropped.record(self.items.len() as u64);
+                    self.items.clear();
+                    if let Some((id, tx)) = &self.reporter {
+                        let _ = tx.try_send(ControlMessage::Error(*id, msg));
+                    }
+                    return;
+                }
+                let pause = RETRY_PAUSE
+                    .saturating_mul(1 << (self.failures - 1).min(16))
+                    .min(MAX_RETRY_PAUSE);
+                warn!(
+                    "{}: keeping {} rows, retry {}/{} in {:?}",
+                    self.label,
+                    self.items.len(),
+                    self.failures,
+                    self.max_retries,
+                    pause
+                );
+                self.paused_until = Some(Instant::now() + pause);
+            }
+            result => {
+                match result {
+                    Ok(()) => self.count_written(),
+                    Err(e) => {
+                        error!(
+                            "{}: write failed ({}), dropping {} rows",
+                            self.label,
+                            e,
+                            self.items.len()
+                        );
+                        self.dropped.record(self.items.len() as u64);
+                    }
+                }
+                self.items.clear();
+                self.paused_until = None;
+                self.overflowing = false;
+                self.failures = 0;
+            }
+        }
+    }
+
+    /// Applies the outcome of a flush made after the writer's channel closed.
+    /// Returns true, after a short pause, if a retryable failure leaves
+    /// attempts to try again; rows that still can't be written are counted
+    /// as dropped and logged rather than silently lost.
+    pub async fn settle_final(&mut self, result: Result<(), StorageError>) -> bool {
+        match result {
+            Ok(()) => {
+                self.count_written();
+                self.items.clear();
+                false
+            }
+            Err(e) if e.is_retryable() && self.final_attempts + 1 < FINAL_FLUSH_ATTEMPTS => {
+                self.final_attempts += 1;
+                warn!(
+                    "{}: final flush of {} rows failed ({}), retry {}/{}",
+                    self.label,
+                    self.items.len(),
+                    e,
+                    self.final_attempts,
+                    FINAL_FLUSH_ATTEMPTS - 1
+                );
+                tokio::time::sleep(FINAL_FLUSH_PAUSE).await;
+                true
+            }
+            Err(e) => {
+                error!(
+                    "{}: final flush failed ({}), dropping {} rows",
+                    self.label,
+                    e,
+                    self.items.len()
+                );
+                self.dropped.record(self.items.len() as u64);
+                self.items.clear();
+                false
+            }
+        }
+    }
+
+    /// Writes what is left once the writer's channel has closed, retrying
+    /// through `settle_final` until the rows are written or dropped.
+    pub async fn drain<C: ?Sized>(&mut self, ctx: &C, insert: InsertFn<C, T>) {
+        while !self.is_empty() {
+            let result = insert(ctx, self.as_slice()).await;
+            if !self.settle_final(result).await {
+                break;
+            }
+        }
+    }
+
+    /// True once `max_retries` flushes have failed in a row. The writer
+    /// should then exit: its service fails to forward the next row and
+    /// stops, and the supervisor restarts it with a fresh writer.
+    pub fn gave_up(&self) -> bool {
+        self.failures >= self.max_retries
+    }
+
+    fn count_written(&self) {
+        if let Some(throughput) = &self.throughput {
+            throughput.wrote(self.items.len() as u64);
+        }
+    }
+
+    fn report_overflow(&self) {
+        let msg = format!(
+            "{}: over {} pending rows while the DB stalls, dropping the oldest",
+            self.label, self.max_pending
+        );
+        warn!("{}", msg);
+        if let Some((id, tx)) = &self.reporter {
+            let _ = tx.try_send(ControlMessage::Error(*id, msg));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_caps_each_symbol_separately() {
+        let mut limiter = SymbolRateLimiter::new("test", 2);
+        let start = Instant::now();
+        assert!(limiter.allow_at("PEPEUSDT", start));
+        assert!(limiter.allow_at("PEPEUSDT", start));
+        assert!(!limiter.allow_at("PEPEUSDT", start));
+        assert!(limiter.allow_at("BTCUSDT", start), "own bucket");
+
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.allow_at("PEPEUSDT", later));
+        assert!(!limiter.allow_at("PEPEUSDT", later));
+        // Refills up to one second of writes, not more.
+        let idle = start + Duration::from_secs(60);
+        assert!(limiter.allow_at("PEPEUSDT", idle));
+        assert!(limiter.allow_at("PEPEUSDT", idle));
+        assert!(!limiter.allow_at("PEPEUSDT", idle));
+    }
+
+    #[test]
+    fn test_lag_tracker_bounds_the_gap() {
+        let mut lag = LagTracker::new("test");
+        assert_eq!(lag.observe(Some(1)), None);
+        assert_eq!(lag.observe(Some(2)), None);
+        lag.lagged(3);
+        assert_eq!(lag.observe(Some(6)), Some((3, 5)));
+        assert_eq!(lag.observe(Some(7)), None);
+
+        // Lagged before the first event: bounded by the count alone.
+        let mut lag = LagTracker::new("test");
+        lag.lagged(4);
+        assert_eq!(lag.observe(Some(10)), Some((6, 9)));
+    }
+
+    #[test]
+    fn test_policy_parse() {
+        assert_eq!("block".parse(), Ok(BackpressurePolicy::Block));
+        assert_eq!(" DROP ".parse(), Ok(BackpressurePolicy::Drop));
+        assert!("lossy".parse::<BackpressurePolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drop_policy_counts_when_full() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let dropped = DropCounter::new("test");
+
+        let policy = BackpressurePolicy::Drop;
+        assert!(policy.forward(&tx, 1, &dropped).await.unwrap());
+        assert!(!policy.forward(&tx, 2, &dropped).await.unwrap());
+        assert!(!policy.forward(&tx, 3, &dropped).await.unwrap());
+
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(dropped.total(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_closed_channel_is_an_error() {
+        let (tx, rx) = mpsc::channel::<u8>(1);
+        drop(rx);
+        let dropped = DropCounter::new("test");
+
+        assert!(
+            BackpressurePolicy::Drop
+                .forward(&tx, 1, &dropped)
+                .await
+                .is_err()
+        );
+        assert!(
+            BackpressurePolicy::Block
+                .forward(&tx, 1, &dropped)
+                .await
+                .is_err()
+        );
+        assert_eq!(dropped.total(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pending_buffer_keeps_retryable_batches_within_cap() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let id = Uuid::new_v4();
+        let mut buffer = PendingBuffer::new("test", 3).with_reporter(id, tx);
+
+        for n in 0..3 {
+            buffer.push(n);
+        }
+        assert!(buffer.should_flush(3, false));
+        buffer.settle(Err(StorageError::from(sqlx::Error::PoolTimedOut)));
+        assert_eq!(buffer.as_slice(), &[0, 1, 2]);
+        assert!(
+            !buffer.should_flush(1, true),
+            "paused after a retryable failure"
+        );
+
+        buffer.push(3);
+        buffer.push(4);
+        assert_eq!(buffer.as_slice(), &[2, 3, 4]);
+        assert_eq!(buffer.dropped.total(), 2);
+        assert!(matches!(rx.try_recv(), Ok(ControlMessage::Error(got, _)) if got == id));
+        assert!(rx.try_recv().is_err(), "one report per stall");
+
+        buffer.settle(Err(StorageError::Io(std::io::Error::other("disk gone"))));
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.dropped.total(), 5, "permanent failures count too");
+    }
+
+    #[tokio::test]
+    async fn test_pending_buffer_backs_off_then_gives_up() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let id = Uuid::new_v4();
+        let mut buffer = PendingBuffer::new("test", 10)
+            .with_max_retries(3)
+            .with_reporter(id, tx);
+        let busy = || Err(StorageError::from(sqlx::Error::PoolTimedOut));
+
+        buffer.push(1);
+        buffer.settle(busy());
+        let first = buffer.paused_until.unwrap() - Instant::now();
+        buffer.settle(busy());
+        let second = buffer.paused_until.unwrap() - Instant::now();
+        assert!(first <= RETRY_PAUSE && second > RETRY_PAUSE);
+        assert!(!buffer.gave_up());
+        assert!(rx.try_recv().is_err());
+
+        buffer.settle(busy());
+        assert!(buffer.gave_up());
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.dropped.total(), 1);
+        assert!(matches!(rx.try_recv(), Ok(ControlMessage::Error(got, _)) if got == id));
+    }
+
+    #[tokio::test]
+    async fn test_final_flush_retries_before_dropping() {
+        let busy = || Err(StorageError::from(sqlx::Error::PoolTimedOut));
+        let mut buffer = PendingBuffer::new("test", 10);
+        buffer.push(1);
+        assert!(buffer.settle_final(busy()).await);
+        assert!(!buffer.settle_final(Ok(())).await);
+        assert!(buffer.is_empty());
+
+        buffer.push(2);
+        assert!(buffer.settle_final(busy()).await);
+        assert!(!buffer.settle_final(busy()).await, "out of attempts");
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.dropped.total(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drain_retries_until_written() {
+        let failures = std::sync::atomic::AtomicU32::new(1);
+        let mut buffer = PendingBuffer::new("test", 10);
+        buffer.push(1);
+        buffer.push(2);
+        buffer
+            .drain(&failures, |failures, _| {
+                Box::pin(async move {
+                    match failures
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                    {
+                        Ok(_) => Err(StorageError::from(sqlx::Error::PoolTimedOut)),
+                        Err(_) => Ok(()),
+                    }
+                })
+            })
+            .await;
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.dropped.total(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_writer_flushes_when_the_window_expires() {
+        let written = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::channel(8);
+        let writer = tokio::spawn(run_db_writer(
+            written.clone(),
+            rx,
+            BatchWindow::new(10, Duration::from_millis(50)),
+            PendingBuffer::new("test", 10),
+            |written, batch| {
+                written.lock().unwrap().push(batch.to_vec());
+                Box::pin(async { Ok(()) })
+            },
+        ));
+
+        tx.send(1).await.unwrap();
+        tx.send(2).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec![vec![1, 2]],
+            "no more rows came"
+        );
+
+        tx.send(3).await.unwrap();
+        drop(tx);
+        writer.await.unwrap();
+        assert_eq!(
+            written.lock().unwrap().last(),
+            Some(&vec![3]),
+            "drained on close"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use common::config::{parse_secs, parse_whole};
+use tracing::{info, warn};
+
+/// Default number of reconnects within the window that triggers a warning.
+pub const DEFAULT_RECONNECT_WARN_COUNT: usize = 5;
+
+/// Default window over which reconnects are counted for the warning.
+pub const DEFAULT_RECONNECT_WARN_WINDOW: Duration = Duration::from_secs(600);
+
+/// How many reconnects within how long make a socket worth a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectWarning {
+    pub count: usize,
+    pub window: Duration,
+}
+
+impl Default for ReconnectWarning {
+    fn default() -> Self {
+        Self {
+            count: DEFAULT_RECONNECT_WARN_COUNT,
+            window: DEFAULT_RECONNECT_WARN_WINDOW,
+        }
+    }
+}
+
+impl ReconnectWarning {
+    /// Reads `RECONNECT_WARN_COUNT` (5) and `RECONNECT_WARN_WINDOW_SECS`
+    /// (600). An unset variable keeps its default; an invalid one is an error.
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        Ok(Self {
+            count: match env::var("RECONNECT_WARN_COUNT") {
+                Ok(value) => parse_whole("RECONNECT_WARN_COUNT", &value)?,
+                Err(_) => defaults.count,
+            },
+            window: match env::var("RECONNECT_WARN_WINDOW_SECS") {
+                Ok(value) => parse_secs("RECONNECT_WARN_WINDOW_SECS", &value)?,
+                Err(_) => defaults.window,
+            },
+        })
+    }
+}
+
+/// Connection history of one websocket: when it came up, how long it lasted
+/// and how often it had to be re-established.
+///
+/// Failed connection attempts count as reconnects too, so a socket that can't
+/// get through at all trips the warning just like one that keeps dropping.
+pub struct ConnectionStats {
+    label: &'static str,
+    reconnects: AtomicU64,
+    rotations: AtomicU64,
+    connected_at: Mutex<Option<Instant>>,
+    recent: Mutex<VecDeque<Instant>>,
+    warn_count: usize,
+    warn_window: Duration,
+}
+
+impl ConnectionStats {
+    pub fn new(label: &'static str, warn_count: usize, warn_window: Duration) -> Self {
+        Self {
+            label,
+            reconnects: AtomicU64::new(0),
+            rotations: AtomicU64::new(0),
+            connected_at: Mutex::new(None),
+            recent: Mutex::new(VecDeque::new()),
+            warn_count: warn_count.max(1),
+            warn_window,
+        }
+    }
+
+    pub fn connected(&self) {
+        *self.connected_at.lock().expect("connection stats poisoned") = Some(Instant::now());
+        info!(
+            "{} connected ({} reconnects so far)",
+            self.label,
+            self.reconnects()
+        );
+    }
+
+    /// Records the end of a connection, or a failed attempt at one, and logs
+    /// how long it was up.
+    pub fn disconnected(&self, reason: &str) {
+        let uptime = self
+            .connected_at
+            .lock()
+            .expect("connection stats poisoned")
+            .take()
+            .map(|since| since.elapsed());
+        match uptime {
+            Some(uptime) => warn!(
+                "{} disconnected after {}s up: {}",
+                self.label,
+                uptime.as_secs(),
+                reason
+            ),
+            None => warn!("{} could not connect: {}", self.label, reason),
+        }
+        self.record_reconnect(Instant::now());
+    }
+
+    /// Records a planned switch to a fresh socket. The connection never went
+    /// down, so this restarts the uptime without counting a reconnect.
+    pub fn rotated(&self) {
+        let uptime = self
+            .connected_at
+            .lock()
+            .expect("connection stats poisoned")
+            .replace(Instant::now())
+            .map_or(0, |since| since.elapsed().as_secs());
+        let rotations = self.rotations.fetch_add(1, Ordering::Relaxed) + 1;
+        info!(
+            "{} rotated to a new connection after {}s up (planned, {} so far)",
+            self.label, uptime, rotations
+        );
+    }
+
+    /// Counts a reconnect at `now`. Returns true, after warning, if there were
+    /// `warn_count` or more within the window.
+    fn record_reconnect(&self, now: Instant) -> bool {
+        let total = self.reconnects.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut recent = self.recent.lock().expect("connection stats poisoned");
+        recent.push_back(now);
+        while recent
+            .front()
+            .is_some_and(|&t| now.duration_since(t) > self.warn_window)
+        {
+            recent.pop_front();
+        }
+        if recent.len() < self.warn_count {
+            return false;
+        }
+        warn!(
+            "{} reconnected {} times in the last {}s ({} total): network trouble or an IP ban?",
+            self.label,
+            recent.len(),
+            self.warn_window.as_secs(),
+            total
+        );
+        true
+    }
+
+    /// Reconnects since startup.
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects.load(Ordering::Relaxed)
+    }
+
+    /// Planned rotations since startup.
+    pub fn rotations(&self) -> u64 {
+        self.rotations.load(Ordering::Relaxed)
+    }
+
+    /// How long the current connection has been up, `None` while down.
+    pub fn uptime(&self) -> Option<Duration> {
+        self.connected_at
+            .lock()
+            .expect("connection stats poisoned")
+            .map(|since| since.elapsed())
+    }
+}
+
+impl fmt::Display for ConnectionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.uptime() {
+            Some(uptime) => write!(f, "{} up {}s", self.label, uptime.as_secs())?,
+            None => write!(f, "{} down", self.label)?,
+        }
+        write!(f, ", {} reconnects", self.reconnects())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warns_only_when_reconnects_cluster() {
+        let stats = ConnectionStats::new("spot", 3, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(!stats.record_reconnect(start));
+        assert!(!stats.record_reconnect(start + Duration::from_secs(30)));
+        // The first one has left the window by now.
+        assert!(!stats.record_reconnect(start + Duration::from_secs(70)));
+        assert!(stats.record_reconnect(start + Duration::from_secs(80)));
+        assert_eq!(stats.reconnects(), 4);
+
+        assert_eq!(stats.uptime(), None);
+        stats.connected();
+        assert!(stats.uptime().is_some());
+        stats.rotated();
+        assert!(stats.uptime().is_some());
+        assert_eq!((stats.rotations(), stats.reconnects()), (1, 4));
+        stats.disconnected("closed by peer");
+        assert_eq!((stats.uptime(), stats.reconnects()), (None, 5));
+    }
+}
@@ -1,3 +1,13 @@
+pub mod backpressure;
+pub mod connection_stats;
+pub mod order_book;
+pub mod raw_capture;
 pub mod remote;
 pub mod services;
+pub mod streams;
 mod traits;
+
+pub use backpressure::rate_limited;
+pub use remote::orderbook_response::invalid_books;
+pub use services::orderbook_service::repeated_books;
+pub use traits::parse_errors;
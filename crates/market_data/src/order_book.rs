@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+
+use common::models::DepthDiff;
+
+/// `(price, quantity)`.
+pub type Level = (f64, f64);
+
+/// What `LocalOrderBook::apply` did with a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOutcome {
+    Applied,
+    /// Already covered by the snapshot or an earlier diff; ignored.
+    Stale,
+    /// Updates between the book and this diff were missed. The diff is not
+    /// applied and the book stays behind until it is rebuilt from a new snapshot.
+    Gap {
+        expected: u64,
+        first_update_id: u64,
+    },
+}
+
+/// Full order book for one symbol, built from a REST snapshot and kept current
+/// with the `@depth` diff stream using Binance's `U`/`u` update ids.
+///
+/// Levels are keyed by the price's bit pattern: prices are positive, and
+/// positive floats sort the same way as their bits.
+pub struct LocalOrderBook {
+    last_update_id: u64,
+    /// Set once the first diff after the snapshot has been applied; from then on
+    /// every diff must start exactly where the previous one ended.
+    bridged: bool,
+    bids: BTreeMap<u64, f64>,
+    asks: BTreeMap<u64, f64>,
+}
+
+impl LocalOrderBook {
+    pub fn from_snapshot(last_update_id: u64, bids: &[Level], asks: &[Level]) -> Self {
+        let mut book = Self {
+            last_update_id,
+            bridged: false,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        };
+        Self::update(&mut book.bids, bids);
+        Self::update(&mut book.asks, asks);
+        book
+    }
+
+    /// Applies `diff` if it continues the book's sequence.
+    pub fn apply(&mut self, diff: &DepthDiff) -> DiffOutcome {
+        if diff.final_update_id <= self.last_update_id {
+            return DiffOutcome::Stale;
+        }
+
+        let expected = self.last_update_id + 1;
+        // The first diff after the snapshot may start before it, as long as it
+        // covers `lastUpdateId + 1`.
+        let in_sequence = if self.bridged {
+            diff.first_update_id == expected
+        } else {
+            diff.first_update_id <= expected
+        };
+        if !in_sequence {
+            return DiffOutcome::Gap {
+                expected,
+                first_update_id: diff.first_update_id,
+            };
+        }
+
+        Self::update(&mut self.bids, &diff.bids);
+        Self::update(&mut self.asks, &diff.asks);
+        self.last_update_id = diff.final_update_id;
+        self.bridged = true;
+        DiffOutcome::Applied
+    }
+
+    pub fn last_update_id(&self) -> u64 {
+        self.last_update_id
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        self.bids.iter().next_back().map(Self::level)
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        self.asks.iter().next().map(Self::level)
+    }
+
+    /// The best `n` levels of each side, best first.
+    pub fn top(&self, n: usize) -> (Vec<Level>, Vec<Level>) {
+        (
+            self.bids.iter().rev().take(n).map(Self::level).collect(),
+            self.asks.iter().take(n).map(Self::level).collect(),
+        )
+    }
+
+    fn update(side: &mut BTreeMap<u64, f64>, levels: &[Level]) {
+        for &(price, quantity) in levels {
+            if quantity == 0.0 {
+                side.remove(&price.to_bits());
+            } else {
+                side.insert(price.to_bits(), quantity);
+            }
+        }
+    }
+
+    fn level((price, quantity): (&u64, &f64)) -> Level {
+        (f64::from_bits(*price), *quantity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(first: u64, last: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> DepthDiff {
+        DepthDiff {
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            final_update_id: last,
+            bids: bids.to_vec(),
+            asks: asks.to_vec(),
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn test_applies_diffs_in_sequence_after_snapshot() {
+        let mut book =
+            LocalOrderBook::from_snapshot(100, &[(9.0, 1.0), (10.0, 2.0)], &[(11.0, 3.0)]);
+
+        // Entirely before the snapshot.
+        assert_eq!(
+            book.apply(&diff(90, 100, &[(10.0, 0.0)], &[])),
+            DiffOutcome::Stale
+        );
+        // Straddles lastUpdateId + 1.
+        assert_eq!(
+            book.apply(&diff(95, 105, &[(10.0, 0.0)], &[(10.5, 1.0)])),
+            DiffOutcome::Applied
+        );
+        assert_eq!(
+            book.apply(&diff(106, 110, &[(9.5, 4.0)], &[])),
+            DiffOutcome::Applied
+        );
+
+        assert_eq!(book.last_update_id(), 110);
+        assert_eq!(book.best_bid(), Some((9.5, 4.0)));
+        assert_eq!(book.best_ask(), Some((10.5, 1.0)));
+        assert_eq!(
+            book.top(5),
+            (vec![(9.5, 4.0), (9.0, 1.0)], vec![(10.5, 1.0), (11.0, 3.0)])
+        );
+    }
+
+    #[test]
+    fn test_gap_is_reported_and_not_applied() {
+        let mut book = LocalOrderBook::from_snapshot(100, &[(10.0, 2.0)], &[]);
+        assert_eq!(
+            book.apply(&diff(105, 110, &[], &[])),
+            DiffOutcome::Gap {
+                expected: 101,
+                first_update_id: 105
+            }
+        );
+
+        book.apply(&diff(101, 102, &[], &[]));
+        assert_eq!(
+            book.apply(&diff(104, 106, &[(10.0, 0.0)], &[])),
+            DiffOutcome::Gap {
+                expected: 103,
+                first_update_id: 104
+            }
+        );
+        assert_eq!(book.best_bid(), Some((10.0, 2.0)));
+        assert_eq!(book.last_update_id(), 102);
+    }
+}
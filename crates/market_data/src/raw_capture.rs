@@ -0,0 +1,251 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tracing::{error, info};
+
+use crate::backpressure::DropCounter;
+
+/// Default uncompressed size at which the capture moves to a new file.
+pub const DEFAULT_RAW_CAPTURE_ROTATE_MB: u64 = 256;
+
+/// Frames queued for the writer thread before new ones are dropped.
+const CAPTURE_QUEUE: usize = 10_000;
+
+/// How often the writer flushes a zstd block, so a crash loses at most this
+/// much of the capture.
+const FLUSH_EVERY: Duration = Duration::from_secs(5);
+
+/// Settings of the raw frame capture, read by `from_env`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawCaptureConfig {
+    /// `RAW_CAPTURE_DIR`: where the `.jsonl.zst` files go. Capture is off
+    /// while it is unset.
+    pub dir: PathBuf,
+    /// `RAW_CAPTURE_SAMPLE`: keep one frame in every `sample_every`
+    /// (default 1, every frame).
+    pub sample_every: u64,
+    /// `RAW_CAPTURE_ROTATE_MB`: uncompressed bytes per file.
+    pub rotate_bytes: u64,
+}
+
+impl RawCaptureConfig {
+    /// `None` unless `RAW_CAPTURE_DIR` is set.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(dir) = env::var("RAW_CAPTURE_DIR") else {
+            return Ok(None);
+        };
+        let number = |name: &str, default: u64| match env::var(name) {
+            Ok(value) => value
+                .parse::<u64>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("{} must be a positive number, got '{}'", name, value)),
+            Err(_) => Ok(default),
+        };
+        Ok(Some(Self {
+            dir: PathBuf::from(dir),
+            sample_every: number("RAW_CAPTURE_SAMPLE", 1)?,
+            rotate_bytes: number("RAW_CAPTURE_ROTATE_MB", DEFAULT_RAW_CAPTURE_ROTATE_MB)?
+                * 1024
+                * 1024,
+        }))
+    }
+}
+
+/// Append-only log of the text frames the gateway receives, exactly as
+/// Binance sent them, for replay and for inspecting frames that failed to
+/// parse.
+///
+/// Each line is `{"recv_ms":..,"venue":"spot","frame":"<raw text>"}`. Files
+/// are zstd-compressed and named `raw_<epoch ms>_<n>.jsonl.zst`. Writing happens on
+/// a dedicated thread; when it falls behind, frames are dropped rather than
+/// slowing the socket. Cheap to clone, all clones feed the same files.
+#[derive(Clone)]
+pub struct RawCapture {
+    tx: SyncSender<Frame>,
+    sample_every: u64,
+    seen: Arc<AtomicU64>,
+    dropped: Arc<DropCounter>,
+}
+
+/// What the capture sends its writer thread.
+enum Frame {
+    Line(String),
+    /// Write what is queued, finish the file and exit.
+    Finish,
+}
+
+/// The capture's writer thread. Call `finish` on shutdown: the thread
+/// otherwise only ends once every `RawCapture` clone is dropped, and a
+/// process that exits first leaves the current file without its final
+/// zstd frame.
+pub struct RawCaptureWriter {
+    tx: SyncSender<Frame>,
+    thread: JoinHandle<()>,
+}
+
+impl RawCaptureWriter {
+    /// Writes the frames still queued, finishes the current file and waits
+    /// for the thread. Frames recorded afterwards are dropped. Blocks, so
+    /// call it from `spawn_blocking` in async code.
+    pub fn finish(self) {
+        // Fails only if the writer already stopped on an I/O error.
+        let _ = self.tx.send(Frame::Finish);
+        if self.thread.join().is_err() {
+            error!("Raw capture writer panicked");
+        }
+    }
+}
+
+impl RawCapture {
+    /// Starts the writer thread. It finishes the current file and exits on
+    /// `RawCaptureWriter::finish` or once every clone is dropped.
+    pub fn spawn(config: RawCaptureConfig) -> io::Result<(Self, RawCaptureWriter)> {
+        fs::create_dir_all(&config.dir)?;
+        let (tx, rx) = mpsc::sync_channel(CAPTURE_QUEUE);
+        let thread = thread::Builder::new()
+            .name("raw-capture".to_string())
+            .spawn(move || write_frames(&config.dir, config.rotate_bytes, rx))?;
+        Ok((
+            Self {
+                tx: tx.clone(),
+                sample_every: config.sample_every,
+                seen: Arc::new(AtomicU64::new(0)),
+                dropped: Arc::new(DropCounter::new("Raw capture")),
+            },
+            RawCaptureWriter { tx, thread },
+        ))
+    }
+
+    /// Records `frame` if it falls on the sampling interval, or regardless
+    /// of sampling when `always` is set (frames that failed to parse).
+    pub fn record(&self, venue: &str, frame: &str, always: bool) {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+        if !always && !n.is_multiple_of(self.sample_every) {
+            return;
+        }
+        let line = serde_json::json!({
+            "recv_ms": now_millis(),
+            "venue": venue,
+            "frame": frame,
+        })
+        .to_string();
+        match self.tx.try_send(Frame::Line(line)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => self.dropped.record(1),
+            // The writer finished, or gave up on an I/O error and logged it.
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
+type CaptureFile = zstd::Encoder<'static, BufWriter<File>>;
+
+fn open_file(dir: &Path, n: u32) -> io::Result<CaptureFile> {
+    let path = dir.join(format!("raw_{}_{}.jsonl.zst", now_millis(), n));
+    info!("Raw capture writing to {}", path.display());
+    let file = BufWriter::new(File::create(path)?);
+    zstd::Encoder::new(file, 3)
+}
+
+/// Writes the end of the zstd frame and flushes it to disk.
+fn close_file(file: CaptureFile) -> io::Result<()> {
+    file.finish()?.flush()
+}
+
+fn write_frames(dir: &Path, rotate_bytes: u64, rx: Receiver<Frame>) {
+    if let Err(e) = try_write_frames(dir, rotate_bytes, rx) {
+        error!("Raw capture stopped: {}", e);
+    }
+}
+
+fn try_write_frames(dir: &Path, rotate_bytes: u64, rx: Receiver<Frame>) -> io::Result<()> {
+    let mut files = 0;
+    let mut file = open_file(dir, files)?;
+    let mut written = 0u64;
+    let mut last_flush = Instant::now();
+    loop {
+        match rx.recv_timeout(FLUSH_EVERY) {
+            Ok(Frame::Line(line)) => {
+                if written >= rotate_bytes {
+                    files += 1;
+                    close_file(std::mem::replace(&mut file, open_file(dir, files)?))?;
+                    written = 0;
+                }
+                file.write_all(line.as_bytes())?;
+                file.write_all(b"\n")?;
+                written += line.len() as u64 + 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // Shutdown, or every `RawCapture` is gone.
+            Ok(Frame::Finish) | Err(RecvTimeoutError::Disconnected) => return close_file(file),
+        }
+        if last_flush.elapsed() >= FLUSH_EVERY {
+            file.flush()?;
+            last_flush = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use storage::test_support::TempDir;
+
+    #[test]
+    fn test_samples_keeps_failures_and_rotates() {
+        let dir = TempDir::new("raw");
+        let (capture, writer) = RawCapture::spawn(RawCaptureConfig {
+            dir: dir.path().to_path_buf(),
+            sample_every: 2,
+            rotate_bytes: 1,
+        })
+        .unwrap();
+
+        capture.record("spot", r#"{"stream":"a"}"#, false);
+        capture.record("spot", r#"{"stream":"b"}"#, false);
+        capture.record("futures", "not json", true);
+        // Finishes the files while the capture is still held, as on shutdown.
+        writer.finish();
+        capture.record("spot", r#"{"stream":"c"}"#, true);
+
+        let mut lines = Vec::new();
+        for entry in fs::read_dir(dir.path()).unwrap() {
+            let mut text = String::new();
+            zstd::Decoder::new(File::open(entry.unwrap().path()).unwrap())
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            lines.extend(text.lines().map(|l| {
+                let v: serde_json::Value = serde_json::from_str(l).unwrap();
+                (
+                    v["venue"].to_string(),
+                    v["frame"].as_str().unwrap().to_string(),
+                )
+            }));
+        }
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                (r#""futures""#.to_string(), "not json".to_string()),
+                (r#""spot""#.to_string(), r#"{"stream":"a"}"#.to_string()),
+            ]
+        );
+    }
+}
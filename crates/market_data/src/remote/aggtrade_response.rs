@@ -2,7 +2,7 @@ use serde::Deserialize;
 
 use common::models::AggTradeInsert;
 
-use crate::traits::RemoteResponse;
+use crate::traits::{RemoteResponse, parse_field};
 
 #[derive(Deserialize, Debug)]
 pub struct AggTradeCombinedEvent {
@@ -11,6 +11,8 @@ pub struct AggTradeCombinedEvent {
 
 #[derive(Deserialize, Debug)]
 pub struct AggTradeEvent {
+    #[serde(rename(deserialize = "a"))]
+    pub agg_trade_id: i64,
     #[serde(rename(deserialize = "s"))]
     pub symbol: String,
     #[serde(rename(deserialize = "p"))]
@@ -19,16 +21,42 @@ pub struct AggTradeEvent {
     pub quantity: String,
     #[serde(rename(deserialize = "m"))]
     pub is_buyer_maker: bool,
+    /// Trade time, epoch milliseconds.
+    #[serde(rename(deserialize = "T"))]
+    pub trade_time: i64,
 }
 
 impl RemoteResponse<AggTradeInsert> for AggTradeCombinedEvent {
     fn to_insertable(&self) -> Result<AggTradeInsert, serde_json::Error> {
         Ok(AggTradeInsert {
+            agg_trade_id: self.data.agg_trade_id,
             time: self.get_time_f64(),
+            trade_time: self.data.trade_time as f64 / 1000.0,
             symbol: self.data.symbol.clone(),
-            price: self.data.price.parse::<f64>().unwrap_or(0_f64),
-            quantity: self.data.quantity.parse::<f64>().unwrap_or(0_f64),
+            price: parse_field("price", &self.data.price)?,
+            quantity: parse_field("quantity", &self.data.quantity)?,
             is_buyer_maker: self.data.is_buyer_maker,
+            seq: None,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::parse_errors;
+
+    #[test]
+    fn test_malformed_price_is_an_error_not_zero() {
+        let event: AggTradeCombinedEvent = serde_json::from_str(
+            r#"{"data":{"a":1,"s":"BTCUSDT","p":"12.5O","q":"1.0","m":false,"T":1700000000000}}"#,
+        )
+        .unwrap();
+        let before = parse_errors();
+
+        let err = event.to_insertable().unwrap_err();
+
+        assert!(err.to_string().contains("invalid price '12.5O'"));
+        assert!(parse_errors() > before);
+    }
+}
@@ -1,13 +1,28 @@
+use common::clock::{Clock, SystemClock};
+use common::config::{BinanceConfig, HttpConfig};
+use common::models::BalanceInsert;
 use hmac::{Hmac, Mac};
-use reqwest::{Client, Method};
+use reqwest::{Client, ClientBuilder, Method};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
-use std::env;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::{error, info};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::remote::depth_diff_response::DepthSnapshotResponse;
 
 type HmacSha256 = Hmac<Sha256>;
 
+/// Builder every Binance REST client starts from, with the timeouts and
+/// connection reuse of `http`.
+pub fn http_client(http: &HttpConfig) -> ClientBuilder {
+    Client::builder()
+        .user_agent("binance_crypto_bot/0.0.1")
+        .timeout(http.timeout)
+        .connect_timeout(http.connect_timeout)
+        .pool_idle_timeout(http.pool_idle_timeout)
+        .pool_max_idle_per_host(http.pool_max_idle)
+}
+
 #[derive(Debug, Serialize)]
 pub struct OrderRequest {
     pub symbol: String,
@@ -47,28 +62,78 @@ pub struct AccountInformation {
     pub can_trade: bool,
 }
 
+impl AccountInformation {
+    /// Assets with a non-zero free or locked amount, stamped with `time`.
+    /// Entries whose amounts don't parse are skipped.
+    pub fn non_zero_balances(&self, time: f64) -> Vec<BalanceInsert> {
+        self.balances
+            .iter()
+            .filter_map(|b| {
+                let (Ok(free), Ok(locked)) = (b.free.parse::<f64>(), b.locked.parse::<f64>())
+                else {
+                    warn!(
+                        "Unparsable balance for {}: free={} locked={}",
+                        b.asset, b.free, b.locked
+                    );
+                    return None;
+                };
+                (free > 0.0 || locked > 0.0).then(|| BalanceInsert {
+                    time,
+                    asset: b.asset.clone(),
+                    free,
+                    locked,
+                })
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone)]
 pub struct BinanceClient {
     client: Client,
     base_url: String,
     api_key: String,
     secret_key: String,
+    clock: Arc<dyn Clock>,
 }
 
 impl BinanceClient {
-    pub fn new() -> Self {
-        let api_key = env::var("BINANCE_API_KEY").expect("BINANCE_API_KEY not set");
-        let secret_key = env::var("BINANCE_SECRET_KEY").expect("BINANCE_SECRET_KEY not set");
-        let base_url = env::var("BINANCE_BASE_URL").unwrap_or_else(|_| "https://api.binance.com".to_string());
+    /// A signed client for the configured account, or `None` when no API
+    /// credentials are set.
+    pub fn from_config(config: &BinanceConfig) -> Option<Self> {
+        let credentials = config.credentials.as_ref()?;
+        Some(Self::with_credentials(
+            config.rest_url.clone(),
+            credentials.api_key.clone(),
+            credentials.secret_key.clone(),
+            &config.http,
+        ))
+    }
 
+    pub fn with_credentials(
+        base_url: String,
+        api_key: String,
+        secret_key: String,
+        http: &HttpConfig,
+    ) -> Self {
         Self {
-            client: Client::new(),
+            client: http_client(http)
+                .build()
+                .expect("Failed to build HTTP client."),
             base_url,
             api_key,
             secret_key,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Replaces the clock used for request timestamps (tests pin it to get a
+    /// reproducible signature).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     fn sign(&self, query: &str) -> String {
         let mut mac = HmacSha256::new_from_slice(self.secret_key.as_bytes())
             .expect("HMAC can take key of any size");
@@ -76,18 +141,34 @@ impl BinanceClient {
         hex::encode(mac.finalize().into_bytes())
     }
 
-    pub async fn get_account(&self) -> Result<AccountInformation, Box<dyn std::error::Error + Send + Sync>> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-            
-        let params = format!("timestamp={}", timestamp);
-        let signature = self.sign(&params);
-        let full_query = format!("{}&signature={}", params, signature);
+    /// Appends the HMAC-SHA256 `signature` parameter to `params`.
+    fn signed_query(&self, params: &str) -> String {
+        format!("{}&signature={}", params, self.sign(params))
+    }
+
+    fn account_params(&self) -> String {
+        format!("timestamp={}", self.clock.now_millis())
+    }
+
+    fn order_params(&self, symbol: &str, side: &str, quantity: f64) -> String {
+        // Simple Market Order for MVP
+        format!(
+            "symbol={}&side={}&type=MARKET&quantity={}&timestamp={}",
+            symbol.to_uppercase(),
+            side,
+            quantity,
+            self.clock.now_millis()
+        )
+    }
+
+    pub async fn get_account(
+        &self,
+    ) -> Result<AccountInformation, Box<dyn std::error::Error + Send + Sync>> {
+        let full_query = self.signed_query(&self.account_params());
         let url = format!("{}/api/v3/account?{}", self.base_url, full_query);
-        
-        let resp = self.client
+
+        let resp = self
+            .client
             .get(&url)
             .header("X-MBX-APIKEY", &self.api_key)
             .send()
@@ -103,23 +184,38 @@ impl BinanceClient {
         Ok(account_info)
     }
 
-    pub async fn post_order(&self, symbol: &str, side: &str, quantity: f64) -> Result<OrderResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-
-        // Simple Market Order for MVP
-        let params = format!(
-            "symbol={}&side={}&type=MARKET&quantity={}&timestamp={}",
+    /// Order book snapshot used to seed a `LocalOrderBook`. `limit` may be up to 5000.
+    pub async fn get_depth_snapshot(
+        &self,
+        symbol: &str,
+        limit: u16,
+    ) -> Result<DepthSnapshotResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let url = format!(
+            "{}/api/v3/depth?symbol={}&limit={}",
+            self.base_url,
             symbol.to_uppercase(),
-            side,
-            quantity,
-            timestamp
+            limit
         );
 
-        let signature = self.sign(&params);
-        let full_query = format!("{}&signature={}", params, signature);
+        let resp = self.client.get(&url).send().await?;
+
+        if !resp.status().is_success() {
+            let error_text = resp.text().await?;
+            error!("Binance Depth Snapshot Failed: {}", error_text);
+            return Err(error_text.into());
+        }
+
+        let snapshot = resp.json::<DepthSnapshotResponse>().await?;
+        Ok(snapshot)
+    }
+
+    pub async fn post_order(
+        &self,
+        symbol: &str,
+        side: &str,
+        quantity: f64,
+    ) -> Result<OrderResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let full_query = self.signed_query(&self.order_params(symbol, side, quantity));
         let url = format!("{}/api/v3/order?{}", self.base_url, full_query);
 
         info!("Placing Order: {} {} {}", side, quantity, symbol);
@@ -141,3 +237,78 @@ impl BinanceClient {
         Ok(order_resp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::clock::FixedClock;
+
+    // Example credentials from the Binance spot API docs ("SIGNED Endpoint Examples").
+    const DOC_API_KEY: &str = "vmPUZE6mv9SD5VNHk4HlWFsOr6aKE2zvsw0MuIgwCIPy6utIco14y7Ju91duEh8A";
+    const DOC_SECRET_KEY: &str = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+
+    fn doc_client() -> BinanceClient {
+        BinanceClient::with_credentials(
+            "http://localhost".to_string(),
+            DOC_API_KEY.to_string(),
+            DOC_SECRET_KEY.to_string(),
+            &HttpConfig::default(),
+        )
+        .with_clock(Arc::new(FixedClock::from_millis(1499827319559)))
+    }
+
+    #[test]
+    fn test_non_zero_balances_skips_empty_and_bad_entries() {
+        let info: AccountInformation = serde_json::from_str(
+            r#"{"canTrade":true,"balances":[
+                {"asset":"BTC","free":"0.00100000","locked":"0.00000000"},
+                {"asset":"ETH","free":"0.00000000","locked":"0.00000000"},
+                {"asset":"USDT","free":"0.00000000","locked":"12.50000000"},
+                {"asset":"BAD","free":"n/a","locked":"1"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let balances = info.non_zero_balances(1.5);
+        let assets: Vec<&str> = balances.iter().map(|b| b.asset.as_str()).collect();
+        assert_eq!(assets, ["BTC", "USDT"]);
+        assert_eq!(balances[1].locked, 12.5);
+        assert_eq!(balances[0].time, 1.5);
+    }
+
+    #[test]
+    fn test_sign_matches_binance_doc_vector() {
+        let query = "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559";
+
+        assert_eq!(
+            doc_client().sign(query),
+            "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71"
+        );
+    }
+
+    #[test]
+    fn test_order_query_uses_injected_clock() {
+        let client = doc_client();
+        let params = client.order_params("ltcbtc", "BUY", 1.0);
+
+        assert_eq!(
+            params,
+            "symbol=LTCBTC&side=BUY&type=MARKET&quantity=1&timestamp=1499827319559"
+        );
+        assert_eq!(
+            client.signed_query(&params),
+            format!("{}&signature={}", params, client.sign(&params))
+        );
+    }
+
+    #[test]
+    fn test_account_query_is_deterministic() {
+        let client = doc_client();
+
+        assert_eq!(client.account_params(), "timestamp=1499827319559");
+        assert_eq!(
+            client.signed_query(&client.account_params()),
+            client.signed_query(&client.account_params())
+        );
+    }
+}
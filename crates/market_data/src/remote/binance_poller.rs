@@ -2,19 +2,42 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Context, bail};
+use common::config::DEFAULT_FUTURES_REST_URL;
 use common::models::OpenInterestInsert;
 use reqwest::Client;
+use reqwest::header::RETRY_AFTER;
 use tokio::sync::Semaphore;
-use tokio::time::sleep;
-use tracing::{debug, warn};
+use tokio::time::{Instant, sleep, sleep_until};
+use tracing::{debug, info, warn};
 
 use crate::{remote::openinterest_response::OpenInterestResponse, traits::RemoteResponse};
 
+/// Pause after a sweep is cut short when Binance sends no `Retry-After`.
+const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Binance refused a request for exceeding its limits (429) or because the IP
+/// is banned (418).
+#[derive(Debug, thiserror::Error)]
+#[error("HTTP {status}: {reason}")]
+pub struct RateLimited {
+    status: u16,
+    reason: &'static str,
+    /// The `Retry-After` header, when sent.
+    retry_after: Option<Duration>,
+}
+
 pub struct BinancePoller {
     client: Client,
     base_url: String,
     semaphore: Arc<Semaphore>,
     request_delay_ms: u64,
+    /// Where the next sweep starts: the symbol a rate limit stopped the last
+    /// one at.
+    resume_from: Option<String>,
+    /// The symbols the last sweep didn't reach.
+    skipped: Vec<String>,
+    /// No request is sent before this.
+    backoff_until: Option<Instant>,
 }
 
 impl BinancePoller {
@@ -25,19 +48,47 @@ impl BinancePoller {
                 .timeout(Duration::from_secs(10))
                 .build()
                 .expect("Failed to build HTTP client."),
-            base_url: "https://fapi.binance.com".to_string(),
+            base_url: DEFAULT_FUTURES_REST_URL.to_string(),
             semaphore: Arc::new(Semaphore::new(5)),
             request_delay_ms: 100,
+            resume_from: None,
+            skipped: Vec::new(),
+            backoff_until: None,
         }
     }
 
+    /// Polls `base_url` instead of Binance's production futures API.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Fetches the open interest of every symbol, one after another.
+    ///
+    /// A rate limit ends the sweep early: the remaining symbols are recorded
+    /// in `skipped`, and the next call waits out the `Retry-After` (or
+    /// `DEFAULT_RATE_LIMIT_BACKOFF`) and starts at the symbol that was
+    /// refused, so the ones at the end of the list aren't starved.
     pub async fn fetch_all_open_interest(
-        &self,
+        &mut self,
         symbols: &[String],
     ) -> anyhow::Result<Vec<anyhow::Result<OpenInterestInsert>>> {
-        let mut results = Vec::with_capacity(symbols.len());
+        if let Some(until) = self.backoff_until.take() {
+            sleep_until(until).await;
+        }
+        let order = sweep_order(symbols, self.resume_from.take().as_deref());
+        if !self.skipped.is_empty()
+            && let Some(first) = order.first()
+        {
+            info!(
+                "Resuming open interest sweep at {} after a rate limit",
+                first
+            );
+        }
+        self.skipped.clear();
 
-        for (i, symbol) in symbols.iter().enumerate() {
+        let mut results = Vec::with_capacity(order.len());
+        for (i, symbol) in order.iter().enumerate() {
             if i > 0 {
                 sleep(Duration::from_millis(self.request_delay_ms)).await;
             }
@@ -53,11 +104,20 @@ impl BinancePoller {
 
             drop(permit);
 
-            if let Err(ref e) = result {
-                if Self::is_rate_limit_error(e) {
-                    warn!("Rate limit detected, stopping further requests");
-                    break;
-                }
+            if let Err(e) = &result
+                && let Some(limited) = e.downcast_ref::<RateLimited>()
+            {
+                let backoff = limited.retry_after.unwrap_or(DEFAULT_RATE_LIMIT_BACKOFF);
+                self.skipped = order[i..].iter().map(|s| s.to_string()).collect();
+                self.resume_from = Some(symbol.to_string());
+                self.backoff_until = Some(Instant::now() + backoff);
+                warn!(
+                    "Rate limited, skipping {} symbols until the next sweep in {:?}: {}",
+                    self.skipped.len(),
+                    backoff,
+                    self.skipped.join(", ")
+                );
+                break;
             }
 
             results.push(result);
@@ -66,6 +126,14 @@ impl BinancePoller {
         Ok(results)
     }
 
+    /// The symbols the last `fetch_all_open_interest` left unfetched because
+    /// of a rate limit.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Retries a rate-limited request a few times, waiting `Retry-After` or an
+    /// exponential backoff in between, before giving up with `RateLimited`.
     async fn fetch_single_open_interest(&self, symbol: &str) -> anyhow::Result<OpenInterestInsert> {
         let url = format!("{}/fapi/v1/openInterest", self.base_url);
 
@@ -76,19 +144,21 @@ impl BinancePoller {
             match self.make_request(&url, symbol).await {
                 Ok(response) => return Ok(response.to_insertable()?),
                 Err(e) => {
-                    if Self::is_rate_limit_error(&e) {
+                    if let Some(limited) = e.downcast_ref::<RateLimited>() {
                         retry_count += 1;
                         if retry_count > max_retries {
-                            bail!("Max retries exceeded for rate limit");
+                            return Err(e);
                         }
 
-                        let backoff_seconds = 2_u64.pow(retry_count);
+                        let backoff = limited
+                            .retry_after
+                            .unwrap_or(Duration::from_secs(2_u64.pow(retry_count)));
                         warn!(
-                            "Rate limited for symbol {}, backing off for {} seconds (attempt {}/{})",
-                            symbol, backoff_seconds, retry_count, max_retries
+                            "Rate limited for symbol {}, backing off for {:?} (attempt {}/{})",
+                            symbol, backoff, retry_count, max_retries
                         );
 
-                        sleep(Duration::from_secs(backoff_seconds)).await;
+                        sleep(backoff).await;
                         continue;
                     }
                     bail!("Failed to fetch open interest for {}: {}", symbol, e);
@@ -107,11 +177,22 @@ impl BinancePoller {
             .context("Failed to send request")?;
 
         let status = response.status();
-        if status == 429 {
-            bail!("HTTP 429: Too Many Requests");
-        }
-        if status == 418 {
-            bail!("HTTP 418: IP has been auto-banned");
+        if status == 429 || status == 418 {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok()?.trim().parse().ok())
+                .map(Duration::from_secs);
+            return Err(RateLimited {
+                status: status.as_u16(),
+                reason: if status == 429 {
+                    "Too Many Requests"
+                } else {
+                    "IP has been auto-banned"
+                },
+                retry_after,
+            }
+            .into());
         }
 
         if let Some(used_weight) = response.headers().get("x-mbx-used-weight-1m") {
@@ -134,14 +215,6 @@ impl BinancePoller {
             .context("Failed to parse JSON response")?;
         Ok(data)
     }
-
-    fn is_rate_limit_error(error: &anyhow::Error) -> bool {
-        let error_str = error.to_string();
-        error_str.contains("429")
-            || error_str.contains("418")
-            || error_str.contains("Too Mani Requests")
-            || error_str.contains("auto-banned")
-    }
 }
 
 impl Default for BinancePoller {
@@ -149,3 +222,43 @@ impl Default for BinancePoller {
         Self::new()
     }
 }
+
+/// `symbols` rotated to start at `resume_from`, or as given when it is `None`
+/// or no longer in the list.
+fn sweep_order<'a>(symbols: &'a [String], resume_from: Option<&str>) -> Vec<&'a str> {
+    let start = resume_from
+        .and_then(|symbol| symbols.iter().position(|s| s == symbol))
+        .unwrap_or(0);
+    symbols[start..]
+        .iter()
+        .chain(&symbols[..start])
+        .map(String::as_str)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_resumes_at_the_refused_symbol() {
+        let symbols: Vec<String> = ["BTCUSDT", "ETHUSDT", "SOLUSDT"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            sweep_order(&symbols, Some("SOLUSDT")),
+            vec!["SOLUSDT", "BTCUSDT", "ETHUSDT"]
+        );
+        assert_eq!(
+            sweep_order(&symbols, None),
+            vec!["BTCUSDT", "ETHUSDT", "SOLUSDT"]
+        );
+        // Unsubscribed since: start over.
+        assert_eq!(
+            sweep_order(&symbols, Some("DOGEUSDT")),
+            vec!["BTCUSDT", "ETHUSDT", "SOLUSDT"]
+        );
+        assert!(sweep_order(&[], Some("BTCUSDT")).is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Deserialize;
+
+use common::models::DepthDiff;
+
+use crate::order_book::LocalOrderBook;
+use crate::traits::{RemoteResponse, parse_field};
+
+/// Payload of the `<symbol>@depth` diff stream (`@depth@100ms` at the faster speed).
+#[derive(Deserialize, Debug)]
+pub struct DepthDiffEvent {
+    #[serde(rename(deserialize = "s"))]
+    pub symbol: String,
+    #[serde(rename(deserialize = "U"))]
+    pub first_update_id: u64,
+    #[serde(rename(deserialize = "u"))]
+    pub final_update_id: u64,
+    #[serde(rename(deserialize = "b"))]
+    pub bids: Vec<[String; 2]>,
+    #[serde(rename(deserialize = "a"))]
+    pub asks: Vec<[String; 2]>,
+}
+
+impl RemoteResponse<DepthDiff> for DepthDiffEvent {
+    fn to_insertable(&self) -> Result<DepthDiff, serde_json::Error> {
+        Ok(DepthDiff {
+            symbol: self.symbol.clone(),
+            first_update_id: self.first_update_id,
+            final_update_id: self.final_update_id,
+            bids: parse_levels(&self.bids)?,
+            asks: parse_levels(&self.asks)?,
+            seq: None,
+        })
+    }
+}
+
+/// `GET /api/v3/depth` response, the starting point for `LocalOrderBook`.
+#[derive(Deserialize, Debug)]
+pub struct DepthSnapshotResponse {
+    #[serde(rename(deserialize = "lastUpdateId"))]
+    pub last_update_id: u64,
+    pub bids: Vec<[String; 2]>,
+    pub asks: Vec<[String; 2]>,
+}
+
+impl DepthSnapshotResponse {
+    pub fn to_book(&self) -> Result<LocalOrderBook, serde_json::Error> {
+        Ok(LocalOrderBook::from_snapshot(
+            self.last_update_id,
+            &parse_levels(&self.bids)?,
+            &parse_levels(&self.asks)?,
+        ))
+    }
+}
+
+/// Unlike the top-20 snapshots, a bad level can't default to 0 here: a zero
+/// quantity deletes the level from the book.
+fn parse_levels(levels: &[[String; 2]]) -> Result<Vec<(f64, f64)>, serde_json::Error> {
+    levels
+        .iter()
+        .map(|[price, quantity]| {
+            Ok((
+                parse_field("price", price)?,
+                parse_field("quantity", quantity)?,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff_payload() {
+        let json = r#"{"e":"depthUpdate","E":1672515782136,"s":"BNBBTC","U":157,"u":160,
+            "b":[["0.0024","10"]],"a":[["0.0026","100"],["0.0027","0.00000000"]]}"#;
+        let diff = serde_json::from_str::<DepthDiffEvent>(json)
+            .unwrap()
+            .to_insertable()
+            .unwrap();
+
+        assert_eq!(
+            diff,
+            DepthDiff {
+                symbol: "BNBBTC".to_string(),
+                first_update_id: 157,
+                final_update_id: 160,
+                bids: vec![(0.0024, 10.0)],
+                asks: vec![(0.0026, 100.0), (0.0027, 0.0)],
+                seq: None,
+            }
+        );
+    }
+}
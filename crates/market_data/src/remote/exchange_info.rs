@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use reqwest::Client;
+use serde::Deserialize;
+
+use common::config::HttpConfig;
+use common::symbols::SymbolAssets;
+
+use crate::remote::binance_client::http_client;
+
+#[derive(Deserialize, Debug)]
+struct ExchangeInfo {
+    symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SymbolInfo {
+    symbol: String,
+    base_asset: String,
+    quote_asset: String,
+}
+
+const MIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Unauthenticated spot `exchangeInfo`, used to check the configured symbols
+/// before subscribing to them.
+pub struct ExchangeInfoClient {
+    client: Client,
+    base_url: String,
+}
+
+impl ExchangeInfoClient {
+    /// The full symbol list is several megabytes, so requests get at least
+    /// `MIN_TIMEOUT` whatever `http` says.
+    pub fn new(base_url: String, http: &HttpConfig) -> Self {
+        Self {
+            client: http_client(http)
+                .timeout(http.timeout.max(MIN_TIMEOUT))
+                .build()
+                .expect("Failed to build HTTP client."),
+            base_url,
+        }
+    }
+
+    /// Every spot symbol currently trading, uppercase, with its assets.
+    pub async fn trading_symbols(&self) -> anyhow::Result<HashMap<String, SymbolAssets>> {
+        let resp = self
+            .client
+            .get(format!("{}/api/v3/exchangeInfo", self.base_url))
+            .query(&[("symbolStatus", "TRADING"), ("showPermissionSets", "false")])
+            .send()
+            .await
+            .context("exchangeInfo request failed")?;
+        if !resp.status().is_success() {
+            bail!(
+                "exchangeInfo request returned HTTP {}: {}",
+                resp.status(),
+                resp.text().await.unwrap_or_default()
+            );
+        }
+
+        let body = resp
+            .text()
+            .await
+            .context("exchangeInfo response unreadable")?;
+        parse_symbols(&body)
+    }
+}
+
+fn parse_symbols(body: &str) -> anyhow::Result<HashMap<String, SymbolAssets>> {
+    let info: ExchangeInfo = serde_json::from_str(body).context("invalid exchangeInfo response")?;
+    Ok(info
+        .symbols
+        .into_iter()
+        .map(|s| {
+            let assets = SymbolAssets {
+                base: s.base_asset,
+                quote: s.quote_asset,
+            };
+            (s.symbol, assets)
+        })
+        .collect())
+}
+
+/// Splits `symbols` into those in `trading` and those that aren't, keeping
+/// their order. The comparison ignores case.
+pub fn partition_symbols<'a>(
+    symbols: &[&'a str],
+    trading: &HashMap<String, SymbolAssets>,
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    symbols
+        .iter()
+        .copied()
+        .partition(|symbol| trading.contains_key(&symbol.to_uppercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partitions_configured_symbols() {
+        let trading = parse_symbols(
+            r#"{"timezone":"UTC","serverTime":1767225600000,"rateLimits":[],
+            "exchangeFilters":[],"symbols":[
+                {"symbol":"BTCUSDT","status":"TRADING","baseAsset":"BTC","quoteAsset":"USDT"},
+                {"symbol":"WIFUSDT","status":"TRADING","baseAsset":"WIF","quoteAsset":"USDT"},
+                {"symbol":"ETHBTC","status":"TRADING","baseAsset":"ETH","quoteAsset":"BTC"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            trading["ETHBTC"],
+            SymbolAssets {
+                base: "ETH".to_string(),
+                quote: "BTC".to_string()
+            }
+        );
+
+        let (valid, invalid) = partition_symbols(&["btcusdt", "wifiusdt", "WIFUSDT"], &trading);
+        assert_eq!(valid, vec!["btcusdt", "WIFUSDT"]);
+        assert_eq!(invalid, vec!["wifiusdt"]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use common::clock::{Clock, SystemClock};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+/// Longest pause between two preflight attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Clock difference above which signed requests get close to Binance's
+/// default 5000ms `recvWindow`.
+const SKEW_WARN_MS: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+struct ServerTime {
+    #[serde(rename(deserialize = "serverTime"))]
+    server_time: i64,
+}
+
+/// Startup check that the spot REST API answers before any actor is started.
+pub struct ExchangeStatus {
+    client: Client,
+    base_url: String,
+}
+
+impl ExchangeStatus {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            client: Client::builder()
+                .user_agent("binance_crypto_bot/0.0.1")
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build HTTP client."),
+            base_url,
+        }
+    }
+
+    /// Calls `/api/v3/ping` and `/api/v3/time` once. Returns how far the local
+    /// clock is ahead of the exchange, in milliseconds.
+    pub async fn check(&self) -> anyhow::Result<i64> {
+        let ping = self
+            .client
+            .get(format!("{}/api/v3/ping", self.base_url))
+            .send()
+            .await
+            .context("ping failed")?;
+        if !ping.status().is_success() {
+            bail!("ping returned HTTP {}", ping.status());
+        }
+
+        let time = self
+            .client
+            .get(format!("{}/api/v3/time", self.base_url))
+            .send()
+            .await
+            .context("time request failed")?
+            .json::<ServerTime>()
+            .await
+            .context("invalid time response")?;
+
+        Ok(SystemClock.now_millis() as i64 - time.server_time)
+    }
+
+    /// Retries `check` with exponential backoff until the exchange answers.
+    pub async fn wait_until_reachable(&self) {
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            match self.check().await {
+                Ok(skew) => {
+                    if skew.abs() > SKEW_WARN_MS {
+                        warn!(
+                            "Local clock is {}ms off Binance server time; signed requests may be rejected",
+                            skew
+                        );
+                    }
+                    info!(
+                        "Binance reachable at {} (clock skew {}ms)",
+                        self.base_url, skew
+                    );
+                    return;
+                }
+                Err(e) => {
+                    warn!(
+                        "Waiting for exchange: {} unreachable ({:#}), retrying in {}s",
+                        self.base_url,
+                        e,
+                        backoff.as_secs()
+                    );
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use common::models::force_order::ForceOrderInsert;
 use serde::Deserialize;
 
-use crate::traits::RemoteResponse;
+use crate::traits::{RemoteResponse, parse_field};
 
 #[derive(Deserialize, Debug)]
 pub struct ForceOrderCombinedEvent {
@@ -27,8 +27,9 @@ impl RemoteResponse<ForceOrderInsert> for ForceOrderCombinedEvent {
             time: self.get_time_f64(),
             symbol: self.data.symbol.clone(),
             side: self.data.side.clone(),
-            price: self.data.price.parse::<f64>().unwrap_or(0_f64),
-            quantity: self.data.quantity.parse::<f64>().unwrap_or(0_f64),
+            price: parse_field("price", &self.data.price)?,
+            quantity: parse_field("quantity", &self.data.quantity)?,
+            seq: None,
         })
     }
 }
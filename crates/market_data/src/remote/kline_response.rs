@@ -1,15 +1,31 @@
-use serde::Deserialize;
+use serde::{Deserialize, de::Error};
 
 use common::models::KlineInsert;
+pub use common::models::kline::KLINE_INTERVALS;
 
-use crate::traits::RemoteResponse;
+use crate::traits::{RemoteResponse, parse_field};
 
 #[derive(Deserialize, Debug)]
 pub struct KlineDataCombinedEvent {
+    /// When Binance sent the update, ms since the epoch.
+    #[serde(rename(deserialize = "E"), default)]
+    pub event_time: Option<u64>,
     #[serde(rename(deserialize = "k"))]
     pub data: KlineEvent,
 }
 
+impl KlineDataCombinedEvent {
+    /// Binance marks the last update of a candle with `x`. An update sent
+    /// after the candle's close time is final as well, so the candle counts
+    /// as closed even if the `x` update is lost, e.g. across a reconnect.
+    pub fn is_closed(&self) -> bool {
+        self.data.is_closed
+            || self
+                .event_time
+                .is_some_and(|event_time| event_time > self.data.close_time)
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct KlineEvent {
     #[serde(rename(deserialize = "s"))]
@@ -40,21 +56,79 @@ pub struct KlineEvent {
 
 impl RemoteResponse<(KlineInsert, bool)> for KlineDataCombinedEvent {
     fn to_insertable(&self) -> Result<(KlineInsert, bool), serde_json::Error> {
+        if !KLINE_INTERVALS.contains(&self.data.interval.as_str()) {
+            return Err(serde_json::Error::custom(format!(
+                "unexpected kline interval '{}' for {}",
+                self.data.interval, self.data.symbol
+            )));
+        }
+
         Ok((
             KlineInsert {
                 symbol: self.data.symbol.clone(),
-                start_time: self.data.start_time as i32,
-                close_time: self.data.close_time as i32,
+                start_time: self.data.start_time as i64,
+                close_time: self.data.close_time as i64,
                 interval: self.data.interval.clone(),
-                open_price: self.data.open_price.parse::<f32>().unwrap_or(0_f32),
-                close_price: self.data.close_price.parse::<f32>().unwrap_or(0_f32),
-                high_price: self.data.high_price.parse::<f32>().unwrap_or(0_f32),
-                low_price: self.data.low_price.parse::<f32>().unwrap_or(0_f32),
-                volume: self.data.volume.parse::<f64>().unwrap_or(0_f64),
+                open_price: parse_field("open price", &self.data.open_price)?,
+                close_price: parse_field("close price", &self.data.close_price)?,
+                high_price: parse_field("high price", &self.data.high_price)?,
+                low_price: parse_field("low price", &self.data.low_price)?,
+                volume: parse_field("volume", &self.data.volume)?,
                 no_of_trades: self.data.no_of_trades as i32,
-                taker_buy_vol: self.data.taker_buy_vol.parse::<f32>().unwrap_or(0_f32),
+                taker_buy_vol: parse_field("taker buy volume", &self.data.taker_buy_vol)?,
+                seq: None,
             },
-            self.data.is_closed,
+            self.is_closed(),
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline_json(interval: &str) -> String {
+        format!(
+            r#"{{"k":{{"s":"BTCUSDT","t":1700000000000,"T":1700000059999,"i":"{}","o":"1.0","c":"2.0","h":"3.0","l":"0.5","v":"10.0","n":5,"x":true,"V":"4.0"}}}}"#,
+            interval
+        )
+    }
+
+    #[test]
+    fn test_whitelisted_interval_is_accepted() {
+        let event: KlineDataCombinedEvent = serde_json::from_str(&kline_json("1m")).unwrap();
+        let (kline, closed) = event.to_insertable().unwrap();
+
+        assert_eq!(kline.interval, "1m");
+        // Millisecond epochs are past i32::MAX and must survive whole.
+        assert_eq!(
+            (kline.start_time, kline.close_time),
+            (1_700_000_000_000, 1_700_000_059_999)
+        );
+        assert!(closed);
+    }
+
+    #[test]
+    fn test_event_time_after_close_means_closed() {
+        let update = |event_time: u64| {
+            let json = format!(
+                r#"{{"E":{},"k":{{"s":"BTCUSDT","t":1700000000000,"T":1700000059999,"i":"1m","o":"1.0","c":"2.0","h":"3.0","l":"0.5","v":"10.0","n":5,"x":false,"V":"4.0"}}}}"#,
+                event_time
+            );
+            serde_json::from_str::<KlineDataCombinedEvent>(&json).unwrap()
+        };
+
+        let open = update(1_700_000_030_000);
+        assert_eq!(open.event_time, Some(1_700_000_030_000));
+        assert!(!open.to_insertable().unwrap().1);
+        assert!(update(1_700_000_060_001).to_insertable().unwrap().1);
+    }
+
+    #[test]
+    fn test_unexpected_interval_is_rejected() {
+        let event: KlineDataCombinedEvent = serde_json::from_str(&kline_json("5m")).unwrap();
+        let err = event.to_insertable().unwrap_err();
+
+        assert!(err.to_string().contains("unexpected kline interval '5m'"));
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::{Context, bail};
+use common::config::HttpConfig;
+use common::models::KlineInsert;
+use reqwest::Client;
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+
+use crate::remote::binance_client::http_client;
+use crate::traits::parse_field;
+
+/// Most candles `GET /api/v3/klines` returns per request.
+pub const MAX_KLINES_PER_REQUEST: usize = 1000;
+
+/// One row of `GET /api/v3/klines`: open time, OHLCV, close time, quote
+/// volume, trade count, taker buy base/quote volume and an unused field.
+#[derive(Deserialize, Debug)]
+struct RestKline(
+    i64,
+    String,
+    String,
+    String,
+    String,
+    String,
+    i64,
+    IgnoredAny,
+    i64,
+    String,
+    IgnoredAny,
+    IgnoredAny,
+);
+
+impl RestKline {
+    fn into_insert(self, symbol: &str, interval: &str) -> Result<KlineInsert, serde_json::Error> {
+        Ok(KlineInsert {
+            symbol: symbol.to_uppercase(),
+            start_time: self.0,
+            close_time: self.6,
+            interval: interval.to_string(),
+            open_price: parse_field("open price", &self.1)?,
+            close_price: parse_field("close price", &self.4)?,
+            high_price: parse_field("high price", &self.2)?,
+            low_price: parse_field("low price", &self.3)?,
+            volume: parse_field("volume", &self.5)?,
+            no_of_trades: self.8 as i32,
+            taker_buy_vol: parse_field("taker buy volume", &self.9)?,
+            seq: None,
+        })
+    }
+}
+
+/// Unauthenticated spot kline history, used to fill candles the websocket missed.
+#[derive(Clone)]
+pub struct KlineRestClient {
+    client: Client,
+    base_url: String,
+}
+
+impl KlineRestClient {
+    /// `base_url` is the spot REST root, e.g. `https://api.binance.com`.
+    pub fn new(base_url: String, http: &HttpConfig) -> Self {
+        Self {
+            client: http_client(http)
+                .build()
+                .expect("Failed to build HTTP client."),
+            base_url,
+        }
+    }
+
+    /// Candles of `symbol`/`interval` whose start time lies in
+    /// `[start_ms, end_ms]`, at most `MAX_KLINES_PER_REQUEST` of them.
+    pub async fn fetch(
+        &self,
+        symbol: &str,
+        interval: &str,
+        start_ms: i64,
+        end_ms: i64,
+    ) -> anyhow::Result<Vec<KlineInsert>> {
+        let url = format!(
+            "{}/api/v3/klines?symbol={}&interval={}&startTime={}&endTime={}&limit={}",
+            self.base_url,
+            symbol.to_uppercase(),
+            interval,
+            start_ms,
+            end_ms,
+            MAX_KLINES_PER_REQUEST
+        );
+
+        let resp = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context("klines request failed")?;
+        if !resp.status().is_success() {
+            bail!(
+                "klines request returned HTTP {}: {}",
+                resp.status(),
+                resp.text().await.unwrap_or_default()
+            );
+        }
+
+        let body = resp.text().await.context("klines response unreadable")?;
+        parse_klines(&body, symbol, interval)
+    }
+}
+
+fn parse_klines(body: &str, symbol: &str, interval: &str) -> anyhow::Result<Vec<KlineInsert>> {
+    let rows: Vec<RestKline> = serde_json::from_str(body).context("invalid klines response")?;
+    rows.into_iter()
+        .map(|row| Ok(row.into_insert(symbol, interval)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rest_klines() {
+        let body = r#"[[1700000040000,"1.0","3.0","0.5","2.0","10.0",1700000099999,"20.0",5,"4.0","8.0","0"]]"#;
+        let klines = parse_klines(body, "btcusdt", "1m").unwrap();
+
+        assert_eq!(klines.len(), 1);
+        let kline = &klines[0];
+        assert_eq!(kline.symbol, "BTCUSDT");
+        assert_eq!(
+            (kline.start_time, kline.close_time),
+            (1700000040000, 1700000099999)
+        );
+        assert_eq!(
+            (
+                kline.open_price,
+                kline.high_price,
+                kline.low_price,
+                kline.close_price
+            ),
+            (1.0, 3.0, 0.5, 2.0)
+        );
+        assert_eq!(kline.volume, 10.0);
+        assert_eq!(kline.no_of_trades, 5);
+        assert_eq!(kline.taker_buy_vol, 4.0);
+    }
+}
@@ -1,7 +1,7 @@
 use common::models::markprice::MarkPriceInsert;
 use serde::Deserialize;
 
-use crate::traits::RemoteResponse;
+use crate::traits::{RemoteResponse, parse_field};
 
 #[derive(Deserialize, Debug)]
 pub struct MarkPriceEvent {
@@ -13,6 +13,8 @@ pub struct MarkPriceEvent {
     pub index_price: String,
     #[serde(rename(deserialize = "r"))]
     pub funding_rate: String,
+    #[serde(rename(deserialize = "T"), default)]
+    pub next_funding_time: Option<i64>,
 }
 
 impl RemoteResponse<MarkPriceInsert> for MarkPriceEvent {
@@ -20,9 +22,11 @@ impl RemoteResponse<MarkPriceInsert> for MarkPriceEvent {
         Ok(MarkPriceInsert {
             time: self.get_time_f64(),
             symbol: self.symbol.clone(),
-            mark_price: self.mark_price.parse::<f64>().unwrap_or(0_f64),
-            index_price: self.index_price.parse::<f64>().unwrap_or(0_f64),
-            funding_rate: self.funding_rate.parse::<f64>().unwrap_or(0_f64),
+            mark_price: parse_field("mark price", &self.mark_price)?,
+            index_price: parse_field("index price", &self.index_price)?,
+            funding_rate: parse_field("funding rate", &self.funding_rate)?,
+            next_funding_time: self.next_funding_time,
+            seq: None,
         })
     }
 }
@@ -1,7 +1,7 @@
 use common::models::OpenInterestInsert;
 use serde::Deserialize;
 
-use crate::traits::RemoteResponse;
+use crate::traits::{RemoteResponse, parse_field};
 
 #[derive(Debug, Deserialize)]
 pub struct OpenInterestResponse {
@@ -15,7 +15,8 @@ impl RemoteResponse<OpenInterestInsert> for OpenInterestResponse {
         Ok(OpenInterestInsert {
             time: self.get_time_f64(),
             symbol: self.symbol.clone(),
-            oi_value: self.open_interest.parse::<f64>().unwrap_or(0_f64),
+            oi_value: parse_field("open interest", &self.open_interest)?,
+            seq: None,
         })
     }
 }
@@ -1,8 +1,72 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
 use serde::Deserialize;
+use tracing::warn;
 
 use common::models::OrderBookInsert;
 
-use crate::traits::RemoteResponse;
+use crate::traits::{RemoteResponse, parse_field};
+
+/// Bad books are warned about at most once per this window.
+const INVALID_BOOK_WARN_EVERY: Duration = Duration::from_secs(60);
+
+static INVALID_BOOKS: AtomicU64 = AtomicU64::new(0);
+
+/// Depth snapshots that failed `OrderBookInsert::validate` since startup.
+pub fn invalid_books() -> u64 {
+    INVALID_BOOKS.load(Ordering::Relaxed)
+}
+
+/// Checks depth snapshots before they are published, counting the invalid
+/// ones and optionally dropping them.
+#[derive(Debug)]
+pub struct BookValidator {
+    drop_invalid: bool,
+    /// When the last warning was logged and the bad books seen since.
+    window: Mutex<(Option<Instant>, u64)>,
+}
+
+impl BookValidator {
+    /// `drop_invalid` discards unsorted or crossed snapshots instead of only
+    /// counting them.
+    pub fn new(drop_invalid: bool) -> Self {
+        Self {
+            drop_invalid,
+            window: Mutex::new((None, 0)),
+        }
+    }
+
+    /// Whether `book` should be published.
+    pub fn accept(&self, book: &OrderBookInsert) -> bool {
+        let Err(violation) = book.validate() else {
+            return true;
+        };
+        let total = INVALID_BOOKS.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut window = self.window.lock().expect("book window poisoned");
+        window.1 += 1;
+        if window
+            .0
+            .is_none_or(|at| at.elapsed() >= INVALID_BOOK_WARN_EVERY)
+        {
+            warn!(
+                "{} depth snapshot invalid: {}, {} bad books in the last window, {} so far{}",
+                book.symbol,
+                violation,
+                window.1,
+                total,
+                if self.drop_invalid {
+                    ", dropping them"
+                } else {
+                    ""
+                }
+            );
+            *window = (Some(Instant::now()), 0);
+        }
+        !self.drop_invalid
+    }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct OrderBookCombinedEvent {
@@ -28,24 +92,75 @@ impl RemoteResponse<OrderBookInsert> for OrderBookCombinedEvent {
         Ok(OrderBookInsert {
             time: self.get_time_f64(),
             symbol: symbol_upper.to_string(),
-            bids: Self::pack_level(&self.data.bids),
-            asks: Self::pack_level(&self.data.asks),
+            bids: Self::pack_level(&self.data.bids)?,
+            asks: Self::pack_level(&self.data.asks)?,
+            seq: None,
         })
     }
 }
 
 impl OrderBookCombinedEvent {
-    fn pack_level(items: &Vec<[String; 2]>) -> Vec<u8> {
+    /// Packs `[price, qty]` string levels as little-endian `f32` pairs, the
+    /// layout `common::models::orderbook::unpack_levels` reads.
+    pub fn pack_level(items: &[[String; 2]]) -> Result<Vec<u8>, serde_json::Error> {
         let capacity = items.len() * 8;
         let mut writer = Vec::with_capacity(capacity);
 
         for item in items {
-            let price = item[0].parse::<f32>().unwrap_or(0_f32);
-            let quantity = item[1].parse::<f32>().unwrap_or(0_f32);
+            let price: f32 = parse_field("price", &item[0])?;
+            let quantity: f32 = parse_field("quantity", &item[1])?;
 
             writer.extend_from_slice(&price.to_le_bytes());
             writer.extend_from_slice(&quantity.to_le_bytes());
         }
-        writer
+        Ok(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::models::BookViolation;
+
+    fn book(bids: &[[&str; 2]], asks: &[[&str; 2]]) -> OrderBookInsert {
+        let levels = |side: &[[&str; 2]]| side.iter().map(|l| l.map(String::from)).collect();
+        OrderBookCombinedEvent {
+            stream: "btcusdt@depth20".to_string(),
+            data: DepthPayload {
+                bids: levels(bids),
+                asks: levels(asks),
+            },
+        }
+        .to_insertable()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_counts_unsorted_and_crossed_books() {
+        let keep = BookValidator::new(false);
+        let drop = BookValidator::new(true);
+        let before = invalid_books();
+
+        let good = book(
+            &[["10.0", "1"], ["9.5", "1"]],
+            &[["10.5", "1"], ["11.0", "1"]],
+        );
+        assert_eq!(good.validate(), Ok(()));
+        assert!(drop.accept(&good));
+        let one_sided = book(&[], &[["10.5", "1"]]);
+        assert_eq!(one_sided.validate(), Ok(()));
+        assert!(drop.accept(&one_sided));
+        assert_eq!(invalid_books(), before);
+
+        let unsorted = book(&[["9.5", "1"], ["10.0", "1"]], &[["10.5", "1"]]);
+        assert_eq!(unsorted.validate(), Err(BookViolation::UnsortedBids));
+        let crossed = book(&[["10.0", "1"]], &[["10.0", "1"]]);
+        assert!(matches!(
+            crossed.validate(),
+            Err(BookViolation::Crossed { .. })
+        ));
+        assert!(keep.accept(&unsorted));
+        assert!(!drop.accept(&crossed));
+        assert!(invalid_books() >= before + 2);
     }
 }
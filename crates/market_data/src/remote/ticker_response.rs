@@ -0,0 +1,40 @@
+use common::models::Ticker24hInsert;
+use serde::Deserialize;
+
+use crate::traits::{RemoteResponse, parse_field};
+
+/// One symbol of `GET /api/v3/ticker/24hr`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Ticker24hResponse {
+    pub symbol: String,
+    pub price_change: String,
+    pub price_change_percent: String,
+    pub last_price: String,
+    pub open_price: String,
+    pub high_price: String,
+    pub low_price: String,
+    pub volume: String,
+    pub quote_volume: String,
+    /// End of the rolling window, in milliseconds.
+    pub close_time: i64,
+    pub count: i64,
+}
+
+impl RemoteResponse<Ticker24hInsert> for Ticker24hResponse {
+    fn to_insertable(&self) -> Result<Ticker24hInsert, serde_json::Error> {
+        Ok(Ticker24hInsert {
+            time: self.close_time as f64 / 1000.0,
+            symbol: self.symbol.clone(),
+            price_change: parse_field("price change", &self.price_change)?,
+            price_change_pct: parse_field("price change percent", &self.price_change_percent)?,
+            last_price: parse_field("last price", &self.last_price)?,
+            open_price: parse_field("open price", &self.open_price)?,
+            high_price: parse_field("high price", &self.high_price)?,
+            low_price: parse_field("low price", &self.low_price)?,
+            volume: parse_field("volume", &self.volume)?,
+            quote_volume: parse_field("quote volume", &self.quote_volume)?,
+            trade_count: self.count,
+        })
+    }
+}
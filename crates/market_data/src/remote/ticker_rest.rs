@@ -0,0 +1,108 @@
+use anyhow::{Context, bail};
+use common::config::HttpConfig;
+use common::models::Ticker24hInsert;
+use reqwest::Client;
+use tracing::warn;
+
+use crate::remote::binance_client::http_client;
+use crate::remote::ticker_response::Ticker24hResponse;
+use crate::traits::RemoteResponse;
+
+/// Unauthenticated spot 24h ticker statistics.
+#[derive(Clone)]
+pub struct Ticker24hClient {
+    client: Client,
+    base_url: String,
+}
+
+impl Ticker24hClient {
+    pub fn new(base_url: String, http: &HttpConfig) -> Self {
+        Self {
+            client: http_client(http)
+                .build()
+                .expect("Failed to build HTTP client."),
+            base_url,
+        }
+    }
+
+    /// Rolling 24h statistics of every symbol in `symbols`, in one request.
+    pub async fn fetch(&self, symbols: &[String]) -> anyhow::Result<Vec<Ticker24hInsert>> {
+        let symbols =
+            serde_json::to_string(&symbols.iter().map(|s| s.to_uppercase()).collect::<Vec<_>>())?;
+
+        let resp = self
+            .client
+            .get(format!("{}/api/v3/ticker/24hr", self.base_url))
+            .query(&[("symbols", symbols)])
+            .send()
+            .await
+            .context("24h ticker request failed")?;
+        if !resp.status().is_success() {
+            bail!(
+                "24h ticker request returned HTTP {}: {}",
+                resp.status(),
+                resp.text().await.unwrap_or_default()
+            );
+        }
+
+        let body = resp
+            .text()
+            .await
+            .context("24h ticker response unreadable")?;
+        parse_tickers(&body)
+    }
+}
+
+/// Parses the ticker array, skipping symbols with malformed fields.
+fn parse_tickers(body: &str) -> anyhow::Result<Vec<Ticker24hInsert>> {
+    let rows: Vec<Ticker24hResponse> =
+        serde_json::from_str(body).context("invalid 24h ticker response")?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| match row.to_insertable() {
+            Ok(ticker) => Some(ticker),
+            Err(e) => {
+                warn!("Skipping 24h ticker of {}: {}", row.symbol, e);
+                None
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tickers() {
+        let body = r#"[{"symbol":"BTCUSDT","priceChange":"-94.99","priceChangePercent":"-0.095",
+            "weightedAvgPrice":"0.29","prevClosePrice":"0.10","lastPrice":"4.00","lastQty":"200.0",
+            "bidPrice":"4.00","bidQty":"100.0","askPrice":"4.00","askQty":"100.0",
+            "openPrice":"99.00","highPrice":"100.00","lowPrice":"0.10","volume":"8913.3",
+            "quoteVolume":"15.3","openTime":1499783499040,"closeTime":1499869899040,
+            "firstId":28385,"lastId":28460,"count":76},
+            {"symbol":"ETHUSDT","priceChange":"x","priceChangePercent":"0","lastPrice":"0",
+            "openPrice":"0","highPrice":"0","lowPrice":"0","volume":"0","quoteVolume":"0",
+            "closeTime":0,"count":0}]"#;
+        let tickers = parse_tickers(body).unwrap();
+
+        assert_eq!(tickers.len(), 1);
+        let ticker = &tickers[0];
+        assert_eq!(ticker.symbol, "BTCUSDT");
+        assert_eq!(ticker.time, 1499869899.04);
+        assert_eq!(
+            (
+                ticker.price_change,
+                ticker.price_change_pct,
+                ticker.last_price
+            ),
+            (-94.99, -0.095, 4.0)
+        );
+        assert_eq!(
+            (ticker.open_price, ticker.high_price, ticker.low_price),
+            (99.0, 100.0, 0.1)
+        );
+        assert_eq!((ticker.volume, ticker.quote_volume), (8913.3, 15.3));
+        assert_eq!(ticker.trade_count, 76);
+    }
+}
@@ -1,23 +1,35 @@
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use anyhow::bail;
 use async_trait::async_trait;
-use storage::data_manager::DataManager;
+use storage::StorageError;
+use storage::backend::Storage;
+use storage::data_manager::WRITER_DRAIN_TIMEOUT;
 use tokio::sync::{broadcast, mpsc};
-use tokio::time;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::services::market_gateway::MarketEvent;
-use common::actors::{Actor, ActorType, ControlMessage};
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, LagTracker, PendingBuffer, SymbolRateLimiter,
+    WriterLimits, run_db_writer,
+};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+use common::actors::{Actor, ActorType, ControlMessage, Throughput};
 use common::models::AggTradeInsert;
-use storage::repositories::AggTradeRepository;
+
+/// Default trade batching, overridden by `TRADE_BATCH_ROWS`/`TRADE_BATCH_MS`.
+pub const DEFAULT_TRADE_BATCH: BatchWindow = BatchWindow::new(1000, Duration::from_secs(10));
 
 pub struct AggTradeService {
     id: Uuid,
-    rotating_pool: Arc<DataManager>,
-    trade_rx: broadcast::Receiver<Arc<MarketEvent>>,
+    storage: Arc<dyn Storage>,
+    trade_rx: EventReceiver,
+    policy: BackpressurePolicy,
+    window: BatchWindow,
+    limiter: Option<SymbolRateLimiter>,
+    dropped: DropCounter,
+    limits: WriterLimits,
 }
 
 #[async_trait]
@@ -31,34 +43,60 @@ impl Actor for AggTradeService {
     }
 
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
-        let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
 
         info!("Starting AggTrade Ingestion Service");
 
         let (db_tx, db_rx) = mpsc::channel(2000);
 
-        tokio::spawn(Self::db_writer(self.rotating_pool.clone(), db_rx));
+        let pending = PendingBuffer::with_limits("AggTrade DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
+
+        let writer = self.storage.spawn_writer(Box::pin(run_db_writer(
+            self.storage.clone(),
+            db_rx,
+            self.window,
+            pending,
+            |storage, batch| Box::pin(Self::flush_batch(storage, batch)),
+        )));
 
+        let mut lag = LagTracker::new("AggTrade service").with_throughput(throughput);
         loop {
             match self.trade_rx.recv().await {
                 Ok(event_arc) => {
                     let event = &*event_arc;
-
-                    if let MarketEvent::AggTrade(trade) = event {
-                        if let Err(e) = db_tx.send(trade.to_owned()).await {
-                            heartbeat_handle.abort();
-                            supervisor_tx.try_send(ControlMessage::Error(
-                                self.id,
-                                format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
-                            ))?;
-                            bail!("Failed to send to DB writer: {}", e);
-                        }
+                    lag.observe(event.seq());
+
+                    if let MarketEvent::AggTrade(trade) = event
+                        && self.storage.is_recording(&trade.symbol)
+                        && self.limiter.as_mut().is_none_or(|l| l.allow(&trade.symbol))
+                        && let Err(e) = self
+                            .policy
+                            .forward(&db_tx, trade.to_owned(), &self.dropped)
+                            .await
+                    {
+                        heartbeat_handle.abort();
+                        supervisor_tx.try_send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
+                        ))?;
+                        bail!("Failed to send to DB writer: {}", e);
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("AggTrade service lagged: missed {} signals", n);
+                    lag.lagged(n);
                 }
                 Err(_) => {
+                    drop(db_tx);
+                    if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                        warn!(
+                            "AggTrade DB writer still flushing after {:?}",
+                            WRITER_DRAIN_TIMEOUT
+                        );
+                    }
                     heartbeat_handle.abort();
                     supervisor_tx
                         .send(ControlMessage::Error(
@@ -74,59 +112,54 @@ impl Actor for AggTradeService {
 }
 
 impl AggTradeService {
-    pub fn new(
-        rotating_pool: Arc<DataManager>,
-        trade_rx: broadcast::Receiver<Arc<MarketEvent>>,
-    ) -> Self {
+    pub fn new(storage: Arc<dyn Storage>, trade_rx: EventReceiver) -> Self {
         Self {
             id: Uuid::new_v4(),
-            rotating_pool,
+            storage,
             trade_rx,
+            policy: BackpressurePolicy::default(),
+            window: DEFAULT_TRADE_BATCH,
+            limiter: None,
+            dropped: DropCounter::new("AggTrade DB writer"),
+            limits: WriterLimits::default(),
         }
     }
 
-    async fn db_writer(r_pool: Arc<DataManager>, mut trade_rx: mpsc::Receiver<AggTradeInsert>) {
-        let mut buffer = Vec::with_capacity(1200);
-        let mut last_flush = Instant::now();
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-        loop {
-            tokio::select! {
-                result = trade_rx.recv() => {
-                    match result {
-                        Some(trade) => {
-                            buffer.push(trade);
-                            if buffer.len() >= 1000 || last_flush.elapsed() >= Duration::from_secs(10) {
-                                Self::flush_batch(&*r_pool, &buffer).await;
-                                buffer.clear();
-                                last_flush = Instant::now();
-                            }
-                        }
-                        None => {
-                            info!("DB Channel closed. Flushing remaining buffer.");
-                            if !buffer.is_empty() {
-                                Self::flush_batch(&*r_pool, &buffer).await;
-                            }
-                            break;
-                        }
-                    }
-                }
+    /// Flushes once `window` fills or expires instead of `DEFAULT_TRADE_BATCH`.
+    pub fn with_batch_window(mut self, window: BatchWindow) -> Self {
+        self.window = window;
+        self
+    }
 
-                _ = time::sleep(Duration::from_millis(2000)) => {
-                    if !buffer.is_empty() {
-                        Self::flush_batch(&*r_pool, &buffer).await;
-                        buffer.clear();
-                        last_flush = Instant::now();
-                    }
-                }
-            }
-        }
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Caps each symbol at `per_sec` trades written per second; `None`
+    /// writes every one.
+    pub fn with_rate_limit(mut self, per_sec: Option<u32>) -> Self {
+        self.limiter = per_sec.map(|per_sec| SymbolRateLimiter::new("AggTrade service", per_sec));
+        self
     }
 
-    async fn flush_batch(r_pool: &DataManager, batch: &[AggTradeInsert]) {
-        if let Err(e) = AggTradeRepository::insert_batch(r_pool, batch).await {
-            error!("DB write failed: {}", e);
-        } else {
-            debug!("Wrote {} aggTrades to DB", batch.len());
+    async fn flush_batch(
+        storage: &dyn Storage,
+        batch: &[AggTradeInsert],
+    ) -> Result<(), StorageError> {
+        let result = storage.insert_agg_trades(batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} aggTrades to DB", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
         }
+        result
     }
 }
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::clock::{Clock, SystemClock};
+use storage::data_manager::DataManager;
+use tokio::sync::mpsc;
+use tokio::time;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::remote::BinanceClient;
+use common::actors::{Actor, ActorType, ControlMessage};
+use storage::repositories::BalanceRepository;
+
+/// Default minutes between balance snapshots.
+pub const DEFAULT_BALANCE_SNAPSHOT_MINUTES: u64 = 15;
+
+/// Samples the account's non-zero balances into the `balances` table.
+///
+/// A failed API call or write is logged and retried at the next tick; the
+/// actor itself only stops if the supervisor channel closes.
+pub struct BalanceSnapshotActor {
+    id: Uuid,
+    rotating_pool: Arc<DataManager>,
+    client: BinanceClient,
+    period: Duration,
+}
+
+#[async_trait]
+impl Actor for BalanceSnapshotActor {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::BalanceSnapshotActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let _heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+
+        info!(
+            "Starting Balance Snapshot Actor (every {}s)",
+            self.period.as_secs()
+        );
+
+        let mut ticker = time::interval(self.period);
+        loop {
+            ticker.tick().await;
+            self.snapshot().await;
+        }
+    }
+}
+
+impl BalanceSnapshotActor {
+    pub fn new(rotating_pool: Arc<DataManager>, client: BinanceClient, period: Duration) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            rotating_pool,
+            client,
+            period,
+        }
+    }
+
+    async fn snapshot(&self) {
+        let account = match self.client.get_account().await {
+            Ok(account) => account,
+            Err(e) => {
+                warn!("Balance snapshot skipped, account request failed: {}", e);
+                return;
+            }
+        };
+
+        let time = SystemClock.now_millis() as f64 / 1000.0;
+        let balances = account.non_zero_balances(time);
+        match BalanceRepository::insert_batch(&self.rotating_pool, &balances).await {
+            Ok(()) => debug!("Wrote {} balances to DB", balances.len()),
+            Err(e) => warn!("Balance snapshot write failed: {}", e),
+        }
+    }
+}
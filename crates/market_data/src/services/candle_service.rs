@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use storage::StorageError;
+use storage::data_manager::{DataManager, WRITER_DRAIN_TIMEOUT};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, PendingBuffer, WriterLimits, run_db_writer,
+};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+use common::actors::{Actor, ActorType, ControlMessage, Throughput};
+use common::clock::{Clock, SystemClock};
+use common::models::{AggTradeInsert, KlineInsert};
+use storage::repositories::KlinesRepository;
+
+/// `klines.interval` of candles built from trades, kept apart from the
+/// exchange's own `1m`.
+pub const SYNTH_INTERVAL: &str = "1m_synth";
+
+const CANDLE_MS: i64 = 60_000;
+
+/// How long after its minute ends a candle stays open for late trades.
+const CLOSE_GRACE_MS: i64 = 2_000;
+
+#[derive(Default)]
+struct SymbolCandle {
+    open: Option<KlineInsert>,
+    /// Trades before this bucket belong to a candle that was already closed.
+    next_start: i64,
+}
+
+/// Builds 1m OHLCV candles per symbol from aggTrades.
+///
+/// A candle whose minute began before `started_ms` is missing the trades from
+/// before startup, so it is dropped instead of written.
+const CANDLE_BATCH: BatchWindow = BatchWindow::new(60, Duration::from_secs(60));
+
+pub struct CandleAggregator {
+    started_ms: i64,
+    symbols: HashMap<String, SymbolCandle>,
+}
+
+impl CandleAggregator {
+    pub fn new(started_ms: i64) -> Self {
+        Self {
+            started_ms,
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Adds a trade to its symbol's candle. Returns the previous candle once a
+    /// trade for a later minute arrives.
+    pub fn add(&mut self, trade: &AggTradeInsert) -> Option<KlineInsert> {
+        let start = Self::candle_start(trade.time);
+        let slot = self.symbols.entry(trade.symbol.clone()).or_default();
+        if start < slot.next_start {
+            return None;
+        }
+
+        match slot.open.as_mut() {
+            Some(candle) if candle.start_time == start => {
+                let price = trade.price as f32;
+                candle.high_price = candle.high_price.max(price);
+                candle.low_price = candle.low_price.min(price);
+                candle.close_price = price;
+                candle.volume += trade.quantity;
+                candle.no_of_trades += 1;
+                if !trade.is_buyer_maker {
+                    candle.taker_buy_vol += trade.quantity as f32;
+                }
+                None
+            }
+            _ => {
+                let previous = slot.open.replace(Self::open_candle(trade, start));
+                slot.next_start = start;
+                previous.and_then(|candle| self.complete(candle))
+            }
+        }
+    }
+
+    /// Closes every candle whose minute ended more than `CLOSE_GRACE_MS`
+    /// before `now_ms`, so quiet symbols don't hold their last candle back.
+    pub fn close_due(&mut self, now_ms: i64) -> Vec<KlineInsert> {
+        let mut closed = Vec::new();
+        for slot in self.symbols.values_mut() {
+            if let Some(candle) = &slot.open
+                && candle.start_time + CANDLE_MS + CLOSE_GRACE_MS <= now_ms
+            {
+                slot.next_start = candle.start_time + CANDLE_MS;
+                closed.extend(slot.open.take());
+            }
+        }
+        closed.retain(|candle| !self.is_partial(candle));
+        closed
+    }
+
+    fn complete(&self, candle: KlineInsert) -> Option<KlineInsert> {
+        (!self.is_partial(&candle)).then_some(candle)
+    }
+
+    fn is_partial(&self, candle: &KlineInsert) -> bool {
+        if candle.start_time < self.started_ms {
+            debug!(
+                "Dropping partial startup candle for {} at {}",
+                candle.symbol, candle.start_time
+            );
+            return true;
+        }
+        false
+    }
+
+    fn open_candle(trade: &AggTradeInsert, start: i64) -> KlineInsert {
+        let price = trade.price as f32;
+        KlineInsert {
+            symbol: trade.symbol.clone(),
+            start_time: start,
+            close_time: start + CANDLE_MS - 1,
+            interval: SYNTH_INTERVAL.to_string(),
+            open_price: price,
+            close_price: price,
+            high_price: price,
+            low_price: price,
+            volume: trade.quantity,
+            no_of_trades: 1,
+            taker_buy_vol: if trade.is_buyer_maker {
+                0.0
+            } else {
+                trade.quantity as f32
+            },
+            seq: None,
+        }
+    }
+
+    /// Start of the minute, in epoch milliseconds, that `time` (epoch seconds) falls in.
+    fn candle_start(time: f64) -> i64 {
+        let millis = (time * 1000.0) as i64;
+        millis - millis.rem_euclid(CANDLE_MS)
+    }
+}
+
+pub struct AggTradeCandleService {
+    id: Uuid,
+    rotating_pool: Arc<DataManager>,
+    trade_rx: EventReceiver,
+    aggregator: CandleAggregator,
+    policy: BackpressurePolicy,
+    dropped: DropCounter,
+    limits: WriterLimits,
+    clock: Arc<dyn Clock>,
+}
+
+#[async_trait]
+impl Actor for AggTradeCandleService {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::AggTradeCandleActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
+
+        info!("Starting AggTrade Candle Service");
+
+        let (db_tx, db_rx) = mpsc::channel(256);
+
+        let pending = PendingBuffer::with_limits("AggTrade candle DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
+
+        let writer = self.rotating_pool.spawn_writer(run_db_writer(
+            self.rotating_pool.clone(),
+            db_rx,
+            CANDLE_BATCH,
+            pending,
+            |pool, batch| Box::pin(Self::flush_batch(pool, batch)),
+        ));
+
+        let mut close_check = time::interval(Duration::from_secs(1));
+
+        loop {
+            let closed = tokio::select! {
+                result = self.trade_rx.recv() => match result {
+                    Ok(event_arc) => match &*event_arc {
+                        MarketEvent::AggTrade(trade) => self.aggregator.add(trade).into_iter().collect(),
+                        _ => Vec::new(),
+                    },
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        throughput.lagged(n);
+                        warn!("AggTrade candle service lagged: missed {} signals", n);
+                        Vec::new()
+                    }
+                    Err(_) => {
+                        drop(db_tx);
+                        if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                            warn!("AggTrade candle DB writer still flushing after {:?}", WRITER_DRAIN_TIMEOUT);
+                        }
+                        heartbeat_handle.abort();
+                        supervisor_tx
+                            .send(ControlMessage::Error(
+                                self.id,
+                                format!("{:?}: AggTrade channel closed unexpectedly.", self.name()),
+                            ))
+                            .await?;
+                        bail!("AggTrade channel closed unexpectedly.");
+                    }
+                },
+                _ = close_check.tick() => self.aggregator.close_due(self.clock.now_millis() as i64),
+            };
+
+            for candle in closed
+                .into_iter()
+                .filter(|candle| self.rotating_pool.is_recording(&candle.symbol))
+            {
+                if let Err(e) = self.policy.forward(&db_tx, candle, &self.dropped).await {
+                    heartbeat_handle.abort();
+                    supervisor_tx.try_send(ControlMessage::Error(
+                        self.id,
+                        format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
+                    ))?;
+                    bail!("Failed to send to DB writer: {}", e);
+                }
+            }
+        }
+    }
+}
+
+impl AggTradeCandleService {
+    pub fn new(rotating_pool: Arc<DataManager>, trade_rx: EventReceiver) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            rotating_pool,
+            trade_rx,
+            aggregator: CandleAggregator::new(SystemClock.now_millis() as i64),
+            policy: BackpressurePolicy::default(),
+            dropped: DropCounter::new("AggTrade candle DB writer"),
+            limits: WriterLimits::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Replaces the clock that decides startup and quiet-symbol candle closes.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.aggregator = CandleAggregator::new(clock.now_millis() as i64);
+        self.clock = clock;
+        self
+    }
+
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    async fn flush_batch(r_pool: &DataManager, batch: &[KlineInsert]) -> Result<(), StorageError> {
+        let result = KlinesRepository::insert_batch(r_pool, batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} synthetic candles to DB", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2023-11-14T22:13:00Z
+    const MINUTE: f64 = 1_699_999_980.0;
+
+    fn trade(time: f64, price: f64, quantity: f64, is_buyer_maker: bool) -> AggTradeInsert {
+        AggTradeInsert {
+            agg_trade_id: 1,
+            time,
+            trade_time: time,
+            symbol: "BTCUSDT".to_string(),
+            price,
+            quantity,
+            is_buyer_maker,
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn test_candle_tracks_ohlcv_and_closes_on_next_minute() {
+        let mut agg = CandleAggregator::new(0);
+
+        assert!(agg.add(&trade(MINUTE + 1.0, 10.0, 1.0, false)).is_none());
+        assert!(agg.add(&trade(MINUTE + 20.0, 12.0, 2.0, true)).is_none());
+        assert!(agg.add(&trade(MINUTE + 40.0, 9.0, 0.5, false)).is_none());
+        assert!(agg.add(&trade(MINUTE + 59.0, 11.0, 1.5, true)).is_none());
+
+        let candle = agg
+            .add(&trade(MINUTE + 61.0, 13.0, 1.0, false))
+            .expect("minute rolled over");
+        assert_eq!(candle.start_time, 1_699_999_980_000);
+        assert_eq!(candle.close_time, 1_700_000_039_999);
+        assert_eq!(candle.interval, SYNTH_INTERVAL);
+        assert_eq!(
+            (
+                candle.open_price,
+                candle.high_price,
+                candle.low_price,
+                candle.close_price
+            ),
+            (10.0, 12.0, 9.0, 11.0)
+        );
+        assert_eq!(candle.volume, 5.0);
+        assert_eq!(candle.taker_buy_vol, 1.5);
+        assert_eq!(candle.no_of_trades, 4);
+
+        // A straggler for the closed minute doesn't reopen it.
+        assert!(agg.add(&trade(MINUTE + 30.0, 1.0, 1.0, false)).is_none());
+    }
+
+    #[test]
+    fn test_partial_startup_candle_is_dropped_and_quiet_symbols_close() {
+        // Started 30s into the minute.
+        let mut agg = CandleAggregator::new(1_700_000_010_000);
+
+        assert!(agg.add(&trade(MINUTE + 31.0, 10.0, 1.0, false)).is_none());
+        assert!(agg.add(&trade(MINUTE + 61.0, 11.0, 1.0, false)).is_none());
+
+        assert!(agg.close_due(1_700_000_100_000).is_empty());
+        let closed = agg.close_due(1_700_000_100_000 + CLOSE_GRACE_MS);
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].start_time, 1_700_000_040_000);
+        assert!(agg.close_due(1_700_000_200_000).is_empty());
+    }
+}
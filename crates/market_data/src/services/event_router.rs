@@ -0,0 +1,243 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::bail;
+use async_trait::async_trait;
+use common::actors::{Actor, ActorType, ControlMessage};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::backpressure::BackpressurePolicy;
+use crate::services::market_gateway::{
+    EventKind, EventReceiver, EventSender, MarketEvent, event_channel,
+};
+
+/// Splits the gateway's broadcast into one channel per `EventKind`, so a
+/// service that records trades is no longer woken for every order book,
+/// kline and mark price as well.
+///
+/// The routed channels carry the same `Arc<MarketEvent>`, so services keep
+/// their receiver type and simply never see other variants. Their `seq`
+/// skips the numbers of other kinds; after a `Lagged` the range `LagTracker`
+/// reports therefore spans those too. Under `BackpressurePolicy::Block` the
+/// router waits on a full routed channel the way the gateway waits on its
+/// own, so a slow service still ends up stalling the socket.
+#[derive(Clone)]
+pub struct EventRouter {
+    /// Indexed by `EventKind as usize`.
+    channels: Arc<Vec<EventSender>>,
+    stats: Arc<RouterStats>,
+}
+
+impl EventRouter {
+    /// One channel of `capacity` slots per kind.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            channels: Arc::new(
+                EventKind::ALL
+                    .iter()
+                    .map(|_| event_channel(capacity).0)
+                    .collect(),
+            ),
+            stats: Arc::default(),
+        }
+    }
+
+    /// The channel of `kind` events. Subscribe from the service factory, as
+    /// with the gateway's channel.
+    pub fn sender(&self, kind: EventKind) -> EventSender {
+        self.channels[kind as usize].clone()
+    }
+
+    pub fn stats(&self) -> Arc<RouterStats> {
+        self.stats.clone()
+    }
+
+    /// Forwards every event of `market_rx` to its kind's channel until the
+    /// gateway's channel closes. `EventRouterActor` runs this under the
+    /// supervisor.
+    pub async fn run(&self, market_rx: &mut EventReceiver, policy: BackpressurePolicy) {
+        loop {
+            match market_rx.recv().await {
+                Ok(event) => self.route(event, policy).await,
+                Err(RecvError::Lagged(n)) => {
+                    self.stats.lagged.fetch_add(n, Ordering::Relaxed);
+                    warn!("Event router lagged: missed {} events", n);
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    async fn route(&self, event: Arc<MarketEvent>, policy: BackpressurePolicy) {
+        let tx = &self.channels[event.kind() as usize];
+        if policy == BackpressurePolicy::Block {
+            tx.reserve().await;
+        }
+        let subscribers: usize = self.channels.iter().map(|c| c.receiver_count()).sum();
+        self.stats.record(tx.receiver_count(), subscribers);
+        let _ = tx.send(event);
+    }
+}
+
+/// Runs an `EventRouter` under the supervisor, so a router that dies is
+/// restarted like any service. Its factory subscribes to the gateway's
+/// channel afresh.
+pub struct EventRouterActor {
+    id: Uuid,
+    router: EventRouter,
+    market_rx: EventReceiver,
+    policy: BackpressurePolicy,
+}
+
+impl EventRouterActor {
+    pub fn new(router: EventRouter, market_rx: EventReceiver) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            router,
+            market_rx,
+            policy: BackpressurePolicy::default(),
+        }
+    }
+
+    /// What to do when a subscriber is a full channel behind: wait (`Block`,
+    /// the default) or let it lose the oldest events.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+#[async_trait]
+impl Actor for EventRouterActor {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::EventRouterActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let _heartbeat = self.spawn_heartbeat(supervisor_tx.clone());
+        info!("Starting Event Router");
+
+        self.router.run(&mut self.market_rx, self.policy).await;
+        supervisor_tx
+            .send(ControlMessage::Error(
+                self.id,
+                format!("{:?}: Market channel closed unexpectedly.", self.name()),
+            ))
+            .await?;
+        bail!("Market channel closed unexpectedly.")
+    }
+}
+
+/// Wakeups the router saved: how many receivers each event reached, against
+/// how many it would have reached on one channel shared by all of them.
+#[derive(Debug, Default)]
+pub struct RouterStats {
+    events: AtomicU64,
+    delivered: AtomicU64,
+    unrouted: AtomicU64,
+    lagged: AtomicU64,
+}
+
+impl RouterStats {
+    fn record(&self, delivered: usize, unrouted: usize) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+        self.delivered
+            .fetch_add(delivered as u64, Ordering::Relaxed);
+        self.unrouted.fetch_add(unrouted as u64, Ordering::Relaxed);
+    }
+
+    pub fn events(&self) -> u64 {
+        self.events.load(Ordering::Relaxed)
+    }
+
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    /// Deliveries had every subscriber received every event.
+    pub fn unrouted(&self) -> u64 {
+        self.unrouted.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for RouterStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (delivered, unrouted) = (self.delivered(), self.unrouted());
+        let saved = if unrouted == 0 {
+            0.0
+        } else {
+            100.0 * (unrouted - delivered) as f64 / unrouted as f64
+        };
+        write!(
+            f,
+            "{} events routed, {} service wakeups instead of {} ({:.0}% fewer), {} missed",
+            self.events(),
+            delivered,
+            unrouted,
+            saved,
+            self.lagged.load(Ordering::Relaxed)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::models::{AggTradeInsert, OrderBookInsert};
+
+    #[tokio::test]
+    async fn test_each_kind_reaches_only_its_subscribers() {
+        let (market_tx, mut market_rx) = event_channel(16);
+        let router = EventRouter::new(16);
+        let mut trades = router.sender(EventKind::AggTrade).subscribe();
+        let mut trades_too = router.sender(EventKind::AggTrade).subscribe();
+        let mut books = router.sender(EventKind::OrderBook).subscribe();
+
+        let trade = || {
+            Arc::new(MarketEvent::AggTrade(AggTradeInsert {
+                agg_trade_id: 1,
+                time: 1.0,
+                trade_time: 1.0,
+                symbol: "BTCUSDT".to_string(),
+                price: 100.0,
+                quantity: 1.0,
+                is_buyer_maker: false,
+                seq: None,
+            }))
+        };
+        let book = Arc::new(MarketEvent::OrderBook(OrderBookInsert {
+            time: 1.0,
+            symbol: "BTCUSDT".to_string(),
+            bids: vec![],
+            asks: vec![],
+            seq: None,
+        }));
+        assert!(market_tx.send(trade()).is_ok());
+        assert!(market_tx.send(book).is_ok());
+        assert!(market_tx.send(trade()).is_ok());
+        drop(market_tx);
+        router.run(&mut market_rx, BackpressurePolicy::Block).await;
+
+        for rx in [&mut trades, &mut trades_too] {
+            for _ in 0..2 {
+                assert_eq!(rx.try_recv().unwrap().kind(), EventKind::AggTrade);
+            }
+            assert!(rx.try_recv().is_err());
+        }
+        assert_eq!(books.try_recv().unwrap().kind(), EventKind::OrderBook);
+        assert!(books.try_recv().is_err());
+
+        let stats = router.stats();
+        assert_eq!(stats.events(), 3);
+        assert_eq!(stats.delivered(), 5);
+        assert_eq!(stats.unrouted(), 9);
+    }
+}
@@ -1,26 +1,36 @@
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use anyhow::bail;
 use async_trait::async_trait;
 use common::{
-    actors::{Actor, ActorType, ControlMessage},
+    actors::{Actor, ActorType, ControlMessage, Throughput},
     models::ForceOrderInsert,
 };
-use storage::{data_manager::DataManager, repositories::forceorder_repo::ForceOrderRepository};
-use tokio::{
-    sync::{broadcast, mpsc},
-    time,
+use storage::{
+    StorageError,
+    data_manager::{DataManager, WRITER_DRAIN_TIMEOUT},
+    repositories::forceorder_repo::ForceOrderRepository,
 };
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::services::market_gateway::MarketEvent;
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, LagTracker, PendingBuffer, WriterLimits,
+    run_db_writer,
+};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+
+const FORCE_ORDER_BATCH: BatchWindow = BatchWindow::new(512, Duration::from_secs(10));
 
 pub struct ForceOrderService {
     id: Uuid,
     rotating_pool: Arc<DataManager>,
-    order_rx: broadcast::Receiver<Arc<MarketEvent>>,
+    order_rx: EventReceiver,
+    policy: BackpressurePolicy,
+    dropped: DropCounter,
+    limits: WriterLimits,
 }
 
 #[async_trait]
@@ -34,34 +44,59 @@ impl Actor for ForceOrderService {
     }
 
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
-        let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
 
         info!("Starting ForceOrder Ingestion Service");
 
         let (db_tx, db_rx) = mpsc::channel(512);
 
-        tokio::spawn(Self::db_writer(self.rotating_pool.clone(), db_rx));
+        let pending = PendingBuffer::with_limits("ForceOrder DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
 
+        let writer = self.rotating_pool.spawn_writer(run_db_writer(
+            self.rotating_pool.clone(),
+            db_rx,
+            FORCE_ORDER_BATCH,
+            pending,
+            |pool, batch| Box::pin(Self::flush_batch(pool, batch)),
+        ));
+
+        let mut lag = LagTracker::new("ForceOrder service").with_throughput(throughput);
         loop {
             match self.order_rx.recv().await {
                 Ok(order_arc) => {
                     let event = &*order_arc;
-
-                    if let MarketEvent::ForceOrder(order) = event {
-                        if let Err(e) = db_tx.send(order.to_owned()).await {
-                            heartbeat_handle.abort();
-                            supervisor_tx.try_send(ControlMessage::Error(
-                                self.id,
-                                format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
-                            ))?;
-                            bail!("Failed to send to DB writer: {}", e);
-                        }
+                    lag.observe(event.seq());
+
+                    if let MarketEvent::ForceOrder(order) = event
+                        && self.rotating_pool.is_recording(&order.symbol)
+                        && let Err(e) = self
+                            .policy
+                            .forward(&db_tx, order.to_owned(), &self.dropped)
+                            .await
+                    {
+                        heartbeat_handle.abort();
+                        supervisor_tx.try_send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
+                        ))?;
+                        bail!("Failed to send to DB writer: {}", e);
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("ForceOrder service lagged: missed {} signals", n);
+                    lag.lagged(n);
                 }
                 Err(_) => {
+                    drop(db_tx);
+                    if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                        warn!(
+                            "ForceOrder DB writer still flushing after {:?}",
+                            WRITER_DRAIN_TIMEOUT
+                        );
+                    }
                     heartbeat_handle.abort();
                     supervisor_tx
                         .send(ControlMessage::Error(
@@ -77,59 +112,39 @@ impl Actor for ForceOrderService {
 }
 
 impl ForceOrderService {
-    pub fn new(
-        rotating_pool: Arc<DataManager>,
-        order_rx: broadcast::Receiver<Arc<MarketEvent>>,
-    ) -> Self {
+    pub fn new(rotating_pool: Arc<DataManager>, order_rx: EventReceiver) -> Self {
         Self {
             id: Uuid::new_v4(),
             rotating_pool,
             order_rx,
+            policy: BackpressurePolicy::default(),
+            dropped: DropCounter::new("ForceOrder DB writer"),
+            limits: WriterLimits::default(),
         }
     }
 
-    async fn db_writer(r_pool: Arc<DataManager>, mut order_rx: mpsc::Receiver<ForceOrderInsert>) {
-        let mut buffer = Vec::with_capacity(1024);
-        let mut last_flush = Instant::now();
-
-        loop {
-            tokio::select! {
-                result = order_rx.recv() => {
-                    match result {
-                        Some(order) => {
-                            buffer.push(order);
-                            if buffer.len() >= 512 || last_flush.elapsed() >= Duration::from_secs(10) {
-                                Self::flush_batch(&*r_pool, &buffer).await;
-                                buffer.clear();
-                                last_flush = Instant::now();
-                            }
-                        }
-                        None => {
-                            info!("DB Channel closed. Flusing remaining buffer.");
-                            if !buffer.is_empty() {
-                                Self::flush_batch(&*r_pool, &buffer).await;
-                            }
-                            break;
-                        }
-                    }
-                }
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-                _ = time::sleep(Duration::from_secs(5)) => {
-                    if !buffer.is_empty() {
-                        Self::flush_batch(&*r_pool, &buffer).await;
-                        buffer.clear();
-                        last_flush = Instant::now();
-                    }
-                }
-            }
-        }
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
     }
 
-    async fn flush_batch(r_pool: &DataManager, batch: &[ForceOrderInsert]) {
-        if let Err(e) = ForceOrderRepository::insert_batch(r_pool, batch).await {
-            error!("DB write failed: {}", e);
-        } else {
-            debug!("Wrote {} ForceOrder to DB", batch.len());
+    async fn flush_batch(
+        r_pool: &DataManager,
+        batch: &[ForceOrderInsert],
+    ) -> Result<(), StorageError> {
+        let result = ForceOrderRepository::insert_batch(r_pool, batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} ForceOrder to DB", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
         }
+        result
     }
 }
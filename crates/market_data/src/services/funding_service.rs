@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use common::{
+    actors::{Actor, ActorType, ControlMessage},
+    models::{FundingEventInsert, MarkPriceInsert},
+};
+use storage::{data_manager::DataManager, repositories::FundingRepository};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use crate::backpressure::LagTracker;
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+
+/// Turns the mark price stream into funding settlements.
+///
+/// Every update carries the rate that will be charged at `next_funding_time`.
+/// Once a symbol's next funding time moves forward, the previous one has been
+/// settled, at the rate and mark price last streamed before it.
+#[derive(Debug, Default)]
+pub struct FundingTracker {
+    /// Symbol -> (next funding time, rate, mark price) of its last update.
+    last: HashMap<String, (i64, f64, f64)>,
+}
+
+impl FundingTracker {
+    pub fn observe(&mut self, mark: &MarkPriceInsert) -> Option<FundingEventInsert> {
+        let next_time = mark.next_funding_time?;
+        let previous = self.last.insert(
+            mark.symbol.clone(),
+            (next_time, mark.funding_rate, mark.mark_price),
+        );
+        match previous {
+            Some((funding_time, rate, mark_price)) if next_time > funding_time => {
+                Some(FundingEventInsert {
+                    funding_time,
+                    symbol: mark.symbol.clone(),
+                    rate,
+                    mark_price,
+                })
+            }
+            // An update from before a settlement we have already recorded.
+            Some(kept) if next_time < kept.0 => {
+                self.last.insert(mark.symbol.clone(), kept);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Records each funding settlement seen on the mark price stream into
+/// `funding_events`.
+///
+/// A symbol's first settlement after a start is only recorded if its previous
+/// funding time was streamed, so the one in progress at startup is missed.
+/// Writes are single rows a few times a day; a failed one is logged and lost.
+pub struct FundingService {
+    id: Uuid,
+    rotating_pool: Arc<DataManager>,
+    mark_rx: EventReceiver,
+    tracker: FundingTracker,
+}
+
+#[async_trait]
+impl Actor for FundingService {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::FundingActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+
+        info!("Starting Funding Settlement Service");
+
+        let mut lag = LagTracker::new("Funding service");
+        loop {
+            match self.mark_rx.recv().await {
+                Ok(event_mark) => {
+                    let event = &*event_mark;
+                    lag.observe(event.seq());
+
+                    if let MarketEvent::MarkPrice(mark) = event
+                        && self.rotating_pool.is_recording(&mark.symbol)
+                        && let Some(settled) = self.tracker.observe(mark)
+                    {
+                        match FundingRepository::insert(&self.rotating_pool, &settled).await {
+                            Ok(()) => debug!(
+                                "Recorded {} funding at {}: {}",
+                                settled.symbol, settled.funding_time, settled.rate
+                            ),
+                            Err(e) => error!(
+                                "Failed to record {} funding at {}: {}",
+                                settled.symbol, settled.funding_time, e
+                            ),
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    lag.lagged(n);
+                }
+                Err(_) => {
+                    heartbeat_handle.abort();
+                    supervisor_tx.try_send(ControlMessage::Error(
+                        self.id,
+                        format!("{:?}: MarkPrice channel closed unexpectedly.", self.name()),
+                    ))?;
+                    bail!("MarkPrice channel closed unexpectedly.")
+                }
+            }
+        }
+    }
+}
+
+impl FundingService {
+    pub fn new(rotating_pool: Arc<DataManager>, mark_rx: EventReceiver) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            rotating_pool,
+            mark_rx,
+            tracker: FundingTracker::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settles_when_next_funding_time_advances() {
+        let mark = |next_funding_time, funding_rate, mark_price| MarkPriceInsert {
+            time: 0.0,
+            symbol: "BTCUSDT".to_string(),
+            mark_price,
+            index_price: mark_price,
+            funding_rate,
+            next_funding_time: Some(next_funding_time),
+            seq: None,
+        };
+        let mut tracker = FundingTracker::default();
+
+        assert_eq!(tracker.observe(&mark(8_000, 0.0001, 100.0)), None);
+        assert_eq!(tracker.observe(&mark(8_000, 0.0002, 101.0)), None);
+        assert_eq!(
+            tracker.observe(&mark(16_000, 0.0003, 102.0)),
+            Some(FundingEventInsert {
+                funding_time: 8_000,
+                symbol: "BTCUSDT".to_string(),
+                rate: 0.0002,
+                mark_price: 101.0,
+            })
+        );
+        // A late update from before the settlement doesn't record it again.
+        assert_eq!(tracker.observe(&mark(8_000, 0.0002, 101.0)), None);
+        assert_eq!(tracker.observe(&mark(16_000, 0.0004, 103.0)), None);
+        assert_eq!(
+            tracker
+                .observe(&mark(24_000, 0.0, 104.0))
+                .map(|e| (e.funding_time, e.rate)),
+            Some((16_000, 0.0004))
+        );
+    }
+}
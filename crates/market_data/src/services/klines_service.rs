@@ -1,22 +1,103 @@
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use anyhow::bail;
 use async_trait::async_trait;
-use storage::data_manager::DataManager;
+use storage::StorageError;
+use storage::backend::Storage;
+use storage::data_manager::WRITER_DRAIN_TIMEOUT;
+use storage::maintenance::interval_ms;
 use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::services::market_gateway::MarketEvent;
-use common::actors::{Actor, ActorType, ControlMessage};
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, LagTracker, PendingBuffer, WriterLimits,
+    run_db_writer_urgent,
+};
+use crate::remote::kline_rest::{KlineRestClient, MAX_KLINES_PER_REQUEST};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+use common::actors::{Actor, ActorType, ControlMessage, Throughput};
 use common::models::KlineInsert;
-use storage::repositories::KlinesRepository;
+
+const KLINE_BATCH: BatchWindow = BatchWindow::new(300, Duration::from_secs(20));
+
+/// Intervals whose holes are backfilled. Quiet symbols legitimately skip `1s`
+/// candles, so a missing one there is not a recording gap.
+const BACKFILL_INTERVALS: &[&str] = &["1m", "1h"];
+
+/// Intervals written as soon as a candle closes unless
+/// `with_flush_on_close` says otherwise.
+pub const DEFAULT_FLUSH_ON_CLOSE: &[&str] = &["1h"];
+
+/// Candles missing between the last closed one seen and `next_start`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KlineGap {
+    pub symbol: String,
+    pub interval: String,
+    /// First missing start time, or `None` on the first candle since startup,
+    /// when only the database knows where recording stopped.
+    pub first_missing: Option<i64>,
+    /// Start time of the candle that revealed the gap.
+    pub next_start: i64,
+    pub step: i64,
+}
+
+impl KlineGap {
+    /// Earliest start time worth fetching: backfill never reaches further back
+    /// than `MAX_KLINES_PER_REQUEST` candles.
+    pub fn floor(&self) -> i64 {
+        self.next_start - MAX_KLINES_PER_REQUEST as i64 * self.step
+    }
+}
+
+/// Remembers the last closed candle per symbol/interval and reports holes.
+#[derive(Default)]
+pub struct KlineGapTracker {
+    last_closed: HashMap<(String, String), i64>,
+}
+
+impl KlineGapTracker {
+    pub fn observe(&mut self, kline: &KlineInsert) -> Option<KlineGap> {
+        if !BACKFILL_INTERVALS.contains(&kline.interval.as_str()) {
+            return None;
+        }
+        let step = interval_ms(&kline.interval)?;
+        let key = (kline.symbol.clone(), kline.interval.clone());
+        let previous = self.last_closed.get(&key).copied();
+        if previous.is_some_and(|last| kline.start_time <= last) {
+            return None;
+        }
+        self.last_closed.insert(key, kline.start_time);
+
+        let first_missing = match previous {
+            Some(last) if kline.start_time > last + step => Some(last + step),
+            Some(_) => return None,
+            None => None,
+        };
+        Some(KlineGap {
+            symbol: kline.symbol.clone(),
+            interval: kline.interval.clone(),
+            first_missing,
+            next_start: kline.start_time,
+            step,
+        })
+    }
+}
 
 pub struct KlinesService {
     id: Uuid,
-    rotating_pool: Arc<DataManager>,
-    kline_rx: broadcast::Receiver<Arc<MarketEvent>>,
+    storage: Arc<dyn Storage>,
+    kline_rx: EventReceiver,
+    policy: BackpressurePolicy,
+    dropped: DropCounter,
+    limits: WriterLimits,
+    gaps: KlineGapTracker,
+    /// Symbol/interval -> start time of the last closed candle forwarded.
+    stored: HashMap<(String, String), i64>,
+    rest: KlineRestClient,
+    flush_on_close: Arc<[String]>,
 }
 
 #[async_trait]
@@ -30,35 +111,68 @@ impl Actor for KlinesService {
     }
 
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
-        let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
 
         info!("Starting Klines Ingestion Service");
 
         let (db_tx, db_rx) = mpsc::channel(600);
 
-        tokio::spawn(Self::db_writer(self.rotating_pool.clone(), db_rx));
+        let pending = PendingBuffer::with_limits("Kline DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
 
+        let writer = self.storage.spawn_writer(Box::pin(Self::db_writer(
+            self.storage.clone(),
+            db_rx,
+            pending,
+            self.flush_on_close.clone(),
+        )));
+
+        let mut lag = LagTracker::new("Klines service").with_throughput(throughput);
         loop {
             match self.kline_rx.recv().await {
                 Ok(event_arc) => {
                     let event = &*event_arc;
+                    lag.observe(event.seq());
 
-                    if let MarketEvent::Kline((kline, closed)) = event {
-                        if let Err(e) = db_tx.send((kline.to_owned(), *closed)).await {
-                            let err_msg = format!("Failed to send to DB writer: {}", e);
-                            heartbeat_handle.abort();
-                            supervisor_tx
-                                .send(ControlMessage::Error(self.id, err_msg.clone()))
-                                .await?;
-                            bail!(err_msg);
-                        }
+                    if let MarketEvent::Kline((kline, true)) = event
+                        && self.storage.is_recording(&kline.symbol)
+                        && let Some(gap) = self.gaps.observe(kline)
+                    {
+                        tokio::spawn(Self::backfill(self.storage.clone(), self.rest.clone(), gap));
+                    }
+
+                    // Only closed candles are stored, each once.
+                    if let MarketEvent::Kline((kline, true)) = event
+                        && self.storage.is_recording(&kline.symbol)
+                        && self.first_close(kline)
+                        && let Err(e) = self
+                            .policy
+                            .forward(&db_tx, kline.to_owned(), &self.dropped)
+                            .await
+                    {
+                        let err_msg = format!("Failed to send to DB writer: {}", e);
+                        heartbeat_handle.abort();
+                        supervisor_tx
+                            .send(ControlMessage::Error(self.id, err_msg.clone()))
+                            .await?;
+                        bail!(err_msg);
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("Klines service lagged: missed {} signals", n);
+                    lag.lagged(n);
                 }
                 Err(_) => {
-                    let err_msg = format!("Kline channel closed. Stopping service.");
+                    drop(db_tx);
+                    if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                        warn!(
+                            "Kline DB writer still flushing after {:?}",
+                            WRITER_DRAIN_TIMEOUT
+                        );
+                    }
+                    let err_msg = "Kline channel closed. Stopping service.".to_string();
                     heartbeat_handle.abort();
                     supervisor_tx
                         .send(ControlMessage::Error(self.id, err_msg.clone()))
@@ -71,57 +185,222 @@ impl Actor for KlinesService {
 }
 
 impl KlinesService {
-    pub fn new(
-        rotating_pool: Arc<DataManager>,
-        kline_rx: broadcast::Receiver<Arc<MarketEvent>>,
-    ) -> Self {
+    pub fn new(storage: Arc<dyn Storage>, kline_rx: EventReceiver, rest: KlineRestClient) -> Self {
         Self {
             id: Uuid::new_v4(),
-            rotating_pool,
+            storage,
             kline_rx,
+            policy: BackpressurePolicy::default(),
+            dropped: DropCounter::new("Klines DB writer"),
+            limits: WriterLimits::default(),
+            gaps: KlineGapTracker::default(),
+            stored: HashMap::new(),
+            rest,
+            flush_on_close: DEFAULT_FLUSH_ON_CLOSE
+                .iter()
+                .map(|i| i.to_string())
+                .collect(),
         }
     }
 
-    async fn db_writer(
-        r_pool: Arc<DataManager>,
-        mut kline_rx: mpsc::Receiver<(KlineInsert, bool)>,
-    ) {
-        let mut buffer = Vec::with_capacity(300);
-        let mut last_flush = Instant::now();
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-        loop {
-            tokio::select! {
-                result = kline_rx.recv() => {
-                    match result {
-                        Some((kline, closed)) => {
-                            if closed {
-                                buffer.push(kline);
-                            }
-
-                            if buffer.len() >= 300 || last_flush.elapsed() >= Duration::from_secs(20) {
-                                Self::flush_batch(&r_pool, &buffer).await;
-                                buffer.clear();
-                                last_flush = Instant::now();
-                            }
-                        }
-                        None => {
-                            info!("DB Channel closed. Flushing remaining buffer.");
-                            if !buffer.is_empty() {
-                                Self::flush_batch(&r_pool, &buffer).await;
-                            }
-                            break;
-                        }
-                    }
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Closed candles of `intervals` are written right away, with whatever
+    /// else is buffered, instead of waiting for a full batch or the 20s
+    /// timer. Noisy intervals like `1s` are best left batched.
+    pub fn with_flush_on_close(mut self, intervals: &[String]) -> Self {
+        self.flush_on_close = intervals.into();
+        self
+    }
+
+    /// False if `kline`, or a later candle of its symbol and interval, was
+    /// already stored: a candle closed by its event time is usually followed
+    /// by Binance's own final update.
+    fn first_close(&mut self, kline: &KlineInsert) -> bool {
+        let key = (kline.symbol.clone(), kline.interval.clone());
+        if self
+            .stored
+            .get(&key)
+            .is_some_and(|&last| kline.start_time <= last)
+        {
+            return false;
+        }
+        self.stored.insert(key, kline.start_time);
+        true
+    }
+
+    /// Fetches the candles of `gap` over REST and inserts those not stored yet.
+    async fn backfill(storage: Arc<dyn Storage>, rest: KlineRestClient, gap: KlineGap) {
+        let first_missing = match gap.first_missing {
+            Some(start) => start,
+            None => match storage.latest_kline_start(&gap.symbol, &gap.interval).await {
+                Ok(Some(last)) => last + gap.step,
+                // Nothing recorded yet in this week's file.
+                Ok(None) => return,
+                Err(e) => {
+                    warn!(
+                        "Kline backfill lookup failed for {} {}: {}",
+                        gap.symbol, gap.interval, e
+                    );
+                    return;
                 }
+            },
+        };
+        let until = gap.next_start - gap.step;
+        let from = first_missing.max(gap.floor());
+        if from > until {
+            return;
+        }
+        if from > first_missing {
+            warn!(
+                "{} {} is missing {} candles; backfilling only the last {}",
+                gap.symbol,
+                gap.interval,
+                (until - first_missing) / gap.step + 1,
+                MAX_KLINES_PER_REQUEST
+            );
+        }
+
+        let klines = match rest.fetch(&gap.symbol, &gap.interval, from, until).await {
+            Ok(klines) => klines,
+            Err(e) => {
+                warn!(
+                    "Kline backfill fetch failed for {} {}: {}",
+                    gap.symbol, gap.interval, e
+                );
+                return;
             }
+        };
+        match storage.insert_missing_klines(&klines).await {
+            Ok(inserted) => info!(
+                "Backfilled {} {} {} candles from {} to {}",
+                inserted, gap.symbol, gap.interval, from, until
+            ),
+            Err(e) => error!("Kline backfill write failed: {}", e),
+        }
+    }
+
+    /// Writes closed candles in batches of `KLINE_BATCH`, and at once when
+    /// one of a `flush_on_close` interval comes in.
+    async fn db_writer(
+        storage: Arc<dyn Storage>,
+        kline_rx: mpsc::Receiver<KlineInsert>,
+        buffer: PendingBuffer<KlineInsert>,
+        flush_on_close: Arc<[String]>,
+    ) {
+        run_db_writer_urgent(
+            storage,
+            kline_rx,
+            KLINE_BATCH,
+            buffer,
+            |storage, batch| Box::pin(Self::flush_batch(storage, batch)),
+            |kline| flush_on_close.contains(&kline.interval),
+        )
+        .await
+    }
+
+    async fn flush_batch(storage: &dyn Storage, batch: &[KlineInsert]) -> Result<(), StorageError> {
+        let result = storage.insert_klines(batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} klines to DB", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
         }
+        result
     }
+}
 
-    async fn flush_batch(r_pool: &DataManager, batch: &[KlineInsert]) {
-        if let Err(e) = KlinesRepository::insert_batch(r_pool, batch).await {
-            error!("DB write failed: {}", e);
-        } else {
-            debug!("Wrote {} klines to DB", batch.len());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::test_support::{TempDir, manager};
+
+    fn closed(start_time: i64) -> KlineInsert {
+        KlineInsert {
+            symbol: "BTCUSDT".to_string(),
+            start_time,
+            close_time: start_time + 59_999,
+            interval: "1m".to_string(),
+            open_price: 1.0,
+            close_price: 1.0,
+            high_price: 1.0,
+            low_price: 1.0,
+            volume: 1.0,
+            no_of_trades: 1,
+            taker_buy_vol: 0.0,
+            seq: None,
         }
     }
+
+    #[test]
+    fn test_tracker_reports_holes_between_closed_candles() {
+        let mut gaps = KlineGapTracker::default();
+
+        // The first candle asks the DB where recording stopped.
+        let first = gaps.observe(&closed(0)).unwrap();
+        assert_eq!(first.first_missing, None);
+
+        assert_eq!(gaps.observe(&closed(60_000)), None);
+        // A replayed candle is not a gap.
+        assert_eq!(gaps.observe(&closed(60_000)), None);
+
+        let gap = gaps.observe(&closed(240_000)).unwrap();
+        assert_eq!(gap.first_missing, Some(120_000));
+        assert_eq!(gap.next_start, 240_000);
+        assert_eq!(gap.floor(), 240_000 - 1000 * 60_000);
+    }
+
+    #[tokio::test]
+    async fn test_writer_flushes_closed_hour_candles_at_once() {
+        let dir = TempDir::new("kline_flush");
+        let manager = manager(&dir, storage::db::StorageConfig::default()).await;
+        let (db_tx, db_rx) = mpsc::channel(8);
+        let writer = manager.spawn_writer(KlinesService::db_writer(
+            manager.clone(),
+            db_rx,
+            PendingBuffer::new("test", 100),
+            vec!["1h".to_string()].into(),
+        ));
+        let stored = async || {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM klines")
+                .fetch_one(&manager.pool_rotator.get_pool().await.unwrap().0)
+                .await
+                .unwrap()
+        };
+
+        let second = KlineInsert {
+            interval: "1s".to_string(),
+            ..closed(0)
+        };
+        db_tx.send(second).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(stored().await, 0);
+
+        let hour = KlineInsert {
+            interval: "1h".to_string(),
+            ..closed(0)
+        };
+        db_tx.send(hour).await.unwrap();
+        // Well before the 20s timer; the batched 1s candle goes out with it.
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while stored().await < 2 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        drop(db_tx);
+        assert!(writer.drain(WRITER_DRAIN_TIMEOUT).await);
+    }
 }
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow::bail;
+use async_trait::async_trait;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+use common::actors::{Actor, ActorType, ControlMessage};
+use common::models::AggTradeInsert;
+
+/// Default number of trades kept per symbol.
+pub const DEFAULT_LIVE_CACHE_TRADES: usize = 1000;
+
+type TradeRing = Arc<Mutex<VecDeque<AggTradeInsert>>>;
+
+/// The latest trades per symbol, kept in memory for dashboards.
+///
+/// Each symbol has its own lock, so a reader only ever waits on the writer of
+/// the symbol it reads. The outer map is write-locked only when a symbol is
+/// seen for the first time.
+pub struct LiveCache {
+    capacity: usize,
+    symbols: RwLock<HashMap<String, TradeRing>>,
+}
+
+impl LiveCache {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            capacity: capacity.max(1),
+            symbols: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub fn push(&self, trade: &AggTradeInsert) {
+        let ring = self.ring(&trade.symbol);
+        let mut ring = ring.lock().expect("trade ring poisoned");
+        if ring.len() == self.capacity {
+            ring.pop_front();
+        }
+        ring.push_back(trade.clone());
+    }
+
+    /// Up to `n` most recent trades for `symbol`, newest first.
+    pub fn latest(&self, symbol: &str, n: usize) -> Vec<AggTradeInsert> {
+        let ring = {
+            let symbols = self.symbols.read().expect("symbol map poisoned");
+            match symbols.get(&symbol.to_uppercase()) {
+                Some(ring) => ring.clone(),
+                None => return Vec::new(),
+            }
+        };
+        let ring = ring.lock().expect("trade ring poisoned");
+        ring.iter().rev().take(n).cloned().collect()
+    }
+
+    fn ring(&self, symbol: &str) -> TradeRing {
+        let key = symbol.to_uppercase();
+        if let Some(ring) = self.symbols.read().expect("symbol map poisoned").get(&key) {
+            return ring.clone();
+        }
+        self.symbols
+            .write()
+            .expect("symbol map poisoned")
+            .entry(key)
+            .or_insert_with(|| Arc::new(Mutex::new(VecDeque::with_capacity(self.capacity))))
+            .clone()
+    }
+}
+
+/// Feeds aggTrades from the market channel into a shared `LiveCache`. The cache
+/// outlives the actor, so a restart keeps what was already collected.
+pub struct LiveCacheService {
+    id: Uuid,
+    cache: Arc<LiveCache>,
+    trade_rx: EventReceiver,
+}
+
+#[async_trait]
+impl Actor for LiveCacheService {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::LiveCacheActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+
+        info!("Starting Live Trade Cache");
+
+        loop {
+            match self.trade_rx.recv().await {
+                Ok(event_arc) => {
+                    if let MarketEvent::AggTrade(trade) = &*event_arc {
+                        self.cache.push(trade);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Live cache lagged: missed {} signals", n);
+                }
+                Err(_) => {
+                    heartbeat_handle.abort();
+                    supervisor_tx
+                        .send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?}: Live cache channel closed unexpectedly.", self.name()),
+                        ))
+                        .await?;
+                    bail!("Live cache channel closed unexpectedly.");
+                }
+            }
+        }
+    }
+}
+
+impl LiveCacheService {
+    pub fn new(cache: Arc<LiveCache>, trade_rx: EventReceiver) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            cache,
+            trade_rx,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(symbol: &str, price: f64) -> AggTradeInsert {
+        AggTradeInsert {
+            agg_trade_id: 1,
+            time: 0.0,
+            trade_time: 0.0,
+            symbol: symbol.to_string(),
+            price,
+            quantity: 1.0,
+            is_buyer_maker: false,
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn test_keeps_latest_per_symbol_newest_first() {
+        let cache = LiveCache::new(3);
+        for price in 1..=5 {
+            cache.push(&trade("BTCUSDT", price as f64));
+        }
+        cache.push(&trade("ETHUSDT", 9.0));
+
+        let prices: Vec<f64> = cache
+            .latest("btcusdt", 10)
+            .iter()
+            .map(|t| t.price)
+            .collect();
+        assert_eq!(prices, vec![5.0, 4.0, 3.0]);
+        assert_eq!(cache.latest("BTCUSDT", 1)[0].price, 5.0);
+        assert_eq!(cache.latest("ETHUSDT", 10).len(), 1);
+        assert!(cache.latest("SOLUSDT", 10).is_empty());
+    }
+}
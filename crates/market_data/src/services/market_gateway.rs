@@ -1,43 +1,98 @@
+use std::collections::HashMap;
 use std::error::Error;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
 
 use anyhow::bail;
 use async_trait::async_trait;
-use common::models::{ForceOrderInsert, MarkPriceInsert, OpenInterestInsert};
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
 use tokio::{
-    sync::{broadcast, mpsc},
+    sync::{Mutex, broadcast, mpsc},
+    task::JoinHandle,
     time::{self, Duration},
 };
-use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 use tracing::{debug, error, info, warn};
 
 use serde::Deserialize;
 use serde_json::Value;
 use uuid::Uuid;
 
+use crate::backpressure::BackpressurePolicy;
+use crate::connection_stats::{ConnectionStats, ReconnectWarning};
+use crate::raw_capture::RawCapture;
+use crate::remote::depth_diff_response::DepthDiffEvent;
+use crate::remote::forceorder_response::ForceOrderCombinedEvent;
 use crate::remote::{binance_poller::BinancePoller, markprice_response::MarkPriceEvent};
-use crate::remote::{forceorder_response::ForceOrderCombinedEvent, get_futures_ws_base_url};
+use crate::streams::StreamSelection;
 use crate::{
     remote::{
-        AggTradeCombinedEvent, AggTradeEvent, DepthPayload, KlineDataCombinedEvent,
-        OrderBookCombinedEvent, get_ws_base_url,
+        AggTradeCombinedEvent, AggTradeEvent, BookValidator, DepthPayload, KlineDataCombinedEvent,
+        OrderBookCombinedEvent,
     },
     traits::RemoteResponse,
 };
 
 use common::{
     actors::{Actor, ActorType, ControlMessage},
-    models::{AggTradeInsert, KlineInsert, OrderBookInsert},
+    config::{DEFAULT_FUTURES_REST_URL, DEFAULT_FUTURES_WS_URL, DEFAULT_WS_URL},
 };
 
-pub enum MarketEvent {
-    AggTrade(AggTradeInsert),
-    OrderBook(OrderBookInsert),
-    Kline((KlineInsert, bool)),
-    MarkPrice(MarkPriceInsert),
-    ForceOrder(ForceOrderInsert),
-    OpenInterest(OpenInterestInsert),
+pub use common::events::{EventKind, EventReceiver, EventSender, MarketEvent, event_channel};
+
+/// Fraction of the market channel in use above which the monitor warns.
+const QUEUE_WARN_RATIO: f64 = 0.8;
+
+type Socket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+type SocketWrite = SplitSink<Socket, Message>;
+type SocketRead = SplitStream<Socket>;
+
+/// Default age at which a socket is replaced, an hour ahead of the forced
+/// close Binance sends every combined stream after 24 hours.
+pub const DEFAULT_ROTATE_AFTER: Duration = Duration::from_secs(23 * 3600);
+
+/// How long the old socket keeps being read once its replacement is open.
+const ROTATION_OVERLAP: Duration = Duration::from_secs(2);
+
+/// Wait before trying again when the replacement socket fails to connect.
+const ROTATION_RETRY: Duration = Duration::from_secs(60);
+
+/// Periodically logs how many events are waiting in the `label` channel for
+/// the slowest subscriber, warning once it gets close to `capacity`.
+pub fn spawn_queue_monitor(
+    label: &'static str,
+    market_tx: EventSender,
+    capacity: usize,
+    period: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = time::interval(period);
+        loop {
+            ticker.tick().await;
+            let depth = market_tx.len();
+            let ratio = depth as f64 / capacity as f64;
+            if ratio >= QUEUE_WARN_RATIO {
+                warn!(
+                    "{} channel near full: {}/{} ({:.0}%), {} subscribers",
+                    label,
+                    depth,
+                    capacity,
+                    ratio * 100.0,
+                    market_tx.receiver_count()
+                );
+            } else {
+                debug!(
+                    "{} channel depth: {}/{} ({:.0}%)",
+                    label,
+                    depth,
+                    capacity,
+                    ratio * 100.0
+                );
+            }
+        }
+    })
 }
 
 #[derive(Deserialize)]
@@ -46,10 +101,324 @@ struct RawStreamEvent {
     data: Value, // Delay parsing this until we know what it is!
 }
 
+/// Reply to a SUBSCRIBE/UNSUBSCRIBE frame: `{"result":null,"id":1}` on success,
+/// `{"error":{"code":2,"msg":"..."},"id":1}` on failure.
+#[derive(Deserialize)]
+struct SubscriptionResponse {
+    id: u64,
+    error: Option<SubscriptionError>,
+}
+
+#[derive(Deserialize)]
+struct SubscriptionError {
+    code: i64,
+    msg: String,
+}
+
+/// Anything a combined-stream socket sends as text.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SocketFrame {
+    Stream(RawStreamEvent),
+    Response(SubscriptionResponse),
+}
+
+/// Runtime change to the set of symbols the gateway streams.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GatewayCommand {
+    AddSymbol(String),
+    RemoveSymbol(String),
+}
+
+impl GatewayCommand {
+    fn method(&self) -> &'static str {
+        match self {
+            Self::AddSymbol(_) => "SUBSCRIBE",
+            Self::RemoveSymbol(_) => "UNSUBSCRIBE",
+        }
+    }
+
+    fn symbol(&self) -> &str {
+        match self {
+            Self::AddSymbol(symbol) | Self::RemoveSymbol(symbol) => symbol,
+        }
+    }
+}
+
+/// Connection history of the gateway's spot and futures sockets.
+pub struct GatewayStats {
+    pub spot: ConnectionStats,
+    pub futures: ConnectionStats,
+}
+
+impl GatewayStats {
+    fn new(warning: ReconnectWarning) -> Self {
+        Self {
+            spot: ConnectionStats::new("Spot socket", warning.count, warning.window),
+            futures: ConnectionStats::new("Futures socket", warning.count, warning.window),
+        }
+    }
+}
+
+impl std::fmt::Display for GatewayStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}; {}", self.spot, self.futures)
+    }
+}
+
+/// Symbol set, command queue and connection stats shared by every incarnation
+/// of the gateway, so symbols added or removed at runtime survive both
+/// reconnects and restarts, and reconnect counts and event sequence numbers
+/// keep growing across them.
+#[derive(Clone)]
+pub struct GatewayControl {
+    symbols: Arc<RwLock<Vec<String>>>,
+    streams: Arc<StreamSelection>,
+    commands: Arc<Mutex<mpsc::Receiver<GatewayCommand>>>,
+    stats: Arc<GatewayStats>,
+    spot_url: Arc<str>,
+    futures_url: Arc<str>,
+    open_interest_url: Arc<str>,
+    rotate_after: Option<Duration>,
+    books: Arc<BookValidator>,
+    next_seq: Arc<AtomicI64>,
+}
+
+impl GatewayControl {
+    /// Returns the control together with the sender used to issue commands.
+    ///
+    /// Sockets are rotated after `DEFAULT_ROTATE_AFTER` and warn about
+    /// reconnects at the default thresholds; see `with_rotate_after` and
+    /// `with_reconnect_warning`.
+    pub fn new(symbols: &[&str], streams: StreamSelection) -> (Self, mpsc::Sender<GatewayCommand>) {
+        let (tx, rx) = mpsc::channel(32);
+        let control = Self {
+            symbols: Arc::new(RwLock::new(
+                symbols.iter().map(|s| s.to_lowercase()).collect(),
+            )),
+            streams: Arc::new(streams),
+            commands: Arc::new(Mutex::new(rx)),
+            stats: Arc::new(GatewayStats::new(ReconnectWarning::default())),
+            spot_url: DEFAULT_WS_URL.into(),
+            futures_url: DEFAULT_FUTURES_WS_URL.into(),
+            open_interest_url: DEFAULT_FUTURES_REST_URL.into(),
+            rotate_after: Some(DEFAULT_ROTATE_AFTER),
+            books: Arc::new(BookValidator::new(false)),
+            next_seq: Arc::new(AtomicI64::new(1)),
+        };
+        (control, tx)
+    }
+
+    /// Combined-stream URL prefixes of the spot and futures sockets, instead
+    /// of Binance's production endpoints.
+    pub fn with_endpoints(mut self, spot_url: &str, futures_url: &str) -> Self {
+        self.spot_url = spot_url.into();
+        self.futures_url = futures_url.into();
+        self
+    }
+
+    /// Futures REST API the open interest is polled from, instead of
+    /// Binance's production one.
+    pub fn with_open_interest_url(mut self, url: &str) -> Self {
+        self.open_interest_url = url.into();
+        self
+    }
+
+    /// Replaces each socket with a fresh one once it is `rotate_after` old, or
+    /// never with `None`.
+    pub fn with_rotate_after(mut self, rotate_after: Option<Duration>) -> Self {
+        self.rotate_after = rotate_after;
+        self
+    }
+
+    /// Warns once a socket reconnects `warning.count` times within
+    /// `warning.window`. Resets the connection stats, so call it before the
+    /// gateway runs.
+    pub fn with_reconnect_warning(mut self, warning: ReconnectWarning) -> Self {
+        self.stats = Arc::new(GatewayStats::new(warning));
+        self
+    }
+
+    /// Discards unsorted or crossed depth snapshots instead of only counting
+    /// them.
+    pub fn with_drop_invalid_books(mut self, drop_invalid: bool) -> Self {
+        self.books = Arc::new(BookValidator::new(drop_invalid));
+        self
+    }
+
+    /// Uptime and reconnect counts of both sockets.
+    pub fn stats(&self) -> Arc<GatewayStats> {
+        self.stats.clone()
+    }
+
+    /// Snapshot of the symbols currently streamed.
+    pub fn symbols(&self) -> Vec<String> {
+        self.symbols.read().expect("symbol set poisoned").clone()
+    }
+
+    /// Applies `command` to the symbol set. Returns false if it changed nothing.
+    fn apply(&self, command: &GatewayCommand) -> bool {
+        let mut symbols = self.symbols.write().expect("symbol set poisoned");
+        match command {
+            GatewayCommand::AddSymbol(symbol) => {
+                let symbol = symbol.to_lowercase();
+                if symbols.contains(&symbol) {
+                    return false;
+                }
+                symbols.push(symbol);
+                true
+            }
+            GatewayCommand::RemoveSymbol(symbol) => {
+                let before = symbols.len();
+                symbols.retain(|s| !s.eq_ignore_ascii_case(symbol));
+                symbols.len() != before
+            }
+        }
+    }
+}
+
+/// The two Binance websocket endpoints the gateway keeps open.
+#[derive(Debug, Clone, Copy)]
+enum Venue {
+    Spot,
+    Futures,
+}
+
+impl Venue {
+    /// Stream names this venue carries for one symbol.
+    fn streams(self, symbol: &str, selection: &StreamSelection) -> Vec<String> {
+        match self {
+            Venue::Spot => selection.stream_names(symbol),
+            Venue::Futures => {
+                let sl = symbol.to_lowercase();
+                vec![format!("{sl}@forceOrder"), format!("{sl}@markPrice@1s")]
+            }
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Venue::Spot => "spot",
+            Venue::Futures => "futures",
+        }
+    }
+
+    fn stats(self, stats: &GatewayStats) -> &ConnectionStats {
+        match self {
+            Venue::Spot => &stats.spot,
+            Venue::Futures => &stats.futures,
+        }
+    }
+
+    fn url(self, control: &GatewayControl) -> String {
+        let base = match self {
+            Venue::Spot => &control.spot_url,
+            Venue::Futures => &control.futures_url,
+        };
+        let streams: Vec<String> = control
+            .symbols()
+            .iter()
+            .flat_map(|s| self.streams(s, &control.streams))
+            .collect();
+        format!("{}{}", base, streams.join("/"))
+    }
+
+    /// `{"method":"SUBSCRIBE","params":[...],"id":n}` frame for `command`.
+    fn subscription_frame(
+        self,
+        command: &GatewayCommand,
+        selection: &StreamSelection,
+        id: u64,
+    ) -> String {
+        serde_json::json!({
+            "method": command.method(),
+            "params": self.streams(command.symbol(), selection),
+            "id": id,
+        })
+        .to_string()
+    }
+}
+
+/// Newest event published on each stream of one socket connection, so the
+/// events both sockets deliver while a rotation reads them are published once.
+///
+/// An event is placed by what orders its stream on the exchange: the
+/// aggregate trade id, a book's `lastUpdateId` or a diff's final update id,
+/// a kline's start time, then closed flag, then event time, a mark price's
+/// event time and a liquidation's trade time. During the overlap an event at
+/// or behind its stream's mark was already published and is dropped; two
+/// liquidations of a symbol in the same millisecond count as one then.
+/// Outside it every event is published and only moves the mark.
+#[derive(Default)]
+struct HighWaterMarks {
+    marks: HashMap<String, (i64, i64, i64)>,
+    overlapping: bool,
+    dropped: u64,
+}
+
+impl HighWaterMarks {
+    /// Both sockets are read from now on.
+    fn start_overlap(&mut self) {
+        self.overlapping = true;
+    }
+
+    /// Back to one socket. Returns how many repeated events were dropped.
+    fn end_overlap(&mut self) -> u64 {
+        self.overlapping = false;
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// Whether the event `data` of `stream` is new and should be published.
+    fn advance(&mut self, stream: &str, data: &Value) -> bool {
+        let Some(mark) = Self::mark(stream, data) else {
+            return true;
+        };
+        match self.marks.get_mut(stream) {
+            Some(last) if self.overlapping && mark <= *last => {
+                self.dropped += 1;
+                false
+            }
+            Some(last) => {
+                *last = mark;
+                true
+            }
+            None => {
+                self.marks.insert(stream.to_string(), mark);
+                true
+            }
+        }
+    }
+
+    /// Position of `data` in its stream, or `None` if the fields are missing.
+    fn mark(stream: &str, data: &Value) -> Option<(i64, i64, i64)> {
+        if stream.ends_with("@aggTrade") {
+            Some((data["a"].as_i64()?, 0, 0))
+        } else if stream.contains("@depth") {
+            let update_id = data.get("lastUpdateId").or_else(|| data.get("u"))?;
+            Some((update_id.as_i64()?, 0, 0))
+        } else if stream.contains("@kline") {
+            let kline = &data["k"];
+            Some((
+                kline["t"].as_i64()?,
+                kline["x"].as_bool()? as i64,
+                data["E"].as_i64()?,
+            ))
+        } else if stream.ends_with("@markPrice@1s") {
+            Some((data["E"].as_i64()?, 0, 0))
+        } else if stream.ends_with("@forceOrder") {
+            Some((data["o"]["T"].as_i64()?, 0, 0))
+        } else {
+            None
+        }
+    }
+}
+
 pub struct MarketGateway {
     id: Uuid,
-    symbols: Vec<String>,
-    market_tx: broadcast::Sender<Arc<MarketEvent>>,
+    control: GatewayControl,
+    market_tx: EventSender,
+    policy: BackpressurePolicy,
+    raw_capture: Option<RawCapture>,
 }
 
 #[async_trait]
@@ -65,131 +434,249 @@ impl Actor for MarketGateway {
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
         let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
 
-        let streams: Vec<String> = self
-            .symbols
-            .iter()
-            .map(|s| {
-                format!(
-                    "{sl}@aggTrade/{sl}@depth20@100ms/{sl}@kline_1h/{sl}@kline_1m/{sl}@kline_1s",
-                    sl = s.to_lowercase()
-                )
-            })
-            .collect();
-
-        let fstreams: Vec<String> = self
-            .symbols
-            .iter()
-            .map(|s| format!("{sl}@forceOrder/{sl}@markPrice@1s", sl = s.to_lowercase()))
-            .collect();
-
-        let url = format!("{}{}", get_ws_base_url(), streams.join("/"));
-        let furl = format!("{}{}", get_futures_ws_base_url(), fstreams.join("/"));
+        let (commands_tx, _) = broadcast::channel(16);
 
         tokio::select! {
-            _ = self.websocket_connection(&url, supervisor_tx.clone()) => {
+            _ = self.websocket_connection(Venue::Spot, supervisor_tx.clone(), commands_tx.subscribe()) => {
                 heartbeat_handle.abort()
             }
-            _ = self.websocket_connection(&furl, supervisor_tx.clone()) => {
+            _ = self.websocket_connection(Venue::Futures, supervisor_tx.clone(), commands_tx.subscribe()) => {
                 heartbeat_handle.abort()
             }
             _ = self.oi_connection() => {
                 heartbeat_handle.abort();
             }
+            _ = self.control_loop(&commands_tx) => {
+                heartbeat_handle.abort();
+            }
         }
         Ok(())
     }
 }
 
 impl MarketGateway {
-    pub fn new(symbols: &[&str], market_tx: broadcast::Sender<Arc<MarketEvent>>) -> Self {
+    pub fn new(control: GatewayControl, market_tx: EventSender) -> Self {
         Self {
             id: Uuid::new_v4(),
-            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            control,
             market_tx,
+            policy: BackpressurePolicy::default(),
+            raw_capture: None,
+        }
+    }
+
+    /// What to do when a subscriber is a full market channel behind: wait
+    /// (`Block`, the default) or let it lose the oldest events.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Logs received text frames to `capture`: sampled ones, plus every frame
+    /// that fails to parse.
+    pub fn with_raw_capture(mut self, capture: RawCapture) -> Self {
+        self.raw_capture = Some(capture);
+        self
+    }
+
+    /// Stamps the next sequence number on an event and publishes it to the
+    /// services.
+    ///
+    /// Under `BackpressurePolicy::Block` we hold off until the slowest subscriber
+    /// has read enough to leave a free slot. This keeps the caller from reading
+    /// the next frame and pushes the stall back onto the socket. Under `Drop`
+    /// the send goes through and lagging subscribers lose the oldest events,
+    /// which they report as `RecvError::Lagged`.
+    async fn publish(&self, mut event: MarketEvent) {
+        if self.policy == BackpressurePolicy::Block {
+            self.market_tx.reserve().await;
         }
+        // Every publisher runs on the gateway task and nothing awaits between
+        // numbering and sending, so channel order is seq order.
+        event.set_seq(self.control.next_seq.fetch_add(1, Ordering::Relaxed));
+        let _ = self.market_tx.send(Arc::new(event));
+    }
+
+    /// Applies queued symbol changes and fans them out to both sockets.
+    async fn control_loop(&self, fanout: &broadcast::Sender<GatewayCommand>) {
+        let mut commands = self.control.commands.lock().await;
+        while let Some(command) = commands.recv().await {
+            if self.control.apply(&command) {
+                info!("Applying {:?}", command);
+                let _ = fanout.send(command);
+            } else {
+                debug!("{:?} changes nothing, ignoring", command);
+            }
+        }
+        // Every sender is gone: keep streaming the current set.
+        std::future::pending::<()>().await
     }
 
     async fn oi_connection(&self) -> anyhow::Result<()> {
-        let poller = BinancePoller::new();
+        let mut poller = BinancePoller::new().with_base_url(&self.control.open_interest_url);
 
         loop {
-            let general_result = poller.fetch_all_open_interest(&self.symbols).await;
+            let general_result = poller
+                .fetch_all_open_interest(&self.control.symbols())
+                .await;
 
             if let Err(e) = general_result {
                 bail!("OI connection error: {}", e);
             } else {
                 let results = general_result.unwrap();
 
-                results.into_iter().for_each(|res| match res {
-                    Ok(data) => {
-                        let _ = self
-                            .market_tx
-                            .send(Arc::new(MarketEvent::OpenInterest(data)));
-                    }
-                    Err(e) => {
-                        warn!("Failed to fetch OI: {}", e);
+                for res in results {
+                    match res {
+                        Ok(data) => self.publish(MarketEvent::OpenInterest(data)).await,
+                        Err(e) => {
+                            warn!("Failed to fetch OI: {}", e);
+                        }
                     }
-                });
+                }
             }
         }
     }
 
     async fn websocket_connection(
         &self,
-        url: &str,
+        venue: Venue,
         supervisor_tx: mpsc::Sender<ControlMessage>,
+        mut commands: broadcast::Receiver<GatewayCommand>,
     ) -> Result<(), Box<dyn Error>> {
-        info!("Connecting to: {}", url);
+        let stats = venue.stats(&self.control.stats);
         loop {
-            match tokio_tungstenite::connect_async(url).await {
+            // Rebuilt on every attempt so a reconnect picks up runtime symbol changes.
+            let url = venue.url(&self.control);
+            info!("Connecting to: {}", url);
+
+            match tokio_tungstenite::connect_async(&url).await {
                 Ok((ws_stream, _)) => {
+                    stats.connected();
                     let (mut write, mut read) = ws_stream.split();
+                    let mut pending: HashMap<u64, GatewayCommand> = HashMap::new();
+                    let mut next_id = 1;
+                    let mut marks = HighWaterMarks::default();
+                    // Planned rotation: at `rotate_at` a second socket is opened
+                    // in the background while this one keeps being read. Once it
+                    // is up both are read for `ROTATION_OVERLAP`, then the old
+                    // one is closed. `marks` drops what both deliver.
+                    let rotate_after = self.control.rotate_after;
+                    let mut rotate_at = time::Instant::now() + rotate_after.unwrap_or_default();
+                    let mut connecting: Option<JoinHandle<Result<Socket, WsError>>> = None;
+                    let mut next: Option<(SocketWrite, SocketRead)> = None;
+                    let mut handover_at = time::Instant::now();
 
-                    while let Some(msg) = read.next().await {
-                        match msg {
-                            Ok(Message::Text(ref text)) => {
-                                match Self::parse_websocket_message(&text) {
-                                    Ok(stream) => {
-                                        let _ = self.market_tx.send(Arc::new(stream));
+                    let reason = loop {
+                        tokio::select! {
+                            msg = read.next() => {
+                                if let Some(reason) = self
+                                    .handle_frame(venue, msg, &mut write, &mut pending, &mut marks, &supervisor_tx)
+                                    .await?
+                                {
+                                    break reason;
+                                }
+                            }
+
+                            msg = Self::next_frame(&mut next) => {
+                                let (next_write, _) = next.as_mut().expect("only read while open");
+                                if let Some(reason) = self
+                                    .handle_frame(venue, msg, next_write, &mut pending, &mut marks, &supervisor_tx)
+                                    .await?
+                                {
+                                    warn!(
+                                        "{:?}: new socket lost during the rotation ({}), keeping the current one, retrying in {}s",
+                                        venue,
+                                        reason,
+                                        ROTATION_RETRY.as_secs()
+                                    );
+                                    next = None;
+                                    marks.end_overlap();
+                                    rotate_at = time::Instant::now() + ROTATION_RETRY;
+                                }
+                            }
+
+                            command = commands.recv() => {
+                                match command {
+                                    Ok(command) => {
+                                        let frame =
+                                            venue.subscription_frame(&command, &self.control.streams, next_id);
+                                        if let Err(e) = write.send(Message::Text(frame.clone().into())).await {
+                                            error!("{:?}: failed to send {:?}: {}", venue, command, e);
+                                            break format!("failed to send {:?}: {}", command, e);
+                                        }
+                                        // The replacement's URL predates this change.
+                                        if let Some((next_write, _)) = next.as_mut()
+                                            && let Err(e) = next_write.send(Message::Text(frame.into())).await
+                                        {
+                                            warn!("{:?}: failed to send {:?} to the new socket: {}", venue, command, e);
+                                        }
+                                        pending.insert(next_id, command);
+                                        next_id += 1;
                                     }
                                     Err(e) => {
-                                        supervisor_tx
-                                            .send(ControlMessage::Error(
-                                                self.id,
-                                                format!("Unknown socket response: {}", e),
-                                            ))
-                                            .await?;
-                                        continue;
+                                        // Missed a change: reconnecting rebuilds the URL from the
+                                        // current symbol set, which resyncs the subscriptions.
+                                        warn!("{:?}: lost gateway commands ({}), reconnecting", venue, e);
+                                        break "resyncing symbols".to_string();
                                     }
                                 }
                             }
-                            Ok(Message::Ping(pg)) => {
-                                let _ = write.send(Message::Pong(pg));
-                                info!("Ping - Pong message sent to websocket.");
-                                continue;
-                            }
-                            Ok(Message::Close(_)) => {
-                                debug!("Close message received");
-                                break;
+
+                            _ = time::sleep_until(rotate_at), if rotate_after.is_some() && next.is_none() && connecting.is_none() => {
+                                info!("{:?}: planned rotation, opening a new socket before closing this one", venue);
+                                let url = venue.url(&self.control);
+                                connecting = Some(tokio::spawn(async move {
+                                    tokio_tungstenite::connect_async(&url)
+                                        .await
+                                        .map(|(ws_stream, _)| ws_stream)
+                                }));
                             }
-                            Err(e) => {
-                                error!("WebSocket error: {}", e);
-                                break;
+
+                            connected = async { connecting.as_mut().expect("checked by the guard").await }, if connecting.is_some() => {
+                                connecting = None;
+                                let connected = match connected {
+                                    Ok(result) => result.map_err(|e| e.to_string()),
+                                    Err(e) => Err(e.to_string()),
+                                };
+                                match connected {
+                                    Ok(ws_stream) => {
+                                        next = Some(ws_stream.split());
+                                        marks.start_overlap();
+                                        handover_at = time::Instant::now() + ROTATION_OVERLAP;
+                                    }
+                                    Err(e) => {
+                                        warn!(
+                                            "{:?}: planned rotation could not connect ({}), keeping the current socket, retrying in {}s",
+                                            venue,
+                                            e,
+                                            ROTATION_RETRY.as_secs()
+                                        );
+                                        rotate_at = time::Instant::now() + ROTATION_RETRY;
+                                    }
+                                }
                             }
-                            _ => {
-                                supervisor_tx
-                                    .send(ControlMessage::Error(
-                                        self.id,
-                                        "Unexpected message received, continuing...".to_string(),
-                                    ))
-                                    .await?;
-                                continue;
+
+                            _ = time::sleep_until(handover_at), if next.is_some() => {
+                                let _ = write.send(Message::Close(None)).await;
+                                (write, read) = next.take().expect("checked by the guard");
+                                debug!(
+                                    "{:?}: handed over, {} events both sockets delivered published once",
+                                    venue,
+                                    marks.end_overlap()
+                                );
+                                rotate_at = time::Instant::now() + rotate_after.unwrap_or_default();
+                                stats.rotated();
                             }
                         }
+                    };
+                    if let Some(connecting) = connecting {
+                        connecting.abort();
                     }
+                    stats.disconnected(&reason);
                 }
                 Err(e) => {
                     error!("Connection failed: {}. Retrying in 2s...", e);
+                    stats.disconnected(&e.to_string());
 
                     supervisor_tx
                         .send(ControlMessage::Error(
@@ -203,42 +690,484 @@ impl MarketGateway {
         }
     }
 
-    fn parse_websocket_message(json_input: &str) -> Result<MarketEvent, anyhow::Error> {
-        let raw_event: RawStreamEvent = serde_json::from_str(json_input)?;
+    /// Handles one frame read from a socket, answering pings on `write`.
+    /// Returns why the socket is done, or `None` while it is still usable.
+    async fn handle_frame(
+        &self,
+        venue: Venue,
+        msg: Option<Result<Message, WsError>>,
+        write: &mut SocketWrite,
+        pending: &mut HashMap<u64, GatewayCommand>,
+        marks: &mut HighWaterMarks,
+        supervisor_tx: &mpsc::Sender<ControlMessage>,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let Some(msg) = msg else {
+            return Ok(Some("stream ended".to_string()));
+        };
+        match msg {
+            Ok(Message::Text(ref text)) => {
+                let mut failed = false;
+                match serde_json::from_str::<SocketFrame>(text) {
+                    Ok(SocketFrame::Stream(raw_event))
+                        if !marks.advance(&raw_event.stream, &raw_event.data) => {}
+                    Ok(SocketFrame::Stream(raw_event)) => {
+                        match Self::parse_websocket_message(raw_event) {
+                            Ok(MarketEvent::OrderBook(ref book))
+                                if !self.control.books.accept(book) => {}
+                            Ok(stream) => self.publish(stream).await,
+                            Err(e) => {
+                                failed = true;
+                                supervisor_tx
+                                    .send(ControlMessage::Error(
+                                        self.id,
+                                        format!("Unknown socket response: {}", e),
+                                    ))
+                                    .await?;
+                            }
+                        }
+                    }
+                    Ok(SocketFrame::Response(response)) => {
+                        Self::handle_response(venue, pending, response);
+                    }
+                    Err(e) => {
+                        failed = true;
+                        supervisor_tx
+                            .send(ControlMessage::Error(
+                                self.id,
+                                format!("Unknown socket response: {}", e),
+                            ))
+                            .await?;
+                    }
+                }
+                if let Some(capture) = &self.raw_capture {
+                    capture.record(venue.label(), text, failed);
+                }
+            }
+            Ok(Message::Ping(pg)) => {
+                let _ = write.send(Message::Pong(pg)).await;
+                info!("Ping - Pong message sent to websocket.");
+            }
+            Ok(Message::Close(_)) => {
+                debug!("Close message received");
+                return Ok(Some("closed by server".to_string()));
+            }
+            Err(e) => {
+                error!("WebSocket error: {}", e);
+                return Ok(Some(format!("websocket error: {}", e)));
+            }
+            _ => {
+                supervisor_tx
+                    .send(ControlMessage::Error(
+                        self.id,
+                        "Unexpected message received, continuing...".to_string(),
+                    ))
+                    .await?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// The next frame of the replacement socket; never resolves without one.
+    async fn next_frame(
+        next: &mut Option<(SocketWrite, SocketRead)>,
+    ) -> Option<Result<Message, WsError>> {
+        match next {
+            Some((_, read)) => read.next().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn handle_response(
+        venue: Venue,
+        pending: &mut HashMap<u64, GatewayCommand>,
+        response: SubscriptionResponse,
+    ) {
+        let command = pending.remove(&response.id);
+        match (response.error, command) {
+            (None, Some(command)) => info!("{:?}: {:?} acknowledged", venue, command),
+            (None, None) => debug!("{:?}: ack for unknown request id {}", venue, response.id),
+            (Some(err), command) => error!(
+                "{:?}: request {} ({:?}) rejected: {} (code {})",
+                venue, response.id, command, err.msg, err.code
+            ),
+        }
+    }
 
+    fn parse_websocket_message(raw_event: RawStreamEvent) -> Result<MarketEvent, anyhow::Error> {
+        // Depth streams come as `@depth20`/`@depth` at 1000ms or with an
+        // `@100ms` suffix; both carry the same payload.
+        let depth_stream = raw_event
+            .stream
+            .strip_suffix("@100ms")
+            .or_else(|| raw_event.stream.strip_suffix("@1000ms"))
+            .unwrap_or(&raw_event.stream);
         if raw_event.stream.ends_with("@aggTrade") {
             let specific_data = serde_json::from_value::<AggTradeEvent>(raw_event.data)?;
 
-            return Ok(MarketEvent::AggTrade(
+            Ok(MarketEvent::AggTrade(
                 AggTradeCombinedEvent {
                     data: specific_data,
                 }
                 .to_insertable()?,
-            ));
-        } else if raw_event.stream.ends_with("@depth20@100ms") {
+            ))
+        } else if depth_stream.ends_with("@depth20") {
             let specific_data = serde_json::from_value::<DepthPayload>(raw_event.data)?;
 
-            return Ok(MarketEvent::OrderBook(
+            Ok(MarketEvent::OrderBook(
                 OrderBookCombinedEvent {
                     stream: raw_event.stream,
                     data: specific_data,
                 }
                 .to_insertable()?,
-            ));
+            ))
+        } else if depth_stream.ends_with("@depth") {
+            let specific_data = serde_json::from_value::<DepthDiffEvent>(raw_event.data)?;
+
+            Ok(MarketEvent::DepthDiff(specific_data.to_insertable()?))
         } else if raw_event.stream.contains("@kline") {
             let specific_data = serde_json::from_value::<KlineDataCombinedEvent>(raw_event.data)?;
 
-            return Ok(MarketEvent::Kline(specific_data.to_insertable()?));
+            Ok(MarketEvent::Kline(specific_data.to_insertable()?))
         } else if raw_event.stream.ends_with("@markPrice@1s") {
             let specific_data = serde_json::from_value::<MarkPriceEvent>(raw_event.data)?;
 
-            return Ok(MarketEvent::MarkPrice(specific_data.to_insertable()?));
+            Ok(MarketEvent::MarkPrice(specific_data.to_insertable()?))
         } else if raw_event.stream.ends_with("@forceOrder") {
             let specific_data = serde_json::from_value::<ForceOrderCombinedEvent>(raw_event.data)?;
 
-            return Ok(MarketEvent::ForceOrder(specific_data.to_insertable()?));
+            Ok(MarketEvent::ForceOrder(specific_data.to_insertable()?))
         } else {
             bail!("Unknown received data.");
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::models::OpenInterestInsert;
+    use tokio::net::TcpListener;
+    use tokio::sync::Notify;
+
+    #[test]
+    fn test_control_apply_is_idempotent_and_case_insensitive() {
+        let (control, _tx) = GatewayControl::new(&["BTCUSDT"], StreamSelection::default());
+
+        assert!(!control.apply(&GatewayCommand::AddSymbol("btcusdt".to_string())));
+        assert!(control.apply(&GatewayCommand::AddSymbol("ETHUSDT".to_string())));
+        assert_eq!(control.symbols(), vec!["btcusdt", "ethusdt"]);
+
+        assert!(control.apply(&GatewayCommand::RemoveSymbol("BTCUSDT".to_string())));
+        assert!(!control.apply(&GatewayCommand::RemoveSymbol("btcusdt".to_string())));
+        assert_eq!(control.symbols(), vec!["ethusdt"]);
+    }
+
+    #[tokio::test]
+    async fn test_publish_numbers_events_across_gateways() {
+        let (control, _tx) = GatewayControl::new(&["BTCUSDT"], StreamSelection::default());
+        let (market_tx, mut market_rx) = event_channel(8);
+        let interest = || {
+            MarketEvent::OpenInterest(OpenInterestInsert {
+                time: 1.0,
+                symbol: "BTCUSDT".to_string(),
+                oi_value: 2.0,
+                seq: None,
+            })
+        };
+
+        MarketGateway::new(control.clone(), market_tx.clone())
+            .publish(interest())
+            .await;
+        // A restarted gateway shares the control, so numbering carries on.
+        MarketGateway::new(control, market_tx)
+            .publish(interest())
+            .await;
+        assert_eq!(market_rx.recv().await.unwrap().seq(), Some(1));
+        assert_eq!(market_rx.recv().await.unwrap().seq(), Some(2));
+    }
+
+    #[test]
+    fn test_subscription_frame_and_ack() {
+        let frame = Venue::Futures.subscription_frame(
+            &GatewayCommand::RemoveSymbol("SOLUSDT".to_string()),
+            &StreamSelection::default(),
+            7,
+        );
+        let frame: Value = serde_json::from_str(&frame).unwrap();
+        assert_eq!(
+            frame,
+            serde_json::json!({
+                "method": "UNSUBSCRIBE",
+                "params": ["solusdt@forceOrder", "solusdt@markPrice@1s"],
+                "id": 7,
+            })
+        );
+
+        let ack = serde_json::from_str::<SocketFrame>(r#"{"result":null,"id":7}"#).unwrap();
+        assert!(matches!(
+            ack,
+            SocketFrame::Response(SubscriptionResponse { id: 7, error: None })
+        ));
+
+        let rejected = serde_json::from_str::<SocketFrame>(
+            r#"{"error":{"code":2,"msg":"Invalid request"},"id":8}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            rejected,
+            SocketFrame::Response(SubscriptionResponse { error: Some(_), .. })
+        ));
+    }
+
+    #[test]
+    fn test_parses_depth_snapshots_at_either_speed() {
+        for stream in ["btcusdt@depth20", "btcusdt@depth20@100ms"] {
+            let raw: RawStreamEvent = serde_json::from_value(serde_json::json!({
+                "stream": stream,
+                "data": {"lastUpdateId": 1, "bids": [["1.0", "2.0"]], "asks": []},
+            }))
+            .unwrap();
+            match MarketGateway::parse_websocket_message(raw).unwrap() {
+                MarketEvent::OrderBook(book) => assert_eq!(book.symbol, "BTCUSDT"),
+                _ => panic!("{} did not parse as an order book", stream),
+            }
+        }
+    }
+
+    /// Each line of a `tests/fixtures/*.jsonl` file, recorded off the
+    /// combined stream, parsed the way the socket loop does it.
+    fn parse_fixture(jsonl: &str) -> Vec<MarketEvent> {
+        jsonl
+            .lines()
+            .map(|line| {
+                let raw: RawStreamEvent = serde_json::from_str(line).unwrap();
+                MarketGateway::parse_websocket_message(raw).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parses_recorded_frames() {
+        let trades = parse_fixture(include_str!("../../tests/fixtures/aggtrade.jsonl"));
+        let [MarketEvent::AggTrade(btc), MarketEvent::AggTrade(doge)] = trades.as_slice() else {
+            panic!("aggtrade.jsonl did not parse as two trades");
+        };
+        assert_eq!(btc.symbol, "BTCUSDT");
+        assert_eq!(btc.agg_trade_id, 3456789012);
+        assert_eq!(btc.price, 87654.32);
+        assert_eq!(btc.quantity, 0.00125);
+        assert_eq!(btc.trade_time, 1767225600.12);
+        assert!(btc.is_buyer_maker);
+        assert_eq!((doge.symbol.as_str(), doge.price), ("DOGEUSDT", 0.14321));
+        assert!(!doge.is_buyer_maker);
+
+        let books = parse_fixture(include_str!("../../tests/fixtures/depth20.jsonl"));
+        let [MarketEvent::OrderBook(eth), MarketEvent::OrderBook(sol)] = books.as_slice() else {
+            panic!("depth20.jsonl did not parse as two order books");
+        };
+        // The symbol comes from the lowercase stream name.
+        assert_eq!(eth.symbol, "ETHUSDT");
+        assert_eq!(
+            common::models::orderbook::unpack_levels(&eth.bids).collect::<Vec<_>>(),
+            vec![(2950.12, 3.456), (2950.11, 0.017), (2950.0, 12.5)]
+        );
+        assert_eq!(eth.asks.len(), 2 * 8);
+        assert_eq!(sol.symbol, "SOLUSDT");
+        assert!(sol.asks.is_empty());
+
+        let klines = parse_fixture(include_str!("../../tests/fixtures/kline.jsonl"));
+        let [
+            MarketEvent::Kline((sol, sol_closed)),
+            MarketEvent::Kline((btc, btc_closed)),
+        ] = klines.as_slice()
+        else {
+            panic!("kline.jsonl did not parse as two klines");
+        };
+        assert_eq!(
+            (sol.symbol.as_str(), sol.interval.as_str()),
+            ("SOLUSDT", "1m")
+        );
+        assert_eq!(sol.start_time, 1767225600000);
+        assert_eq!(sol.close_time, 1767225659999);
+        assert_eq!(
+            (
+                sol.open_price,
+                sol.close_price,
+                sol.high_price,
+                sol.low_price
+            ),
+            (125.4, 125.52, 125.6, 125.38)
+        );
+        assert_eq!(
+            (sol.volume, sol.no_of_trades, sol.taker_buy_vol),
+            (812.45, 80, 430.1)
+        );
+        assert!(!sol_closed);
+        assert_eq!(
+            (btc.symbol.as_str(), btc.interval.as_str()),
+            ("BTCUSDT", "1h")
+        );
+        assert_eq!(btc.no_of_trades, 43211);
+        assert!(btc_closed);
+    }
+
+    /// A spot trade frame with aggregate id `id`.
+    fn trade_frame(id: i64) -> Message {
+        let frame = serde_json::json!({
+            "stream": "btcusdt@aggTrade",
+            "data": {
+                "e": "aggTrade", "E": 1767225600123_i64, "s": "BTCUSDT", "a": id,
+                "p": "87654.32", "q": "0.00125", "f": 1, "l": 1,
+                "T": 1767225600120_i64, "m": true, "M": true,
+            },
+        });
+        Message::Text(frame.to_string().into())
+    }
+
+    async fn next_trade(rx: &mut EventReceiver) -> i64 {
+        let event = time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("no event within 5s")
+            .unwrap();
+        match &*event {
+            MarketEvent::AggTrade(trade) => trade.agg_trade_id,
+            _ => panic!("expected a trade"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotation_reads_both_sockets_until_the_handover() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/stream?streams=", listener.local_addr().unwrap());
+        let handshake = Arc::new(Notify::new());
+        let (old_closed_tx, mut old_closed) = mpsc::channel(1);
+
+        let server_handshake = handshake.clone();
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut old = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            old.send(trade_frame(1)).await.unwrap();
+            // The replacement's handshake is held until the gateway has read
+            // the old socket's next trade, which it can't while it waits on
+            // the connect.
+            let (tcp, _) = listener.accept().await.unwrap();
+            old.send(trade_frame(2)).await.unwrap();
+            server_handshake.notified().await;
+            let mut new = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            new.send(trade_frame(3)).await.unwrap();
+            while let Some(Ok(msg)) = old.next().await {
+                if msg.is_close() {
+                    break;
+                }
+            }
+            old_closed_tx.send(()).await.unwrap();
+            while new.next().await.is_some() {}
+        });
+
+        let (control, _tx) = GatewayControl::new(&["BTCUSDT"], StreamSelection::default());
+        let control = control
+            .with_endpoints(&url, &url)
+            .with_rotate_after(Some(Duration::from_millis(100)));
+        let (market_tx, mut market_rx) = event_channel(16);
+        let gateway = MarketGateway::new(control.clone(), market_tx);
+        let (supervisor_tx, _supervisor_rx) = mpsc::channel(16);
+        let (_commands_tx, commands) = broadcast::channel(1);
+
+        let check = async {
+            assert_eq!(next_trade(&mut market_rx).await, 1);
+            assert_eq!(next_trade(&mut market_rx).await, 2);
+            handshake.notify_one();
+            // Read off the new socket while the old one is still open.
+            assert_eq!(next_trade(&mut market_rx).await, 3);
+            assert!(old_closed.try_recv().is_err());
+
+            time::timeout(Duration::from_secs(5), old_closed.recv())
+                .await
+                .expect("old socket not closed after the overlap");
+            assert_eq!(control.stats().spot.rotations(), 1);
+            assert_eq!(control.stats().spot.reconnects(), 0);
+        };
+        tokio::select! {
+            _ = gateway.websocket_connection(Venue::Spot, supervisor_tx, commands) => {
+                panic!("gateway stopped")
+            }
+            _ = check => {}
+        }
+        server.abort();
+    }
+
+    #[test]
+    fn test_marks_keep_kline_updates_and_drop_repeats_while_overlapping() {
+        let kline = |start: i64, closed: bool, event_time: i64| serde_json::json!({"E": event_time, "k": {"t": start, "x": closed}});
+        let stream = "btcusdt@kline_1m";
+        let mut marks = HighWaterMarks::default();
+        assert!(marks.advance(stream, &kline(0, false, 1)));
+        assert!(marks.advance(stream, &kline(0, false, 2)));
+
+        marks.start_overlap();
+        assert!(!marks.advance(stream, &kline(0, false, 2)));
+        assert!(marks.advance(stream, &kline(0, true, 3)));
+        assert!(!marks.advance(stream, &kline(0, true, 3)));
+        assert!(marks.advance("btcusdt@kline_5m", &kline(0, true, 3)));
+        assert!(marks.advance(stream, &kline(60_000, false, 4)));
+        assert_eq!(marks.end_overlap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rotation_publishes_what_both_sockets_deliver_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/stream?streams=", listener.local_addr().unwrap());
+        let (old_closed_tx, mut old_closed) = mpsc::channel(1);
+
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut old = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            old.send(trade_frame(1)).await.unwrap();
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut new = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            // Both sockets carry trades 2 to 4, the new one a step behind.
+            old.send(trade_frame(2)).await.unwrap();
+            old.send(trade_frame(3)).await.unwrap();
+            new.send(trade_frame(2)).await.unwrap();
+            new.send(trade_frame(3)).await.unwrap();
+            new.send(trade_frame(4)).await.unwrap();
+            old.send(trade_frame(4)).await.unwrap();
+            while let Some(Ok(msg)) = old.next().await {
+                if msg.is_close() {
+                    break;
+                }
+            }
+            old_closed_tx.send(()).await.unwrap();
+            while new.next().await.is_some() {}
+        });
+
+        let (control, _tx) = GatewayControl::new(&["BTCUSDT"], StreamSelection::default());
+        let control = control
+            .with_endpoints(&url, &url)
+            .with_rotate_after(Some(Duration::from_millis(100)));
+        let (market_tx, mut market_rx) = event_channel(16);
+        let gateway = MarketGateway::new(control.clone(), market_tx);
+        let (supervisor_tx, _supervisor_rx) = mpsc::channel(16);
+        let (_commands_tx, commands) = broadcast::channel(1);
+
+        let check = async {
+            for id in 1..=4 {
+                assert_eq!(next_trade(&mut market_rx).await, id);
+            }
+            time::timeout(Duration::from_secs(5), old_closed.recv())
+                .await
+                .expect("old socket not closed after the overlap");
+            assert!(
+                market_rx.try_recv().is_err(),
+                "a repeated trade was published"
+            );
+        };
+        tokio::select! {
+            _ = gateway.websocket_connection(Venue::Spot, supervisor_tx, commands) => {
+                panic!("gateway stopped")
+            }
+            _ = check => {}
+        }
+        server.abort();
+    }
+}
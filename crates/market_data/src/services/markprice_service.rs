@@ -1,24 +1,36 @@
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use anyhow::bail;
 use async_trait::async_trait;
 use common::{
-    actors::{Actor, ActorType, ControlMessage},
+    actors::{Actor, ActorType, ControlMessage, Throughput},
     models::MarkPriceInsert,
 };
-use storage::{data_manager::DataManager, repositories::markprice_repo::MarkPriceRepository};
+use storage::{
+    StorageError,
+    data_manager::{DataManager, WRITER_DRAIN_TIMEOUT},
+    repositories::markprice_repo::MarkPriceRepository,
+};
 use tokio::sync::{broadcast, mpsc};
-use tokio::time;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::services::market_gateway::MarketEvent;
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, LagTracker, PendingBuffer, WriterLimits,
+    run_db_writer,
+};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+
+const MARK_PRICE_BATCH: BatchWindow = BatchWindow::new(300, Duration::from_secs(10));
 
 pub struct MarkPriceService {
     id: Uuid,
     rotating_pool: Arc<DataManager>,
-    mark_rx: broadcast::Receiver<Arc<MarketEvent>>,
+    mark_rx: EventReceiver,
+    policy: BackpressurePolicy,
+    dropped: DropCounter,
+    limits: WriterLimits,
 }
 
 #[async_trait]
@@ -32,34 +44,59 @@ impl Actor for MarkPriceService {
     }
 
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
-        let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
 
         info!("Starting MarkPrice Ingestion Service");
 
         let (db_tx, db_rx) = mpsc::channel(1200);
 
-        tokio::spawn(Self::db_writer(self.rotating_pool.clone(), db_rx));
+        let pending = PendingBuffer::with_limits("MarkPrice DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
+
+        let writer = self.rotating_pool.spawn_writer(run_db_writer(
+            self.rotating_pool.clone(),
+            db_rx,
+            MARK_PRICE_BATCH,
+            pending,
+            |pool, batch| Box::pin(Self::flush_batch(pool, batch)),
+        ));
 
+        let mut lag = LagTracker::new("MarkPrice service").with_throughput(throughput);
         loop {
             match self.mark_rx.recv().await {
                 Ok(event_mark) => {
                     let event = &*event_mark;
-
-                    if let MarketEvent::MarkPrice(mark) = event {
-                        if let Err(e) = db_tx.send(mark.to_owned()).await {
-                            heartbeat_handle.abort();
-                            supervisor_tx.try_send(ControlMessage::Error(
-                                self.id,
-                                format!("{:?} Failed to send to DB writer: {}", self.name(), e),
-                            ))?;
-                            bail!("Failed to send to DB writer: {}", e);
-                        }
+                    lag.observe(event.seq());
+
+                    if let MarketEvent::MarkPrice(mark) = event
+                        && self.rotating_pool.is_recording(&mark.symbol)
+                        && let Err(e) = self
+                            .policy
+                            .forward(&db_tx, mark.to_owned(), &self.dropped)
+                            .await
+                    {
+                        heartbeat_handle.abort();
+                        supervisor_tx.try_send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?} Failed to send to DB writer: {}", self.name(), e),
+                        ))?;
+                        bail!("Failed to send to DB writer: {}", e);
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("MarkPrice service lagged: missed {} signals", n);
+                    lag.lagged(n);
                 }
                 Err(_) => {
+                    drop(db_tx);
+                    if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                        warn!(
+                            "MarkPrice DB writer still flushing after {:?}",
+                            WRITER_DRAIN_TIMEOUT
+                        );
+                    }
                     heartbeat_handle.abort();
                     supervisor_tx.try_send(ControlMessage::Error(
                         self.id,
@@ -73,60 +110,39 @@ impl Actor for MarkPriceService {
 }
 
 impl MarkPriceService {
-    pub fn new(
-        rotating_pool: Arc<DataManager>,
-        mark_rx: broadcast::Receiver<Arc<MarketEvent>>,
-    ) -> Self {
+    pub fn new(rotating_pool: Arc<DataManager>, mark_rx: EventReceiver) -> Self {
         Self {
             id: Uuid::new_v4(),
             rotating_pool,
             mark_rx,
+            policy: BackpressurePolicy::default(),
+            dropped: DropCounter::new("MarkPrice DB writer"),
+            limits: WriterLimits::default(),
         }
     }
 
-    async fn db_writer(r_pool: Arc<DataManager>, mut mark_rx: mpsc::Receiver<MarkPriceInsert>) {
-        let mut buffer = Vec::with_capacity(256);
-        let mut last_flush = Instant::now();
-
-        loop {
-            tokio::select! {
-                result = mark_rx.recv() => {
-                    match result {
-                        Some(mark) => {
-                            buffer.push(mark);
-                            if buffer.len() >= 300 || last_flush.elapsed() >= Duration::from_secs(10) {
-                                Self::flush_batch(&*r_pool, &buffer).await;
-                                buffer.clear();
-                                last_flush = Instant::now();
-                            }
-                        }
-                        None => {
-                            info!("DB Channel closed. Flushing remaining buffer.");
-                            if !buffer.is_empty() {
-                                Self::flush_batch(&*r_pool, &buffer).await;
-                            }
-                            break;
-                        }
-                    }
-                }
-
-                _ = time::sleep(Duration::from_secs(20)) => {
-                    if !buffer.is_empty() {
-                        Self::flush_batch(&*r_pool, &buffer).await;
-                        buffer.clear();
-                        last_flush = Instant::now();
-                    }
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-                }
-            }
-        }
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
     }
 
-    async fn flush_batch(r_pool: &DataManager, batch: &[MarkPriceInsert]) {
-        if let Err(e) = MarkPriceRepository::insert_batch(r_pool, batch).await {
-            error!("DB write failed: {}", e);
-        } else {
-            debug!("Wrote {} MarkPrices to DB", batch.len());
+    async fn flush_batch(
+        r_pool: &DataManager,
+        batch: &[MarkPriceInsert],
+    ) -> Result<(), StorageError> {
+        let result = MarkPriceRepository::insert_batch(r_pool, batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} MarkPrices to DB", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
         }
+        result
     }
 }
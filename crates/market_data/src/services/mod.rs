@@ -1,7 +1,15 @@
 pub mod aggtrade_service;
+pub mod balance_snapshot;
+pub mod candle_service;
+pub mod event_router;
 pub mod forceorder_service;
+pub mod funding_service;
 pub mod klines_service;
+pub mod live_cache;
 pub mod market_gateway;
 pub mod markprice_service;
 pub mod openinterest_service;
+pub mod orderbook_feature_service;
 pub mod orderbook_service;
+pub mod ticker_service;
+pub mod trade_flow_service;
@@ -3,23 +3,33 @@ use std::{sync::Arc, time::Duration};
 use anyhow::bail;
 use async_trait::async_trait;
 use common::{
-    actors::{Actor, ActorType, ControlMessage},
+    actors::{Actor, ActorType, ControlMessage, Throughput},
     models::OpenInterestInsert,
 };
-use storage::{data_manager::DataManager, repositories::openinterest_repo::OpenInterestRepository};
-use tokio::{
-    sync::{broadcast, mpsc},
-    time::{self, Instant},
+use storage::{
+    StorageError,
+    data_manager::{DataManager, WRITER_DRAIN_TIMEOUT},
+    repositories::openinterest_repo::OpenInterestRepository,
 };
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::services::market_gateway::MarketEvent;
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, LagTracker, PendingBuffer, WriterLimits,
+    run_db_writer,
+};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+
+const OPEN_INTEREST_BATCH: BatchWindow = BatchWindow::new(512, Duration::from_secs(20));
 
 pub struct OpenInterestService {
     id: Uuid,
     rotating_pool: Arc<DataManager>,
-    interest_rx: broadcast::Receiver<Arc<MarketEvent>>,
+    interest_rx: EventReceiver,
+    policy: BackpressurePolicy,
+    dropped: DropCounter,
+    limits: WriterLimits,
 }
 
 #[async_trait]
@@ -33,32 +43,56 @@ impl Actor for OpenInterestService {
     }
 
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
-        let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
 
         info!("Starting ForceOrder Ingestion Service");
         let (db_tx, db_rx) = mpsc::channel(512);
-        tokio::spawn(Self::db_writer(self.rotating_pool.clone(), db_rx));
+        let pending = PendingBuffer::with_limits("OpenInterest DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
+        let writer = self.rotating_pool.spawn_writer(run_db_writer(
+            self.rotating_pool.clone(),
+            db_rx,
+            OPEN_INTEREST_BATCH,
+            pending,
+            |pool, batch| Box::pin(Self::flush_batch(pool, batch)),
+        ));
 
+        let mut lag = LagTracker::new("OpenInterest service").with_throughput(throughput);
         loop {
             match self.interest_rx.recv().await {
                 Ok(interest_arc) => {
                     let event = &*interest_arc;
+                    lag.observe(event.seq());
 
-                    if let MarketEvent::OpenInterest(interest) = event {
-                        if let Err(e) = db_tx.send(interest.to_owned()).await {
-                            heartbeat_handle.abort();
-                            supervisor_tx.try_send(ControlMessage::Error(
-                                self.id,
-                                format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
-                            ))?;
-                            bail!("Failed to send to DB writer: {}", e);
-                        }
+                    if let MarketEvent::OpenInterest(interest) = event
+                        && self.rotating_pool.is_recording(&interest.symbol)
+                        && let Err(e) = self
+                            .policy
+                            .forward(&db_tx, interest.to_owned(), &self.dropped)
+                            .await
+                    {
+                        heartbeat_handle.abort();
+                        supervisor_tx.try_send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
+                        ))?;
+                        bail!("Failed to send to DB writer: {}", e);
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("OpenInterest service lagged: missed {} signals", n);
+                    lag.lagged(n);
                 }
                 Err(_) => {
+                    drop(db_tx);
+                    if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                        warn!(
+                            "OpenInterest DB writer still flushing after {:?}",
+                            WRITER_DRAIN_TIMEOUT
+                        );
+                    }
                     heartbeat_handle.abort();
                     supervisor_tx
                         .send(ControlMessage::Error(
@@ -77,62 +111,39 @@ impl Actor for OpenInterestService {
 }
 
 impl OpenInterestService {
-    pub fn new(
-        rotating_pool: Arc<DataManager>,
-        interest_rx: broadcast::Receiver<Arc<MarketEvent>>,
-    ) -> Self {
+    pub fn new(rotating_pool: Arc<DataManager>, interest_rx: EventReceiver) -> Self {
         Self {
             id: Uuid::new_v4(),
             rotating_pool,
             interest_rx,
+            policy: BackpressurePolicy::default(),
+            dropped: DropCounter::new("OpenInterest DB writer"),
+            limits: WriterLimits::default(),
         }
     }
 
-    async fn db_writer(
-        r_pool: Arc<DataManager>,
-        mut interest_rx: mpsc::Receiver<OpenInterestInsert>,
-    ) {
-        let mut buffer = Vec::with_capacity(1024);
-        let mut last_flush = Instant::now();
-
-        loop {
-            tokio::select! {
-                result = interest_rx.recv() => {
-                    match result {
-                        Some(interest) => {
-                            buffer.push(interest);
-                            if buffer.len() >= 512 || last_flush.elapsed() >= Duration::from_secs(20) {
-                                Self::flush_batch(&*r_pool, &buffer).await;
-                                buffer.clear();
-                                last_flush = Instant::now();
-                            }
-                        }
-                        None => {
-                            info!("DB Channel closed. Flusing remaining buffer.");
-                            if !buffer.is_empty() {
-                                Self::flush_batch(&*r_pool, &buffer).await;
-                            }
-                            break;
-                        }
-                    }
-                }
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-                _ = time::sleep(Duration::from_secs(10)) => {
-                    if !buffer.is_empty() {
-                        Self::flush_batch(&*r_pool, &buffer).await;
-                        buffer.clear();
-                        last_flush = Instant::now();
-                    }
-                }
-            }
-        }
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
     }
 
-    async fn flush_batch(r_pool: &DataManager, batch: &[OpenInterestInsert]) {
-        if let Err(e) = OpenInterestRepository::insert_batch(r_pool, batch).await {
-            error!("DB write failed: {}", e);
-        } else {
-            debug!("Wrote {} OpenInterest to DB", batch.len());
+    async fn flush_batch(
+        r_pool: &DataManager,
+        batch: &[OpenInterestInsert],
+    ) -> Result<(), StorageError> {
+        let result = OpenInterestRepository::insert_batch(r_pool, batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} OpenInterest to DB", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
         }
+        result
     }
 }
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use storage::StorageError;
+use storage::data_manager::{DataManager, WRITER_DRAIN_TIMEOUT};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, PendingBuffer, WriterLimits, run_db_writer,
+};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+use common::actors::{Actor, ActorType, ControlMessage, Throughput};
+use common::models::{OrderBookFeatureInsert, OrderBookInsert};
+use storage::repositories::OrderBookFeatureRepository;
+
+/// Default spacing, in milliseconds, between stored samples of one symbol.
+pub const DEFAULT_OBI_SAMPLE_MS: u64 = 1_000;
+
+/// Keeps at most one imbalance sample per symbol every `spacing_ms`.
+const FEATURE_BATCH: BatchWindow = BatchWindow::new(100, Duration::from_secs(10));
+
+pub struct FeatureSampler {
+    spacing_ms: i64,
+    last: HashMap<String, i64>,
+}
+
+impl FeatureSampler {
+    pub fn new(spacing_ms: u64) -> Self {
+        Self {
+            spacing_ms: spacing_ms as i64,
+            last: HashMap::new(),
+        }
+    }
+
+    /// The book's features if `spacing_ms` has passed since the symbol's last
+    /// sample. Empty books are skipped without resetting the spacing.
+    pub fn sample(&mut self, book: &OrderBookInsert) -> Option<OrderBookFeatureInsert> {
+        let millis = (book.time * 1000.0) as i64;
+        if let Some(&last) = self.last.get(&book.symbol)
+            && millis - last < self.spacing_ms
+        {
+            return None;
+        }
+        let features = OrderBookFeatureInsert::from_book(book)?;
+        self.last.insert(book.symbol.clone(), millis);
+        Some(features)
+    }
+}
+
+/// Records the order book imbalance the strategy trades on, so models can be
+/// trained offline on the same feature.
+pub struct OrderBookFeatureService {
+    id: Uuid,
+    rotating_pool: Arc<DataManager>,
+    order_rx: EventReceiver,
+    sampler: FeatureSampler,
+    policy: BackpressurePolicy,
+    dropped: DropCounter,
+    limits: WriterLimits,
+}
+
+#[async_trait]
+impl Actor for OrderBookFeatureService {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::OrderBookFeatureActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
+
+        info!("Starting OrderBook Feature Service");
+
+        let (db_tx, db_rx) = mpsc::channel(256);
+
+        let pending = PendingBuffer::with_limits("OrderBook feature DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
+
+        let writer = self.rotating_pool.spawn_writer(run_db_writer(
+            self.rotating_pool.clone(),
+            db_rx,
+            FEATURE_BATCH,
+            pending,
+            |pool, batch| Box::pin(Self::flush_batch(pool, batch)),
+        ));
+
+        loop {
+            match self.order_rx.recv().await {
+                Ok(event_arc) => {
+                    let event = &*event_arc;
+
+                    if let MarketEvent::OrderBook(book) = event
+                        && self.rotating_pool.is_recording(&book.symbol)
+                        && let Some(features) = self.sampler.sample(book)
+                        && let Err(e) = self.policy.forward(&db_tx, features, &self.dropped).await
+                    {
+                        heartbeat_handle.abort();
+                        supervisor_tx.try_send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
+                        ))?;
+                        bail!("Failed to send to DB writer: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    throughput.lagged(n);
+                    warn!("OrderBook feature service lagged: missed {} signals", n);
+                }
+                Err(_) => {
+                    drop(db_tx);
+                    if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                        warn!(
+                            "OrderBook feature DB writer still flushing after {:?}",
+                            WRITER_DRAIN_TIMEOUT
+                        );
+                    }
+                    heartbeat_handle.abort();
+                    supervisor_tx
+                        .send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?}: OrderBook channel closed unexpectedly.", self.name()),
+                        ))
+                        .await?;
+                    bail!("OrderBook channel closed unexpectedly.");
+                }
+            }
+        }
+    }
+}
+
+impl OrderBookFeatureService {
+    pub fn new(rotating_pool: Arc<DataManager>, order_rx: EventReceiver, spacing_ms: u64) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            rotating_pool,
+            order_rx,
+            sampler: FeatureSampler::new(spacing_ms),
+            policy: BackpressurePolicy::default(),
+            dropped: DropCounter::new("OrderBook feature DB writer"),
+            limits: WriterLimits::default(),
+        }
+    }
+
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    async fn flush_batch(
+        r_pool: &DataManager,
+        batch: &[OrderBookFeatureInsert],
+    ) -> Result<(), StorageError> {
+        let result = OrderBookFeatureRepository::insert_batch(r_pool, batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} order book features to DB", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(time: f64, bid_qty: f32, ask_qty: f32) -> OrderBookInsert {
+        let level = |qty: f32| [1.0f32.to_le_bytes(), qty.to_le_bytes()].concat();
+        OrderBookInsert {
+            time,
+            symbol: "BTCUSDT".to_string(),
+            bids: level(bid_qty),
+            asks: level(ask_qty),
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn test_top_levels_have_their_own_imbalance() {
+        let level = |price: f32, qty: f32| [price.to_le_bytes(), qty.to_le_bytes()].concat();
+        // One large bid at the touch, the asks' weight deeper in the book.
+        let book = OrderBookInsert {
+            bids: [level(10.0, 3.0), level(9.0, 1.0)].concat(),
+            asks: [level(11.0, 1.0), level(12.0, 11.0)].concat(),
+            ..book(100.0, 0.0, 0.0)
+        };
+        let features = FeatureSampler::new(1_000).sample(&book).unwrap();
+        assert_eq!(features.obi, -0.5);
+        assert_eq!(features.obi_1, Some(0.5));
+        assert_eq!(features.obi_5, Some(-0.5));
+    }
+
+    #[test]
+    fn test_samples_imbalance_once_per_spacing() {
+        let mut sampler = FeatureSampler::new(1_000);
+
+        let first = sampler.sample(&book(100.0, 3.0, 1.0)).unwrap();
+        assert_eq!((first.bid_vol, first.ask_vol, first.obi), (3.0, 1.0, 0.5));
+
+        assert!(sampler.sample(&book(100.5, 1.0, 1.0)).is_none());
+        // An empty book doesn't count as a sample.
+        assert!(sampler.sample(&book(101.0, 0.0, 0.0)).is_none());
+        assert_eq!(sampler.sample(&book(101.1, 1.0, 3.0)).unwrap().obi, -0.5);
+    }
+}
@@ -1,23 +1,93 @@
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
 use anyhow::bail;
 use async_trait::async_trait;
-use storage::data_manager::DataManager;
+use storage::StorageError;
+use storage::backend::Storage;
+use storage::data_manager::WRITER_DRAIN_TIMEOUT;
 use tokio::sync::{broadcast, mpsc};
-use tokio::time;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::services::market_gateway::MarketEvent;
-use common::actors::{Actor, ActorType, ControlMessage};
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, LagTracker, PendingBuffer, SymbolRateLimiter,
+    WriterLimits, run_db_writer,
+};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+use common::actors::{Actor, ActorType, ControlMessage, Throughput};
 use common::models::OrderBookInsert;
-use storage::repositories::OrderBookRepository;
+
+/// Default order book batching, overridden by `ORDERBOOK_BATCH_ROWS`/`ORDERBOOK_BATCH_MS`.
+pub const DEFAULT_ORDERBOOK_BATCH: BatchWindow = BatchWindow::new(600, Duration::from_secs(5));
+
+/// How often (in skipped books) the running total is logged.
+const REPEATED_BOOK_REPORT_EVERY: u64 = 10_000;
+
+static REPEATED_BOOKS: AtomicU64 = AtomicU64::new(0);
+
+/// Depth snapshots not written because they repeated the last stored one.
+pub fn repeated_books() -> u64 {
+    REPEATED_BOOKS.load(Ordering::Relaxed)
+}
+
+/// Skips a depth snapshot whose packed bids and asks are byte-identical to
+/// the last one stored for its symbol, as long as that one is less than
+/// `window` old. Binance resends unchanged partial books, so quiet symbols
+/// otherwise fill the table with copies; past the window a copy is stored
+/// anyway, so a flat book still shows up at least that often.
+pub struct BookDedup {
+    window: f64,
+    /// Symbol -> time, bids and asks of its last stored book.
+    last: HashMap<String, (f64, Vec<u8>, Vec<u8>)>,
+}
+
+impl BookDedup {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: window.as_secs_f64(),
+            last: HashMap::new(),
+        }
+    }
+
+    /// True if `book` repeats its symbol's last stored book within the
+    /// window.
+    pub fn is_repeat(&self, book: &OrderBookInsert) -> bool {
+        let Some((time, bids, asks)) = self.last.get(&book.symbol) else {
+            return false;
+        };
+        if book.time - time >= self.window || *bids != book.bids || *asks != book.asks {
+            return false;
+        }
+        let total = REPEATED_BOOKS.fetch_add(1, Ordering::Relaxed) + 1;
+        if total.is_multiple_of(REPEATED_BOOK_REPORT_EVERY) {
+            debug!("{} repeated order books skipped so far", total);
+        }
+        true
+    }
+
+    /// Makes `book` its symbol's last stored book. Called only once the book
+    /// reached the DB writer, so a dropped one doesn't hide its copies.
+    pub fn stored(&mut self, book: &OrderBookInsert) {
+        self.last.insert(
+            book.symbol.clone(),
+            (book.time, book.bids.clone(), book.asks.clone()),
+        );
+    }
+}
 
 pub struct OrderBookService {
     id: Uuid,
-    rotating_pool: Arc<DataManager>,
-    order_tx: broadcast::Receiver<Arc<MarketEvent>>,
+    storage: Arc<dyn Storage>,
+    order_tx: EventReceiver,
+    policy: BackpressurePolicy,
+    window: BatchWindow,
+    dedup: Option<BookDedup>,
+    limiter: Option<SymbolRateLimiter>,
+    dropped: DropCounter,
+    limits: WriterLimits,
 }
 
 #[async_trait]
@@ -31,35 +101,72 @@ impl Actor for OrderBookService {
     }
 
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
-        let heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
 
         info!("Starting OrderBook Ingestion Service");
 
         let (db_tx, db_rx) = mpsc::channel(2000);
 
-        tokio::spawn(Self::db_writer(self.rotating_pool.clone(), db_rx));
+        let pending = PendingBuffer::with_limits("OrderBook DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
 
+        let writer = self.storage.spawn_writer(Box::pin(run_db_writer(
+            self.storage.clone(),
+            db_rx,
+            self.window,
+            pending,
+            |storage, batch| Box::pin(Self::flush_batch(storage, batch)),
+        )));
+
+        let mut lag = LagTracker::new("OrderBook service").with_throughput(throughput);
         loop {
             match self.order_tx.recv().await {
                 Ok(order_arc) => {
                     let event = &*order_arc;
+                    lag.observe(event.seq());
 
-                    if let MarketEvent::OrderBook(order) = event {
-                        if let Err(e) = db_tx.send(order.to_owned()).await {
-                            let err_msg = format!("Failed to send to DB writer: {}", e);
-                            heartbeat_handle.abort();
-                            supervisor_tx
-                                .send(ControlMessage::Error(self.id, err_msg.clone()))
-                                .await?;
-                            bail!(err_msg);
+                    if let MarketEvent::OrderBook(order) = event
+                        && self.storage.is_recording(&order.symbol)
+                        && !self.dedup.as_ref().is_some_and(|d| d.is_repeat(order))
+                        && self.limiter.as_mut().is_none_or(|l| l.allow(&order.symbol))
+                    {
+                        match self
+                            .policy
+                            .forward(&db_tx, order.to_owned(), &self.dropped)
+                            .await
+                        {
+                            Ok(true) => {
+                                if let Some(dedup) = &mut self.dedup {
+                                    dedup.stored(order);
+                                }
+                            }
+                            Ok(false) => {}
+                            Err(e) => {
+                                let err_msg = format!("Failed to send to DB writer: {}", e);
+                                heartbeat_handle.abort();
+                                supervisor_tx
+                                    .send(ControlMessage::Error(self.id, err_msg.clone()))
+                                    .await?;
+                                bail!(err_msg);
+                            }
                         }
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("OrderBook service lagged: missed {} signals", n);
+                    lag.lagged(n);
                 }
                 Err(_) => {
-                    let err_msg = format!("OrderBook channel closed unexpectedly.");
+                    drop(db_tx);
+                    if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                        warn!(
+                            "OrderBook DB writer still flushing after {:?}",
+                            WRITER_DRAIN_TIMEOUT
+                        );
+                    }
+                    let err_msg = "OrderBook channel closed unexpectedly.".to_string();
                     heartbeat_handle.abort();
                     supervisor_tx
                         .send(ControlMessage::Error(self.id, err_msg.clone()))
@@ -72,62 +179,109 @@ impl Actor for OrderBookService {
 }
 
 impl OrderBookService {
-    pub fn new(
-        rotating_pool: Arc<DataManager>,
-        order_tx: broadcast::Receiver<Arc<MarketEvent>>,
-    ) -> Self {
+    pub fn new(storage: Arc<dyn Storage>, order_tx: EventReceiver) -> Self {
         Self {
             id: Uuid::new_v4(),
-            rotating_pool,
+            storage,
             order_tx,
+            policy: BackpressurePolicy::default(),
+            window: DEFAULT_ORDERBOOK_BATCH,
+            dedup: None,
+            limiter: None,
+            dropped: DropCounter::new("OrderBook DB writer"),
+            limits: WriterLimits::default(),
         }
     }
 
-    async fn db_writer(
-        rotating_pool: Arc<DataManager>,
-        mut order_rx: mpsc::Receiver<OrderBookInsert>,
-    ) {
-        let mut buffer = Vec::with_capacity(750);
-        let mut last_flush = Instant::now();
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-        loop {
-            tokio::select! {
-                result = order_rx.recv() => {
-                    match result {
-                        Some(order) => {
-                            buffer.push(order);
-                            if buffer.len() >= 600 || last_flush.elapsed() >= Duration::from_secs(5) {
-                                Self::flush_batch(&*rotating_pool, &buffer).await;
-                                buffer.clear();
-                                last_flush = Instant::now();
-                            }
-                        }
-                        None => {
-                            info!("DB Channel closed. Flusing remaining buffer.");
-                            if !buffer.is_empty() {
-                                Self::flush_batch(&*rotating_pool, &buffer).await;
-                            }
-                            break;
-                        }
-                    }
-                }
+    /// Flushes once `window` fills or expires instead of `DEFAULT_ORDERBOOK_BATCH`.
+    pub fn with_batch_window(mut self, window: BatchWindow) -> Self {
+        self.window = window;
+        self
+    }
 
-                _ = time::sleep(Duration::from_secs(5)) => {
-                    if !buffer.is_empty() {
-                        Self::flush_batch(&*rotating_pool, &buffer).await;
-                        buffer.clear();
-                        last_flush = Instant::now();
-                    }
-                }
-            }
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Skips books repeating their symbol's last stored one within
+    /// `window`; `None` stores every book.
+    pub fn with_dedup(mut self, window: Option<Duration>) -> Self {
+        self.dedup = window.map(BookDedup::new);
+        self
+    }
+
+    /// Caps each symbol at `per_sec` books written per second; `None` writes
+    /// every one.
+    pub fn with_rate_limit(mut self, per_sec: Option<u32>) -> Self {
+        self.limiter = per_sec.map(|per_sec| SymbolRateLimiter::new("OrderBook service", per_sec));
+        self
+    }
+
+    async fn flush_batch(
+        storage: &dyn Storage,
+        batch: &[OrderBookInsert],
+    ) -> Result<(), StorageError> {
+        let result = storage.insert_order_books(batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} order_books to DB.", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
         }
+        result
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    async fn flush_batch(rotating_pool: &DataManager, batch: &[OrderBookInsert]) {
-        if let Err(e) = OrderBookRepository::insert_batch(rotating_pool, batch).await {
-            error!("DB write failed: {}", e);
-        } else {
-            debug!("Wrote {} order_books to DB.", batch.len());
+    fn book(time: f64, bid_qty: f32) -> OrderBookInsert {
+        let level = |qty: f32| [1.0f32.to_le_bytes(), qty.to_le_bytes()].concat();
+        OrderBookInsert {
+            time,
+            symbol: "BTCUSDT".to_string(),
+            bids: level(bid_qty),
+            asks: level(1.0),
+            seq: None,
         }
     }
+
+    #[test]
+    fn test_skips_identical_books_within_the_window() {
+        let mut dedup = BookDedup::new(Duration::from_secs(1));
+        let mut store = |book: OrderBookInsert| {
+            let repeat = dedup.is_repeat(&book);
+            if !repeat {
+                dedup.stored(&book);
+            }
+            repeat
+        };
+
+        assert!(!store(book(100.0, 2.0)));
+        assert!(store(book(100.1, 2.0)));
+        assert!(!store(book(100.2, 3.0)));
+        assert!(store(book(101.1, 3.0)));
+        // A second after the last stored copy, it is stored again.
+        assert!(!store(book(101.2, 3.0)));
+        assert!(!store(OrderBookInsert {
+            symbol: "ETHUSDT".to_string(),
+            ..book(101.3, 3.0)
+        }));
+    }
+
+    #[test]
+    fn test_book_not_stored_is_no_reference() {
+        let dedup = BookDedup::new(Duration::from_secs(1));
+        // Checked but never forwarded, e.g. rate limited or dropped.
+        assert!(!dedup.is_repeat(&book(100.0, 2.0)));
+        assert!(!dedup.is_repeat(&book(100.1, 2.0)));
+    }
 }
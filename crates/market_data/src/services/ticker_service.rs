@@ -0,0 +1,98 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use storage::data_manager::DataManager;
+use tokio::sync::mpsc;
+use tokio::time;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::remote::Ticker24hClient;
+use common::actors::{Actor, ActorType, ControlMessage};
+use storage::repositories::Ticker24hRepository;
+
+/// Default seconds between 24h ticker polls.
+pub const DEFAULT_TICKER_24H_SECONDS: u64 = 60;
+
+/// Polls Binance's rolling 24h statistics of every recorded symbol into
+/// `ticker_24h`, for dashboards that want exchange-computed volume and price
+/// change next to the raw trades.
+///
+/// A failed request or write is logged and retried at the next tick.
+pub struct Ticker24hActor {
+    id: Uuid,
+    rotating_pool: Arc<DataManager>,
+    client: Ticker24hClient,
+    symbols: Vec<String>,
+    period: Duration,
+}
+
+#[async_trait]
+impl Actor for Ticker24hActor {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::Ticker24hActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let _heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+
+        info!(
+            "Starting 24h Ticker Actor ({} symbols, every {}s)",
+            self.symbols.len(),
+            self.period.as_secs()
+        );
+
+        let mut ticker = time::interval(self.period);
+        loop {
+            ticker.tick().await;
+            self.poll().await;
+        }
+    }
+}
+
+impl Ticker24hActor {
+    pub fn new(
+        rotating_pool: Arc<DataManager>,
+        client: Ticker24hClient,
+        symbols: Vec<String>,
+        period: Duration,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            rotating_pool,
+            client,
+            symbols,
+            period,
+        }
+    }
+
+    async fn poll(&self) {
+        let symbols: Vec<String> = self
+            .symbols
+            .iter()
+            .filter(|s| self.rotating_pool.is_recording(s))
+            .cloned()
+            .collect();
+        if symbols.is_empty() {
+            return;
+        }
+
+        let tickers = match self.client.fetch(&symbols).await {
+            Ok(tickers) => tickers,
+            Err(e) => {
+                warn!("24h ticker poll skipped: {}", e);
+                return;
+            }
+        };
+
+        match Ticker24hRepository::insert_batch(&self.rotating_pool, &tickers).await {
+            Ok(()) => debug!("Wrote {} 24h tickers to DB", tickers.len()),
+            Err(e) => warn!("24h ticker write failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use storage::StorageError;
+use storage::data_manager::{DataManager, WRITER_DRAIN_TIMEOUT};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::backpressure::{
+    BackpressurePolicy, BatchWindow, DropCounter, PendingBuffer, WriterLimits, run_db_writer,
+};
+use crate::services::market_gateway::{EventReceiver, MarketEvent};
+use common::actors::{Actor, ActorType, ControlMessage, Throughput};
+use common::models::{AggTradeInsert, TradeFlowInsert};
+use storage::repositories::TradeFlowRepository;
+
+/// Bucket width in milliseconds. Matches the 1m kline so `bucket_start` lines
+/// up with `klines.start_time`.
+const BUCKET_MS: i64 = 60_000;
+
+const TRADE_FLOW_BATCH: BatchWindow = BatchWindow::new(60, Duration::from_secs(60));
+
+/// Sums aggTrade volume per symbol into 1m buckets, split by aggressor side.
+///
+/// A bucket may be written as more than one row, e.g. a late trade or a
+/// minute split by a restart; readers sum the rows per `bucket_start`.
+#[derive(Default)]
+pub struct TradeFlowAggregator {
+    open: HashMap<String, TradeFlowInsert>,
+}
+
+impl TradeFlowAggregator {
+    /// Adds a trade to its symbol's bucket. Returns the previous bucket once a
+    /// trade for a later minute arrives, and a trade from a minute already
+    /// closed as a row of its own.
+    pub fn add(&mut self, trade: &AggTradeInsert) -> Option<TradeFlowInsert> {
+        let bucket_start = Self::bucket_start(trade.time);
+        let (buy_vol, sell_vol) = if trade.is_buyer_maker {
+            (0.0, trade.quantity)
+        } else {
+            (trade.quantity, 0.0)
+        };
+        let flow = TradeFlowInsert {
+            symbol: trade.symbol.clone(),
+            bucket_start,
+            buy_vol,
+            sell_vol,
+            trade_count: 1,
+        };
+
+        match self.open.get_mut(&trade.symbol) {
+            Some(bucket) if bucket.bucket_start == bucket_start => {
+                bucket.buy_vol += buy_vol;
+                bucket.sell_vol += sell_vol;
+                bucket.trade_count += 1;
+                None
+            }
+            Some(bucket) if bucket.bucket_start > bucket_start => Some(flow),
+            _ => self.open.insert(trade.symbol.clone(), flow),
+        }
+    }
+
+    /// Takes every open bucket, to be written before the aggregator goes away.
+    pub fn drain(&mut self) -> impl Iterator<Item = TradeFlowInsert> + '_ {
+        self.open.drain().map(|(_, flow)| flow)
+    }
+
+    /// Start of the 1m bucket, in epoch milliseconds, that `time` (epoch seconds) falls in.
+    fn bucket_start(time: f64) -> i64 {
+        let millis = (time * 1000.0) as i64;
+        millis - millis.rem_euclid(BUCKET_MS)
+    }
+}
+
+/// The buckets a running service has open. However `run` ends, an abort by
+/// the supervisor included, they go to the DB writer on drop, so the last
+/// minute of every symbol is written too.
+struct OpenBuckets {
+    aggregator: TradeFlowAggregator,
+    db_tx: mpsc::Sender<TradeFlowInsert>,
+}
+
+impl Drop for OpenBuckets {
+    fn drop(&mut self) {
+        for flow in self.aggregator.drain() {
+            if let Err(e) = self.db_tx.try_send(flow) {
+                warn!("TradeFlow: open bucket not written on stop: {}", e);
+            }
+        }
+    }
+}
+
+pub struct TradeFlowService {
+    id: Uuid,
+    rotating_pool: Arc<DataManager>,
+    trade_rx: EventReceiver,
+    policy: BackpressurePolicy,
+    dropped: DropCounter,
+    limits: WriterLimits,
+}
+
+#[async_trait]
+impl Actor for TradeFlowService {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::TradeFlowActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let throughput = Arc::new(Throughput::default());
+        let heartbeat_handle =
+            self.spawn_heartbeat_with_metrics(supervisor_tx.clone(), throughput.clone());
+
+        info!("Starting TradeFlow Aggregation Service");
+
+        let (db_tx, db_rx) = mpsc::channel(256);
+
+        let pending = PendingBuffer::with_limits("TradeFlow DB writer", self.limits)
+            .with_reporter(self.id, supervisor_tx.clone())
+            .with_throughput(throughput.clone());
+
+        let writer = self.rotating_pool.spawn_writer(run_db_writer(
+            self.rotating_pool.clone(),
+            db_rx,
+            TRADE_FLOW_BATCH,
+            pending,
+            |pool, batch| Box::pin(Self::flush_batch(pool, batch)),
+        ));
+
+        let mut open = OpenBuckets {
+            aggregator: TradeFlowAggregator::default(),
+            db_tx,
+        };
+        loop {
+            match self.trade_rx.recv().await {
+                Ok(event_arc) => {
+                    let event = &*event_arc;
+
+                    if let MarketEvent::AggTrade(trade) = event
+                        && self.rotating_pool.is_recording(&trade.symbol)
+                        && let Some(flow) = open.aggregator.add(trade)
+                        && let Err(e) = self.policy.forward(&open.db_tx, flow, &self.dropped).await
+                    {
+                        heartbeat_handle.abort();
+                        supervisor_tx.try_send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?}: Failed to send to DB writer: {}", self.name(), e),
+                        ))?;
+                        bail!("Failed to send to DB writer: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    throughput.lagged(n);
+                    warn!("TradeFlow service lagged: missed {} signals", n);
+                }
+                Err(_) => {
+                    drop(open);
+                    if !writer.drain(WRITER_DRAIN_TIMEOUT).await {
+                        warn!(
+                            "TradeFlow DB writer still flushing after {:?}",
+                            WRITER_DRAIN_TIMEOUT
+                        );
+                    }
+                    heartbeat_handle.abort();
+                    supervisor_tx
+                        .send(ControlMessage::Error(
+                            self.id,
+                            format!("{:?}: TradeFlow channel closed unexpectedly.", self.name()),
+                        ))
+                        .await?;
+                    bail!("TradeFlow channel closed unexpectedly.");
+                }
+            }
+        }
+    }
+}
+
+impl TradeFlowService {
+    pub fn new(rotating_pool: Arc<DataManager>, trade_rx: EventReceiver) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            rotating_pool,
+            trade_rx,
+            policy: BackpressurePolicy::default(),
+            dropped: DropCounter::new("TradeFlow DB writer"),
+            limits: WriterLimits::default(),
+        }
+    }
+
+    /// What to do when the DB writer has no room: wait (`Block`, the
+    /// default) or discard the rows.
+    pub fn with_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Buffer cap and retry count of the DB writer while flushes fail.
+    pub fn with_writer_limits(mut self, limits: WriterLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    async fn flush_batch(
+        r_pool: &DataManager,
+        batch: &[TradeFlowInsert],
+    ) -> Result<(), StorageError> {
+        let result = TradeFlowRepository::insert_batch(r_pool, batch).await;
+        match &result {
+            Ok(()) => debug!("Wrote {} trade_flow buckets to DB", batch.len()),
+            Err(e) => error!("DB write failed: {}", e),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(symbol: &str, time: f64, quantity: f64, is_buyer_maker: bool) -> AggTradeInsert {
+        AggTradeInsert {
+            agg_trade_id: 1,
+            time,
+            trade_time: time,
+            symbol: symbol.to_string(),
+            price: 1.0,
+            quantity,
+            is_buyer_maker,
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn test_bucket_aligns_to_kline_minute() {
+        // 2023-11-14T22:13:20.5Z -> 22:13:00Z
+        assert_eq!(
+            TradeFlowAggregator::bucket_start(1_700_000_000.5),
+            1_699_999_980_000
+        );
+        assert_eq!(
+            TradeFlowAggregator::bucket_start(1_699_999_980.0),
+            1_699_999_980_000
+        );
+    }
+
+    #[test]
+    fn test_buckets_split_by_side_and_close_on_next_minute() {
+        let mut agg = TradeFlowAggregator::default();
+
+        assert_eq!(
+            agg.add(&trade("BTCUSDT", 1_699_999_980.0, 2.0, false)),
+            None
+        );
+        assert_eq!(agg.add(&trade("BTCUSDT", 1_699_999_999.0, 0.5, true)), None);
+        // Another symbol never closes BTC's bucket.
+        assert_eq!(agg.add(&trade("ETHUSDT", 1_700_000_100.0, 9.0, true)), None);
+
+        let closed = agg
+            .add(&trade("BTCUSDT", 1_700_000_040.0, 1.0, true))
+            .expect("minute rolled over");
+        assert_eq!(
+            closed,
+            TradeFlowInsert {
+                symbol: "BTCUSDT".to_string(),
+                bucket_start: 1_699_999_980_000,
+                buy_vol: 2.0,
+                sell_vol: 0.5,
+                trade_count: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_late_trade_keeps_its_own_minute() {
+        let mut agg = TradeFlowAggregator::default();
+        agg.add(&trade("BTCUSDT", 1_699_999_980.0, 2.0, false));
+        assert!(
+            agg.add(&trade("BTCUSDT", 1_700_000_040.0, 1.0, true))
+                .is_some()
+        );
+
+        // A trade from the closed minute must not count toward the open one.
+        let late = agg
+            .add(&trade("BTCUSDT", 1_699_999_990.0, 0.25, false))
+            .expect("late trade written on its own");
+        assert_eq!(late.bucket_start, 1_699_999_980_000);
+        assert_eq!((late.buy_vol, late.trade_count), (0.25, 1));
+
+        let open: Vec<_> = agg.drain().collect();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].bucket_start, 1_700_000_040_000);
+        assert_eq!((open[0].sell_vol, open[0].trade_count), (1.0, 1));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+
+use crate::remote::kline_response::KLINE_INTERVALS;
+
+/// A family of spot streams a symbol can be subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamKind {
+    /// `@aggTrade`
+    Trades,
+    /// `@depth20` top-20 snapshots, at the selection's `DepthSpeed`.
+    Depth,
+    /// `@depth` diffs for full book reconstruction, at the same speed.
+    DepthDiff,
+    /// `@kline_<interval>` for every entry of `KLINE_INTERVALS`.
+    Klines,
+}
+
+impl StreamKind {
+    /// Stream names this kind contributes for `symbol`.
+    pub fn stream_names(self, symbol: &str, depth_speed: DepthSpeed) -> Vec<String> {
+        let sl = symbol.to_lowercase();
+        let speed = depth_speed.suffix();
+        match self {
+            Self::Trades => vec![format!("{sl}@aggTrade")],
+            Self::Depth => vec![format!("{sl}@depth20{speed}")],
+            Self::DepthDiff => vec![format!("{sl}@depth{speed}")],
+            Self::Klines => KLINE_INTERVALS
+                .iter()
+                .map(|i| format!("{sl}@kline_{i}"))
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for StreamKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "trades" => Ok(Self::Trades),
+            "depth" => Ok(Self::Depth),
+            "diff" => Ok(Self::DepthDiff),
+            "klines" => Ok(Self::Klines),
+            other => Err(format!("unknown stream kind: {}", other)),
+        }
+    }
+}
+
+/// How often Binance pushes the depth snapshot and diff streams.
+///
+/// 100ms is ten times the rows of 1000ms; for long-term storage the slower
+/// feed keeps the `order_books` table far smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthSpeed {
+    #[default]
+    Ms100,
+    Ms1000,
+}
+
+impl DepthSpeed {
+    /// Stream name suffix. 1000ms is Binance's default and has none.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Ms100 => "@100ms",
+            Self::Ms1000 => "",
+        }
+    }
+}
+
+impl FromStr for DepthSpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "100ms" | "100" => Ok(Self::Ms100),
+            "1000ms" | "1000" | "1s" => Ok(Self::Ms1000),
+            other => Err(format!(
+                "depth speed must be 100ms or 1000ms, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+/// Which spot streams each symbol is subscribed to.
+///
+/// Symbols without an entry get `default`. Futures streams (liquidations,
+/// mark price) are cheap and always on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamSelection {
+    default: Vec<StreamKind>,
+    per_symbol: HashMap<String, Vec<StreamKind>>,
+    depth_speed: DepthSpeed,
+}
+
+impl Default for StreamSelection {
+    fn default() -> Self {
+        Self {
+            default: vec![StreamKind::Trades, StreamKind::Depth, StreamKind::Klines],
+            per_symbol: HashMap::new(),
+            depth_speed: DepthSpeed::default(),
+        }
+    }
+}
+
+impl StreamSelection {
+    /// Reads per-symbol overrides from `SYMBOL_STREAMS`, e.g.
+    /// `pepeusdt=trades;bonkusdt=trades,klines`, and the depth speed from
+    /// `DEPTH_UPDATE_SPEED` (`100ms` or `1000ms`, default `100ms`).
+    pub fn from_env() -> Result<Self, String> {
+        let selection: Self = match env::var("SYMBOL_STREAMS") {
+            Ok(spec) => spec.parse()?,
+            Err(_) => Self::default(),
+        };
+        match env::var("DEPTH_UPDATE_SPEED") {
+            Ok(speed) => Ok(selection.with_depth_speed(speed.parse()?)),
+            Err(_) => Ok(selection),
+        }
+    }
+
+    pub fn with_depth_speed(mut self, depth_speed: DepthSpeed) -> Self {
+        self.depth_speed = depth_speed;
+        self
+    }
+
+    pub fn depth_speed(&self) -> DepthSpeed {
+        self.depth_speed
+    }
+
+    pub fn kinds(&self, symbol: &str) -> &[StreamKind] {
+        self.per_symbol
+            .get(&symbol.to_lowercase())
+            .unwrap_or(&self.default)
+    }
+
+    /// Drops the depth snapshot and diff streams from every symbol, for runs
+    /// that record no order books.
+    pub fn without_depth(mut self) -> Self {
+        let is_depth =
+            |kind: &StreamKind| matches!(kind, StreamKind::Depth | StreamKind::DepthDiff);
+        self.default.retain(|kind| !is_depth(kind));
+        for kinds in self.per_symbol.values_mut() {
+            kinds.retain(|kind| !is_depth(kind));
+        }
+        self
+    }
+
+    /// Every spot stream name `symbol` is subscribed to.
+    pub fn stream_names(&self, symbol: &str) -> Vec<String> {
+        self.kinds(symbol)
+            .iter()
+            .flat_map(|kind| kind.stream_names(symbol, self.depth_speed))
+            .collect()
+    }
+}
+
+impl FromStr for StreamSelection {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut selection = Self::default();
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (symbol, kinds) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected 'symbol=kind,...', got '{}'", entry))?;
+            let kinds = kinds
+                .split(',')
+                .filter(|k| !k.trim().is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<StreamKind>, _>>()?;
+            selection
+                .per_symbol
+                .insert(symbol.trim().to_lowercase(), kinds);
+        }
+        Ok(selection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_overrides() {
+        let selection: StreamSelection =
+            "PEPEUSDT=trades; bonkusdt=trades,klines;".parse().unwrap();
+
+        assert_eq!(selection.kinds("pepeusdt"), &[StreamKind::Trades]);
+        assert_eq!(
+            selection.kinds("BONKUSDT"),
+            &[StreamKind::Trades, StreamKind::Klines]
+        );
+        assert_eq!(
+            selection.kinds("btcusdt"),
+            &[StreamKind::Trades, StreamKind::Depth, StreamKind::Klines]
+        );
+        assert_eq!(
+            selection.stream_names("PEPEUSDT"),
+            vec!["pepeusdt@aggTrade"]
+        );
+
+        let klines_only = "pepeusdt=trades,diff"
+            .parse::<StreamSelection>()
+            .unwrap()
+            .without_depth();
+        assert_eq!(klines_only.kinds("pepeusdt"), &[StreamKind::Trades]);
+        assert_eq!(
+            klines_only.kinds("btcusdt"),
+            &[StreamKind::Trades, StreamKind::Klines]
+        );
+
+        let slow = StreamSelection::default().with_depth_speed("1000ms".parse().unwrap());
+        assert_eq!(
+            slow.stream_names("BTCUSDT")[..2],
+            ["btcusdt@aggTrade", "btcusdt@depth20"]
+        );
+        assert_eq!(
+            StreamKind::DepthDiff.stream_names("btcusdt", DepthSpeed::Ms100),
+            ["btcusdt@depth@100ms"]
+        );
+        assert!("250ms".parse::<DepthSpeed>().is_err());
+
+        assert!("pepeusdt".parse::<StreamSelection>().is_err());
+        assert!("pepeusdt=candles".parse::<StreamSelection>().is_err());
+    }
+}
@@ -1,15 +1,44 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::de::Error;
+use tracing::warn;
+
+/// How often (in malformed fields) `parse_field` reports the running total.
+const PARSE_ERROR_REPORT_EVERY: u64 = 100;
+
+static PARSE_ERRORS: AtomicU64 = AtomicU64::new(0);
+
 pub trait RemoteResponse<T> {
     fn to_insertable(&self) -> Result<T, serde_json::Error>;
 
     fn get_time_f64(&self) -> f64 {
-        let now = SystemTime::now();
-        let timestamp_float = now
+        SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
-            .as_secs_f64();
-
-        timestamp_float
+            .as_secs_f64()
     }
 }
+
+/// Parses a numeric string field of an exchange payload.
+///
+/// A malformed value fails the whole event instead of being recorded as 0,
+/// and is counted in `parse_errors`.
+pub fn parse_field<T: FromStr>(field: &str, value: &str) -> Result<T, serde_json::Error> {
+    value.parse::<T>().map_err(|_| {
+        let total = PARSE_ERRORS.fetch_add(1, Ordering::Relaxed) + 1;
+        if total == 1 || total.is_multiple_of(PARSE_ERROR_REPORT_EVERY) {
+            warn!(
+                "Malformed {} '{}', {} bad fields so far",
+                field, value, total
+            );
+        }
+        serde_json::Error::custom(format!("invalid {} '{}'", field, value))
+    })
+}
+
+/// Numeric fields rejected by `parse_field` since startup.
+pub fn parse_errors() -> u64 {
+    PARSE_ERRORS.load(Ordering::Relaxed)
+}
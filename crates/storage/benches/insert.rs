@@ -0,0 +1,68 @@
+//! `AggTradeRepository::insert_batch` of 1000 rows.
+//!
+//! Writes go to a weekly file in the temp directory, so the numbers include
+//! WAL appends but, with `synchronous = NORMAL`, no fsync per commit. Run with
+//! `cargo bench -p storage --bench insert`.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use common::models::AggTradeInsert;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use storage::data_manager::DataManager;
+use storage::db::StorageConfig;
+use storage::repositories::AggTradeRepository;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+const BATCH_ROWS: i64 = 1000;
+const SYMBOLS: &[&str] = &["BTCUSDT", "ETHUSDT", "SOLUSDT", "DOGEUSDT"];
+
+fn insert_batch(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let folder = std::env::temp_dir().join(format!("insert_bench_{}", uuid::Uuid::new_v4()));
+    let (tx, _rx) = mpsc::channel(1);
+    let manager = runtime
+        .block_on(DataManager::new(
+            folder.to_str().unwrap().to_string(),
+            tx,
+            StorageConfig::default(),
+        ))
+        .unwrap();
+
+    // Fresh ids every batch, so no row is skipped as a duplicate.
+    let next_id = AtomicI64::new(0);
+    c.bench_function("agg_trade_insert_batch_1000", |b| {
+        b.to_async(&runtime).iter_batched(
+            || trades(next_id.fetch_add(BATCH_ROWS, Ordering::Relaxed)),
+            |trades| {
+                let manager = &manager;
+                async move {
+                    AggTradeRepository::insert_batch(manager, &trades)
+                        .await
+                        .unwrap()
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let _ = std::fs::remove_dir_all(&folder);
+}
+
+fn trades(first_id: i64) -> Vec<AggTradeInsert> {
+    (0..BATCH_ROWS)
+        .map(|i| AggTradeInsert {
+            agg_trade_id: first_id + i,
+            time: (first_id + i) as f64,
+            trade_time: (first_id + i) as f64,
+            symbol: SYMBOLS[i as usize % SYMBOLS.len()].to_string(),
+            price: 100.0 + i as f64,
+            quantity: 0.5,
+            is_buyer_maker: i % 2 == 0,
+            seq: None,
+        })
+        .collect()
+}
+
+criterion_group!(benches, insert_batch);
+criterion_main!(benches);
@@ -0,0 +1,156 @@
+//! Insert throughput of the per-service writers against `DB_SHARED_WRITER`.
+//!
+//! Several tasks flush trade, order book and kline batches into one weekly
+//! file at the same time, once with every writer racing for the WAL lock and
+//! once through the shared write queue. Run with
+//! `cargo run --release -p storage --example writer_bench [batches]`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::models::{AggTradeInsert, KlineInsert, OrderBookInsert};
+use storage::data_manager::DataManager;
+use storage::db::StorageConfig;
+use storage::repositories::{AggTradeRepository, KlinesRepository, OrderBookRepository};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+const BATCH_ROWS: usize = 1000;
+const SYMBOLS: &[&str] = &["BTCUSDT", "ETHUSDT", "SOLUSDT", "DOGEUSDT"];
+
+#[tokio::main]
+async fn main() {
+    let batches: usize = std::env::args()
+        .nth(1)
+        .map(|n| n.parse().expect("batches must be a number"))
+        .unwrap_or(50);
+    for shared_writer in [false, true] {
+        run(shared_writer, batches).await;
+    }
+}
+
+async fn run(shared_writer: bool, batches: usize) {
+    let folder = std::env::temp_dir().join(format!("writer_bench_{}", uuid::Uuid::new_v4()));
+    let (tx, _rx) = mpsc::channel(1);
+    let config = StorageConfig {
+        shared_writer,
+        ..StorageConfig::default()
+    };
+    let manager = DataManager::new(folder.to_str().unwrap().to_string(), tx, config)
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let mut writers = JoinSet::new();
+    // Two writers per kind, like trades and candles from spot and replay.
+    for writer in 0..2 {
+        writers.spawn(flush_all("trades", batches, manager.clone(), move |b| {
+            let trades = trades(writer * batches + b);
+            async move |m: &DataManager| AggTradeRepository::insert_batch(m, &trades).await
+        }));
+        writers.spawn(flush_all("books", batches, manager.clone(), move |b| {
+            let books = books(writer * batches + b);
+            async move |m: &DataManager| OrderBookRepository::insert_batch(m, &books).await
+        }));
+        writers.spawn(flush_all("klines", batches, manager.clone(), move |b| {
+            let klines = klines(writer * batches + b);
+            async move |m: &DataManager| KlinesRepository::insert_batch(m, &klines).await
+        }));
+    }
+    let mut lines = Vec::new();
+    while let Some(line) = writers.join_next().await {
+        lines.push(line.unwrap());
+    }
+    let elapsed = started.elapsed();
+
+    let rows = 6 * batches * BATCH_ROWS;
+    println!(
+        "shared_writer={}: {} rows in {:.2?}, {:.0} rows/s",
+        shared_writer,
+        rows,
+        elapsed,
+        rows as f64 / elapsed.as_secs_f64()
+    );
+    lines.sort();
+    for line in lines {
+        println!("  {}", line);
+    }
+    let _ = std::fs::remove_dir_all(&folder);
+}
+
+/// Flushes `batches` batches made by `batch`, returning the mean and worst
+/// time per flush.
+async fn flush_all<B, F>(label: &str, batches: usize, manager: Arc<DataManager>, batch: B) -> String
+where
+    B: Fn(usize) -> F,
+    F: AsyncFnOnce(&DataManager) -> Result<(), storage::StorageError>,
+{
+    let mut total = Duration::ZERO;
+    let mut worst = Duration::ZERO;
+    for b in 0..batches {
+        let flush = batch(b);
+        let started = Instant::now();
+        flush(&manager).await.unwrap();
+        let took = started.elapsed();
+        total += took;
+        worst = worst.max(took);
+    }
+    format!(
+        "{:<6} flush mean {:>8.2?} max {:>8.2?}",
+        label,
+        total / batches as u32,
+        worst
+    )
+}
+
+fn trades(batch: usize) -> Vec<AggTradeInsert> {
+    (0..BATCH_ROWS)
+        .map(|i| AggTradeInsert {
+            agg_trade_id: (batch * BATCH_ROWS + i) as i64,
+            time: 1.0,
+            trade_time: 1.0,
+            symbol: SYMBOLS[i % SYMBOLS.len()].to_string(),
+            price: 100.0 + i as f64,
+            quantity: 0.5,
+            is_buyer_maker: i % 2 == 0,
+            seq: None,
+        })
+        .collect()
+}
+
+fn books(batch: usize) -> Vec<OrderBookInsert> {
+    let levels: Vec<u8> = (0..20)
+        .flat_map(|l| [(100.0 + l as f32).to_le_bytes(), 1.0f32.to_le_bytes()].concat())
+        .collect();
+    (0..BATCH_ROWS)
+        .map(|i| OrderBookInsert {
+            time: (batch * BATCH_ROWS + i) as f64,
+            symbol: SYMBOLS[i % SYMBOLS.len()].to_string(),
+            bids: levels.clone(),
+            asks: levels.clone(),
+            seq: None,
+        })
+        .collect()
+}
+
+fn klines(batch: usize) -> Vec<KlineInsert> {
+    (0..BATCH_ROWS)
+        .map(|i| {
+            let start_time = ((batch * BATCH_ROWS + i) * 1000) as i64;
+            KlineInsert {
+                symbol: SYMBOLS[i % SYMBOLS.len()].to_string(),
+                start_time,
+                close_time: start_time + 999,
+                interval: "1s".to_string(),
+                open_price: 1.0,
+                close_price: 1.0,
+                high_price: 1.0,
+                low_price: 1.0,
+                volume: 1.0,
+                no_of_trades: 1,
+                taker_buy_vol: 1.0,
+                seq: None,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,247 @@
+CREATE TABLE IF NOT EXISTS symbols(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    ticker TEXT UNIQUE NOT NULL
+);
+
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('BTCUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('ETHUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('BNBUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('SOLUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('AVAXUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('NEARUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('MATICUSDT');
+
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('DOGEUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('SHIBUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('PEPEUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('WIFIUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('BONKUSDT');
+
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('XRPUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('ADAUSDT');
+INSERT OR IGNORE INTO symbols (ticker) VALUES ('DOTUSDT');
+
+CREATE TABLE IF NOT EXISTS order_books(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time REAL NOT NULL,
+    symbol_id INTEGER NOT NULL,
+    bids BLOB NOT NULL,
+    asks BLOB NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_time ON order_books(time);
+CREATE INDEX IF NOT EXISTS idx_symbol_time ON order_books(symbol_id, time);
+
+CREATE TABLE IF NOT EXISTS agg_trades(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time REAL NOT NULL,
+    symbol_id INTEGER NOT NULL,
+    price REAL NOT NULL,
+    quantity REAL NOT NULL,
+    is_buyer_maker BOOLEAN NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_agg_symbol_time ON agg_trades(symbol_id, time);
+CREATE INDEX IF NOT EXISTS idx_agg_time ON agg_trades(time);
+
+CREATE TABLE IF NOT EXISTS klines(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol_id INTEGER NOT NULL,
+    interval TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    close_time INTEGER NOT NULL,
+    open_price REAL NOT NULL,
+    close_price REAL NOT NULL,
+    high_price REAL NOT NULL,
+    low_price REAL NOT NULL,
+    volume REAL NOT NULL,
+    no_of_trades INTEGER NOT NULL,
+    taker_buy_vol REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_klines_symbol_interval_starttime ON klines(symbol_id, interval, start_time);
+
+-- With `KLINE_TABLE_PER_INTERVAL` set, 1s/1m/1h candles go to their own table
+-- instead of `klines`; `klines_all` reads all of them as one.
+CREATE TABLE IF NOT EXISTS klines_1s(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol_id INTEGER NOT NULL,
+    interval TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    close_time INTEGER NOT NULL,
+    open_price REAL NOT NULL,
+    close_price REAL NOT NULL,
+    high_price REAL NOT NULL,
+    low_price REAL NOT NULL,
+    volume REAL NOT NULL,
+    no_of_trades INTEGER NOT NULL,
+    taker_buy_vol REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_klines_1s_symbol_starttime ON klines_1s(symbol_id, start_time);
+
+CREATE TABLE IF NOT EXISTS klines_1m(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol_id INTEGER NOT NULL,
+    interval TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    close_time INTEGER NOT NULL,
+    open_price REAL NOT NULL,
+    close_price REAL NOT NULL,
+    high_price REAL NOT NULL,
+    low_price REAL NOT NULL,
+    volume REAL NOT NULL,
+    no_of_trades INTEGER NOT NULL,
+    taker_buy_vol REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_klines_1m_symbol_starttime ON klines_1m(symbol_id, start_time);
+
+CREATE TABLE IF NOT EXISTS klines_1h(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol_id INTEGER NOT NULL,
+    interval TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    close_time INTEGER NOT NULL,
+    open_price REAL NOT NULL,
+    close_price REAL NOT NULL,
+    high_price REAL NOT NULL,
+    low_price REAL NOT NULL,
+    volume REAL NOT NULL,
+    no_of_trades INTEGER NOT NULL,
+    taker_buy_vol REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_klines_1h_symbol_starttime ON klines_1h(symbol_id, start_time);
+
+CREATE VIEW IF NOT EXISTS klines_all AS
+    SELECT * FROM klines
+    UNION ALL SELECT * FROM klines_1s
+    UNION ALL SELECT * FROM klines_1m
+    UNION ALL SELECT * FROM klines_1h;
+
+CREATE TABLE IF NOT EXISTS funding_rates(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time REAL NOT NULL,
+    symbol_id INTEGER NOT NULL,
+    mark_price REAL NOT NULL,
+    index_price REAL NOT NULL,
+    rate REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_funding_rates_symbol_time ON funding_rates(symbol_id, time);
+
+CREATE TABLE IF NOT EXISTS open_interest(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time REAL NOT NULL,
+    symbol_id INTEGER NOT NULL,
+    oi_value REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_open_interest_symbol_time ON open_interest(symbol_id, time);
+
+CREATE TABLE IF NOT EXISTS liquidations(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time REAL NOT NULL,
+    symbol_id INTEGER NOT NULL,
+    side TEXT NOT NULL,
+    price REAL NOT NULL,
+    quantity REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_liquidations_symbol_time ON liquidations(symbol_id, time);
+
+CREATE TABLE IF NOT EXISTS trade_flow(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol_id INTEGER NOT NULL,
+    bucket_start INTEGER NOT NULL,
+    buy_vol REAL NOT NULL,
+    sell_vol REAL NOT NULL,
+    trade_count INTEGER NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_trade_flow_symbol_bucket ON trade_flow(symbol_id, bucket_start);
+
+-- Order book imbalance sampled per symbol (at most once per `OBI_SAMPLE_MS`).
+CREATE TABLE IF NOT EXISTS orderbook_features(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol_id INTEGER NOT NULL,
+    time REAL NOT NULL,
+    obi REAL NOT NULL,
+    bid_vol REAL NOT NULL,
+    ask_vol REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_orderbook_features_symbol_time ON orderbook_features(symbol_id, time);
+
+-- Binance's rolling 24h statistics, polled per symbol.
+CREATE TABLE IF NOT EXISTS ticker_24h(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time REAL NOT NULL,
+    symbol_id INTEGER NOT NULL,
+    price_change REAL NOT NULL,
+    price_change_pct REAL NOT NULL,
+    last_price REAL NOT NULL,
+    open_price REAL NOT NULL,
+    high_price REAL NOT NULL,
+    low_price REAL NOT NULL,
+    volume REAL NOT NULL,
+    quote_volume REAL NOT NULL,
+    trade_count INTEGER NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_ticker_24h_symbol_time ON ticker_24h(symbol_id, time);
+
+-- Account balances sampled over time; assets aren't symbols, so no symbol_id.
+CREATE TABLE IF NOT EXISTS balances(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time REAL NOT NULL,
+    asset TEXT NOT NULL,
+    free REAL NOT NULL,
+    locked REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_balances_asset_time ON balances(asset, time);
+
+-- Read-only views that join symbol_id back to the ticker, so queries written
+-- against the old `symbol TEXT` columns keep working.
+CREATE VIEW IF NOT EXISTS agg_trades_v AS
+    SELECT t.id, t.time, s.ticker AS symbol, t.price, t.quantity, t.is_buyer_maker
+    FROM agg_trades t JOIN symbols s ON s.id = t.symbol_id;
+
+CREATE VIEW IF NOT EXISTS order_books_v AS
+    SELECT o.id, o.time, s.ticker AS symbol, o.bids, o.asks
+    FROM order_books o JOIN symbols s ON s.id = o.symbol_id;
+
+-- Recreated so files from before the per-interval tables read `klines_all`.
+DROP VIEW IF EXISTS klines_v;
+CREATE VIEW klines_v AS
+    SELECT k.id, s.ticker AS symbol, k.interval, k.start_time, k.close_time,
+           k.open_price, k.close_price, k.high_price, k.low_price, k.volume,
+           k.no_of_trades, k.taker_buy_vol
+    FROM klines_all k JOIN symbols s ON s.id = k.symbol_id;
+
+CREATE VIEW IF NOT EXISTS funding_rates_v AS
+    SELECT f.id, f.time, s.ticker AS symbol, f.mark_price, f.index_price, f.rate
+    FROM funding_rates f JOIN symbols s ON s.id = f.symbol_id;
+
+CREATE VIEW IF NOT EXISTS open_interest_v AS
+    SELECT o.id, o.time, s.ticker AS symbol, o.oi_value
+    FROM open_interest o JOIN symbols s ON s.id = o.symbol_id;
+
+CREATE VIEW IF NOT EXISTS liquidations_v AS
+    SELECT l.id, l.time, s.ticker AS symbol, l.side, l.price, l.quantity
+    FROM liquidations l JOIN symbols s ON s.id = l.symbol_id;
+
+CREATE VIEW IF NOT EXISTS trade_flow_v AS
+    SELECT f.id, s.ticker AS symbol, f.bucket_start, f.buy_vol, f.sell_vol, f.trade_count
+    FROM trade_flow f JOIN symbols s ON s.id = f.symbol_id;
+
+CREATE VIEW IF NOT EXISTS orderbook_features_v AS
+    SELECT f.id, f.time, s.ticker AS symbol, f.obi, f.bid_vol, f.ask_vol
+    FROM orderbook_features f JOIN symbols s ON s.id = f.symbol_id;
+
+CREATE VIEW IF NOT EXISTS ticker_24h_v AS
+    SELECT t.id, t.time, s.ticker AS symbol, t.price_change, t.price_change_pct,
+           t.last_price, t.open_price, t.high_price, t.low_price, t.volume,
+           t.quote_volume, t.trade_count
+    FROM ticker_24h t JOIN symbols s ON s.id = t.symbol_id;
@@ -0,0 +1,3 @@
+-- Replayed trades after a reconnect are ignored on insert. The column itself
+-- is added by the runner, since it may already exist in older files.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_agg_symbol_trade_id ON agg_trades(symbol_id, agg_trade_id);
@@ -0,0 +1,21 @@
+-- Every order the bot placed or tried to place, with the exchange's answer.
+CREATE TABLE IF NOT EXISTS orders(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_ts REAL NOT NULL,
+    symbol_id INTEGER NOT NULL,
+    side TEXT NOT NULL,
+    qty REAL NOT NULL,
+    type TEXT NOT NULL,
+    order_id INTEGER,
+    status TEXT NOT NULL,
+    executed_qty REAL,
+    cummulative_quote_qty REAL,
+    error TEXT,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id)
+);
+CREATE INDEX IF NOT EXISTS idx_orders_symbol_ts ON orders(symbol_id, client_ts);
+
+CREATE VIEW IF NOT EXISTS orders_v AS
+    SELECT o.id, o.client_ts, s.ticker AS symbol, o.side, o.qty, o.type, o.order_id,
+           o.status, o.executed_qty, o.cummulative_quote_qty, o.error
+    FROM orders o JOIN symbols s ON s.id = o.symbol_id;
@@ -0,0 +1,10 @@
+-- `trade_time` (Binance's `T`) is added by the runner. `time` stays the local
+-- receipt time, so their difference is how far ingestion trails the exchange.
+ALTER TABLE agg_trades ADD COLUMN latency_ms REAL
+    GENERATED ALWAYS AS ((time - trade_time) * 1000.0) VIRTUAL;
+
+DROP VIEW IF EXISTS agg_trades_v;
+CREATE VIEW agg_trades_v AS
+    SELECT t.id, t.time, s.ticker AS symbol, t.price, t.quantity, t.is_buyer_maker,
+           t.trade_time, t.latency_ms
+    FROM agg_trades t JOIN symbols s ON s.id = t.symbol_id;
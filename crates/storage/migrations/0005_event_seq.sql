@@ -0,0 +1,27 @@
+-- `seq` (the gateway's publish order, restarting at 1 with the process) is
+-- added by the runner. The views are recreated to show it.
+DROP VIEW IF EXISTS agg_trades_v;
+CREATE VIEW agg_trades_v AS
+    SELECT t.id, t.time, s.ticker AS symbol, t.price, t.quantity, t.is_buyer_maker,
+           t.trade_time, t.latency_ms, t.seq
+    FROM agg_trades t JOIN symbols s ON s.id = t.symbol_id;
+
+DROP VIEW IF EXISTS order_books_v;
+CREATE VIEW order_books_v AS
+    SELECT o.id, o.time, s.ticker AS symbol, o.bids, o.asks, o.seq
+    FROM order_books o JOIN symbols s ON s.id = o.symbol_id;
+
+DROP VIEW IF EXISTS funding_rates_v;
+CREATE VIEW funding_rates_v AS
+    SELECT f.id, f.time, s.ticker AS symbol, f.mark_price, f.index_price, f.rate, f.seq
+    FROM funding_rates f JOIN symbols s ON s.id = f.symbol_id;
+
+DROP VIEW IF EXISTS open_interest_v;
+CREATE VIEW open_interest_v AS
+    SELECT o.id, o.time, s.ticker AS symbol, o.oi_value, o.seq
+    FROM open_interest o JOIN symbols s ON s.id = o.symbol_id;
+
+DROP VIEW IF EXISTS liquidations_v;
+CREATE VIEW liquidations_v AS
+    SELECT l.id, l.time, s.ticker AS symbol, l.side, l.price, l.quantity, l.seq
+    FROM liquidations l JOIN symbols s ON s.id = l.symbol_id;
@@ -0,0 +1,5 @@
+-- `symbols.base_asset` and `quote_asset` (from exchangeInfo, so `ETHBTC` is
+-- ETH/BTC rather than a guess from the ticker's suffix) are added by the
+-- runner. Rows created before this, or while exchangeInfo was unreachable,
+-- stay NULL until the ticker is next looked up with its assets known.
+CREATE INDEX IF NOT EXISTS idx_symbols_quote_asset ON symbols(quote_asset);
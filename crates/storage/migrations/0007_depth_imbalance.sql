@@ -0,0 +1,7 @@
+-- `orderbook_features.obi_1` and `obi_5`, the imbalance of the best 1 and 5
+-- levels next to the full-book `obi`, are added by the runner. They are NULL
+-- in rows sampled before this.
+DROP VIEW IF EXISTS orderbook_features_v;
+CREATE VIEW orderbook_features_v AS
+    SELECT f.id, f.time, s.ticker AS symbol, f.obi, f.obi_1, f.obi_5, f.bid_vol, f.ask_vol
+    FROM orderbook_features f JOIN symbols s ON s.id = f.symbol_id;
@@ -0,0 +1,16 @@
+-- One row per symbol and funding settlement, with the rate and mark price
+-- last streamed before it. A long position of `q` pays `q * mark_price * rate`
+-- at `funding_time` (ms), a short receives it; negative rates reverse that.
+CREATE TABLE IF NOT EXISTS funding_events(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    funding_time INTEGER NOT NULL,
+    symbol_id INTEGER NOT NULL,
+    rate REAL NOT NULL,
+    mark_price REAL NOT NULL,
+    FOREIGN KEY(symbol_id) REFERENCES symbols(id),
+    UNIQUE(symbol_id, funding_time)
+);
+
+CREATE VIEW IF NOT EXISTS funding_events_v AS
+    SELECT f.id, f.funding_time, s.ticker AS symbol, f.rate, f.mark_price
+    FROM funding_events f JOIN symbols s ON s.id = f.symbol_id;
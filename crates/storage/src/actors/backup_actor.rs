@@ -1,17 +1,106 @@
 use anyhow::bail;
 use async_trait::async_trait;
-use chrono::Utc;
 use common::actors::{Actor, ActorType, ControlMessage};
-use std::env;
+use common::clock::Clock;
+use common::paths::{Paths, weekly_stem};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
 use tokio::process::Command;
-use tokio::sync::mpsc;
-use tracing::{error, info};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::{actors::BackupScriptError, db::get_previous_iso_week_components};
+use crate::data_manager::TABLES;
+use crate::db::{self, ReaderOptions};
+use crate::error::StorageError;
+use crate::{actors::BackupScriptError, db::WeekAnchor};
+
+/// What went into a weekly backup, sent to the notifier once it is uploaded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeekSummary {
+    pub file_name: String,
+    /// Row count of each table in `TABLES`.
+    pub rows: Vec<(&'static str, i64)>,
+    /// Size of the `.sql.zst` archive, if it could be read.
+    pub compressed_bytes: Option<u64>,
+    /// rclone remote the archive was copied to, as logged by the script.
+    pub destination: Option<String>,
+    /// Holes in each symbol's 1m klines: (number of holes, candles missing).
+    pub kline_gaps: (i64, i64),
+}
+
+impl fmt::Display for WeekSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Weekly backup done: {}", self.file_name)?;
+        for (table, rows) in &self.rows {
+            writeln!(f, "  {} {}", table, rows)?;
+        }
+        match self.compressed_bytes {
+            Some(bytes) => writeln!(f, "Archive: {:.1} MiB", bytes as f64 / 1_048_576.0)?,
+            None => writeln!(f, "Archive: size unknown")?,
+        }
+        writeln!(
+            f,
+            "Uploaded to: {}",
+            self.destination.as_deref().unwrap_or("unknown")
+        )?;
+        match self.kline_gaps {
+            (0, _) => write!(f, "Gaps: none in 1m klines"),
+            (holes, missing) => write!(
+                f,
+                "Gaps: {} in 1m klines ({} candles missing)",
+                holes, missing
+            ),
+        }
+    }
+}
+
+/// Pulls the rclone remote out of the script's "Uploading to remote storage: X" line.
+fn upload_destination(stdout: &str) -> Option<String> {
+    stdout.lines().find_map(|line| {
+        line.split_once("Uploading to remote storage:")
+            .map(|(_, dest)| dest.trim().to_string())
+    })
+}
+
+/// Files with a backup in progress. Two rotations racing on the same week
+/// each spawn an actor; running `dump_db.sh` twice on one file would corrupt
+/// the archive or upload it twice.
+static RUNNING_BACKUPS: LazyLock<Mutex<HashSet<String>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Holds a file in `RUNNING_BACKUPS` until dropped.
+struct BackupClaim(String);
+
+impl BackupClaim {
+    /// `None` if a backup of `path` is already running.
+    fn claim(path: &str) -> Option<Self> {
+        RUNNING_BACKUPS
+            .lock()
+            .expect("running backups poisoned")
+            .insert(path.to_string())
+            .then(|| Self(path.to_string()))
+    }
+}
+
+impl Drop for BackupClaim {
+    fn drop(&mut self) {
+        RUNNING_BACKUPS
+            .lock()
+            .expect("running backups poisoned")
+            .remove(&self.0);
+    }
+}
 
 pub struct BackupOneShotActor {
     id: Uuid,
+    clock: Arc<dyn Clock>,
+    paths: Paths,
+    utils_dir: String,
+    notification_tx: Option<broadcast::Sender<String>>,
+    week_anchor: WeekAnchor,
 }
 
 #[async_trait]
@@ -26,16 +115,41 @@ impl Actor for BackupOneShotActor {
     async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
         let hearbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
 
-        let data_folder_env = env::var("WORKDIR").expect("WORKDIR must be set");
-        let data_folder = format!("{}/sqlitedata", data_folder_env);
+        let (prev_year, prev_week) = self.week_anchor.previous(self.clock.now());
+        let file_stem = weekly_stem(prev_year, prev_week);
 
-        let (prev_year, prev_week) = get_previous_iso_week_components(Utc::now());
+        let db_path = self.paths.weekly_db(prev_year, prev_week);
+        let archive_path = self.paths.archived_dump(prev_year, prev_week);
+        let claim = BackupClaim::claim(&db_path.display().to_string());
+        let skip = if claim.is_none() {
+            Some("a backup of it is already running")
+        } else if !db_path.exists() && archive_path.exists() {
+            Some("it is already archived")
+        } else {
+            None
+        };
+        if let Some(reason) = skip {
+            info!("Skipping backup of {}.db: {}.", file_stem, reason);
+            hearbeat_handle.abort();
+            let _ = supervisor_tx.send(ControlMessage::Shutdown(self.id)).await;
+            return Ok(());
+        }
 
-        let utils_path = env::var("UTILS").expect("UTILS must be set");
+        // The script moves the file away, so read it first.
+        let mut summary = match Self::collect_stats(&db_path, &file_stem).await {
+            Ok(summary) => summary,
+            Err(e) => {
+                warn!("Could not read stats of {}.db: {}", file_stem, e);
+                WeekSummary {
+                    file_name: format!("{}.db", file_stem),
+                    ..Default::default()
+                }
+            }
+        };
 
-        let result = Command::new(format!("{}/dump_db.sh", utils_path))
-            .arg(data_folder)
-            .arg(format!("crypto_{}_{:02}.db", prev_year, prev_week))
+        let result = Command::new(format!("{}/dump_db.sh", self.utils_dir))
+            .arg(self.paths.data_dir())
+            .arg(format!("{}.db", file_stem))
             .output()
             .await;
 
@@ -45,6 +159,13 @@ impl Actor for BackupOneShotActor {
                     info!("Backup finished successfully!");
                     let stdout = String::from_utf8_lossy(&output.stdout);
                     info!("{}", stdout);
+
+                    summary.destination = upload_destination(&stdout);
+                    summary.compressed_bytes = tokio::fs::metadata(&archive_path)
+                        .await
+                        .ok()
+                        .map(|meta| meta.len());
+                    self.notify(summary.to_string());
                 } else {
                     let code = output.status.code().unwrap_or(-1);
 
@@ -53,11 +174,16 @@ impl Actor for BackupOneShotActor {
 
                     error!("Backup failed: {}", error_enum);
                     error!("Script Stderr: {}", stderr);
+                    self.notify(format!("Backup of {}.db failed: {}", file_stem, error_enum));
                     hearbeat_handle.abort();
                     bail!(error_enum);
                 }
             }
             Err(err) => {
+                self.notify(format!(
+                    "Backup of {}.db could not start: {}",
+                    file_stem, err
+                ));
                 bail!("Failed to execute command: {}", err);
             }
         }
@@ -74,7 +200,106 @@ impl Actor for BackupOneShotActor {
 }
 
 impl BackupOneShotActor {
-    pub fn new() -> Self {
-        Self { id: Uuid::new_v4() }
+    /// `clock` must be the rotating pool's, so "previous week" is the file it
+    /// just rotated away from. The file is looked up in `paths` and
+    /// archived with `utils_dir/dump_db.sh`.
+    pub fn new(clock: Arc<dyn Clock>, paths: Paths, utils_dir: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            clock,
+            paths,
+            utils_dir,
+            notification_tx: None,
+            week_anchor: WeekAnchor::default(),
+        }
+    }
+
+    /// Numbering of the weekly files, which must match the rotating pool's.
+    pub fn with_week_anchor(mut self, week_anchor: WeekAnchor) -> Self {
+        self.week_anchor = week_anchor;
+        self
+    }
+
+    /// Sends the weekly summary, or the failure, to `tx`.
+    pub fn with_notifier(mut self, tx: broadcast::Sender<String>) -> Self {
+        self.notification_tx = Some(tx);
+        self
+    }
+
+    fn notify(&self, msg: String) {
+        if let Some(ref tx) = self.notification_tx
+            && tx.send(msg).is_err()
+        {
+            warn!("No notification listener for the backup report.");
+        }
+    }
+
+    async fn collect_stats(path: &Path, file_stem: &str) -> Result<WeekSummary, StorageError> {
+        let pool = db::open_reader(&path.display().to_string(), &ReaderOptions::default()).await?;
+
+        let mut rows = Vec::with_capacity(TABLES.len());
+        for &table in TABLES {
+            let count = sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {}", table))
+                .fetch_one(&pool)
+                .await?;
+            rows.push((table, count));
+        }
+
+        let kline_gaps = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COUNT(*), COALESCE(SUM(step / 60000 - 1), 0) FROM (
+                 SELECT start_time - LAG(start_time) OVER (
+                     PARTITION BY symbol_id ORDER BY start_time
+                 ) AS step
+                 FROM klines_all WHERE interval = '1m'
+             ) WHERE step > 60000",
+        )
+        .fetch_one(&pool)
+        .await?;
+        pool.close().await;
+
+        Ok(WeekSummary {
+            file_name: format!("{}.db", file_stem),
+            rows,
+            compressed_bytes: None,
+            destination: None,
+            kline_gaps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_claim_per_file_at_a_time() {
+        let path = format!("/tmp/{}/crypto_2026_01.db", Uuid::new_v4());
+        let first = BackupClaim::claim(&path).unwrap();
+        assert!(BackupClaim::claim(&path).is_none());
+        assert!(BackupClaim::claim(&path.replace("_01", "_02")).is_some());
+        drop(first);
+        assert!(BackupClaim::claim(&path).is_some());
+    }
+
+    #[test]
+    fn test_summary_reads_destination_and_formats() {
+        let stdout = "[2026-01-05 00:00:01] [INFO] Compression complete.\n\
+                      [2026-01-05 00:00:02] [INFO] Uploading to remote storage: my_drive:backups\n\
+                      [2026-01-05 00:00:09] [INFO] Upload successful.\n";
+        let summary = WeekSummary {
+            file_name: "crypto_2026_01.db".to_string(),
+            rows: vec![("agg_trades", 10), ("klines", 2)],
+            compressed_bytes: Some(3 * 1_048_576),
+            destination: upload_destination(stdout),
+            kline_gaps: (2, 5),
+        };
+
+        assert_eq!(summary.destination.as_deref(), Some("my_drive:backups"));
+        assert_eq!(
+            summary.to_string(),
+            "Weekly backup done: crypto_2026_01.db\n  agg_trades 10\n  klines 2\n\
+             Archive: 3.0 MiB\nUploaded to: my_drive:backups\n\
+             Gaps: 2 in 1m klines (5 candles missing)"
+        );
     }
 }
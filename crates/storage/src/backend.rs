@@ -0,0 +1,116 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use common::models::{AggTradeInsert, KlineInsert, OrderBookInsert};
+use tokio::sync::oneshot;
+
+use crate::combined_writer::StagedRows;
+use crate::data_manager::{DataManager, WriterHandle};
+use crate::error::StorageError;
+use crate::repositories::{KlinesRepository, OrderBookRepository};
+
+/// A service's DB writer task, as handed to `Storage::spawn_writer`.
+pub type WriterTask = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Where the recording services write market data.
+///
+/// `DataManager`, with its weekly SQLite files, is the implementation used
+/// here; a server database such as TimescaleDB can replace it by implementing
+/// this trait. Weekly rotation, and the backup that follows it, happen inside
+/// `DataManager`'s own writes, so a backend that keeps one database has
+/// nothing to do for them.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn insert_agg_trades(&self, trades: &[AggTradeInsert]) -> Result<(), StorageError>;
+
+    async fn insert_klines(&self, klines: &[KlineInsert]) -> Result<(), StorageError>;
+
+    /// Inserts the `klines` not already stored for their symbol, interval and
+    /// start time, returning how many were added. Used for REST backfills.
+    async fn insert_missing_klines(&self, klines: &[KlineInsert]) -> Result<u64, StorageError>;
+
+    /// Start time of the newest stored `interval` candle of `symbol`.
+    async fn latest_kline_start(
+        &self,
+        symbol: &str,
+        interval: &str,
+    ) -> Result<Option<i64>, StorageError>;
+
+    async fn insert_order_books(&self, books: &[OrderBookInsert]) -> Result<(), StorageError>;
+
+    /// Whether events for `symbol` should be written. Every symbol by default.
+    fn is_recording(&self, _symbol: &str) -> bool {
+        true
+    }
+
+    /// Runs a service's DB writer. By default it is simply spawned; the
+    /// handle reports when it has exited.
+    fn spawn_writer(&self, writer: WriterTask) -> WriterHandle {
+        let (done_tx, done_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            writer.await;
+            let _ = done_tx.send(());
+        });
+        WriterHandle(done_rx)
+    }
+}
+
+#[async_trait]
+impl Storage for DataManager {
+    async fn insert_agg_trades(&self, trades: &[AggTradeInsert]) -> Result<(), StorageError> {
+        let sqlite = self.trades_in_sqlite();
+        match self.combined_writer() {
+            Some(combined) if sqlite => {
+                combined
+                    .write(StagedRows::AggTrades(trades.to_vec()))
+                    .await?
+            }
+            _ => self.agg_trade_sink().insert_batch(trades).await?,
+        }
+        if sqlite {
+            self.analyze_after(trades.len()).await;
+        }
+        Ok(())
+    }
+
+    async fn insert_klines(&self, klines: &[KlineInsert]) -> Result<(), StorageError> {
+        KlinesRepository::insert_batch(self, klines).await?;
+        self.analyze_after(klines.len()).await;
+        Ok(())
+    }
+
+    async fn insert_missing_klines(&self, klines: &[KlineInsert]) -> Result<u64, StorageError> {
+        KlinesRepository::insert_missing(self, klines).await
+    }
+
+    async fn latest_kline_start(
+        &self,
+        symbol: &str,
+        interval: &str,
+    ) -> Result<Option<i64>, StorageError> {
+        KlinesRepository::latest_start_time(self, symbol, interval).await
+    }
+
+    async fn insert_order_books(&self, books: &[OrderBookInsert]) -> Result<(), StorageError> {
+        match self.combined_writer() {
+            Some(combined) => {
+                combined
+                    .write(StagedRows::OrderBooks(books.to_vec()))
+                    .await?
+            }
+            None => OrderBookRepository::insert_batch(self, books).await?,
+        }
+        self.analyze_after(books.len()).await;
+        Ok(())
+    }
+
+    fn is_recording(&self, symbol: &str) -> bool {
+        DataManager::is_recording(self, symbol)
+    }
+
+    /// Tracked, so `DataManager::flush_writers` waits for it at shutdown.
+    fn spawn_writer(&self, writer: WriterTask) -> WriterHandle {
+        DataManager::spawn_writer(self, writer)
+    }
+}
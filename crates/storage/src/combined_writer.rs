@@ -0,0 +1,242 @@
+use std::sync::Weak;
+use std::time::Duration;
+
+use common::models::{AggTradeInsert, OrderBookInsert};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, warn};
+
+use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::repositories::{AggTradeRepository, OrderBookRepository};
+use crate::write_queue::WritePriority;
+
+/// One service flush waiting for the next combined transaction.
+pub(crate) enum StagedRows {
+    AggTrades(Vec<AggTradeInsert>),
+    OrderBooks(Vec<OrderBookInsert>),
+}
+
+impl StagedRows {
+    fn len(&self) -> usize {
+        match self {
+            Self::AggTrades(trades) => trades.len(),
+            Self::OrderBooks(books) => books.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn priority(&self) -> WritePriority {
+        match self {
+            Self::AggTrades(_) => WritePriority::Trades,
+            Self::OrderBooks(_) => WritePriority::OrderBooks,
+        }
+    }
+}
+
+type Staged = (StagedRows, oneshot::Sender<Result<(), StorageError>>);
+
+/// Commits the trade and order book batches staged during each tick in a
+/// single transaction, so services flushing around the same time share one
+/// WAL commit (and fsync) instead of paying for one each.
+///
+/// A staged batch's `write` returns once its rows have committed or failed.
+/// If the shared transaction fails, each batch of that tick is committed on
+/// its own, so only the batch at fault gets an error, which its service
+/// retries or drops as usual.
+pub struct CombinedWriter {
+    tx: mpsc::UnboundedSender<Staged>,
+}
+
+impl CombinedWriter {
+    /// Starts the committing task. It exits once `data_manager` is dropped.
+    pub(crate) fn spawn(data_manager: Weak<DataManager>, tick: Duration) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(commit_every_tick(data_manager, rx, tick));
+        Self { tx }
+    }
+
+    /// Stages `rows` for the next tick and waits for its commit.
+    pub(crate) async fn write(&self, rows: StagedRows) -> Result<(), StorageError> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let (done_tx, done_rx) = oneshot::channel();
+        self.tx.send((rows, done_tx)).map_err(|_| stopped())?;
+        done_rx.await.map_err(|_| stopped())?
+    }
+}
+
+fn stopped() -> StorageError {
+    StorageError::CombinedWrite {
+        message: "combined writer stopped".to_string(),
+        retryable: false,
+    }
+}
+
+async fn commit_every_tick(
+    data_manager: Weak<DataManager>,
+    mut rx: mpsc::UnboundedReceiver<Staged>,
+    tick: Duration,
+) {
+    let mut ticker = time::interval(tick);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut staged: Vec<Staged> = Vec::new();
+    loop {
+        tokio::select! {
+            next = rx.recv() => match next {
+                Some(batch) => staged.push(batch),
+                None => return,
+            },
+            _ = ticker.tick() => {
+                if staged.is_empty() {
+                    continue;
+                }
+                let Some(data_manager) = data_manager.upgrade() else {
+                    return;
+                };
+                let batches = std::mem::take(&mut staged);
+                let (rows, waiters): (Vec<StagedRows>, Vec<_>) = batches.into_iter().unzip();
+                match commit(&data_manager, &rows).await {
+                    Ok(()) => {
+                        debug!("Committed {} batches in one transaction.", rows.len());
+                        for done in waiters {
+                            let _ = done.send(Ok(()));
+                        }
+                    }
+                    Err(e) if rows.len() > 1 => {
+                        warn!(
+                            "Combined commit of {} batches failed, committing each alone: {}",
+                            rows.len(),
+                            e
+                        );
+                        for (batch, done) in rows.iter().zip(waiters) {
+                            let result = commit(&data_manager, std::slice::from_ref(batch)).await;
+                            let _ = done.send(result.map_err(|e| failed(&e)));
+                        }
+                    }
+                    Err(e) => {
+                        for done in waiters {
+                            let _ = done.send(Err(failed(&e)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `e` as handed to a staged batch's waiter.
+fn failed(e: &StorageError) -> StorageError {
+    StorageError::CombinedWrite {
+        message: e.to_string(),
+        retryable: e.is_retryable(),
+    }
+}
+
+async fn commit(data_manager: &DataManager, rows: &[StagedRows]) -> Result<(), StorageError> {
+    let priority = rows
+        .iter()
+        .map(StagedRows::priority)
+        .min()
+        .unwrap_or(WritePriority::Other);
+    let _turn = data_manager.write_turn(priority).await;
+    with_busy_retry("Combined", || try_commit(data_manager, rows)).await
+}
+
+async fn try_commit(data_manager: &DataManager, rows: &[StagedRows]) -> Result<(), StorageError> {
+    let mut tickers = Vec::new();
+    for batch in rows {
+        match batch {
+            StagedRows::AggTrades(trades) => {
+                tickers.extend(trades.iter().map(|t| t.symbol.as_str()))
+            }
+            StagedRows::OrderBooks(books) => {
+                tickers.extend(books.iter().map(|b| b.symbol.as_str()))
+            }
+        }
+    }
+    let (pool, ids) = data_manager.pool_with_symbol_ids(tickers).await?;
+    let mut ids = ids.as_slice();
+
+    let mut tx = pool.begin().await?;
+    for batch in rows {
+        let (batch_ids, rest) = ids.split_at(batch.len());
+        ids = rest;
+        match batch {
+            StagedRows::AggTrades(trades) => {
+                AggTradeRepository::insert_rows(&mut tx, trades, batch_ids).await?
+            }
+            StagedRows::OrderBooks(books) => {
+                OrderBookRepository::insert_rows(&mut tx, books, batch_ids).await?
+            }
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Storage;
+    use crate::db::StorageConfig;
+    use crate::test_support::{TempDir, manager, trade};
+
+    #[tokio::test]
+    async fn test_batches_of_one_tick_commit_together() {
+        let dir = TempDir::new("combined");
+        let config = StorageConfig {
+            combined_writer: Some(Duration::from_millis(100)),
+            ..StorageConfig::default()
+        };
+        let manager = manager(&dir, config).await;
+
+        let trade = trade(1, 1.0, "BTCUSDT", 100.0);
+        let book = OrderBookInsert {
+            time: 1.0,
+            symbol: "ETHUSDT".to_string(),
+            bids: vec![1],
+            asks: vec![2],
+            seq: None,
+        };
+        let (trades, books) = tokio::join!(
+            manager.insert_agg_trades(std::slice::from_ref(&trade)),
+            manager.insert_order_books(std::slice::from_ref(&book)),
+        );
+        trades.unwrap();
+        books.unwrap();
+
+        let stats = manager.stats().await.unwrap();
+        assert_eq!(stats.rows("agg_trades"), Some(1));
+        assert_eq!(stats.rows("order_books"), Some(1));
+
+        // A batch the database rejects fails alone; the rest of its tick commits.
+        let (pool, _) = manager.pool_rotator.get_pool().await.unwrap();
+        sqlx::query(
+            "CREATE TRIGGER reject_book BEFORE INSERT ON order_books WHEN NEW.time < 0
+             BEGIN SELECT RAISE(ABORT, 'rejected book'); END",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let bad_book = OrderBookInsert { time: -1.0, ..book };
+        let next_trade = AggTradeInsert {
+            agg_trade_id: 2,
+            ..trade
+        };
+        let (trades, books) = tokio::join!(
+            manager.insert_agg_trades(std::slice::from_ref(&next_trade)),
+            manager.insert_order_books(std::slice::from_ref(&bad_book)),
+        );
+        trades.unwrap();
+        assert!(matches!(books, Err(StorageError::CombinedWrite { .. })));
+        let stats = manager.stats().await.unwrap();
+        assert_eq!(stats.rows("agg_trades"), Some(2));
+        assert_eq!(stats.rows("order_books"), Some(1));
+    }
+}
@@ -1,34 +1,825 @@
 use common::actors::ControlMessage;
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use common::clock::{Clock, SystemClock};
+use common::models::OrderBook;
+use common::paths::Paths;
+use common::symbols::{DisabledSymbols, SymbolAssets};
+use sqlx::pool::PoolConnection;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Sqlite, SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinSet;
+use tracing::warn;
 
-use crate::{db::RotatingPool, symbol_manager::SymbolManager};
+use crate::combined_writer::CombinedWriter;
+use crate::db::{self, CheckpointInfo, CheckpointMode, ReaderOptions, RotatingPool, StorageConfig};
+use crate::error::StorageError;
+use crate::symbol_manager::SymbolManager;
+use crate::trade_sink::{AggTradeBackend, AggTradeSink, ArrowTradeSink};
+use crate::write_queue::{WritePriority, WriteQueue, WriteTurn};
+
+/// How long after a rotation `latest_price` and `latest_orderbook` still
+/// look in last week's file for a symbol the new one has no data for.
+const LATEST_FALLBACK_WINDOW: chrono::Duration = chrono::Duration::minutes(15);
+
+/// Every table the ingestion services write to.
+pub const TABLES: &[&str] = &[
+    "agg_trades",
+    "order_books",
+    "klines",
+    "klines_1s",
+    "klines_1m",
+    "klines_1h",
+    "funding_rates",
+    "funding_events",
+    "open_interest",
+    "liquidations",
+    "trade_flow",
+    "orderbook_features",
+    "ticker_24h",
+    "balances",
+    "orders",
+];
+
+/// How long a stopping service waits for its DB writer to flush the last
+/// partial batch.
+pub const WRITER_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Signals when a writer started with `Storage::spawn_writer` has exited.
+pub struct WriterHandle(pub(crate) oneshot::Receiver<()>);
+
+impl WriterHandle {
+    /// Waits up to `timeout` for the writer to flush and exit. Drop the
+    /// writer's sender first. Returns false if it was still running.
+    pub async fn drain(self, timeout: Duration) -> bool {
+        // A closed channel means the writer task ended without signalling
+        // (it panicked); there is nothing left to wait for either way.
+        tokio::time::timeout(timeout, self.0).await.is_ok()
+    }
+}
+
+/// A pooled connection with `prev` attached by `with_attached`. Unless it
+/// was detached, it is closed on drop instead of going back to the pool, so
+/// an error, a panic in the caller's closure or a cancelled future can't
+/// leave `prev` attached for the next user.
+struct AttachedConnection {
+    conn: PoolConnection<Sqlite>,
+    detached: bool,
+}
+
+impl Drop for AttachedConnection {
+    fn drop(&mut self) {
+        if !self.detached {
+            self.conn.close_on_drop();
+        }
+    }
+}
+
+/// Snapshot of the active weekly database.
+#[derive(Debug, Clone)]
+pub struct DbStats {
+    /// Row count of each table in `TABLES`.
+    pub rows: Vec<(&'static str, i64)>,
+    /// `page_count * page_size` of the main database file.
+    pub file_bytes: i64,
+    /// On-disk size of the `-wal` file, 0 right after a checkpoint truncates it.
+    pub wal_bytes: u64,
+}
+
+impl DbStats {
+    pub fn rows(&self, table: &str) -> Option<i64> {
+        self.rows.iter().find(|(t, _)| *t == table).map(|(_, n)| *n)
+    }
+}
+
+impl std::fmt::Display for DbStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "db {:.1} MiB, wal {:.1} MiB",
+            self.file_bytes as f64 / 1_048_576.0,
+            self.wal_bytes as f64 / 1_048_576.0
+        )?;
+        for (table, rows) in &self.rows {
+            write!(f, ", {} {}", table, rows)?;
+        }
+        Ok(())
+    }
+}
 
 pub struct DataManager {
     pub pool_rotator: RotatingPool,
     symbol_manager: SymbolManager,
+    writers: Mutex<JoinSet<()>>,
+    disabled_symbols: DisabledSymbols,
+    write_queue: Option<WriteQueue>,
+    arrow_trades: Option<ArrowTradeSink>,
+    combined_writer: Option<CombinedWriter>,
 }
 
 impl DataManager {
     pub async fn new(
-        data_folder: String,
+        paths: impl Into<Paths>,
         supervisor_tx: mpsc::Sender<ControlMessage>,
-    ) -> Result<Arc<Self>, sqlx::Error> {
-        let pool_rotator = RotatingPool::new(data_folder, supervisor_tx).await?;
-        Ok(Arc::new(Self {
+        config: StorageConfig,
+    ) -> Result<Arc<Self>, StorageError> {
+        Self::new_with_clock(paths, supervisor_tx, config, Arc::new(SystemClock)).await
+    }
+
+    /// Like `new`, but weekly rotation and backups follow `clock`, so the whole
+    /// rotation path can be driven deterministically (tests, accelerated replay).
+    pub async fn new_with_clock(
+        paths: impl Into<Paths>,
+        supervisor_tx: mpsc::Sender<ControlMessage>,
+        config: StorageConfig,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Arc<Self>, StorageError> {
+        let paths = paths.into();
+        let write_queue = config.shared_writer.then(WriteQueue::spawn);
+        let combined_tick = config.combined_writer;
+        let arrow_trades = (config.agg_trade_sink == AggTradeBackend::Arrow)
+            .then(|| ArrowTradeSink::new(paths.arrow_dir(), config.week_anchor(), clock.clone()));
+        let pool_rotator =
+            RotatingPool::new_with_clock(paths, supervisor_tx, config, clock).await?;
+        Ok(Arc::new_cyclic(|manager| Self {
             pool_rotator,
             symbol_manager: SymbolManager::new(),
+            writers: Mutex::new(JoinSet::new()),
+            disabled_symbols: DisabledSymbols::new(),
+            write_queue,
+            arrow_trades,
+            combined_writer: combined_tick.map(|tick| CombinedWriter::spawn(manager.clone(), tick)),
         }))
     }
 
-    pub async fn get_symbol_id(&self, ticker: &str) -> Result<i64, sqlx::Error> {
+    pub async fn get_symbol_id(&self, ticker: &str) -> Result<i64, StorageError> {
+        let (_, ids) = self.pool_with_symbol_ids([ticker]).await?;
+        Ok(ids[0])
+    }
+
+    /// The current file's pool and the id of each of `tickers` in that same
+    /// file, in order. Call it before `begin()`: a ticker missing from the
+    /// cache is looked up on a second connection, which would stall behind
+    /// the transaction's write lock until the pool times out.
+    pub(crate) fn pool_with_symbol_ids<'a>(
+        &self,
+        tickers: impl IntoIterator<Item = &'a str>,
+    ) -> impl Future<Output = Result<(SqlitePool, Vec<i64>), StorageError>> + Send {
+        // Collected up front so the future doesn't hold the caller's iterator.
+        self.resolve_symbol_ids(tickers.into_iter().collect())
+    }
+
+    async fn resolve_symbol_ids(
+        &self,
+        tickers: Vec<&str>,
+    ) -> Result<(SqlitePool, Vec<i64>), StorageError> {
+        let (pool, week, _) = self.pool_rotator.get_weekly_pool().await?;
+        let aliases = &self.pool_rotator.config().symbol_aliases;
+        let mut ids = Vec::with_capacity(tickers.len());
+        for ticker in tickers {
+            let ticker = aliases.canonical(ticker);
+            ids.push(
+                self.symbol_manager
+                    .get_or_create_id(pool.clone(), week, &ticker)
+                    .await?,
+            );
+        }
+        Ok((pool, ids))
+    }
+
+    /// Base and quote assets of each ticker (uppercase), as `exchangeInfo`
+    /// lists them, stored in `symbols` as each ticker is first resolved. Call
+    /// it before the services start writing.
+    pub fn set_symbol_assets(&self, assets: HashMap<String, SymbolAssets>) {
+        self.symbol_manager.set_assets(assets);
+    }
+
+    /// Sends weekly backup summaries and failures to `tx`.
+    pub fn set_notifier(&self, tx: broadcast::Sender<String>) {
+        self.pool_rotator.set_notifier(tx);
+    }
+
+    /// Directory of `dump_db.sh`, run on each week's file after rotation.
+    pub fn set_backup_scripts(&self, utils_dir: String) {
+        self.pool_rotator.set_backup_scripts(utils_dir);
+    }
+
+    /// Symbols paused at runtime. Share it with whatever toggles them.
+    pub fn disabled_symbols(&self) -> &DisabledSymbols {
+        &self.disabled_symbols
+    }
+
+    /// Whether events for `symbol` should be written. Services check this
+    /// before handing an event to their DB writer.
+    pub fn is_recording(&self, symbol: &str) -> bool {
+        !self.disabled_symbols.is_disabled(symbol)
+    }
+
+    /// With `shared_writer` on, waits for this insert's turn in the write
+    /// queue; hold the result until the transaction has committed. `None`
+    /// (no waiting) otherwise.
+    pub async fn write_turn(&self, priority: WritePriority) -> Option<WriteTurn> {
+        match &self.write_queue {
+            Some(queue) => Some(queue.turn(priority).await),
+            None => None,
+        }
+    }
+
+    /// After a successful insert of `rows` rows: runs the current file's one
+    /// `ANALYZE` if this is its first batch of `analyze_batch_rows` or more.
+    /// A failure is logged; the hourly `PRAGMA optimize` catches up.
+    pub(crate) async fn analyze_after(&self, rows: usize) {
+        let threshold = self.pool_rotator.config().analyze_batch_rows;
+        if threshold == 0 || rows < threshold || !self.pool_rotator.claim_analyze() {
+            return;
+        }
+        let result = match self.pool_rotator.get_pool().await {
+            Ok((pool, _)) => db::refresh_statistics(&pool, "ANALYZE").await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("ANALYZE after the first large batch failed: {}", e);
+        }
+    }
+
+    /// Where aggregate trades are written: this manager's `agg_trades`, or
+    /// with `AGG_TRADE_SINK=arrow` the per-symbol Arrow streams under
+    /// `Paths::arrow_dir`, which the weekly backup leaves alone.
+    ///
+    /// Arrow writes never touch SQLite, so they skip `write_turn` and the
+    /// combined writer.
+    pub fn agg_trade_sink(&self) -> &dyn AggTradeSink {
+        match &self.arrow_trades {
+            Some(sink) => sink,
+            None => self,
+        }
+    }
+
+    /// Whether new aggregate trades land in `agg_trades`. When they don't,
+    /// `latest_price` and `AggTradeRepository::latency_since` fail with
+    /// `TradesInArrow` rather than report an empty table.
+    pub fn trades_in_sqlite(&self) -> bool {
+        self.arrow_trades.is_none()
+    }
+
+    /// With `combined_writer` set, the writer that commits trade and order
+    /// book batches together.
+    pub(crate) fn combined_writer(&self) -> Option<&CombinedWriter> {
+        self.combined_writer.as_ref()
+    }
+
+    /// Spawns a service's DB writer task so `flush_writers` can wait on it.
+    /// The returned handle lets the service itself wait for it when it stops.
+    pub fn spawn_writer<F>(&self, writer: F) -> WriterHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (done_tx, done_rx) = oneshot::channel();
+        let mut writers = self.writers.lock().expect("writer set poisoned");
+        // Reap writers left behind by restarted actors.
+        while writers.try_join_next().is_some() {}
+        writers.spawn(async move {
+            writer.await;
+            let _ = done_tx.send(());
+        });
+        WriterHandle(done_rx)
+    }
+
+    /// Waits for every writer started with `spawn_writer` to flush and exit.
+    ///
+    /// A writer only exits once its channel closes, so stop the actors that
+    /// feed it first or this never returns.
+    pub async fn flush_writers(&self) {
+        let mut writers = std::mem::take(&mut *self.writers.lock().expect("writer set poisoned"));
+        while writers.join_next().await.is_some() {}
+        if let Some(arrow) = &self.arrow_trades
+            && let Err(e) = arrow.finish().await
+        {
+            warn!("Failed to finish the Arrow trade streams: {}", e);
+        }
+    }
+
+    /// A separate read-only pool on the current week's file, for analytics
+    /// that run alongside the writers. See `ReaderOptions` for the WAL
+    /// semantics. The pool does not follow rotation; reopen it after a week
+    /// boundary.
+    pub async fn open_reader(&self, options: &ReaderOptions) -> Result<SqlitePool, StorageError> {
+        // Make sure this week's file exists before opening it without create.
+        self.pool_rotator.get_pool().await?;
+        db::open_reader(&self.pool_rotator.current_db_path(), options).await
+    }
+
+    /// Runs `f` on a connection to the current week's file with the file of
+    /// ISO `year`/`week` attached as `prev`, so one query can span both:
+    /// `SELECT ... FROM prev.agg_trades UNION ALL SELECT ... FROM agg_trades`.
+    ///
+    /// Symbol ids are per file; join each side with its own `symbols` table
+    /// (or use `prev.agg_trades_v`) rather than comparing ids across weeks.
+    /// Fails with `WeekNotFound` once the backup has moved the file away.
+    /// If `prev` can't be detached, because `f` panicked, this future was
+    /// dropped or `DETACH` failed, the connection is closed instead of being
+    /// returned to the pool.
+    pub async fn with_attached<T, F>(&self, year: i32, week: u32, f: F) -> Result<T, StorageError>
+    where
+        F: AsyncFnOnce(&mut SqliteConnection) -> Result<T, StorageError>,
+    {
+        let path = self.pool_rotator.db_path_for_week(year, week);
+        if !tokio::fs::try_exists(&path).await? {
+            return Err(StorageError::WeekNotFound { year, week, path });
+        }
+
         let (pool, _) = self.pool_rotator.get_pool().await?;
+        let mut attached = AttachedConnection {
+            conn: pool.acquire().await?,
+            detached: false,
+        };
+        sqlx::query("ATTACH DATABASE ? AS prev")
+            .bind(&path)
+            .execute(&mut *attached.conn)
+            .await?;
+
+        let result = f(&mut attached.conn).await;
+
+        match sqlx::query("DETACH DATABASE prev")
+            .execute(&mut *attached.conn)
+            .await
+        {
+            Ok(_) => attached.detached = true,
+            Err(e) => warn!("Failed to detach {}: {}", path, e),
+        }
+        result
+    }
+
+    /// Price of the newest aggregate trade of `symbol`.
+    ///
+    /// Like `latest_orderbook`, this reads the current week's file and, if
+    /// the symbol has nothing there yet within `LATEST_FALLBACK_WINDOW` of a
+    /// rotation, last week's. `None` when neither has any;
+    /// `TradesInArrow` with `AGG_TRADE_SINK=arrow`.
+    pub async fn latest_price(&self, symbol: &str) -> Result<Option<f64>, StorageError> {
+        if !self.trades_in_sqlite() {
+            return Err(StorageError::TradesInArrow);
+        }
+        let price: Option<(f64,)> = self
+            .latest(symbol, |db| {
+                format!(
+                    "SELECT t.price FROM {db}.agg_trades t JOIN {db}.symbols s ON s.id = t.symbol_id
+                     WHERE s.ticker = ? ORDER BY t.time DESC LIMIT 1"
+                )
+            })
+            .await?;
+        Ok(price.map(|(price,)| price))
+    }
+
+    /// Newest order book snapshot of `symbol`, from this week's file or, just
+    /// after a rotation, last week's.
+    pub async fn latest_orderbook(&self, symbol: &str) -> Result<Option<OrderBook>, StorageError> {
+        type Row = (i32, f64, String, Vec<u8>, Vec<u8>);
+        let book: Option<Row> = self
+            .latest(symbol, |db| {
+                format!(
+                    "SELECT o.id, o.time, s.ticker, o.bids, o.asks
+                     FROM {db}.order_books o JOIN {db}.symbols s ON s.id = o.symbol_id
+                     WHERE s.ticker = ? ORDER BY o.time DESC LIMIT 1"
+                )
+            })
+            .await?;
+        Ok(book.map(|(id, time, symbol, bids, asks)| OrderBook {
+            id,
+            time,
+            symbol,
+            bids,
+            asks,
+        }))
+    }
 
-        let id = self
-            .symbol_manager
-            .get_or_create_id(pool.clone(), ticker)
+    /// Runs the single-row `query(db)` for `symbol` against the current file
+    /// (`db` = `main`), then against last week's (`prev`) if it found nothing
+    /// and the week began less than `LATEST_FALLBACK_WINDOW` ago. Later on a
+    /// symbol missing from this week has stopped streaming; attaching last
+    /// week's file on every lookup would only find stale values, slowly.
+    async fn latest<T>(
+        &self,
+        symbol: &str,
+        query: impl Fn(&str) -> String,
+    ) -> Result<Option<T>, StorageError>
+    where
+        T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+    {
+        let symbol = symbol.to_uppercase();
+        let (pool, _) = self.pool_rotator.get_pool().await?;
+        let current = sqlx::query_as(&query("main"))
+            .bind(&symbol)
+            .fetch_optional(&pool)
             .await?;
+        if current.is_some() || !self.pool_rotator.rotated_within(LATEST_FALLBACK_WINDOW) {
+            return Ok(current);
+        }
+
+        let (year, week) = self.pool_rotator.previous_week();
+        let previous = self
+            .with_attached(year, week, async |conn| {
+                Ok(sqlx::query_as(&query("prev"))
+                    .bind(&symbol)
+                    .fetch_optional(conn)
+                    .await?)
+            })
+            .await;
+        match previous {
+            Err(StorageError::WeekNotFound { .. }) => Ok(None),
+            previous => previous,
+        }
+    }
+
+    /// Forces a WAL checkpoint on the current week's file, e.g. before copying
+    /// it or reading its size. Rotation already does a `Truncate` on the file
+    /// it leaves behind.
+    pub async fn checkpoint(&self, mode: CheckpointMode) -> Result<CheckpointInfo, StorageError> {
+        let (pool, _) = self.pool_rotator.get_pool().await?;
+        db::checkpoint(&pool, mode).await
+    }
+
+    /// Row count of each table in `TABLES` for the active database file.
+    pub async fn row_counts(&self) -> Result<Vec<(&'static str, i64)>, StorageError> {
+        let (pool, _) = self.pool_rotator.get_pool().await?;
+
+        let mut counts = Vec::with_capacity(TABLES.len());
+        for &table in TABLES {
+            let count = sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {}", table))
+                .fetch_one(&pool)
+                .await?;
+            counts.push((table, count));
+        }
+        Ok(counts)
+    }
+
+    /// Row counts plus the size of the active database file and its WAL.
+    pub async fn stats(&self) -> Result<DbStats, StorageError> {
+        let rows = self.row_counts().await?;
+        let (pool, _) = self.pool_rotator.get_pool().await?;
+
+        let file_bytes = sqlx::query_scalar::<_, i64>(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        let db_path = sqlx::query_scalar::<_, String>(
+            "SELECT file FROM pragma_database_list WHERE name = 'main'",
+        )
+        .fetch_one(&pool)
+        .await?;
+        let wal_bytes = match tokio::fs::metadata(format!("{}-wal", db_path)).await {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(StorageError::Io(e)),
+        };
+
+        Ok(DbStats {
+            rows,
+            file_bytes,
+            wal_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::{AggTradeRepository, OrderBookRepository};
+    use crate::test_support::{TempDir, manager, manager_at, trade};
+    use chrono::{DateTime, TimeZone, Utc};
+    use common::clock::ManualClock;
+    use common::models::OrderBookInsert;
+
+    #[tokio::test]
+    async fn test_analyzes_once_after_first_large_batch() {
+        use crate::backend::Storage;
+
+        let dir = TempDir::new("analyze");
+        let config = StorageConfig {
+            analyze_batch_rows: 2,
+            ..StorageConfig::default()
+        };
+        let manager = manager(&dir, config).await;
+        let trade = |agg_trade_id| trade(agg_trade_id, 1.0, "BTCUSDT", 1.0);
+        let (pool, _) = manager.pool_rotator.get_pool().await.unwrap();
+        let analyzed = || async {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'sqlite_stat1'",
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+                == 1
+        };
+
+        manager.insert_agg_trades(&[trade(1)]).await.unwrap();
+        assert!(!analyzed().await);
+        manager
+            .insert_agg_trades(&[trade(2), trade(3)])
+            .await
+            .unwrap();
+        assert!(analyzed().await);
+        assert!(!manager.pool_rotator.claim_analyze());
+        manager.pool_rotator.optimize().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stored_book_decodes_to_json_levels() {
+        let dir = TempDir::new("book_json");
+        let manager = manager(&dir, StorageConfig::default()).await;
+        let pack = |levels: &[(f32, f32)]| -> Vec<u8> {
+            levels
+                .iter()
+                .flat_map(|(price, qty)| [price.to_le_bytes(), qty.to_le_bytes()].concat())
+                .collect()
+        };
+        let book = OrderBookInsert {
+            time: 1.5,
+            symbol: "BTCUSDT".to_string(),
+            bids: pack(&[(100.5, 2.0), (100.0, 0.25)]),
+            asks: pack(&[(101.0, 1.5), (101.1, 0.1)]),
+            seq: None,
+        };
+        OrderBookRepository::insert_batch(&manager, &[book])
+            .await
+            .unwrap();
+
+        let stored = manager.latest_orderbook("BTCUSDT").await.unwrap().unwrap();
+        assert_eq!(
+            stored.to_json_levels(),
+            serde_json::json!({
+                "time": 1.5,
+                "symbol": "BTCUSDT",
+                "bids": [[100.5, 2.0], [100.0, 0.25]],
+                "asks": [[101.0, 1.5], [101.1, 0.1]],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stats_counts_rows_and_sizes_files() {
+        let dir = TempDir::new("stats");
+        let manager = manager(&dir, StorageConfig::default()).await;
+
+        let trades = [trade(1, 1.0, "BTCUSDT", 1.0), trade(2, 1.0, "BTCUSDT", 1.0)];
+        AggTradeRepository::insert_batch(&manager, &trades)
+            .await
+            .unwrap();
+
+        let stats = manager.stats().await.unwrap();
+        assert_eq!(stats.rows("agg_trades"), Some(2));
+        assert_eq!(stats.rows("klines"), Some(0));
+        assert!(stats.file_bytes > 0);
+        assert!(stats.wal_bytes > 0);
+
+        let info = manager.checkpoint(CheckpointMode::Full).await.unwrap();
+        assert!(!info.busy && info.wal_pages > 0);
+        assert_eq!(info.checkpointed_pages, info.wal_pages);
+        let info = manager.checkpoint(CheckpointMode::Truncate).await.unwrap();
+        assert!(!info.busy);
+
+        // The compatibility view resolves symbol_id back to the ticker.
+        let (pool, _) = manager.pool_rotator.get_pool().await.unwrap();
+        let by_ticker: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM agg_trades_v WHERE symbol = 'BTCUSDT'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(by_ticker, 2);
+    }
+
+    #[tokio::test]
+    async fn test_reader_sees_commits_and_cannot_write() {
+        let dir = TempDir::new("reader");
+        let manager = manager(&dir, StorageConfig::default()).await;
+        let reader = manager
+            .open_reader(&ReaderOptions::default())
+            .await
+            .unwrap();
+
+        AggTradeRepository::insert_batch(&manager, &[trade(1, 1.0, "BTCUSDT", 1.0)])
+            .await
+            .unwrap();
+
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM agg_trades")
+            .fetch_one(&reader)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+        assert!(
+            sqlx::query("DELETE FROM agg_trades")
+                .execute(&reader)
+                .await
+                .is_err()
+        );
+    }
+
+    fn march(day: u32, hour: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, min, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_with_attached_spans_two_weeks() {
+        let dir = TempDir::new("attach");
+
+        // 2026-W10, then 2026-W11 with the symbols registered in another order.
+        let last_week = manager_at(&dir, march(4, 12, 0)).await;
+        AggTradeRepository::insert_batch(&last_week, &[trade(1, 1.0, "BTCUSDT", 1.0)])
+            .await
+            .unwrap();
+        let this_week = manager_at(&dir, march(11, 12, 0)).await;
+        AggTradeRepository::insert_batch(
+            &this_week,
+            &[trade(2, 1.0, "ETHUSDT", 1.0), trade(3, 1.0, "BTCUSDT", 1.0)],
+        )
+        .await
+        .unwrap();
+
+        let btc: i64 = this_week
+            .with_attached(2026, 10, async |conn| {
+                Ok(sqlx::query_scalar(
+                    "SELECT COUNT(*) FROM (
+                        SELECT symbol FROM prev.agg_trades_v
+                        UNION ALL SELECT symbol FROM agg_trades_v
+                    ) WHERE symbol = 'BTCUSDT'",
+                )
+                .fetch_one(conn)
+                .await?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(btc, 2);
+
+        // Cancelled with `prev` attached: the connection must be closed, not
+        // handed back to the pool with `prev` still attached.
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(50),
+            this_week.with_attached(2026, 10, async |_| {
+                std::future::pending::<Result<(), _>>().await
+            }),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        // A dropped connection goes back to the pool from a spawned task.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let (pool, _) = this_week.pool_rotator.get_pool().await.unwrap();
+        let mut idle = Vec::new();
+        for _ in 0..pool.num_idle() {
+            let mut conn = pool.acquire().await.unwrap();
+            let attached: i64 =
+                sqlx::query_scalar("SELECT COUNT(*) FROM pragma_database_list WHERE name = 'prev'")
+                    .fetch_one(&mut *conn)
+                    .await
+                    .unwrap();
+            assert_eq!(attached, 0);
+            idle.push(conn);
+        }
+
+        // A week that was never written (or is already archived).
+        assert!(matches!(
+            this_week.with_attached(2026, 9, async |_| Ok(())).await,
+            Err(StorageError::WeekNotFound { week: 9, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_latest_falls_back_to_last_week() {
+        let dir = TempDir::new("latest");
+
+        let last_week = manager_at(&dir, march(4, 12, 0)).await;
+        AggTradeRepository::insert_batch(
+            &last_week,
+            &[
+                trade(1, 1.0, "BTCUSDT", 100.0),
+                trade(2, 2.0, "BTCUSDT", 101.0),
+            ],
+        )
+        .await
+        .unwrap();
+        let book = OrderBookInsert {
+            time: 2.0,
+            symbol: "BTCUSDT".to_string(),
+            bids: [10.0f32.to_le_bytes(), 1.0f32.to_le_bytes()].concat(),
+            asks: Vec::new(),
+            seq: None,
+        };
+        OrderBookRepository::insert_batch(&last_week, &[book])
+            .await
+            .unwrap();
+        // 2026-W11 began at midnight on Monday the 9th.
+        let this_week = manager_at(&dir, march(9, 0, 5)).await;
+        AggTradeRepository::insert_batch(&this_week, &[trade(3, 3.0, "ETHUSDT", 5.0)])
+            .await
+            .unwrap();
+
+        assert_eq!(this_week.latest_price("ethusdt").await.unwrap(), Some(5.0));
+        assert_eq!(
+            this_week.latest_price("btcusdt").await.unwrap(),
+            Some(101.0)
+        );
+        assert_eq!(this_week.latest_price("SOLUSDT").await.unwrap(), None);
+        let book = this_week
+            .latest_orderbook("BTCUSDT")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((book.time, book.bids.len()), (2.0, 8));
+        assert!(
+            this_week
+                .latest_orderbook("ETHUSDT")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        // No file before the first week.
+        assert_eq!(last_week.latest_price("ETHUSDT").await.unwrap(), None);
+
+        // Well into the week, a symbol that stopped streaming has no price.
+        let later = manager_at(&dir, march(9, 0, 20)).await;
+        assert_eq!(later.latest_price("ETHUSDT").await.unwrap(), Some(5.0));
+        assert_eq!(later.latest_price("BTCUSDT").await.unwrap(), None);
+        assert!(later.latest_orderbook("BTCUSDT").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_symbol_ids_follow_rotation() {
+        let dir = TempDir::new("symbol_ids");
+        let (tx, _rx) = mpsc::channel(4);
+        // Wednesday of 2026-W10.
+        let clock = Arc::new(ManualClock::new(
+            Utc.with_ymd_and_hms(2026, 3, 4, 12, 0, 0).unwrap(),
+        ));
+        let manager =
+            DataManager::new_with_clock(dir.folder(), tx, StorageConfig::default(), clock.clone())
+                .await
+                .unwrap();
+        manager.set_symbol_assets(HashMap::from([(
+            "WIFUSDT".to_string(),
+            SymbolAssets {
+                base: "WIF".to_string(),
+                quote: "USDT".to_string(),
+            },
+        )]));
+        let trade = |agg_trade_id, symbol| trade(agg_trade_id, 1.0, symbol, 1.0);
+
+        // Neither is seeded, so last week's file numbers them after the seeds.
+        AggTradeRepository::insert_batch(&manager, &[trade(1, "POLUSDT"), trade(2, "WIFUSDT")])
+            .await
+            .unwrap();
+        let last_week_id = manager.get_symbol_id("WIFUSDT").await.unwrap();
+
+        // Wednesday of 2026-W11: WIFUSDT is the first new ticker of this file.
+        clock.set(Utc.with_ymd_and_hms(2026, 3, 11, 12, 0, 0).unwrap());
+        AggTradeRepository::insert_batch(&manager, &[trade(3, "WIFUSDT")])
+            .await
+            .unwrap();
+        assert_eq!(
+            manager.get_symbol_id("WIFUSDT").await.unwrap(),
+            last_week_id - 1
+        );
+
+        let (pool, _) = manager.pool_rotator.get_pool().await.unwrap();
+        let stored: (String, Option<String>) = sqlx::query_as(
+            "SELECT v.symbol, s.quote_asset FROM agg_trades_v v JOIN symbols s ON s.ticker = v.symbol",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(stored, ("WIFUSDT".to_string(), Some("USDT".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_writer_to_flush() {
+        let dir = TempDir::new("drain");
+        let manager = manager(&dir, StorageConfig::default()).await;
+
+        let (db_tx, mut db_rx) = mpsc::channel::<u32>(8);
+        let (flushed_tx, mut flushed_rx) = oneshot::channel();
+        let writer = manager.spawn_writer(async move {
+            let mut batch = Vec::new();
+            while let Some(row) = db_rx.recv().await {
+                batch.push(row);
+            }
+            // Channel closed: the final partial batch.
+            let _ = flushed_tx.send(batch);
+        });
+        db_tx.send(1).await.unwrap();
+        db_tx.send(2).await.unwrap();
 
-        return Ok(id);
+        // Still open, so the writer is still waiting for more.
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut flushed_rx)
+                .await
+                .is_err()
+        );
+        drop(db_tx);
+        assert!(writer.drain(WRITER_DRAIN_TIMEOUT).await);
+        assert_eq!(flushed_rx.await.unwrap(), vec![1, 2]);
+        manager.flush_writers().await;
     }
 }
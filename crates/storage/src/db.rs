@@ -1,36 +1,556 @@
-use chrono::{DateTime, Datelike, Duration, Utc};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Offset, Utc};
 use common::actors::ControlMessage;
-use sqlx::sqlite::{self, SqliteConnectOptions, SqlitePool};
+use common::clock::{Clock, SystemClock};
+use common::config::{parse_bool, parse_whole};
+use common::paths::Paths;
+use common::symbols::{DEFAULT_SYMBOL_ALIASES, SymbolAliases};
+use sqlx::sqlite::{self, SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::env;
+use std::path::Path;
 use std::str::FromStr;
-use std::time::Duration as StdDuration;
-use tokio::sync::{RwLock, mpsc};
-use tracing::{error, info};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration as StdDuration, Instant};
+use tokio::sync::{RwLock, broadcast, mpsc};
+use tracing::{error, info, warn};
 
 use crate::actors::backup_actor::BackupOneShotActor;
+use crate::error::StorageError;
+use crate::schema;
+use crate::trade_sink::AggTradeBackend;
+
+/// Connection tuning for the recorder's own pools.
+///
+/// The defaults keep sqlite's own page cache and no memory mapping, which
+/// suits small hosts; read-heavy deployments can raise both.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageConfig {
+    /// Prepared statements kept per connection.
+    pub statement_cache_capacity: usize,
+    /// Queued commands per connection worker.
+    pub command_buffer_size: usize,
+    /// `PRAGMA mmap_size` in bytes. `None` leaves sqlite's default (off).
+    pub mmap_bytes: Option<u64>,
+    /// `PRAGMA cache_size` in KiB. `None` leaves sqlite's default (~2 MiB).
+    pub cache_kib: Option<u64>,
+    /// Route 1s, 1m and 1h candles to `klines_1s`, `klines_1m` and `klines_1h`
+    /// instead of the shared `klines` table. Either way `klines_all` and
+    /// `klines_v` read every candle.
+    pub kline_table_per_interval: bool,
+    /// How dates map to weekly files.
+    pub week_scheme: WeekScheme,
+    /// UTC offset whose local midnight starts each week. See `WeekAnchor`.
+    pub week_offset: FixedOffset,
+    /// Let one insert transaction at a time through, klines first, then
+    /// trades, then order books, instead of every service's writer racing
+    /// for the WAL lock. See `WriteQueue`.
+    pub shared_writer: bool,
+    /// Where aggregate trades go. See `DataManager::agg_trade_sink`.
+    pub agg_trade_sink: AggTradeBackend,
+    /// Renamed pairs; rows are stored under the current ticker.
+    pub symbol_aliases: SymbolAliases,
+    /// Commit the trade and order book batches of every service together,
+    /// one transaction per tick of this length. `None` commits each batch on
+    /// its own. See `CombinedWriter`.
+    pub combined_writer: Option<StdDuration>,
+    /// At startup, migrate the files of this many weeks before the current
+    /// one to the latest schema, so a late write to one after an upgrade
+    /// doesn't fail. 0 leaves them alone.
+    pub upgrade_recent_weeks: u32,
+    /// `CREATE INDEX` statements run on every weekly file after the schema,
+    /// for deployments whose queries need indexes the schema lacks. Checked
+    /// by `parse_extra_indexes`.
+    pub extra_indexes: Vec<String>,
+    /// How often the maintenance actor runs `PRAGMA optimize` on the current
+    /// file, refreshing planner statistics of tables that grew. `None`: never.
+    pub optimize_every: Option<StdDuration>,
+    /// `ANALYZE` each weekly file once, right after its first insert of at
+    /// least this many rows, instead of planning on an empty file's
+    /// statistics until the first `optimize`. 0: never.
+    pub analyze_batch_rows: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            statement_cache_capacity: 100,
+            command_buffer_size: 5000,
+            mmap_bytes: None,
+            cache_kib: None,
+            kline_table_per_interval: false,
+            week_scheme: WeekScheme::Iso,
+            week_offset: Utc.fix(),
+            shared_writer: false,
+            agg_trade_sink: AggTradeBackend::Sqlite,
+            symbol_aliases: SymbolAliases::parse(DEFAULT_SYMBOL_ALIASES)
+                .expect("default aliases parse"),
+            combined_writer: None,
+            upgrade_recent_weeks: 0,
+            extra_indexes: Vec::new(),
+            optimize_every: Some(StdDuration::from_secs(3600)),
+            analyze_batch_rows: 1000,
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Reads `DB_STATEMENT_CACHE` (100), `DB_COMMAND_BUFFER` (5000),
+    /// `DB_MMAP_MB` (unset), `DB_CACHE_KIB` (unset),
+    /// `KLINE_TABLE_PER_INTERVAL` (false), `WEEK_SCHEME` (iso),
+    /// `WEEK_UTC_OFFSET` (`+00:00`),
+    /// `DB_SHARED_WRITER` (false), `AGG_TRADE_SINK` (sqlite),
+    /// `DB_COMBINED_WRITER_MS`
+    /// (unset or 0: off), `DB_UPGRADE_RECENT_WEEKS` (0),
+    /// `DB_EXTRA_INDEXES` (none; `;`-separated), `DB_OPTIMIZE_MINUTES` (60;
+    /// 0: off) and `DB_ANALYZE_BATCH_ROWS` (1000; 0: off). `symbol_aliases`
+    /// keeps its default; set it from `Config::symbol_aliases`.
+    pub fn from_env() -> Result<Self, String> {
+        fn var<T: FromStr>(name: &str) -> Result<Option<T>, String> {
+            env::var(name)
+                .ok()
+                .map(|value| parse_whole(name, &value))
+                .transpose()
+        }
+
+        fn flag(name: &str, default: bool) -> Result<bool, String> {
+            match env::var(name) {
+                Ok(value) => parse_bool(name, &value),
+                Err(_) => Ok(default),
+            }
+        }
+
+        let defaults = Self::default();
+        Ok(Self {
+            statement_cache_capacity: var("DB_STATEMENT_CACHE")?
+                .unwrap_or(defaults.statement_cache_capacity),
+            command_buffer_size: var("DB_COMMAND_BUFFER")?.unwrap_or(defaults.command_buffer_size),
+            mmap_bytes: var::<u64>("DB_MMAP_MB")?.map(|mb| mb * 1024 * 1024),
+            cache_kib: var("DB_CACHE_KIB")?,
+            kline_table_per_interval: flag(
+                "KLINE_TABLE_PER_INTERVAL",
+                defaults.kline_table_per_interval,
+            )?,
+            week_scheme: match env::var("WEEK_SCHEME") {
+                Ok(value) => value.parse()?,
+                Err(_) => defaults.week_scheme,
+            },
+            week_offset: match env::var("WEEK_UTC_OFFSET") {
+                Ok(value) => value.trim().parse().map_err(|_| {
+                    format!("WEEK_UTC_OFFSET must look like +08:00, got '{}'", value)
+                })?,
+                Err(_) => defaults.week_offset,
+            },
+            shared_writer: flag("DB_SHARED_WRITER", defaults.shared_writer)?,
+            agg_trade_sink: match env::var("AGG_TRADE_SINK") {
+                Ok(value) => value.parse()?,
+                Err(_) => defaults.agg_trade_sink,
+            },
+            symbol_aliases: defaults.symbol_aliases,
+            combined_writer: var::<u64>("DB_COMBINED_WRITER_MS")?
+                .filter(|&ms| ms > 0)
+                .map(StdDuration::from_millis),
+            upgrade_recent_weeks: var("DB_UPGRADE_RECENT_WEEKS")?
+                .unwrap_or(defaults.upgrade_recent_weeks),
+            extra_indexes: match env::var("DB_EXTRA_INDEXES") {
+                Ok(value) => parse_extra_indexes(&value)
+                    .map_err(|e| format!("Invalid DB_EXTRA_INDEXES: {}", e))?,
+                Err(_) => defaults.extra_indexes,
+            },
+            optimize_every: match var::<u64>("DB_OPTIMIZE_MINUTES")? {
+                Some(0) => None,
+                Some(minutes) => Some(StdDuration::from_secs(minutes * 60)),
+                None => defaults.optimize_every,
+            },
+            analyze_batch_rows: var("DB_ANALYZE_BATCH_ROWS")?
+                .unwrap_or(defaults.analyze_batch_rows),
+        })
+    }
+
+    /// The week numbering `week_scheme` and `week_offset` make up.
+    pub fn week_anchor(&self) -> WeekAnchor {
+        WeekAnchor {
+            scheme: self.week_scheme,
+            offset: self.week_offset,
+        }
+    }
+
+    fn apply(&self, options: SqliteConnectOptions) -> SqliteConnectOptions {
+        let mut options = options
+            .statement_cache_capacity(self.statement_cache_capacity)
+            .command_buffer_size(self.command_buffer_size);
+        if let Some(bytes) = self.mmap_bytes {
+            options = options.pragma("mmap_size", bytes.to_string());
+        }
+        if let Some(kib) = self.cache_kib {
+            // A negative cache_size is read as KiB rather than pages.
+            options = options.pragma("cache_size", format!("-{}", kib));
+        }
+        options
+    }
+}
+
+/// How a date is numbered into the `crypto_<year>_<week>.db` it is written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WeekScheme {
+    /// ISO 8601 weeks. The year is the ISO year, so the last days of
+    /// December can land in week 01 of the next year (Dec 29 2025 is
+    /// 2026-W01) and the first days of January in week 52/53 of the last.
+    #[default]
+    Iso,
+    /// Monday-anchored weeks of the calendar year, like `%W`: days before
+    /// the year's first Monday are week 00. A file never spans two calendar
+    /// years, so the week around New Year is split in two.
+    Calendar,
+}
+
+impl WeekScheme {
+    /// `(year, week)` of the file `date` belongs to.
+    pub fn components(self, date: DateTime<Utc>) -> (i32, u32) {
+        match self {
+            Self::Iso => {
+                let iso = date.iso_week();
+                (iso.year(), iso.week())
+            }
+            Self::Calendar => {
+                let week = (date.ordinal0() + 7 - date.weekday().num_days_from_monday()) / 7;
+                (date.year(), week)
+            }
+        }
+    }
+
+    /// First day of the file `date` belongs to.
+    pub fn start(self, date: DateTime<Utc>) -> NaiveDate {
+        let monday =
+            date.date_naive() - Duration::days(date.weekday().num_days_from_monday() as i64);
+        match self {
+            Self::Iso => monday,
+            // A calendar week also starts on January 1st.
+            Self::Calendar => monday.max(date.date_naive().with_ordinal(1).expect("day 1 exists")),
+        }
+    }
+
+    /// `(year, week)` of the file before the one `date` belongs to.
+    pub fn previous(self, date: DateTime<Utc>) -> (i32, u32) {
+        let day_before = (self.start(date) - Duration::days(1))
+            .and_hms_opt(12, 0, 0)
+            .expect("noon exists")
+            .and_utc();
+        self.components(day_before)
+    }
+}
+
+/// `WeekScheme` applied to the wall-clock date at `offset` from UTC, so each
+/// file starts at Monday 00:00 in that offset rather than in UTC. A fixed
+/// offset has no daylight saving: a zone that observes it starts the week an
+/// hour off for part of the year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekAnchor {
+    pub scheme: WeekScheme,
+    pub offset: FixedOffset,
+}
+
+impl WeekAnchor {
+    /// `(year, week)` of the file `date` belongs to.
+    pub fn components(self, date: DateTime<Utc>) -> (i32, u32) {
+        self.scheme.components(self.local(date))
+    }
+
+    /// `(year, week)` of the file before the one `date` belongs to.
+    pub fn previous(self, date: DateTime<Utc>) -> (i32, u32) {
+        self.scheme.previous(self.local(date))
+    }
+
+    /// Instant the file `date` belongs to starts at.
+    pub fn start(self, date: DateTime<Utc>) -> DateTime<Utc> {
+        let midnight = self
+            .scheme
+            .start(self.local(date))
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight exists");
+        (midnight - Duration::seconds(self.offset.local_minus_utc().into())).and_utc()
+    }
+
+    /// `date`'s wall-clock time at `offset`, labelled UTC.
+    fn local(self, date: DateTime<Utc>) -> DateTime<Utc> {
+        date.with_timezone(&self.offset).naive_local().and_utc()
+    }
+}
+
+impl Default for WeekAnchor {
+    fn default() -> Self {
+        WeekScheme::default().into()
+    }
+}
+
+impl From<WeekScheme> for WeekAnchor {
+    fn from(scheme: WeekScheme) -> Self {
+        Self {
+            scheme,
+            offset: Utc.fix(),
+        }
+    }
+}
+
+impl FromStr for WeekScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "iso" => Ok(Self::Iso),
+            "calendar" => Ok(Self::Calendar),
+            other => Err(format!(
+                "WEEK_SCHEME must be iso or calendar, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+/// Default `mmap_size` for reader pools.
+const DEFAULT_READER_MMAP_MB: u64 = 256;
+
+/// Tuning for pools that only read a database the recorder is writing.
+///
+/// In WAL mode a reader never blocks the writer and the writer never blocks
+/// readers: each read transaction sees the database as of its first read and
+/// keeps seeing that snapshot until it ends, so keep read transactions short
+/// or the WAL cannot be checkpointed past them and keeps growing. Readers can
+/// still hit `SQLITE_BUSY` briefly while the WAL index is being rebuilt after
+/// a checkpoint, which `busy_timeout` absorbs.
+#[derive(Debug, Clone)]
+pub struct ReaderOptions {
+    /// Share one page cache between connections of this process. It has no
+    /// effect across processes.
+    pub shared_cache: bool,
+    /// With `shared_cache`, skip table read locks and see rows other
+    /// connections of this process have not committed yet.
+    pub read_uncommitted: bool,
+    /// Bytes of the file mapped into memory (`PRAGMA mmap_size`).
+    pub mmap_bytes: u64,
+    pub max_connections: u32,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            shared_cache: false,
+            read_uncommitted: false,
+            mmap_bytes: DEFAULT_READER_MMAP_MB * 1024 * 1024,
+            max_connections: 4,
+        }
+    }
+}
+
+impl ReaderOptions {
+    /// Reads `DB_READER_SHARED_CACHE` (false), `DB_READER_READ_UNCOMMITTED`
+    /// (false) and `DB_READER_MMAP_MB` (256). An invalid value is an error
+    /// rather than the default.
+    pub fn from_env() -> Result<Self, String> {
+        fn flag(name: &str) -> Result<bool, String> {
+            match env::var(name) {
+                Ok(value) => parse_bool(name, &value),
+                Err(_) => Ok(false),
+            }
+        }
+
+        let defaults = Self::default();
+        Ok(Self {
+            shared_cache: flag("DB_READER_SHARED_CACHE")?,
+            read_uncommitted: flag("DB_READER_READ_UNCOMMITTED")?,
+            mmap_bytes: match env::var("DB_READER_MMAP_MB") {
+                Ok(value) => parse_whole::<u64>("DB_READER_MMAP_MB", &value)? * 1024 * 1024,
+                Err(_) => defaults.mmap_bytes,
+            },
+            ..defaults
+        })
+    }
+}
+
+/// Opens `db_path` for reading only. The file must already exist; every
+/// connection is `query_only`, so a stray write fails instead of taking the
+/// write lock from the recorder.
+pub async fn open_reader(
+    db_path: &str,
+    options: &ReaderOptions,
+) -> Result<SqlitePool, StorageError> {
+    let mut connect = SqliteConnectOptions::from_str(&format!("sqlite:{}", db_path))?
+        .create_if_missing(false)
+        .shared_cache(options.shared_cache)
+        .busy_timeout(StdDuration::from_secs(30))
+        .pragma("query_only", "true")
+        .pragma("mmap_size", options.mmap_bytes.to_string());
+    if options.read_uncommitted {
+        connect = connect.pragma("read_uncommitted", "true");
+    }
+
+    SqlitePoolOptions::new()
+        .max_connections(options.max_connections.max(1))
+        .connect_with(connect)
+        .await
+        .map_err(StorageError::from)
+}
+
+/// How hard `PRAGMA wal_checkpoint` tries. See the SQLite docs for the
+/// details; in short each mode waits on more than the one before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointMode {
+    /// Copies what it can without waiting on readers or writers.
+    Passive,
+    /// Waits for writers, then copies the whole WAL.
+    Full,
+    /// Like `Full`, then also waits for readers so the WAL restarts from the
+    /// beginning.
+    Restart,
+    /// Like `Restart`, then truncates the `-wal` file to zero bytes.
+    Truncate,
+}
+
+impl CheckpointMode {
+    fn pragma(self) -> &'static str {
+        match self {
+            Self::Passive => "PRAGMA wal_checkpoint(PASSIVE)",
+            Self::Full => "PRAGMA wal_checkpoint(FULL)",
+            Self::Restart => "PRAGMA wal_checkpoint(RESTART)",
+            Self::Truncate => "PRAGMA wal_checkpoint(TRUNCATE)",
+        }
+    }
+}
+
+/// Result row of `PRAGMA wal_checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointInfo {
+    /// A reader or writer kept the checkpoint from finishing.
+    pub busy: bool,
+    /// Frames in the WAL.
+    pub wal_pages: i64,
+    /// Frames copied back into the database file.
+    pub checkpointed_pages: i64,
+}
+
+/// Copies the WAL of `pool`'s database back into the main file.
+pub async fn checkpoint(
+    pool: &SqlitePool,
+    mode: CheckpointMode,
+) -> Result<CheckpointInfo, StorageError> {
+    let (busy, wal_pages, checkpointed_pages): (i64, i64, i64) =
+        sqlx::query_as(mode.pragma()).fetch_one(pool).await?;
+    Ok(CheckpointInfo {
+        busy: busy != 0,
+        wal_pages,
+        checkpointed_pages,
+    })
+}
 
 pub struct RotatingPool {
-    data_folder: String,
+    paths: Paths,
     inner: RwLock<(u32, SqlitePool)>,
     supervisor_tx: mpsc::Sender<ControlMessage>,
+    config: StorageConfig,
+    clock: Arc<dyn Clock>,
+    notification_tx: OnceLock<broadcast::Sender<String>>,
+    backup_scripts: OnceLock<String>,
+    /// Packed week of the file `ANALYZE` last ran on, 0 for none.
+    analyzed_week: AtomicU32,
 }
 
 impl RotatingPool {
     pub async fn new(
-        data_folder: String,
+        paths: impl Into<Paths>,
+        supervisor_tx: mpsc::Sender<ControlMessage>,
+        config: StorageConfig,
+    ) -> Result<Self, StorageError> {
+        Self::new_with_clock(paths, supervisor_tx, config, Arc::new(SystemClock)).await
+    }
+
+    /// Like `new`, but the current week is read from `clock` instead of the
+    /// wall clock.
+    pub async fn new_with_clock(
+        paths: impl Into<Paths>,
         supervisor_tx: mpsc::Sender<ControlMessage>,
-    ) -> Result<Self, sqlx::Error> {
-        let pool = get_weekly_pool(&data_folder).await?;
-        let packed = Self::current_packed();
+        config: StorageConfig,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, StorageError> {
+        let paths = paths.into();
+        let now = clock.now();
+        let pool = get_weekly_pool(&paths, now, &config).await?;
+        if config.upgrade_recent_weeks > 0 {
+            upgrade_recent_files(&paths, now, &config).await;
+        }
         Ok(Self {
-            data_folder,
-            inner: RwLock::new((packed, pool)),
+            paths,
+            inner: RwLock::new((pack_week(config.week_anchor(), now), pool)),
             supervisor_tx,
+            config,
+            clock,
+            notification_tx: OnceLock::new(),
+            backup_scripts: OnceLock::new(),
+            analyzed_week: AtomicU32::new(0),
         })
     }
 
-    fn current_packed() -> u32 {
-        let (year, week) = get_date_components(Utc::now());
-        (year as u32) << 6 | (week & 0x3f)
+    /// Where backup actors report the weekly summary or their failure. Only
+    /// the first call has an effect.
+    pub fn set_notifier(&self, tx: broadcast::Sender<String>) {
+        if self.notification_tx.set(tx).is_err() {
+            warn!("Backup notifier already set, ignoring.");
+        }
+    }
+
+    /// Directory holding `dump_db.sh`, which archives each week's file after
+    /// rotation. Until it is set, rotation skips the backup. Only the first
+    /// call has an effect.
+    pub fn set_backup_scripts(&self, utils_dir: String) {
+        if self.backup_scripts.set(utils_dir).is_err() {
+            warn!("Backup script directory already set, ignoring.");
+        }
+    }
+
+    pub fn config(&self) -> &StorageConfig {
+        &self.config
+    }
+
+    pub fn paths(&self) -> &Paths {
+        &self.paths
+    }
+
+    /// Path of the database file for the current week.
+    pub fn current_db_path(&self) -> String {
+        weekly_db_path(&self.paths, self.config.week_anchor(), self.clock.now())
+    }
+
+    /// Path of the database file for ISO `year`/`week`, whether or not it
+    /// still exists.
+    pub fn db_path_for_week(&self, year: i32, week: u32) -> String {
+        db_path_for_week(&self.paths, year, week)
+    }
+
+    /// `(year, week)` of the file before the current one.
+    pub fn previous_week(&self) -> (i32, u32) {
+        self.config.week_anchor().previous(self.clock.now())
+    }
+
+    /// Whether the current file's week began less than `window` ago.
+    pub fn rotated_within(&self, window: Duration) -> bool {
+        let anchor = self.config.week_anchor();
+        let now = self.clock.now();
+        anchor.components(now - window) != anchor.components(now)
+    }
+
+    fn current_packed(&self) -> u32 {
+        pack_week(self.config.week_anchor(), self.clock.now())
+    }
+
+    /// Whether the current file is still due its one `ANALYZE`, claiming it
+    /// if so.
+    pub(crate) fn claim_analyze(&self) -> bool {
+        let week = self.current_packed();
+        self.analyzed_week.swap(week, Ordering::Relaxed) != week
+    }
+
+    /// Runs `PRAGMA optimize` on the current file.
+    pub async fn optimize(&self) -> Result<(), StorageError> {
+        let (pool, _) = self.get_pool().await?;
+        refresh_statistics(&pool, "PRAGMA optimize").await
     }
 
     /// Retrieves the current active SQLite connection pool, rotating the database file if necessary.
@@ -45,65 +565,240 @@ impl RotatingPool {
     /// A tuple `(SqlitePool, bool)`:
     /// - `SqlitePool`: The active connection pool.
     /// - `bool`: `true` if a rotation occurred (a new pool was created), `false` otherwise.
-    pub async fn get_pool(&self) -> Result<(SqlitePool, bool), sqlx::Error> {
+    pub async fn get_pool(&self) -> Result<(SqlitePool, bool), StorageError> {
+        let (pool, _, rotated) = self.get_weekly_pool().await?;
+        Ok((pool, rotated))
+    }
+
+    /// Like `get_pool`, with the packed week of the file the pool is open
+    /// on, for caches of per-file values such as symbol ids.
+    pub(crate) async fn get_weekly_pool(&self) -> Result<(SqlitePool, u32, bool), StorageError> {
         let read = self.inner.read().await;
         let (current_packed, ref pool) = *read;
 
-        if current_packed == Self::current_packed() {
-            return Ok((pool.clone(), false));
+        if current_packed == self.current_packed() {
+            return Ok((pool.clone(), current_packed, false));
         }
         drop(read);
 
         let mut write = self.inner.write().await;
         let (current_packed, _) = *write;
 
-        if current_packed != Self::current_packed() {
-            let new_pool = get_weekly_pool(&self.data_folder).await?;
-            *write = (Self::current_packed(), new_pool);
+        let now = self.clock.now();
+        let anchor = self.config.week_anchor();
+        if current_packed != pack_week(anchor, now) {
+            let new_pool = get_weekly_pool(&self.paths, now, &self.config)
+                .await
+                .map_err(|e| StorageError::RotationFailed {
+                    path: weekly_db_path(&self.paths, anchor, now),
+                    source: Box::new(e),
+                })?;
+            let (_, old_pool) = std::mem::replace(&mut *write, (pack_week(anchor, now), new_pool));
+            let (packed, ref pool) = *write;
+            let pool = pool.clone();
+            // Writers move on to the new file while the old one is sealed.
+            drop(write);
+
+            // Seal last week's file before it is archived: everything still in
+            // its WAL goes into the main file, so the dump sees it all.
+            match checkpoint(&old_pool, CheckpointMode::Truncate).await {
+                Ok(info) if !info.busy => info!(
+                    "Sealed last week's database: {} WAL pages checkpointed",
+                    info.checkpointed_pages
+                ),
+                Ok(info) => warn!(
+                    "Last week's database still busy at rotation, {} of {} WAL pages checkpointed",
+                    info.checkpointed_pages, info.wal_pages
+                ),
+                Err(e) => error!("Failed to checkpoint last week's database: {}", e),
+            }
 
             // Spawn the backup actor via the Supervisor
-            let backup_actor = Box::new(BackupOneShotActor::new());
-            let spawn_msg = ControlMessage::Spawn(backup_actor);
+            match self.backup_scripts.get() {
+                Some(utils_dir) => {
+                    let mut backup_actor = BackupOneShotActor::new(
+                        self.clock.clone(),
+                        self.paths.clone(),
+                        utils_dir.clone(),
+                    )
+                    .with_week_anchor(anchor);
+                    if let Some(tx) = self.notification_tx.get() {
+                        backup_actor = backup_actor.with_notifier(tx.clone());
+                    }
+                    let spawn_msg = ControlMessage::Spawn(Box::new(backup_actor));
 
-            if let Err(e) = self.supervisor_tx.try_send(spawn_msg) {
-                error!("Failed to request Backup Actor spawn: {}", e);
-            } else {
-                info!("Requested Backup Actor spawn via Supervisor");
+                    if let Err(e) = self.supervisor_tx.try_send(spawn_msg) {
+                        error!("Failed to request Backup Actor spawn: {}", e);
+                    } else {
+                        info!("Requested Backup Actor spawn via Supervisor");
+                    }
+                }
+                None => {
+                    error!("No backup script directory set, last week's database is not archived.")
+                }
             }
+            return Ok((pool, packed, true));
         }
-        Ok((write.1.clone(), true))
+        Ok((write.1.clone(), current_packed, true))
     }
 }
 
-async fn get_weekly_pool(data_folder: &str) -> Result<SqlitePool, sqlx::Error> {
-    let current_db_path = format!("{}/sqlitedata/current", data_folder);
-    tokio::fs::create_dir_all(&current_db_path)
-        .await
-        .map_err(|e| sqlx::Error::Io(e))?;
+fn pack_week(anchor: WeekAnchor, date: DateTime<Utc>) -> u32 {
+    let (year, week) = anchor.components(date);
+    (year as u32) << 6 | (week & 0x3f)
+}
+
+fn weekly_db_path(paths: &Paths, anchor: WeekAnchor, now: DateTime<Utc>) -> String {
+    let (year, week) = anchor.components(now);
+    db_path_for_week(paths, year, week)
+}
+
+/// `Paths::weekly_db` as the string sqlx and the error types take.
+pub fn db_path_for_week(paths: &Paths, year: i32, week: u32) -> String {
+    paths.weekly_db(year, week).display().to_string()
+}
+
+/// Path of the file a `--period` such as `2026_01` names, as `bot export` and
+/// `analytics` take it.
+pub fn db_path_for_period(paths: &Paths, period: &str) -> Result<String, String> {
+    let (year, week) = period
+        .split_once('_')
+        .and_then(|(y, w)| Some((y.parse::<i32>().ok()?, w.parse::<u32>().ok()?)))
+        .ok_or_else(|| format!("--period must look like 2026_01, got '{}'", period))?;
+    Ok(db_path_for_week(paths, year, week))
+}
+
+async fn get_weekly_pool(
+    paths: &Paths,
+    now: DateTime<Utc>,
+    config: &StorageConfig,
+) -> Result<SqlitePool, StorageError> {
+    tokio::fs::create_dir_all(paths.current_dir()).await?;
+
+    let db_filename = weekly_db_path(paths, config.week_anchor(), now);
+
+    let options = weekly_options(&db_filename, config)?.create_if_missing(true);
+    let pool = SqlitePool::connect_with(options).await?;
+    schema::migrate(&pool).await?;
+    create_extra_indexes(&pool, &config.extra_indexes).await;
+    Ok(pool)
+}
+
+/// Splits `;`-separated `CREATE INDEX` statements, making each
+/// `IF NOT EXISTS` as they run on every open. Anything else is rejected, so
+/// the setting can't be used to alter tables or data.
+pub fn parse_extra_indexes(value: &str) -> Result<Vec<String>, String> {
+    let mut statements = Vec::new();
+    for statement in value.split(';') {
+        let words: Vec<&str> = statement.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let keyword = |i: usize, expected: &str| {
+            words
+                .get(i)
+                .is_some_and(|w| w.eq_ignore_ascii_case(expected))
+        };
+        if !(keyword(0, "CREATE") && keyword(1, "INDEX")) {
+            return Err(format!(
+                "'{}' is not a CREATE INDEX statement",
+                words.join(" ")
+            ));
+        }
+        let rest = if keyword(2, "IF") && keyword(3, "NOT") && keyword(4, "EXISTS") {
+            &words[5..]
+        } else {
+            &words[2..]
+        };
+        statements.push(format!("CREATE INDEX IF NOT EXISTS {}", rest.join(" ")));
+    }
+    Ok(statements)
+}
 
-    let (year, week) = get_date_components(Utc::now());
-    let db_filename = format!("{}/crypto_{}_{:02}.db", current_db_path, year, week);
+/// Runs `statement` (`ANALYZE` or `PRAGMA optimize`), logging how long it
+/// took. Both sample at most `analysis_limit` rows per index.
+pub(crate) async fn refresh_statistics(
+    pool: &SqlitePool,
+    statement: &str,
+) -> Result<(), StorageError> {
+    let started = Instant::now();
+    sqlx::query(statement).execute(pool).await?;
+    info!("{} took {:?}", statement, started.elapsed());
+    Ok(())
+}
+
+/// Runs `config.extra_indexes`. A failing one (say, a misspelled column) is
+/// logged rather than failing the open, which would stop all writes.
+async fn create_extra_indexes(pool: &SqlitePool, statements: &[String]) {
+    for statement in statements {
+        if let Err(e) = sqlx::query(statement).execute(pool).await {
+            warn!("Extra index '{}' failed: {}", statement, e);
+        }
+    }
+}
 
-    let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", db_filename))?
-        .create_if_missing(true)
+fn weekly_options(
+    path: &str,
+    config: &StorageConfig,
+) -> Result<SqliteConnectOptions, StorageError> {
+    let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path))?
         .journal_mode(sqlite::SqliteJournalMode::Wal)
         .synchronous(sqlite::SqliteSynchronous::Normal)
         .busy_timeout(StdDuration::from_secs(30))
-        .statement_cache_capacity(100)
         .auto_vacuum(sqlite::SqliteAutoVacuum::Incremental)
-        .analysis_limit(Some(400))
-        .command_buffer_size(5000);
+        .analysis_limit(Some(400));
+    Ok(config.apply(options))
+}
 
-    let pool = SqlitePool::connect_with(options).await?;
-    // sqlx::migrate!().run(&pool).await?;
-    let schema = include_str!("../migrations/schema.sql");
-    sqlx::query(schema).execute(&pool).await?;
-    Ok(pool)
+/// Migrates the existing files of the `upgrade_recent_weeks` weeks before
+/// `now`'s, logging each one that was behind. A file that can't be upgraded
+/// is logged and skipped. Returns the upgraded paths.
+///
+/// Walks back file by file rather than by 7 days, so the short files either
+/// side of New Year under `WeekScheme::Calendar` each count as a week.
+async fn upgrade_recent_files(
+    paths: &Paths,
+    now: DateTime<Utc>,
+    config: &StorageConfig,
+) -> Vec<String> {
+    let anchor = config.week_anchor();
+    let mut upgraded = Vec::new();
+    let mut date = now;
+    for _ in 0..config.upgrade_recent_weeks {
+        let (year, week) = anchor.previous(date);
+        // The last second of that file, to step back from next time.
+        date = anchor.start(date) - Duration::seconds(1);
+        let path = db_path_for_week(paths, year, week);
+        if !Path::new(&path).exists() {
+            continue;
+        }
+        match upgrade_file(&path, config).await {
+            Ok(from) if from < schema::latest_version() => {
+                info!(
+                    "Upgraded {} from schema version {} to {}",
+                    path,
+                    from,
+                    schema::latest_version()
+                );
+                upgraded.push(path);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to upgrade {}: {}", path, e),
+        }
+    }
+    upgraded
+}
+
+/// Runs the migrations on the file at `path`, returning the version it was at.
+async fn upgrade_file(path: &str, config: &StorageConfig) -> Result<i64, StorageError> {
+    let pool = SqlitePool::connect_with(weekly_options(path, config)?).await?;
+    let result = schema::migrate(&pool).await;
+    pool.close().await;
+    result
 }
 
 pub fn get_date_components(date: DateTime<Utc>) -> (i32, u32) {
-    let iso = date.iso_week();
-    (iso.year(), iso.week())
+    WeekScheme::Iso.components(date)
 }
 
 /// Calculates the ISO year and week of the week prior to the given date.
@@ -116,7 +811,9 @@ pub fn get_previous_iso_week_components(date: DateTime<Utc>) -> (i32, u32) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::TempDir;
     use chrono::TimeZone;
+    use common::clock::ManualClock;
 
     #[test]
     fn test_dec_29_2025_handling() {
@@ -147,4 +844,195 @@ mod tests {
         assert_eq!(prev_year, 2025, "Expected previous year to be 2025");
         assert_eq!(prev_week, 52, "Expected previous week to be 52");
     }
+
+    #[test]
+    fn test_calendar_weeks_stay_in_their_year() {
+        let day = |y, m, d| Utc.with_ymd_and_hms(y, m, d, 12, 0, 0).unwrap();
+        let calendar = WeekScheme::Calendar;
+
+        // Monday Dec 29 2025 stays in 2025; Thursday Jan 1 2026 opens week 00.
+        assert_eq!(calendar.components(day(2025, 12, 29)), (2025, 52));
+        assert_eq!(calendar.components(day(2026, 1, 1)), (2026, 0));
+        assert_eq!(calendar.components(day(2026, 1, 5)), (2026, 1));
+
+        assert_eq!(calendar.previous(day(2026, 1, 1)), (2025, 52));
+        assert_eq!(calendar.previous(day(2026, 1, 5)), (2026, 0));
+        assert_eq!(calendar.previous(day(2025, 12, 29)), (2025, 51));
+        assert_eq!(
+            WeekScheme::Iso.previous(day(2026, 1, 1)),
+            get_previous_iso_week_components(day(2026, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_offset_anchor_moves_the_week_boundary() {
+        // Sunday 20:00 UTC is already Monday 04:00 at +08:00.
+        let sunday_evening = Utc.with_ymd_and_hms(2026, 1, 11, 20, 0, 0).unwrap();
+        let utc = WeekAnchor::default();
+        let shanghai = WeekAnchor {
+            scheme: WeekScheme::Iso,
+            offset: "+08:00".parse().unwrap(),
+        };
+
+        assert_eq!(utc.components(sunday_evening), (2026, 2));
+        assert_eq!(shanghai.components(sunday_evening), (2026, 3));
+        assert_eq!(shanghai.previous(sunday_evening), (2026, 2));
+        assert_eq!(
+            pack_week(shanghai, sunday_evening),
+            pack_week(utc, sunday_evening + Duration::hours(8))
+        );
+    }
+
+    #[test]
+    fn test_period_names_a_weekly_file() {
+        let paths = Paths::new("/srv/bot").with_data_dir("data");
+        assert_eq!(
+            db_path_for_period(&paths, "2026_01").unwrap(),
+            db_path_for_week(&paths, 2026, 1)
+        );
+        assert!(db_path_for_period(&paths, "2026-01").is_err());
+        assert!(db_path_for_period(&paths, "2026_W01").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_storage_config_pragmas_reach_connections() {
+        let dir = TempDir::new("config");
+        let config = StorageConfig {
+            mmap_bytes: Some(1024 * 1024),
+            cache_kib: Some(4096),
+            ..StorageConfig::default()
+        };
+        let (tx, _rx) = mpsc::channel(1);
+        let (pool, _) = RotatingPool::new(dir.folder(), tx, config)
+            .await
+            .unwrap()
+            .get_pool()
+            .await
+            .unwrap();
+
+        let cache: i64 = sqlx::query_scalar("PRAGMA cache_size")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(cache, -4096);
+        let mmap: i64 = sqlx::query_scalar("PRAGMA mmap_size")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(mmap, 1024 * 1024);
+    }
+
+    #[test]
+    fn test_parse_extra_indexes() {
+        assert_eq!(
+            parse_extra_indexes(
+                "create index idx_price ON agg_trades(symbol_id, price); \
+                 CREATE INDEX IF NOT EXISTS idx_obi ON orderbook_features(obi);"
+            )
+            .unwrap(),
+            vec![
+                "CREATE INDEX IF NOT EXISTS idx_price ON agg_trades(symbol_id, price)",
+                "CREATE INDEX IF NOT EXISTS idx_obi ON orderbook_features(obi)",
+            ]
+        );
+        assert!(parse_extra_indexes("DROP TABLE agg_trades").is_err());
+        assert!(parse_extra_indexes("CREATE UNIQUE INDEX u ON symbols(ticker)").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upgrades_only_recent_weeks() {
+        let dir = TempDir::new("upgrade");
+        let paths = Paths::new(dir.folder());
+        std::fs::create_dir_all(paths.current_dir()).unwrap();
+        let config = StorageConfig {
+            upgrade_recent_weeks: 2,
+            ..StorageConfig::default()
+        };
+        // Empty files, as far behind the schema as a file can be.
+        for week in [3, 5] {
+            let path = db_path_for_week(&paths, 2026, week);
+            let options = weekly_options(&path, &config)
+                .unwrap()
+                .create_if_missing(true);
+            SqlitePool::connect_with(options)
+                .await
+                .unwrap()
+                .close()
+                .await;
+        }
+
+        // Wednesday of 2026-W06: W05 is within 2 weeks, W03 is not.
+        let now = Utc.with_ymd_and_hms(2026, 2, 4, 12, 0, 0).unwrap();
+        let upgraded = upgrade_recent_files(&paths, now, &config).await;
+        assert_eq!(upgraded, vec![db_path_for_week(&paths, 2026, 5)]);
+        assert!(upgrade_recent_files(&paths, now, &config).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_upgrades_the_new_year_file_of_calendar_weeks() {
+        let dir = TempDir::new("upgrade");
+        let paths = Paths::new(dir.folder());
+        std::fs::create_dir_all(paths.current_dir()).unwrap();
+        let config = StorageConfig {
+            upgrade_recent_weeks: 2,
+            week_scheme: WeekScheme::Calendar,
+            ..StorageConfig::default()
+        };
+        for (year, week) in [(2025, 51), (2025, 52), (2026, 0)] {
+            let path = db_path_for_week(&paths, year, week);
+            let options = weekly_options(&path, &config)
+                .unwrap()
+                .create_if_missing(true);
+            SqlitePool::connect_with(options)
+                .await
+                .unwrap()
+                .close()
+                .await;
+        }
+
+        // Tuesday of 2026_01. The two files before it are Jan 1-4 (2026_00)
+        // and Dec 29-31 (2025_52), both shorter than a week.
+        let now = Utc.with_ymd_and_hms(2026, 1, 6, 12, 0, 0).unwrap();
+        let upgraded = upgrade_recent_files(&paths, now, &config).await;
+        assert_eq!(
+            upgraded,
+            vec![
+                db_path_for_week(&paths, 2026, 0),
+                db_path_for_week(&paths, 2025, 52),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_pool_rotates_across_year_boundary() {
+        let dir = TempDir::new("rotation");
+        let current = Paths::new(dir.folder()).current_dir();
+
+        // Wednesday of 2025-W52.
+        let clock = Arc::new(ManualClock::new(
+            Utc.with_ymd_and_hms(2025, 12, 24, 23, 0, 0).unwrap(),
+        ));
+        let (tx, mut rx) = mpsc::channel(4);
+        let pool =
+            RotatingPool::new_with_clock(dir.folder(), tx, StorageConfig::default(), clock.clone())
+                .await
+                .unwrap();
+        pool.set_backup_scripts("/opt/utils".to_string());
+
+        let (_, rotated) = pool.get_pool().await.unwrap();
+        assert!(!rotated);
+        assert!(current.join("crypto_2025_52.db").exists());
+
+        // Monday of 2026-W01.
+        clock.set(Utc.with_ymd_and_hms(2025, 12, 29, 0, 0, 1).unwrap());
+        let (_, rotated) = pool.get_pool().await.unwrap();
+        assert!(rotated);
+        assert!(current.join("crypto_2026_01.db").exists());
+        assert!(matches!(rx.try_recv(), Ok(ControlMessage::Spawn(_))));
+
+        // Same week again: no new file, no second backup.
+        let (_, rotated) = pool.get_pool().await.unwrap();
+        assert!(!rotated);
+        assert!(rx.try_recv().is_err());
+    }
 }
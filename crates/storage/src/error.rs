@@ -0,0 +1,130 @@
+use sqlx::error::ErrorKind;
+use thiserror::Error;
+
+/// Failures of the storage API, grouped by what a caller can do about them.
+///
+/// Only `Locked` is worth retrying as-is; the repositories already do so a
+/// few times before surfacing it.
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The pool could not hand out a working connection (timed out, closed,
+    /// bad connect options).
+    #[error("Connection error: {0}")]
+    Connection(#[source] sqlx::Error),
+    /// SQLITE_BUSY/SQLITE_LOCKED: another writer holds the database.
+    #[error("Database is locked: {0}")]
+    Locked(#[source] sqlx::Error),
+    /// Applying the schema to a database file failed.
+    #[error("Schema error: {0}")]
+    Migration(#[source] sqlx::Error),
+    /// A row broke a UNIQUE, NOT NULL, CHECK or foreign key constraint.
+    #[error("Constraint violated: {0}")]
+    Constraint(#[source] sqlx::Error),
+    /// Opening the new week's database failed; writes go nowhere until it succeeds.
+    #[error("Rotating to {path} failed: {source}")]
+    RotationFailed {
+        path: String,
+        #[source]
+        source: Box<StorageError>,
+    },
+    /// No database file for the requested week, either never written or
+    /// already archived by the backup.
+    #[error("No database for week {year}-W{week:02} at {path}")]
+    WeekNotFound { year: i32, week: u32, path: String },
+    /// A table name outside `data_manager::TABLES`.
+    #[error("Unknown table '{0}'")]
+    UnknownTable(String),
+    #[error("Query failed: {0}")]
+    Query(#[source] sqlx::Error),
+    /// Encoding or writing an Arrow stream failed.
+    #[error("Arrow write failed: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    /// A read of `agg_trades` while `AGG_TRADE_SINK=arrow` sends the trades
+    /// to Arrow streams instead, so the table would look empty.
+    #[error("Aggregate trades go to Arrow streams (AGG_TRADE_SINK=arrow), not agg_trades")]
+    TradesInArrow,
+    /// The combined writer's transaction failed, and with it every batch it
+    /// held. `retryable` is the underlying error's `is_retryable`.
+    #[error("Combined write failed: {message}")]
+    CombinedWrite { message: String, retryable: bool },
+}
+
+impl StorageError {
+    /// Whether the same operation may succeed if simply tried again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Locked(_) => true,
+            Self::Connection(e) => matches!(e, sqlx::Error::PoolTimedOut),
+            Self::RotationFailed { source, .. } => source.is_retryable(),
+            Self::CombinedWrite { retryable, .. } => *retryable,
+            _ => false,
+        }
+    }
+}
+
+impl From<sqlx::Error> for StorageError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::Io(e) => Self::Io(e),
+            sqlx::Error::Migrate(_) => Self::Migration(error),
+            sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed
+            | sqlx::Error::Configuration(_)
+            | sqlx::Error::Tls(_) => Self::Connection(error),
+            sqlx::Error::Database(ref db_error) => {
+                // Extended result codes keep the primary code in the low byte.
+                let code = db_error.code().and_then(|code| code.parse::<i32>().ok());
+                if code.is_some_and(|code| matches!(code & 0xff, 5 | 6)) {
+                    return Self::Locked(error);
+                }
+                match db_error.kind() {
+                    ErrorKind::UniqueViolation
+                    | ErrorKind::ForeignKeyViolation
+                    | ErrorKind::NotNullViolation
+                    | ErrorKind::CheckViolation => Self::Constraint(error),
+                    _ => Self::Query(error),
+                }
+            }
+            _ => Self::Query(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    #[tokio::test]
+    async fn test_classifies_constraint_and_schema_errors() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE t (v INTEGER NOT NULL UNIQUE)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO t VALUES (1)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let duplicate = sqlx::query("INSERT INTO t VALUES (1)")
+            .execute(&pool)
+            .await
+            .unwrap_err();
+        let duplicate = StorageError::from(duplicate);
+        assert!(matches!(duplicate, StorageError::Constraint(_)));
+        assert!(!duplicate.is_retryable());
+
+        let missing = sqlx::query("SELECT * FROM nope")
+            .execute(&pool)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            StorageError::from(missing),
+            StorageError::Query(_)
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt::Write as _;
+use std::path::Path;
+use std::pin::pin;
+
+use common::models::orderbook::unpack_levels;
+use common::symbols::SymbolAliases;
+use futures_util::TryStreamExt;
+use sqlx::sqlite::{SqliteRow, SqliteValueRef};
+use sqlx::{Decode, Row, SqlitePool, TypeInfo, ValueRef};
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+use crate::data_manager::TABLES;
+use crate::error::StorageError;
+use crate::repositories::AggTradeRepository;
+
+/// Writes every row of `table` to `out_path` as CSV, header first.
+///
+/// Rows are streamed from the database, so memory use doesn't grow with the
+/// table. Where the table has a `<table>_v` view it is exported instead, so
+/// symbols appear as tickers rather than ids. Blob columns (the packed order
+/// book levels) become JSON `[[price,qty],...]` arrays. Returns the number of
+/// rows written.
+pub async fn to_csv(pool: &SqlitePool, table: &str, out_path: &Path) -> Result<u64, StorageError> {
+    let Some(&table) = TABLES.iter().find(|&&t| t == table) else {
+        return Err(StorageError::UnknownTable(table.to_string()));
+    };
+    let view = format!("{}_v", table);
+    let has_view: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'view' AND name = ?",
+    )
+    .bind(&view)
+    .fetch_one(pool)
+    .await?;
+    let source = if has_view { view.as_str() } else { table };
+
+    let header: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM pragma_table_info(?) ORDER BY cid")
+            .bind(source)
+            .fetch_all(pool)
+            .await?;
+
+    let mut out = BufWriter::new(File::create(out_path).await?);
+    out.write_all(header.join(",").as_bytes()).await?;
+    out.write_all(b"\n").await?;
+    let select = format!("SELECT * FROM {}", source);
+    let mut rows = sqlx::query(&select).fetch(pool);
+    let mut written = 0;
+    let mut line = String::new();
+    while let Some(row) = rows.try_next().await? {
+        line.clear();
+        for i in 0..row.len() {
+            if i > 0 {
+                line.push(',');
+            }
+            push_field(&mut line, &cell(&row, i)?);
+        }
+        line.push('\n');
+        out.write_all(line.as_bytes()).await?;
+        written += 1;
+    }
+    out.flush().await?;
+    Ok(written)
+}
+
+/// Writes the trades of `symbol` received in `[start, end)` (seconds) to
+/// `out_path` as CSV, oldest first, and returns how many were written.
+///
+/// A renamed pair is looked up under its current name in `aliases`, which
+/// is where its rows are stored. Built on `AggTradeRepository::stream_range`,
+/// so a range spanning the whole week is written one row at a time.
+pub async fn trades_to_csv(
+    pool: &SqlitePool,
+    symbol: &str,
+    aliases: &SymbolAliases,
+    start: f64,
+    end: f64,
+    out_path: &Path,
+) -> Result<u64, StorageError> {
+    let symbol = aliases.canonical(symbol);
+    let mut out = BufWriter::new(File::create(out_path).await?);
+    out.write_all(b"agg_trade_id,time,trade_time,price,quantity,is_buyer_maker\n")
+        .await?;
+    let mut trades = pin!(AggTradeRepository::stream_range(pool, &symbol, start, end));
+    let mut written = 0;
+    let mut line = String::new();
+    while let Some(trade) = trades.try_next().await? {
+        line.clear();
+        let agg_trade_id = trade.agg_trade_id.map(|id| id.to_string());
+        let trade_time = trade.trade_time.map(|t| t.to_string());
+        let _ = writeln!(
+            line,
+            "{},{},{},{},{},{}",
+            agg_trade_id.unwrap_or_default(),
+            trade.time,
+            trade_time.unwrap_or_default(),
+            trade.price,
+            trade.quantity,
+            trade.is_buyer_maker as u8
+        );
+        out.write_all(line.as_bytes()).await?;
+        written += 1;
+    }
+    out.flush().await?;
+    Ok(written)
+}
+
+/// Text of column `i`, empty for NULL.
+fn cell(row: &SqliteRow, i: usize) -> Result<String, StorageError> {
+    let value = row.try_get_raw(i)?;
+    if value.is_null() {
+        return Ok(String::new());
+    }
+    let decoded = match value.type_info().name() {
+        "INTEGER" | "BOOLEAN" => decode::<i64>(value).map(|v| v.to_string()),
+        "REAL" => decode::<f64>(value).map(|v| v.to_string()),
+        "BLOB" => decode::<Vec<u8>>(value).map(|levels| levels_json(&levels)),
+        _ => decode::<String>(value),
+    };
+    decoded.map_err(|e| StorageError::Query(sqlx::Error::Decode(e)))
+}
+
+fn decode<'r, T: Decode<'r, sqlx::Sqlite>>(
+    value: SqliteValueRef<'r>,
+) -> Result<T, sqlx::error::BoxDynError> {
+    T::decode(value)
+}
+
+fn levels_json(levels: &[u8]) -> String {
+    let mut json = String::from("[");
+    for (n, (price, qty)) in unpack_levels(levels).enumerate() {
+        if n > 0 {
+            json.push(',');
+        }
+        let _ = write!(json, "[{},{}]", price, qty);
+    }
+    json.push(']');
+    json
+}
+
+/// Appends `field`, quoted when it holds a comma, quote or line break.
+fn push_field(line: &mut String, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        line.push('"');
+        line.push_str(&field.replace('"', "\"\""));
+        line.push('"');
+    } else {
+        line.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[tokio::test]
+    async fn test_exports_view_with_levels_as_json() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::schema::migrate(&pool).await.unwrap();
+        let level = |price: f32, qty: f32| [price.to_le_bytes(), qty.to_le_bytes()].concat();
+        // BTCUSDT is seeded as symbol 1.
+        sqlx::query(
+            "INSERT INTO order_books (time, symbol_id, bids, asks, seq) VALUES (1.5, 1, ?, ?, 7)",
+        )
+        .bind([level(10.5, 2.0), level(10.0, 1.0)].concat())
+        .bind(Vec::<u8>::new())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let dir = TempDir::new("export");
+        let out = dir.path().join("order_books.csv");
+        assert_eq!(to_csv(&pool, "order_books", &out).await.unwrap(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "id,time,symbol,bids,asks,seq\n1,1.5,BTCUSDT,\"[[10.5,2],[10,1]]\",[],7\n"
+        );
+        assert_eq!(to_csv(&pool, "liquidations", &out).await.unwrap(), 0);
+        assert!(
+            std::fs::read_to_string(&out)
+                .unwrap()
+                .starts_with("id,time,symbol,")
+        );
+        assert!(matches!(
+            to_csv(&pool, "sqlite_master", &out).await,
+            Err(StorageError::UnknownTable(_))
+        ));
+    }
+}
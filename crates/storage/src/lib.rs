@@ -1,6 +1,20 @@
 mod actors;
 
+pub mod backend;
+pub mod combined_writer;
 pub mod data_manager;
 pub mod db;
+pub mod error;
+pub mod export;
+pub mod maintenance;
 pub mod repositories;
+pub mod schema;
 pub mod symbol_manager;
+/// Temp dirs and managers for tests, here and in crates built on `storage`
+/// (through the `test-support` feature).
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod trade_sink;
+pub mod write_queue;
+
+pub use error::StorageError;
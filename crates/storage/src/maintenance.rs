@@ -0,0 +1,307 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::actors::{Actor, ActorType, ControlMessage};
+use common::clock::{Clock, SystemClock};
+use tokio::sync::mpsc;
+use tokio::time;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::data_manager::DataManager;
+use crate::repositories::KlinesRepository;
+
+/// What the maintenance window compacts and how often it runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactionConfig {
+    /// Interval whose rows are rolled up and deleted.
+    pub source_interval: String,
+    /// Interval they are rolled up into.
+    pub target_interval: String,
+    /// Source candles younger than this are left alone.
+    pub min_age: Duration,
+    /// Time between maintenance windows.
+    pub period: Duration,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            source_interval: "1s".to_string(),
+            target_interval: "1m".to_string(),
+            min_age: Duration::from_secs(24 * 3600),
+            period: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl CompactionConfig {
+    /// Reads `KLINE_COMPACT_SOURCE` (1s), `KLINE_COMPACT_TARGET` (1m),
+    /// `KLINE_COMPACT_AFTER_HOURS` (24) and `MAINTENANCE_MINUTES` (60).
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        let config = Self {
+            source_interval: env::var("KLINE_COMPACT_SOURCE").unwrap_or(defaults.source_interval),
+            target_interval: env::var("KLINE_COMPACT_TARGET").unwrap_or(defaults.target_interval),
+            min_age: match env::var("KLINE_COMPACT_AFTER_HOURS") {
+                Ok(hours) => Duration::from_secs(
+                    hours
+                        .parse::<u64>()
+                        .map_err(|_| format!("KLINE_COMPACT_AFTER_HOURS: '{}'", hours))?
+                        * 3600,
+                ),
+                Err(_) => defaults.min_age,
+            },
+            period: match env::var("MAINTENANCE_MINUTES") {
+                Ok(minutes) => match minutes.parse::<u64>() {
+                    Ok(minutes) if minutes > 0 => Duration::from_secs(minutes * 60),
+                    _ => return Err(format!("MAINTENANCE_MINUTES: '{}'", minutes)),
+                },
+                Err(_) => defaults.period,
+            },
+        };
+        config.target_ms()?;
+        Ok(config)
+    }
+
+    /// Length of a target candle. It must be a whole number of source candles.
+    pub fn target_ms(&self) -> Result<i64, String> {
+        let source = interval_ms(&self.source_interval)
+            .ok_or_else(|| format!("unknown kline interval '{}'", self.source_interval))?;
+        let target = interval_ms(&self.target_interval)
+            .ok_or_else(|| format!("unknown kline interval '{}'", self.target_interval))?;
+        if target <= source || target % source != 0 {
+            return Err(format!(
+                "cannot compact {} into {}",
+                self.source_interval, self.target_interval
+            ));
+        }
+        Ok(target)
+    }
+
+    /// Source candles starting before this are compacted: `min_age` ago,
+    /// rounded down to a target boundary so no bucket is split.
+    pub fn cutoff(&self, now_ms: i64, target_ms: i64) -> i64 {
+        let cutoff = now_ms - self.min_age.as_millis() as i64;
+        cutoff - cutoff.rem_euclid(target_ms)
+    }
+}
+
+/// Milliseconds in a Binance interval such as `1s`, `15m`, `4h` or `1d`.
+pub fn interval_ms(interval: &str) -> Option<i64> {
+    let unit = match interval.chars().last()? {
+        's' => 1_000,
+        'm' => 60_000,
+        'h' => 3_600_000,
+        'd' => 86_400_000,
+        _ => return None,
+    };
+    let count = interval[..interval.len() - 1].parse::<i64>().ok()?;
+    (count > 0).then_some(count * unit)
+}
+
+/// Periodic maintenance window on the current week's database: compacts aged
+/// klines, then returns the freed pages with `PRAGMA incremental_vacuum`.
+/// On its own schedule (`StorageConfig::optimize_every`) it also runs
+/// `PRAGMA optimize`.
+///
+/// Failures are logged and retried at the next window.
+pub struct MaintenanceActor {
+    id: Uuid,
+    rotating_pool: Arc<DataManager>,
+    config: CompactionConfig,
+}
+
+#[async_trait]
+impl Actor for MaintenanceActor {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> ActorType {
+        ActorType::MaintenanceActor
+    }
+
+    async fn run(&mut self, supervisor_tx: mpsc::Sender<ControlMessage>) -> anyhow::Result<()> {
+        let _heartbeat_handle = self.spawn_heartbeat(supervisor_tx.clone());
+
+        info!(
+            "Starting Maintenance Actor ({} -> {} after {}h, every {}m)",
+            self.config.source_interval,
+            self.config.target_interval,
+            self.config.min_age.as_secs() / 3600,
+            self.config.period.as_secs() / 60
+        );
+
+        let target_ms = self.config.target_ms().map_err(anyhow::Error::msg)?;
+        let mut ticker = time::interval(self.config.period);
+        // The file was just opened; its statistics are no staler than now.
+        let mut optimize = self
+            .rotating_pool
+            .pool_rotator
+            .config()
+            .optimize_every
+            .map(|period| time::interval_at(time::Instant::now() + period, period));
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => self.run_window(target_ms).await,
+                _ = tick(&mut optimize) => {
+                    if let Err(e) = self.rotating_pool.pool_rotator.optimize().await {
+                        warn!("PRAGMA optimize failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MaintenanceActor {
+    pub fn new(rotating_pool: Arc<DataManager>, config: CompactionConfig) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            rotating_pool,
+            config,
+        }
+    }
+
+    async fn run_window(&self, target_ms: i64) {
+        let cutoff = self
+            .config
+            .cutoff(SystemClock.now_millis() as i64, target_ms);
+        match KlinesRepository::compact(
+            &self.rotating_pool,
+            &self.config.source_interval,
+            &self.config.target_interval,
+            target_ms,
+            cutoff,
+        )
+        .await
+        {
+            Ok((0, 0)) => {}
+            Ok((added, deleted)) => info!(
+                "Compacted {} {} klines into {} {} candles",
+                deleted, self.config.source_interval, added, self.config.target_interval
+            ),
+            Err(e) => warn!("Kline compaction failed: {}", e),
+        }
+
+        let pool = match self.rotating_pool.pool_rotator.get_pool().await {
+            Ok((pool, _)) => pool,
+            Err(e) => {
+                warn!("Incremental vacuum skipped: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = sqlx::query("PRAGMA incremental_vacuum")
+            .execute(&pool)
+            .await
+        {
+            warn!("Incremental vacuum failed: {}", e);
+        }
+    }
+}
+
+/// The next tick of `interval`, or never without one.
+async fn tick(interval: &mut Option<time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::StorageConfig;
+    use crate::test_support::{TempDir, manager};
+    use common::models::KlineInsert;
+
+    fn kline(start_time: i64, interval: &str, open: f32, close: f32) -> KlineInsert {
+        KlineInsert {
+            symbol: "BTCUSDT".to_string(),
+            start_time,
+            close_time: start_time + 999,
+            interval: interval.to_string(),
+            open_price: open,
+            close_price: close,
+            high_price: open.max(close),
+            low_price: open.min(close),
+            volume: 1.0,
+            no_of_trades: 2,
+            taker_buy_vol: 0.5,
+            seq: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compacts_aged_seconds_into_minutes() {
+        let dir = TempDir::new("compact");
+        let manager = manager(&dir, StorageConfig::default()).await;
+
+        // Two seconds in minute 0, one in minute 1 (already has a 1m candle),
+        // one in minute 2 (too young).
+        let rows = [
+            kline(0, "1s", 10.0, 11.0),
+            kline(1_000, "1s", 11.0, 9.0),
+            kline(60_000, "1s", 5.0, 5.0),
+            kline(60_000, "1m", 7.0, 7.0),
+            kline(120_000, "1s", 1.0, 1.0),
+        ];
+        KlinesRepository::insert_batch(&manager, &rows)
+            .await
+            .unwrap();
+
+        let config = CompactionConfig {
+            min_age: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let target_ms = config.target_ms().unwrap();
+        let cutoff = config.cutoff(190_000, target_ms);
+        assert_eq!(cutoff, 120_000);
+
+        let (added, deleted) = KlinesRepository::compact(&manager, "1s", "1m", target_ms, cutoff)
+            .await
+            .unwrap();
+        assert_eq!((added, deleted), (1, 3));
+
+        let (pool, _) = manager.pool_rotator.get_pool().await.unwrap();
+        // start, close time, open, close, high, low, volume, trades
+        type Candle = (i64, i64, f32, f32, f32, f32, f64, i64);
+        let minutes: Vec<Candle> = sqlx::query_as(
+            "SELECT start_time, close_time, open_price, close_price, high_price, low_price,
+                    volume, no_of_trades
+             FROM klines WHERE interval = '1m' ORDER BY start_time",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            minutes,
+            vec![
+                (0, 59_999, 10.0, 9.0, 11.0, 9.0, 2.0, 4),
+                (60_000, 60_999, 7.0, 7.0, 7.0, 7.0, 1.0, 2),
+            ]
+        );
+        let seconds: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM klines WHERE interval = '1s'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(seconds, 1);
+    }
+
+    #[test]
+    fn test_rejects_incompatible_intervals() {
+        let config = |source: &str, target: &str| CompactionConfig {
+            source_interval: source.to_string(),
+            target_interval: target.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(config("1s", "1m").target_ms(), Ok(60_000));
+        assert!(config("1m", "1s").target_ms().is_err());
+        assert!(config("1s", "1x").target_ms().is_err());
+    }
+}
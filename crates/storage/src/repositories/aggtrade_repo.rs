@@ -1,38 +1,276 @@
-use common::models::AggTradeInsert;
+use std::fmt;
+
+use common::models::{AggTrade, AggTradeInsert};
+use futures_util::{Stream, TryStreamExt};
+use sqlx::{SqliteConnection, SqlitePool};
 
 use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
 
 pub struct AggTradeRepository;
 
+/// How far one symbol's trades arrived behind their exchange timestamp.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct TradeLatency {
+    pub symbol: String,
+    pub trades: i64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl fmt::Display for TradeLatency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} p50 {:.0}ms p99 {:.0}ms max {:.0}ms ({} trades)",
+            self.symbol, self.p50_ms, self.p99_ms, self.max_ms, self.trades
+        )
+    }
+}
+
 impl AggTradeRepository {
+    /// Inserts `trades` in one transaction, retrying while the database is locked.
+    /// Trades whose `agg_trade_id` is already stored for the symbol are skipped.
     pub async fn insert_batch(
         data_manager: &DataManager,
         trades: &[AggTradeInsert],
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Trades).await;
+        with_busy_retry("AggTrade", || Self::try_insert_batch(data_manager, trades)).await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        trades: &[AggTradeInsert],
+    ) -> Result<(), StorageError> {
         if trades.is_empty() {
             return Ok(());
         }
-        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(trades.iter().map(|trade| trade.symbol.as_str()))
+            .await?;
         let mut tx = pool.begin().await?;
+        Self::insert_rows(&mut tx, trades, &symbol_ids).await?;
+        tx.commit().await?;
+        Ok(())
+    }
 
-        for trade in trades {
-            let symbol_id = data_manager.get_symbol_id(&trade.symbol).await?;
+    /// Inserts `trades`, whose symbols resolve to `symbol_ids`, on `conn`
+    /// without committing.
+    pub(crate) async fn insert_rows(
+        conn: &mut SqliteConnection,
+        trades: &[AggTradeInsert],
+        symbol_ids: &[i64],
+    ) -> Result<(), StorageError> {
+        for (trade, symbol_id) in trades.iter().zip(symbol_ids) {
             sqlx::query(
                 r#"
-                    INSERT INTO agg_trades (
-                        time, symbol_id, price, quantity, is_buyer_maker
-                    ) VALUES (?, ?, ?, ?, ?)
+                    INSERT OR IGNORE INTO agg_trades (
+                        agg_trade_id, time, trade_time, symbol_id, price, quantity,
+                        is_buyer_maker, seq
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
                 "#,
             )
+            .bind(trade.agg_trade_id)
             .bind(trade.time)
+            .bind(trade.trade_time)
             .bind(symbol_id)
             .bind(trade.price)
             .bind(trade.quantity)
             .bind(trade.is_buyer_maker)
-            .execute(&mut *tx)
+            .bind(trade.seq)
+            .execute(&mut *conn)
             .await?;
         }
-        tx.commit().await?;
         Ok(())
     }
+
+    /// Streams the trades of `symbol` received in `[start, end)` (seconds) in
+    /// time order. Rows are decoded as the stream is polled, so a range of any
+    /// size is read in constant memory.
+    pub fn stream_range<'a>(
+        pool: &'a SqlitePool,
+        symbol: &str,
+        start: f64,
+        end: f64,
+    ) -> impl Stream<Item = Result<AggTrade, StorageError>> + Send + use<'a> {
+        sqlx::query_as::<_, (i64, Option<i64>, f64, Option<f64>, i64, f64, f64, bool)>(
+            r#"
+                SELECT t.id, t.agg_trade_id, t.time, t.trade_time, t.symbol_id,
+                       t.price, t.quantity, t.is_buyer_maker
+                FROM agg_trades t JOIN symbols s ON s.id = t.symbol_id
+                WHERE s.ticker = ? AND t.time >= ? AND t.time < ?
+                ORDER BY t.time
+            "#,
+        )
+        .bind(symbol.to_uppercase())
+        .bind(start)
+        .bind(end)
+        .fetch(pool)
+        .map_ok(
+            |(id, agg_trade_id, time, trade_time, symbol_id, price, quantity, is_buyer_maker)| {
+                AggTrade {
+                    id,
+                    agg_trade_id,
+                    time,
+                    trade_time,
+                    symbol_id,
+                    price,
+                    quantity,
+                    is_buyer_maker,
+                }
+            },
+        )
+        .map_err(StorageError::from)
+    }
+
+    /// Ingestion latency percentiles per symbol over trades received at or
+    /// after `since` (seconds), using the nearest-rank method. A p99 that
+    /// keeps climbing means this host or its network is falling behind.
+    /// `TradesInArrow` with `AGG_TRADE_SINK=arrow`.
+    pub async fn latency_since(
+        data_manager: &DataManager,
+        since: f64,
+    ) -> Result<Vec<TradeLatency>, StorageError> {
+        if !data_manager.trades_in_sqlite() {
+            return Err(StorageError::TradesInArrow);
+        }
+        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let rows = sqlx::query_as::<_, TradeLatency>(
+            r#"
+                WITH ranked AS (
+                    SELECT symbol_id, latency_ms,
+                           ROW_NUMBER() OVER (PARTITION BY symbol_id ORDER BY latency_ms) AS rank,
+                           COUNT(*) OVER (PARTITION BY symbol_id) AS n
+                    FROM agg_trades
+                    WHERE time >= ? AND latency_ms IS NOT NULL
+                )
+                SELECT s.ticker AS symbol,
+                       MAX(n) AS trades,
+                       MIN(CASE WHEN rank >= n * 0.50 THEN latency_ms END) AS p50_ms,
+                       MIN(CASE WHEN rank >= n * 0.99 THEN latency_ms END) AS p99_ms,
+                       MAX(latency_ms) AS max_ms
+                FROM ranked r JOIN symbols s ON s.id = r.symbol_id
+                GROUP BY s.ticker
+                ORDER BY s.ticker
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&pool)
+        .await?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::StorageConfig;
+    use crate::test_support::{TempDir, manager, trade};
+
+    #[tokio::test]
+    async fn test_replayed_trade_is_stored_once() {
+        let dir = TempDir::new("dedup");
+        let manager = manager(&dir, StorageConfig::default()).await;
+
+        let trade = trade(42, 1.0, "BTCUSDT", 1.0);
+        AggTradeRepository::insert_batch(&manager, std::slice::from_ref(&trade))
+            .await
+            .unwrap();
+        AggTradeRepository::insert_batch(&manager, &[trade])
+            .await
+            .unwrap();
+
+        let (pool, _) = manager.pool_rotator.get_pool().await.unwrap();
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM agg_trades")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_range_reads_one_symbol_in_time_order() {
+        let dir = TempDir::new("stream");
+        let manager = manager(&dir, StorageConfig::default()).await;
+
+        let trades: Vec<AggTradeInsert> = (1..=6)
+            .map(|i| {
+                // Out of order, so the ORDER BY is what sorts them.
+                let symbol = if i % 3 == 0 { "ETHUSDT" } else { "BTCUSDT" };
+                trade(i, (7 - i) as f64, symbol, i as f64)
+            })
+            .collect();
+        AggTradeRepository::insert_batch(&manager, &trades)
+            .await
+            .unwrap();
+
+        let (pool, _) = manager.pool_rotator.get_pool().await.unwrap();
+        let streamed: Vec<AggTrade> = AggTradeRepository::stream_range(&pool, "btcusdt", 2.0, 6.0)
+            .try_collect()
+            .await
+            .unwrap();
+        let ids: Vec<Option<i64>> = streamed.iter().map(|t| t.agg_trade_id).collect();
+        // Times 2..=5 of BTCUSDT: ids 5, 4 and 2 (3 is ETHUSDT).
+        assert_eq!(ids, vec![Some(5), Some(4), Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_latency_percentiles_per_symbol() {
+        let dir = TempDir::new("latency");
+        let manager = manager(&dir, StorageConfig::default()).await;
+
+        // BTCUSDT received 1..=100ms after each trade, ETHUSDT 5ms.
+        let mut trades: Vec<AggTradeInsert> = (1..=100)
+            .map(|i| AggTradeInsert {
+                agg_trade_id: i,
+                time: 100.0 + i as f64 / 1000.0,
+                trade_time: 100.0,
+                symbol: "BTCUSDT".to_string(),
+                price: 1.0,
+                quantity: 1.0,
+                is_buyer_maker: false,
+                seq: None,
+            })
+            .collect();
+        trades.push(AggTradeInsert {
+            agg_trade_id: 1,
+            time: 100.005,
+            trade_time: 100.0,
+            symbol: "ETHUSDT".to_string(),
+            price: 1.0,
+            quantity: 1.0,
+            is_buyer_maker: true,
+            seq: None,
+        });
+        AggTradeRepository::insert_batch(&manager, &trades)
+            .await
+            .unwrap();
+
+        let latency = AggTradeRepository::latency_since(&manager, 0.0)
+            .await
+            .unwrap();
+        let summary: Vec<(&str, i64, f64, f64, f64)> = latency
+            .iter()
+            .map(|l| {
+                (
+                    l.symbol.as_str(),
+                    l.trades,
+                    l.p50_ms.round(),
+                    l.p99_ms.round(),
+                    l.max_ms.round(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("BTCUSDT", 100, 50.0, 99.0, 100.0),
+                ("ETHUSDT", 1, 5.0, 5.0, 5.0)
+            ]
+        );
+    }
 }
@@ -0,0 +1,47 @@
+use common::models::BalanceInsert;
+
+use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
+
+pub struct BalanceRepository;
+
+impl BalanceRepository {
+    /// Inserts `balances` in one transaction, retrying while the database is locked.
+    pub async fn insert_batch(
+        data_manager: &DataManager,
+        balances: &[BalanceInsert],
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("Balance", || Self::try_insert_batch(data_manager, balances)).await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        balances: &[BalanceInsert],
+    ) -> Result<(), StorageError> {
+        if balances.is_empty() {
+            return Ok(());
+        }
+
+        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let mut tx = pool.begin().await?;
+
+        for balance in balances {
+            sqlx::query(
+                r#"
+                    INSERT INTO balances (time, asset, free, locked) VALUES (?, ?, ?, ?)
+                "#,
+            )
+            .bind(balance.time)
+            .bind(&balance.asset)
+            .bind(balance.free)
+            .bind(balance.locked)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
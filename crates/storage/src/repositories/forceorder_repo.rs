@@ -1,27 +1,43 @@
 use common::models::ForceOrderInsert;
 
 use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
 
 pub struct ForceOrderRepository;
 
 impl ForceOrderRepository {
+    /// Inserts `orders` in one transaction, retrying while the database is locked.
     pub async fn insert_batch(
         data_manager: &DataManager,
         orders: &[ForceOrderInsert],
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("ForceOrder", || {
+            Self::try_insert_batch(data_manager, orders)
+        })
+        .await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        orders: &[ForceOrderInsert],
+    ) -> Result<(), StorageError> {
         if orders.is_empty() {
             return Ok(());
         }
-        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(orders.iter().map(|order| order.symbol.as_str()))
+            .await?;
         let mut tx = pool.begin().await?;
 
-        for order in orders {
-            let symbol_id = data_manager.get_symbol_id(&order.symbol).await?;
+        for (order, symbol_id) in orders.iter().zip(symbol_ids) {
             sqlx::query(
                 r#"
                     INSERT INTO liquidations (
-                        time, symbol_id, side, price, quantity
-                    ) VALUES (?, ?, ?, ?, ?)
+                        time, symbol_id, side, price, quantity, seq
+                    ) VALUES (?, ?, ?, ?, ?, ?)
                 "#,
             )
             .bind(order.time)
@@ -29,6 +45,7 @@ impl ForceOrderRepository {
             .bind(order.side.clone())
             .bind(order.price)
             .bind(order.quantity)
+            .bind(order.seq)
             .execute(&mut *tx)
             .await?;
         }
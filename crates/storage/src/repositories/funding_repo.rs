@@ -0,0 +1,45 @@
+use common::models::FundingEventInsert;
+
+use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
+
+pub struct FundingRepository;
+
+impl FundingRepository {
+    /// Stores `event`, retrying while the database is locked. A settlement
+    /// already stored for its symbol and time, say from before a restart, is
+    /// left as it is.
+    pub async fn insert(
+        data_manager: &DataManager,
+        event: &FundingEventInsert,
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("Funding", || Self::try_insert(data_manager, event)).await
+    }
+
+    async fn try_insert(
+        data_manager: &DataManager,
+        event: &FundingEventInsert,
+    ) -> Result<(), StorageError> {
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids([event.symbol.as_str()])
+            .await?;
+        let symbol_id = symbol_ids[0];
+        sqlx::query(
+            r#"
+                INSERT OR IGNORE INTO funding_events (
+                    funding_time, symbol_id, rate, mark_price
+                ) VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(event.funding_time)
+        .bind(symbol_id)
+        .bind(event.rate)
+        .bind(event.mark_price)
+        .execute(&pool)
+        .await?;
+        Ok(())
+    }
+}
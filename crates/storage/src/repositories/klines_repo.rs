@@ -1,31 +1,221 @@
 use common::models::KlineInsert;
 
 use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
 
 pub struct KlinesRepository;
 
 impl KlinesRepository {
+    /// Table `interval` candles are stored in: their own table for 1s, 1m and
+    /// 1h when `kline_table_per_interval` is on, `klines` otherwise.
+    pub fn table(data_manager: &DataManager, interval: &str) -> &'static str {
+        if !data_manager.pool_rotator.config().kline_table_per_interval {
+            return "klines";
+        }
+        match interval {
+            "1s" => "klines_1s",
+            "1m" => "klines_1m",
+            "1h" => "klines_1h",
+            _ => "klines",
+        }
+    }
+
+    /// Inserts `klines` in one transaction, retrying while the database is locked.
     pub async fn insert_batch(
         data_manager: &DataManager,
         klines: &[KlineInsert],
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Klines).await;
+        with_busy_retry("Kline", || Self::try_insert_batch(data_manager, klines)).await
+    }
+
+    /// Inserts the `klines` not already stored for their symbol, interval and
+    /// start time. Returns how many rows were added.
+    pub async fn insert_missing(
+        data_manager: &DataManager,
+        klines: &[KlineInsert],
+    ) -> Result<u64, StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Klines).await;
+        with_busy_retry("Kline", || Self::try_insert_missing(data_manager, klines)).await
+    }
+
+    /// Rolls every `source` candle starting before `cutoff` up into `target`
+    /// candles of `target_ms` and deletes the `source` rows, in one transaction.
+    /// `target` candles already stored (e.g. from the exchange's own stream) are
+    /// kept as they are. `cutoff` should sit on a `target_ms` boundary so no
+    /// bucket is cut in half. Returns (candles added, rows deleted).
+    pub async fn compact(
+        data_manager: &DataManager,
+        source: &str,
+        target: &str,
+        target_ms: i64,
+        cutoff: i64,
+    ) -> Result<(u64, u64), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("Kline compaction", || {
+            Self::try_compact(data_manager, source, target, target_ms, cutoff)
+        })
+        .await
+    }
+
+    /// Start time of the newest stored `interval` candle of `symbol`.
+    pub async fn latest_start_time(
+        data_manager: &DataManager,
+        symbol: &str,
+        interval: &str,
+    ) -> Result<Option<i64>, StorageError> {
+        let (pool, symbol_ids) = data_manager.pool_with_symbol_ids([symbol]).await?;
+        let symbol_id = symbol_ids[0];
+        sqlx::query_scalar(&format!(
+            "SELECT MAX(start_time) FROM {} WHERE symbol_id = ? AND interval = ?",
+            Self::table(data_manager, interval)
+        ))
+        .bind(symbol_id)
+        .bind(interval)
+        .fetch_one(&pool)
+        .await
+        .map_err(StorageError::from)
+    }
+
+    async fn try_compact(
+        data_manager: &DataManager,
+        source: &str,
+        target: &str,
+        target_ms: i64,
+        cutoff: i64,
+    ) -> Result<(u64, u64), StorageError> {
+        let source_table = Self::table(data_manager, source);
+        let target_table = Self::table(data_manager, target);
+        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let mut tx = pool.begin().await?;
+
+        let inserted = sqlx::query(&format!(
+            r#"
+                INSERT INTO {target_table} (
+                    symbol_id, start_time, close_time, interval, open_price, close_price,
+                    high_price, low_price, volume, no_of_trades, taker_buy_vol
+                )
+                SELECT symbol_id, bucket, bucket + ?3 - 1, ?2, MIN(first_open), MIN(last_close),
+                       MAX(high_price), MIN(low_price), SUM(volume), SUM(no_of_trades),
+                       SUM(taker_buy_vol)
+                FROM (
+                    SELECT symbol_id, start_time - start_time % ?3 AS bucket,
+                           high_price, low_price, volume, no_of_trades, taker_buy_vol,
+                           FIRST_VALUE(open_price) OVER (
+                               PARTITION BY symbol_id, start_time - start_time % ?3
+                               ORDER BY start_time
+                           ) AS first_open,
+                           FIRST_VALUE(close_price) OVER (
+                               PARTITION BY symbol_id, start_time - start_time % ?3
+                               ORDER BY start_time DESC
+                           ) AS last_close
+                    FROM {source_table}
+                    WHERE interval = ?1 AND start_time < ?4
+                ) AS source
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM {target_table} AS existing
+                    WHERE existing.symbol_id = source.symbol_id
+                      AND existing.interval = ?2
+                      AND existing.start_time = source.bucket
+                )
+                GROUP BY symbol_id, bucket
+            "#
+        ))
+        .bind(source)
+        .bind(target)
+        .bind(target_ms)
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let deleted = sqlx::query(&format!(
+            "DELETE FROM {} WHERE interval = ? AND start_time < ?",
+            source_table
+        ))
+        .bind(source)
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok((inserted, deleted))
+    }
+
+    async fn try_insert_missing(
+        data_manager: &DataManager,
+        klines: &[KlineInsert],
+    ) -> Result<u64, StorageError> {
+        if klines.is_empty() {
+            return Ok(0);
+        }
+
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(klines.iter().map(|kline| kline.symbol.as_str()))
+            .await?;
+        let mut tx = pool.begin().await?;
+        let mut inserted = 0;
+
+        for (kline, symbol_id) in klines.iter().zip(symbol_ids) {
+            let table = Self::table(data_manager, &kline.interval);
+            inserted += sqlx::query(&format!(
+                r#"
+                    INSERT INTO {table} (
+                        symbol_id, start_time, close_time, interval, open_price, close_price,
+                        high_price, low_price, volume, no_of_trades, taker_buy_vol
+                    )
+                    SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11
+                    WHERE NOT EXISTS (
+                        SELECT 1 FROM {table}
+                        WHERE symbol_id = ?1 AND interval = ?4 AND start_time = ?2
+                    )
+                "#
+            ))
+            .bind(symbol_id)
+            .bind(kline.start_time)
+            .bind(kline.close_time)
+            .bind(&kline.interval)
+            .bind(kline.open_price)
+            .bind(kline.close_price)
+            .bind(kline.high_price)
+            .bind(kline.low_price)
+            .bind(kline.volume)
+            .bind(kline.no_of_trades)
+            .bind(kline.taker_buy_vol)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        klines: &[KlineInsert],
+    ) -> Result<(), StorageError> {
         if klines.is_empty() {
             return Ok(());
         }
 
-        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(klines.iter().map(|kline| kline.symbol.as_str()))
+            .await?;
         let mut tx = pool.begin().await?;
 
-        for kline in klines {
-            let symbol_id = data_manager.get_symbol_id(&kline.symbol).await?;
-            sqlx::query(
+        for (kline, symbol_id) in klines.iter().zip(symbol_ids) {
+            sqlx::query(&format!(
                 r#"
-                    INSERT INTO klines (
+                    INSERT INTO {} (
                         symbol_id, start_time, close_time, interval, open_price, close_price,
                         high_price, low_price, volume, no_of_trades, taker_buy_vol
                     ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
                 "#,
-            )
+                Self::table(data_manager, &kline.interval)
+            ))
             .bind(symbol_id)
             .bind(kline.start_time)
             .bind(kline.close_time)
@@ -44,3 +234,78 @@ impl KlinesRepository {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::StorageConfig;
+    use crate::test_support::{TempDir, manager};
+
+    fn kline(start_time: i64, interval: &str) -> KlineInsert {
+        KlineInsert {
+            symbol: "BTCUSDT".to_string(),
+            start_time,
+            close_time: start_time + 999,
+            interval: interval.to_string(),
+            open_price: 1.0,
+            close_price: 1.0,
+            high_price: 1.0,
+            low_price: 1.0,
+            volume: 1.0,
+            no_of_trades: 1,
+            taker_buy_vol: 0.5,
+            seq: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_intervals_to_their_own_tables() {
+        let dir = TempDir::new("kline_tables");
+        let config = StorageConfig {
+            kline_table_per_interval: true,
+            ..StorageConfig::default()
+        };
+        let manager = manager(&dir, config).await;
+
+        let rows = [
+            kline(0, "1s"),
+            kline(0, "1m"),
+            kline(60_000, "1m"),
+            kline(0, "5m"),
+        ];
+        KlinesRepository::insert_batch(&manager, &rows)
+            .await
+            .unwrap();
+        assert_eq!(
+            KlinesRepository::insert_missing(&manager, &[kline(60_000, "1m")])
+                .await
+                .unwrap(),
+            0
+        );
+
+        let counts = manager.row_counts().await.unwrap();
+        let rows = |table: &str| counts.iter().find(|(t, _)| *t == table).unwrap().1;
+        assert_eq!(
+            (
+                rows("klines"),
+                rows("klines_1s"),
+                rows("klines_1m"),
+                rows("klines_1h")
+            ),
+            (1, 1, 2, 0)
+        );
+
+        let (pool, _) = manager.pool_rotator.get_pool().await.unwrap();
+        let all: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM klines_v WHERE symbol = 'BTCUSDT'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(all, 4);
+        assert_eq!(
+            KlinesRepository::latest_start_time(&manager, "BTCUSDT", "1m")
+                .await
+                .unwrap(),
+            Some(60_000)
+        );
+    }
+}
@@ -1,28 +1,44 @@
 use common::models::MarkPriceInsert;
 
 use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
 
 pub struct MarkPriceRepository;
 
 impl MarkPriceRepository {
+    /// Inserts `m_prices` in one transaction, retrying while the database is locked.
     pub async fn insert_batch(
         data_manager: &DataManager,
         m_prices: &[MarkPriceInsert],
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("MarkPrice", || {
+            Self::try_insert_batch(data_manager, m_prices)
+        })
+        .await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        m_prices: &[MarkPriceInsert],
+    ) -> Result<(), StorageError> {
         if m_prices.is_empty() {
             return Ok(());
         }
 
-        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(m_prices.iter().map(|m_price| m_price.symbol.as_str()))
+            .await?;
         let mut tx = pool.begin().await?;
 
-        for m_price in m_prices {
-            let symbol_id = data_manager.get_symbol_id(&m_price.symbol).await?;
+        for (m_price, symbol_id) in m_prices.iter().zip(symbol_ids) {
             sqlx::query(
                 r#"
                     INSERT INTO funding_rates (
-                        time, symbol_id, mark_price, index_price, rate
-                    ) VALUES (?, ?, ?, ?, ?)
+                        time, symbol_id, mark_price, index_price, rate, seq
+                    ) VALUES (?, ?, ?, ?, ?, ?)
                 "#,
             )
             .bind(m_price.time)
@@ -30,6 +46,7 @@ impl MarkPriceRepository {
             .bind(m_price.mark_price)
             .bind(m_price.index_price)
             .bind(m_price.funding_rate)
+            .bind(m_price.seq)
             .execute(&mut *tx)
             .await?;
         }
@@ -1,10 +1,23 @@
 pub mod aggtrade_repo;
+pub mod balance_repo;
 pub mod forceorder_repo;
+pub mod funding_repo;
 pub mod klines_repo;
 pub mod markprice_repo;
 pub mod openinterest_repo;
+pub mod order_repo;
+pub mod orderbook_feature_repo;
 pub mod orderbook_repo;
+pub(crate) mod retry;
+pub mod ticker_repo;
+pub mod trade_flow_repo;
 
 pub use aggtrade_repo::AggTradeRepository;
+pub use balance_repo::BalanceRepository;
+pub use funding_repo::FundingRepository;
 pub use klines_repo::KlinesRepository;
+pub use order_repo::OrderRepository;
+pub use orderbook_feature_repo::OrderBookFeatureRepository;
 pub use orderbook_repo::OrderBookRepository;
+pub use ticker_repo::Ticker24hRepository;
+pub use trade_flow_repo::TradeFlowRepository;
@@ -1,33 +1,50 @@
 use common::models::OpenInterestInsert;
 
 use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
 
 pub struct OpenInterestRepository;
 
 impl OpenInterestRepository {
+    /// Inserts `interests` in one transaction, retrying while the database is locked.
     pub async fn insert_batch(
         data_manager: &DataManager,
         interests: &[OpenInterestInsert],
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("OpenInterest", || {
+            Self::try_insert_batch(data_manager, interests)
+        })
+        .await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        interests: &[OpenInterestInsert],
+    ) -> Result<(), StorageError> {
         if interests.is_empty() {
             return Ok(());
         }
 
-        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(interests.iter().map(|interest| interest.symbol.as_str()))
+            .await?;
         let mut tx = pool.begin().await?;
 
-        for interest in interests {
-            let symbol_id = data_manager.get_symbol_id(&interest.symbol).await?;
+        for (interest, symbol_id) in interests.iter().zip(symbol_ids) {
             sqlx::query(
                 r#"
                     INSERT INTO open_interest (
-                        time, symbol_id, oi_value
-                    ) VALUES (?, ?, ?)
+                        time, symbol_id, oi_value, seq
+                    ) VALUES (?, ?, ?, ?)
                 "#,
             )
             .bind(interest.time)
             .bind(symbol_id)
             .bind(interest.oi_value)
+            .bind(interest.seq)
             .execute(&mut *tx)
             .await?;
         }
@@ -0,0 +1,51 @@
+use common::models::OrderAuditInsert;
+
+use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
+
+pub struct OrderRepository;
+
+impl OrderRepository {
+    /// Records one order attempt, retrying while the database is locked.
+    pub async fn insert(
+        data_manager: &DataManager,
+        order: &OrderAuditInsert,
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("Order", || Self::try_insert(data_manager, order)).await
+    }
+
+    async fn try_insert(
+        data_manager: &DataManager,
+        order: &OrderAuditInsert,
+    ) -> Result<(), StorageError> {
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids([order.symbol.as_str()])
+            .await?;
+        let symbol_id = symbol_ids[0];
+
+        sqlx::query(
+            r#"
+                INSERT INTO orders (
+                    client_ts, symbol_id, side, qty, type, order_id, status,
+                    executed_qty, cummulative_quote_qty, error
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(order.client_ts)
+        .bind(symbol_id)
+        .bind(&order.side)
+        .bind(order.quantity)
+        .bind(&order.order_type)
+        .bind(order.order_id)
+        .bind(&order.status)
+        .bind(order.executed_qty)
+        .bind(order.cummulative_quote_qty)
+        .bind(&order.error)
+        .execute(&pool)
+        .await?;
+        Ok(())
+    }
+}
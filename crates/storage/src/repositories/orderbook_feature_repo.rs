@@ -0,0 +1,57 @@
+use common::models::OrderBookFeatureInsert;
+
+use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
+
+pub struct OrderBookFeatureRepository;
+
+impl OrderBookFeatureRepository {
+    /// Inserts `features` in one transaction, retrying while the database is locked.
+    pub async fn insert_batch(
+        data_manager: &DataManager,
+        features: &[OrderBookFeatureInsert],
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("OrderBookFeature", || {
+            Self::try_insert_batch(data_manager, features)
+        })
+        .await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        features: &[OrderBookFeatureInsert],
+    ) -> Result<(), StorageError> {
+        if features.is_empty() {
+            return Ok(());
+        }
+
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(features.iter().map(|feature| feature.symbol.as_str()))
+            .await?;
+        let mut tx = pool.begin().await?;
+
+        for (feature, symbol_id) in features.iter().zip(symbol_ids) {
+            sqlx::query(
+                r#"
+                    INSERT INTO orderbook_features (
+                        symbol_id, time, obi, obi_1, obi_5, bid_vol, ask_vol
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(symbol_id)
+            .bind(feature.time)
+            .bind(feature.obi)
+            .bind(feature.obi_1)
+            .bind(feature.obi_5)
+            .bind(feature.bid_vol)
+            .bind(feature.ask_vol)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
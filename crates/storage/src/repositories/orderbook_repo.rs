@@ -1,36 +1,61 @@
 use common::models::OrderBookInsert;
+use sqlx::SqliteConnection;
 
 use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
 
 pub struct OrderBookRepository;
 
 impl OrderBookRepository {
+    /// Inserts `books` in one transaction, retrying while the database is locked.
     pub async fn insert_batch(
         data_manager: &DataManager,
         books: &[OrderBookInsert],
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::OrderBooks).await;
+        with_busy_retry("OrderBook", || Self::try_insert_batch(data_manager, books)).await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        books: &[OrderBookInsert],
+    ) -> Result<(), StorageError> {
         if books.is_empty() {
             return Ok(());
         }
-        let (pool, _) = data_manager.pool_rotator.get_pool().await?;
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(books.iter().map(|b| b.symbol.as_str()))
+            .await?;
         let mut tx = pool.begin().await?;
+        Self::insert_rows(&mut tx, books, &symbol_ids).await?;
+        tx.commit().await?;
+        Ok(())
+    }
 
-        for b in books {
-            let symbol_id = data_manager.get_symbol_id(&b.symbol).await?;
+    /// Inserts `books`, whose symbols resolve to `symbol_ids`, on `conn`
+    /// without committing.
+    pub(crate) async fn insert_rows(
+        conn: &mut SqliteConnection,
+        books: &[OrderBookInsert],
+        symbol_ids: &[i64],
+    ) -> Result<(), StorageError> {
+        for (b, symbol_id) in books.iter().zip(symbol_ids) {
             sqlx::query(
                 r#"
-                    INSERT INTO order_books(time, symbol_id, bids, asks)
-                    VALUES (?, ?, ?, ?)
+                    INSERT INTO order_books(time, symbol_id, bids, asks, seq)
+                    VALUES (?, ?, ?, ?, ?)
                 "#,
             )
             .bind(b.time)
             .bind(symbol_id)
             .bind(&b.bids)
             .bind(&b.asks)
-            .execute(&mut *tx)
+            .bind(b.seq)
+            .execute(&mut *conn)
             .await?;
         }
-        tx.commit().await?;
         Ok(())
     }
 }
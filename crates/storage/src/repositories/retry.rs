@@ -0,0 +1,99 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::error::StorageError;
+
+/// Attempts made for a batch before its error is surfaced.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the second attempt; doubled for each one after that.
+const BASE_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Runs `insert` again with backoff when SQLite reports the database as busy
+/// or locked, so a transient lock doesn't throw away a whole batch.
+///
+/// Each attempt must be its own transaction: a failed attempt is rolled back
+/// when its transaction drops, so nothing is written twice.
+pub(crate) async fn with_busy_retry<F, Fut, T>(
+    label: &str,
+    mut insert: F,
+) -> Result<T, StorageError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, StorageError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match insert().await {
+            Err(e @ StorageError::Locked(_)) if attempt < MAX_ATTEMPTS => {
+                let backoff = BASE_BACKOFF * 2u32.pow(attempt - 1);
+                warn!(
+                    "{} insert hit a locked database (attempt {}/{}), retrying in {:?}: {}",
+                    label, attempt, MAX_ATTEMPTS, backoff, e
+                );
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+    use sqlx::{Connection, SqliteConnection};
+
+    #[tokio::test]
+    async fn test_retries_until_lock_is_released() {
+        let dir = TempDir::new("retry");
+        let path = dir.path().join("retry.db");
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true)
+            .busy_timeout(Duration::ZERO);
+
+        let pool = SqlitePool::connect_with(options.clone()).await.unwrap();
+        sqlx::query("CREATE TABLE t (v INTEGER)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut holder = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::query("BEGIN EXCLUSIVE")
+            .execute(&mut holder)
+            .await
+            .unwrap();
+
+        let blocked = sqlx::query("INSERT INTO t VALUES (1)")
+            .execute(&pool)
+            .await
+            .unwrap_err();
+        let blocked = StorageError::from(blocked);
+        assert!(
+            blocked.is_retryable(),
+            "expected a busy error, got {blocked}"
+        );
+
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            sqlx::query("COMMIT").execute(&mut holder).await.unwrap();
+        });
+
+        with_busy_retry("test", || async {
+            sqlx::query("INSERT INTO t VALUES (1)")
+                .execute(&pool)
+                .await
+                .map_err(StorageError::from)
+        })
+        .await
+        .unwrap();
+        release.await.unwrap();
+
+        pool.close().await;
+    }
+}
@@ -0,0 +1,62 @@
+use common::models::Ticker24hInsert;
+
+use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
+
+pub struct Ticker24hRepository;
+
+impl Ticker24hRepository {
+    /// Inserts `tickers` in one transaction, retrying while the database is locked.
+    pub async fn insert_batch(
+        data_manager: &DataManager,
+        tickers: &[Ticker24hInsert],
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("Ticker24h", || {
+            Self::try_insert_batch(data_manager, tickers)
+        })
+        .await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        tickers: &[Ticker24hInsert],
+    ) -> Result<(), StorageError> {
+        if tickers.is_empty() {
+            return Ok(());
+        }
+
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(tickers.iter().map(|ticker| ticker.symbol.as_str()))
+            .await?;
+        let mut tx = pool.begin().await?;
+
+        for (ticker, symbol_id) in tickers.iter().zip(symbol_ids) {
+            sqlx::query(
+                r#"
+                    INSERT INTO ticker_24h (
+                        time, symbol_id, price_change, price_change_pct, last_price,
+                        open_price, high_price, low_price, volume, quote_volume, trade_count
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(ticker.time)
+            .bind(symbol_id)
+            .bind(ticker.price_change)
+            .bind(ticker.price_change_pct)
+            .bind(ticker.last_price)
+            .bind(ticker.open_price)
+            .bind(ticker.high_price)
+            .bind(ticker.low_price)
+            .bind(ticker.volume)
+            .bind(ticker.quote_volume)
+            .bind(ticker.trade_count)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
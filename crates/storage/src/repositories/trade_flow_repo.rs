@@ -0,0 +1,52 @@
+use common::models::TradeFlowInsert;
+
+use crate::data_manager::DataManager;
+use crate::error::StorageError;
+use crate::repositories::retry::with_busy_retry;
+use crate::write_queue::WritePriority;
+
+pub struct TradeFlowRepository;
+
+impl TradeFlowRepository {
+    /// Inserts `flows` in one transaction, retrying while the database is locked.
+    pub async fn insert_batch(
+        data_manager: &DataManager,
+        flows: &[TradeFlowInsert],
+    ) -> Result<(), StorageError> {
+        let _turn = data_manager.write_turn(WritePriority::Other).await;
+        with_busy_retry("TradeFlow", || Self::try_insert_batch(data_manager, flows)).await
+    }
+
+    async fn try_insert_batch(
+        data_manager: &DataManager,
+        flows: &[TradeFlowInsert],
+    ) -> Result<(), StorageError> {
+        if flows.is_empty() {
+            return Ok(());
+        }
+
+        let (pool, symbol_ids) = data_manager
+            .pool_with_symbol_ids(flows.iter().map(|flow| flow.symbol.as_str()))
+            .await?;
+        let mut tx = pool.begin().await?;
+
+        for (flow, symbol_id) in flows.iter().zip(symbol_ids) {
+            sqlx::query(
+                r#"
+                    INSERT INTO trade_flow (
+                        symbol_id, bucket_start, buy_vol, sell_vol, trade_count
+                    ) VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(symbol_id)
+            .bind(flow.bucket_start)
+            .bind(flow.buy_vol)
+            .bind(flow.sell_vol)
+            .bind(flow.trade_count)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+
+use crate::error::StorageError;
+
+/// One step of the schema, applied once per database file.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    /// Columns `(table, column, declaration)` added before `sql` runs, unless
+    /// the table already has them. Files written by builds that predate
+    /// `schema_version` may or may not have a column, which plain
+    /// `ALTER TABLE` can't cope with.
+    add_columns: &'static [(&'static str, &'static str, &'static str)],
+    sql: &'static str,
+}
+
+/// Every schema step, oldest first. Append new steps; never edit one that has
+/// shipped, since files that already ran it won't see the change.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial tables and views",
+        add_columns: &[],
+        sql: include_str!("../migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "agg_trades.agg_trade_id",
+        add_columns: &[("agg_trades", "agg_trade_id", "INTEGER")],
+        sql: include_str!("../migrations/0002_agg_trade_id.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "orders audit table",
+        add_columns: &[],
+        sql: include_str!("../migrations/0003_orders.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "agg_trades.trade_time and latency_ms",
+        add_columns: &[("agg_trades", "trade_time", "REAL")],
+        sql: include_str!("../migrations/0004_agg_trade_latency.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "gateway sequence numbers on streamed rows",
+        add_columns: &[
+            ("agg_trades", "seq", "INTEGER"),
+            ("order_books", "seq", "INTEGER"),
+            ("funding_rates", "seq", "INTEGER"),
+            ("open_interest", "seq", "INTEGER"),
+            ("liquidations", "seq", "INTEGER"),
+        ],
+        sql: include_str!("../migrations/0005_event_seq.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "symbols.base_asset and quote_asset",
+        add_columns: &[
+            ("symbols", "base_asset", "TEXT"),
+            ("symbols", "quote_asset", "TEXT"),
+        ],
+        sql: include_str!("../migrations/0006_symbol_assets.sql"),
+    },
+    Migration {
+        version: 7,
+        description: "top-1 and top-5 order book imbalance",
+        add_columns: &[
+            ("orderbook_features", "obi_1", "REAL"),
+            ("orderbook_features", "obi_5", "REAL"),
+        ],
+        sql: include_str!("../migrations/0007_depth_imbalance.sql"),
+    },
+    Migration {
+        version: 8,
+        description: "funding_events settlements",
+        add_columns: &[],
+        sql: include_str!("../migrations/0008_funding_events.sql"),
+    },
+];
+
+/// Version a file has once `migrate` is done with it.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Brings the database behind `pool` up to `latest_version`, running each
+/// missing step in its own transaction and recording it in `schema_version`.
+/// Returns the version the file was at before.
+pub async fn migrate(pool: &SqlitePool) -> Result<i64, StorageError> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_version(
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        )",
+    )
+    .execute(pool)
+    .await
+    .map_err(StorageError::Migration)?;
+
+    let current: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    if current > latest_version() {
+        warn!(
+            "Database is at schema version {}, newer than this build's {}",
+            current,
+            latest_version()
+        );
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        apply(pool, migration)
+            .await
+            .map_err(StorageError::Migration)?;
+        info!(
+            "Applied schema version {}: {}",
+            migration.version, migration.description
+        );
+    }
+    Ok(current)
+}
+
+async fn apply(pool: &SqlitePool, migration: &Migration) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    for (table, column, declaration) in migration.add_columns {
+        let exists: bool = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?",
+            table
+        ))
+        .bind(column)
+        .fetch_one(&mut *tx)
+        .await?;
+        if !exists {
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, declaration
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    sqlx::query(migration.sql).execute(&mut *tx).await?;
+    sqlx::query(
+        "INSERT INTO schema_version (version, description, applied_at)
+         VALUES (?, ?, strftime('%s', 'now'))",
+    )
+    .bind(migration.version)
+    .bind(migration.description)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_upgrades_unversioned_file_once() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        // A file from before `schema_version`, without agg_trade_id.
+        sqlx::query(
+            "CREATE TABLE agg_trades(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                time REAL NOT NULL,
+                symbol_id INTEGER NOT NULL,
+                price REAL NOT NULL,
+                quantity REAL NOT NULL,
+                is_buyer_maker BOOLEAN NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(migrate(&pool).await.unwrap(), 0);
+        let columns: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM pragma_table_info('agg_trades')")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert!(columns.iter().any(|c| c == "agg_trade_id"));
+
+        // Already current: nothing runs again.
+        assert_eq!(migrate(&pool).await.unwrap(), latest_version());
+        let steps: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_version")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(steps, latest_version());
+    }
+}
@@ -1,64 +1,186 @@
+use crate::error::StorageError;
+use common::symbols::SymbolAssets;
 use sqlx::SqlitePool;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{Mutex, OnceCell};
 
+/// (packed week, ticker) -> cell holding its `symbols.id`.
+type IdCells = HashMap<(u32, String), Arc<OnceCell<i64>>>;
+
+/// Ticker -> `symbols.id`, resolved at most once per ticker and weekly file.
+///
+/// Ids are per file: a ticker the migrations don't seed gets whatever id is
+/// next in each week's `symbols`, so entries are keyed by the packed week of
+/// the file they were read from and older weeks are dropped once a newer one
+/// is seen. Each entry is a cell that the first caller fills; concurrent
+/// callers for the same ticker wait on that cell instead of running their
+/// own lookup. A failed lookup leaves the cell empty for the next caller.
 #[derive(Clone)]
 pub struct SymbolManager {
-    cache: Arc<Mutex<HashMap<String, i64>>>,
+    cache: Arc<Mutex<IdCells>>,
+    /// Uppercase ticker -> assets, written to `symbols` as tickers are resolved.
+    assets: Arc<RwLock<HashMap<String, SymbolAssets>>>,
 }
 
 impl SymbolManager {
     pub fn new() -> Self {
         Self {
             cache: Arc::new(Mutex::new(HashMap::new())),
+            assets: Arc::default(),
         }
     }
 
+    /// Base and quote assets to store with the tickers resolved from now on.
+    pub fn set_assets(&self, assets: HashMap<String, SymbolAssets>) {
+        *self.assets.write().expect("symbol assets poisoned") = assets;
+    }
+
+    /// Id of `symbol` in the file `pool` is open on, whose packed week is
+    /// `week`.
     pub async fn get_or_create_id(
         &self,
         pool: SqlitePool,
+        week: u32,
+        symbol: &str,
+    ) -> Result<i64, StorageError> {
+        let cell = {
+            let mut cache = self.cache.lock().await;
+            let key = (week, symbol.to_string());
+            match cache.get(&key) {
+                Some(cell) => cell.clone(),
+                None => {
+                    cache.retain(|(cached_week, _), _| *cached_week >= week);
+                    cache.entry(key).or_default().clone()
+                }
+            }
+        };
+        let assets = self
+            .assets
+            .read()
+            .expect("symbol assets poisoned")
+            .get(&symbol.to_uppercase())
+            .cloned();
+        cell.get_or_try_init(|| Self::lookup(&pool, symbol, assets))
+            .await
+            .copied()
+    }
+
+    /// A read for known tickers; a new one is inserted and its id returned
+    /// by a single upsert, so racing writers (another process on the same
+    /// file) still agree on the id without a transaction. With `assets`
+    /// known the upsert always runs, filling them in on older rows.
+    async fn lookup(
+        pool: &SqlitePool,
         symbol: &str,
-    ) -> Result<i64, sqlx::Error> {
-        {
-            let cache = self.cache.lock().await;
-            if let Some(&id) = cache.get(symbol) {
+        assets: Option<SymbolAssets>,
+    ) -> Result<i64, StorageError> {
+        if assets.is_none() {
+            let existing = sqlx::query_scalar::<_, i64>("SELECT id FROM symbols WHERE ticker = ?")
+                .bind(symbol)
+                .fetch_optional(pool)
+                .await?;
+            if let Some(id) = existing {
                 return Ok(id);
             }
         }
+        let (base, quote) = assets.map(|a| (a.base, a.quote)).unzip();
+        Ok(sqlx::query_scalar::<_, i64>(
+            "INSERT INTO symbols(ticker, base_asset, quote_asset) VALUES (?, ?, ?)
+             ON CONFLICT(ticker) DO UPDATE SET
+                 base_asset = COALESCE(symbols.base_asset, excluded.base_asset),
+                 quote_asset = COALESCE(symbols.quote_asset, excluded.quote_asset)
+             RETURNING id",
+        )
+        .bind(symbol)
+        .bind(base)
+        .bind(quote)
+        .fetch_one(pool)
+        .await?)
+    }
 
-        let mut tx = pool.begin().await?;
+    pub async fn get_cache(&self, week: u32, ticker: &str) -> Option<i64> {
+        let cache = self.cache.lock().await;
+        cache
+            .get(&(week, ticker.to_string()))
+            .and_then(|cell| cell.get().copied())
+    }
+}
 
-        let id_opt = sqlx::query_scalar::<_, i64>("SELECT id FROM symbols WHERE ticker = ?")
-            .bind(symbol)
-            .fetch_optional(&mut *tx)
-            .await?;
+impl Default for SymbolManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        let id = if let Some(existing_id) = id_opt {
-            existing_id
-        } else {
-            let new_id =
-                sqlx::query_scalar::<_, i64>("INSERT INTO symbols(ticker) VALUES (?) RETURNING id")
-                    .bind(symbol)
-                    .fetch_one(&mut *tx)
-                    .await?;
-            new_id
-        };
-        tx.commit().await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut cache = self.cache.lock().await;
-        cache.insert(symbol.to_string(), id);
+    #[tokio::test]
+    async fn test_concurrent_lookups_share_one_id() {
+        // One connection, or every connection gets its own in-memory database.
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE symbols(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ticker TEXT UNIQUE NOT NULL,
+                base_asset TEXT,
+                quote_asset TEXT
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let manager = SymbolManager::new();
 
-        Ok(id)
-    }
+        let lookups = (0..8).map(|_| manager.get_or_create_id(pool.clone(), 1, "POLUSDT"));
+        let ids = futures_util::future::try_join_all(lookups).await.unwrap();
+        assert!(ids.iter().all(|&id| id == ids[0]));
+        assert_eq!(manager.get_cache(1, "POLUSDT").await, Some(ids[0]));
 
-    pub async fn clear_cache(&mut self) {
-        let mut cache = self.cache.lock().await;
-        cache.clear();
-    }
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM symbols")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+        let asset = |base: &str, quote: &str| SymbolAssets {
+            base: base.to_string(),
+            quote: quote.to_string(),
+        };
+        manager.set_assets(HashMap::from([
+            ("ETHBTC".to_string(), asset("ETH", "BTC")),
+            ("POLUSDT".to_string(), asset("POL", "USDT")),
+        ]));
+        let quote_of = async |id: i64| -> Option<String> {
+            sqlx::query_scalar("SELECT quote_asset FROM symbols WHERE id = ?")
+                .bind(id)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+        let eth_btc = manager
+            .get_or_create_id(pool.clone(), 1, "ETHBTC")
+            .await
+            .unwrap();
+        assert_eq!(eth_btc, ids[0] + 1);
+        assert_eq!(quote_of(eth_btc).await.as_deref(), Some("BTC"));
 
-    pub async fn get_cache(&self, ticker: &str) -> Option<i64> {
-        let cache = self.cache.lock().await;
-        cache.get(ticker).cloned()
+        // Cached for this week, so POLUSDT's assets wait for the next file.
+        manager
+            .get_or_create_id(pool.clone(), 1, "POLUSDT")
+            .await
+            .unwrap();
+        assert_eq!(quote_of(ids[0]).await, None);
+        manager
+            .get_or_create_id(pool.clone(), 2, "POLUSDT")
+            .await
+            .unwrap();
+        assert_eq!(quote_of(ids[0]).await.as_deref(), Some("USDT"));
+        assert_eq!(manager.get_cache(1, "POLUSDT").await, None);
     }
 }
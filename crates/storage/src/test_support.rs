@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use common::clock::FixedClock;
+use common::models::AggTradeInsert;
+use tokio::sync::mpsc;
+
+use crate::data_manager::DataManager;
+use crate::db::StorageConfig;
+
+/// A fresh directory under the system temp dir, removed with everything in
+/// it when dropped, so a test that panics halfway cleans up too.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates `<temp>/<prefix>_<uuid>`.
+    pub fn new(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}_{}", prefix, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).expect("temp dir can be created");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// The path as the working directory `DataManager` and `Paths` take.
+    pub fn folder(&self) -> String {
+        self.0.to_str().expect("temp dir is UTF-8").to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A manager on `dir` with `config`. Nothing listens for its control
+/// messages.
+pub async fn manager(dir: &TempDir, config: StorageConfig) -> Arc<DataManager> {
+    let (tx, _rx) = mpsc::channel(1);
+    DataManager::new(dir.folder(), tx, config).await.unwrap()
+}
+
+/// A manager on `dir` whose clock is fixed at `now`.
+pub async fn manager_at(dir: &TempDir, now: DateTime<Utc>) -> Arc<DataManager> {
+    let (tx, _rx) = mpsc::channel(1);
+    DataManager::new_with_clock(
+        dir.folder(),
+        tx,
+        StorageConfig::default(),
+        Arc::new(FixedClock(now)),
+    )
+    .await
+    .unwrap()
+}
+
+/// A taker buy of 1 unit at `price`, received the moment it happened.
+pub fn trade(agg_trade_id: i64, time: f64, symbol: &str, price: f64) -> AggTradeInsert {
+    AggTradeInsert {
+        agg_trade_id,
+        time,
+        trade_time: time,
+        symbol: symbol.to_string(),
+        price,
+        quantity: 1.0,
+        is_buyer_maker: false,
+        seq: None,
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch};
+use arrow_ipc::CompressionType;
+use arrow_ipc::writer::{IpcWriteOptions, StreamWriter};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use async_trait::async_trait;
+use common::clock::Clock;
+use common::models::AggTradeInsert;
+
+use crate::data_manager::DataManager;
+use crate::db::WeekAnchor;
+use crate::error::StorageError;
+use crate::repositories::AggTradeRepository;
+
+/// Where aggregate trades are written.
+#[async_trait]
+pub trait AggTradeSink: Send + Sync {
+    async fn insert_batch(&self, trades: &[AggTradeInsert]) -> Result<(), StorageError>;
+}
+
+/// Which `AggTradeSink` the recorder writes trades to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AggTradeBackend {
+    /// `agg_trades` in the weekly database, like every other table.
+    #[default]
+    Sqlite,
+    /// Per-symbol Arrow IPC streams next to it; see `ArrowTradeSink`.
+    Arrow,
+}
+
+impl FromStr for AggTradeBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "sqlite" => Ok(Self::Sqlite),
+            "arrow" => Ok(Self::Arrow),
+            other => Err(format!(
+                "AGG_TRADE_SINK must be sqlite or arrow, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl AggTradeSink for DataManager {
+    async fn insert_batch(&self, trades: &[AggTradeInsert]) -> Result<(), StorageError> {
+        AggTradeRepository::insert_batch(self, trades).await
+    }
+}
+
+/// Appends aggregate trades to zstd-compressed Arrow IPC streams, one per
+/// symbol and week: `<dir>/<SYMBOL>/agg_trades_<year>_<week>_<start_ms>.arrows`.
+///
+/// Each `insert_batch` becomes one record batch per symbol, flushed before
+/// returning. A stream is finished when the symbol's first trade of the next
+/// week arrives, or by `finish` on shutdown. After a crash the last file has
+/// no end-of-stream marker; IPC stream readers
+/// (`pyarrow.ipc.open_stream`, `polars.read_ipc_stream`) read it up to the
+/// last complete batch. Every start opens new files (`start_ms`) rather than
+/// appending to old ones. Unlike `agg_trades`, nothing is deduplicated, and
+/// the weekly backup does not archive these files.
+pub struct ArrowTradeSink {
+    inner: Arc<Mutex<ArrowFiles>>,
+}
+
+struct ArrowFiles {
+    dir: PathBuf,
+    week_anchor: WeekAnchor,
+    clock: Arc<dyn Clock>,
+    started_ms: i64,
+    schema: SchemaRef,
+    /// Symbol -> its stream for the week it was opened in.
+    open: HashMap<String, OpenStream>,
+}
+
+struct OpenStream {
+    week: (i32, u32),
+    writer: StreamWriter<BufWriter<File>>,
+}
+
+impl ArrowTradeSink {
+    pub fn new(dir: impl Into<PathBuf>, week_anchor: WeekAnchor, clock: Arc<dyn Clock>) -> Self {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("agg_trade_id", DataType::Int64, false),
+            Field::new("time", DataType::Float64, false),
+            Field::new("trade_time", DataType::Float64, false),
+            Field::new("price", DataType::Float64, false),
+            Field::new("quantity", DataType::Float64, false),
+            Field::new("is_buyer_maker", DataType::Boolean, false),
+            Field::new("seq", DataType::Int64, true),
+        ]));
+        let started_ms = clock.now().timestamp_millis();
+        Self {
+            inner: Arc::new(Mutex::new(ArrowFiles {
+                dir: dir.into(),
+                week_anchor,
+                clock,
+                started_ms,
+                schema,
+                open: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Writes the end-of-stream marker of every open stream and closes it.
+    /// A later `insert_batch` opens new files.
+    pub async fn finish(&self) -> Result<(), StorageError> {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            inner.lock().expect("arrow trade files poisoned").finish()
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+}
+
+#[async_trait]
+impl AggTradeSink for ArrowTradeSink {
+    async fn insert_batch(&self, trades: &[AggTradeInsert]) -> Result<(), StorageError> {
+        if trades.is_empty() {
+            return Ok(());
+        }
+        let inner = self.inner.clone();
+        let trades = trades.to_vec();
+        tokio::task::spawn_blocking(move || {
+            inner
+                .lock()
+                .expect("arrow trade files poisoned")
+                .append(&trades)
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+}
+
+impl ArrowFiles {
+    fn append(&mut self, trades: &[AggTradeInsert]) -> Result<(), StorageError> {
+        let week = self.week_anchor.components(self.clock.now());
+        let mut by_symbol: HashMap<&str, Vec<&AggTradeInsert>> = HashMap::new();
+        for trade in trades {
+            by_symbol.entry(&trade.symbol).or_default().push(trade);
+        }
+
+        for (symbol, trades) in by_symbol {
+            let batch = self.record_batch(&trades)?;
+            let writer = self.writer(symbol, week)?;
+            writer.write(&batch)?;
+            writer.get_mut().flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), StorageError> {
+        let mut result = Ok(());
+        for (_, mut stream) in self.open.drain() {
+            if let Err(e) = stream.writer.finish() {
+                result = result.and(Err(e.into()));
+            }
+        }
+        result
+    }
+
+    fn record_batch(&self, trades: &[&AggTradeInsert]) -> Result<RecordBatch, StorageError> {
+        let column = |f: fn(&AggTradeInsert) -> f64| -> ArrayRef {
+            Arc::new(Float64Array::from_iter_values(trades.iter().map(|t| f(t))))
+        };
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from_iter_values(
+                trades.iter().map(|t| t.agg_trade_id),
+            )),
+            column(|t| t.time),
+            column(|t| t.trade_time),
+            column(|t| t.price),
+            column(|t| t.quantity),
+            Arc::new(BooleanArray::from_iter(
+                trades.iter().map(|t| Some(t.is_buyer_maker)),
+            )),
+            Arc::new(Int64Array::from_iter(trades.iter().map(|t| t.seq))),
+        ];
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+
+    /// The stream of `symbol` for `week`, finishing last week's first.
+    fn writer(
+        &mut self,
+        symbol: &str,
+        week: (i32, u32),
+    ) -> Result<&mut StreamWriter<BufWriter<File>>, StorageError> {
+        if let Some(stream) = self.open.get_mut(symbol)
+            && stream.week != week
+        {
+            stream.writer.finish()?;
+            self.open.remove(symbol);
+        }
+        if !self.open.contains_key(symbol) {
+            let path = self.path(symbol, week);
+            fs::create_dir_all(path.parent().expect("file has a parent"))?;
+            let options =
+                IpcWriteOptions::default().try_with_compression(Some(CompressionType::ZSTD))?;
+            let writer = StreamWriter::try_new_with_options(
+                BufWriter::new(File::create(&path)?),
+                &self.schema,
+                options,
+            )?;
+            self.open
+                .insert(symbol.to_string(), OpenStream { week, writer });
+        }
+        Ok(&mut self.open.get_mut(symbol).expect("just opened").writer)
+    }
+
+    fn path(&self, symbol: &str, (year, week): (i32, u32)) -> PathBuf {
+        stream_path(&self.dir, symbol, year, week, self.started_ms)
+    }
+}
+
+fn stream_path(dir: &Path, symbol: &str, year: i32, week: u32, started_ms: i64) -> PathBuf {
+    dir.join(symbol.to_uppercase()).join(format!(
+        "agg_trades_{}_{:02}_{}.arrows",
+        year, week, started_ms
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::WeekScheme;
+    use crate::test_support::TempDir;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, Int64Type};
+    use arrow_ipc::reader::StreamReader;
+    use chrono::{TimeZone, Utc};
+    use common::clock::FixedClock;
+
+    #[tokio::test]
+    async fn test_appends_readable_batches_per_symbol() {
+        let temp = TempDir::new("arrow_sink");
+        let dir = temp.path();
+        let now = Utc.with_ymd_and_hms(2026, 3, 4, 12, 0, 0).unwrap();
+        let sink = ArrowTradeSink::new(dir, WeekScheme::Iso.into(), Arc::new(FixedClock(now)));
+        let trade = |agg_trade_id, symbol: &str, price| AggTradeInsert {
+            agg_trade_id,
+            time: 1.0,
+            trade_time: 0.5,
+            symbol: symbol.to_string(),
+            price,
+            quantity: 2.0,
+            is_buyer_maker: agg_trade_id % 2 == 0,
+            seq: None,
+        };
+
+        sink.insert_batch(&[trade(1, "BTCUSDT", 100.0), trade(2, "ETHUSDT", 5.0)])
+            .await
+            .unwrap();
+        sink.insert_batch(&[trade(3, "BTCUSDT", 101.0)])
+            .await
+            .unwrap();
+
+        let path = stream_path(dir, "btcusdt", 2026, 10, now.timestamp_millis());
+        // Still open: read it without an end-of-stream marker.
+        let reader = StreamReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.len(), 2);
+        let ids: Vec<i64> = batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<Int64Type>().values().to_vec())
+            .collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(
+            batches[1].column(3).as_primitive::<Float64Type>().value(0),
+            101.0
+        );
+        assert!(dir.join("ETHUSDT").exists());
+
+        // Finished: the end-of-stream marker follows the last batch.
+        sink.finish().await.unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.ends_with(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]));
+        let reader = StreamReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        assert_eq!(reader.count(), 2);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+
+use tokio::sync::{mpsc, oneshot};
+
+/// Which writes go first when several services flush at once. Earlier
+/// variants win; writes of the same priority go in the order they asked.
+/// A write passed over `MAX_PASSED_OVER` times goes next regardless.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WritePriority {
+    Klines,
+    Trades,
+    OrderBooks,
+    /// Everything else: futures data, features, tickers, balances, orders,
+    /// maintenance.
+    Other,
+}
+
+/// Permission to write, held for the whole insert transaction. The next
+/// write starts once it is dropped.
+pub struct WriteTurn {
+    _done: oneshot::Sender<()>,
+}
+
+type TurnRequest = (WritePriority, oneshot::Sender<WriteTurn>);
+
+/// Turns handed to other writes after which a waiting write is served before
+/// any priority, so a steady stream of trades and books can't starve `Other`.
+const MAX_PASSED_OVER: u64 = 8;
+
+/// Lets one write transaction at a time through to SQLite, picking the
+/// waiting one with the highest `WritePriority`, unless one has waited too
+/// long (see `MAX_PASSED_OVER`).
+///
+/// Writers contending for the WAL otherwise take turns in whatever order
+/// their busy handlers happen to wake up, and a burst of order book batches
+/// can hold back klines and trades.
+#[derive(Clone)]
+pub struct WriteQueue {
+    tx: mpsc::UnboundedSender<TurnRequest>,
+}
+
+impl WriteQueue {
+    /// Starts the task handing out turns. It exits once every clone is gone.
+    pub fn spawn() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(hand_out_turns(rx));
+        Self { tx }
+    }
+
+    /// Waits until it is this write's turn.
+    pub async fn turn(&self, priority: WritePriority) -> WriteTurn {
+        let (turn_tx, turn_rx) = oneshot::channel();
+        if self.tx.send((priority, turn_tx)).is_ok()
+            && let Ok(turn) = turn_rx.await
+        {
+            return turn;
+        }
+        // The queue's task is gone (runtime shutting down): write unordered
+        // rather than never.
+        let (done, _) = oneshot::channel();
+        WriteTurn { _done: done }
+    }
+}
+
+async fn hand_out_turns(mut rx: mpsc::UnboundedReceiver<TurnRequest>) {
+    // By (priority, arrival): the turns handed out when it arrived, and
+    // where its turn goes.
+    let mut waiting = BTreeMap::new();
+    let mut seq = 0u64;
+    let mut handed_out = 0u64;
+    loop {
+        if waiting.is_empty() {
+            let Some((priority, tx)) = rx.recv().await else {
+                return;
+            };
+            waiting.insert((priority, seq), (handed_out, tx));
+            seq += 1;
+        }
+        // Take every request already queued, so the choice below sees them all.
+        while let Ok((priority, tx)) = rx.try_recv() {
+            waiting.insert((priority, seq), (handed_out, tx));
+            seq += 1;
+        }
+
+        // The longest-waiting write if it has been passed over often enough,
+        // otherwise the first by priority.
+        let overdue = waiting
+            .iter()
+            .min_by_key(|((_, arrival), _)| *arrival)
+            .filter(|(_, (since, _))| handed_out - since >= MAX_PASSED_OVER)
+            .map(|(key, _)| *key);
+        let next = match overdue {
+            Some(key) => waiting.remove_entry(&key),
+            None => waiting.pop_first(),
+        };
+        let Some((_, (_, tx))) = next else {
+            continue;
+        };
+        handed_out += 1;
+        let (done_tx, done_rx) = oneshot::channel();
+        // A requester that gave up before its turn just loses it.
+        if tx.send(WriteTurn { _done: done_tx }).is_ok() {
+            // Resolves with an error when the turn is dropped.
+            let _ = done_rx.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_turns_go_by_priority_then_arrival() {
+        let queue = WriteQueue::spawn();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        // Hold the queue while the others line up.
+        let first = queue.turn(WritePriority::Other).await;
+        let mut waiters = Vec::new();
+        for (name, priority) in [
+            ("book", WritePriority::OrderBooks),
+            ("trade 1", WritePriority::Trades),
+            ("other", WritePriority::Other),
+            ("trade 2", WritePriority::Trades),
+            ("kline", WritePriority::Klines),
+        ] {
+            let (queue, order) = (queue.clone(), order.clone());
+            waiters.push(tokio::spawn(async move {
+                let _turn = queue.turn(priority).await;
+                order.lock().unwrap().push(name);
+            }));
+            // Fix the arrival order.
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        drop(first);
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["kline", "trade 1", "trade 2", "book", "other"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_a_passed_over_write_is_not_starved() {
+        let queue = WriteQueue::spawn();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let first = queue.turn(WritePriority::Klines).await;
+        let mut waiters = Vec::new();
+        let trades = MAX_PASSED_OVER + 2;
+        let requests = std::iter::once(("other".to_string(), WritePriority::Other))
+            .chain((1..=trades).map(|n| (format!("trade {}", n), WritePriority::Trades)));
+        for (name, priority) in requests {
+            let (queue, order) = (queue.clone(), order.clone());
+            waiters.push(tokio::spawn(async move {
+                let _turn = queue.turn(priority).await;
+                order.lock().unwrap().push(name);
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        drop(first);
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+
+        let order = order.lock().unwrap();
+        let other = order.iter().position(|name| name == "other").unwrap();
+        assert_eq!(other as u64, MAX_PASSED_OVER);
+        assert_eq!(order.len() as u64, trades + 1);
+    }
+}
@@ -0,0 +1,22 @@
+//! `InferenceEngine::predict` on the bundled model.
+//!
+//! Run with `cargo bench -p strategy --bench inference`.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use strategy::inference::{DEFAULT_TEMPERATURE, InferenceEngine};
+
+const BUNDLED_MODEL: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../models/strategy.onnx");
+
+fn predict(c: &mut Criterion) {
+    let engine = InferenceEngine::new(BUNDLED_MODEL, DEFAULT_TEMPERATURE);
+    // RSI, OBI, TFI, volatility.
+    let features = [50.0, 0.1, -0.2, 3.0];
+    c.bench_function("inference_predict", |b| {
+        b.iter(|| engine.predict(black_box(&features)).unwrap())
+    });
+}
+
+criterion_group!(benches, predict);
+criterion_main!(benches);
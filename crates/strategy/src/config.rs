@@ -0,0 +1,220 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use common::config::{parse_bool, parse_secs};
+use common::symbols::SymbolAliases;
+
+use crate::groups::{GroupError, SymbolGroups};
+
+/// Time after a signal for a symbol during which it gets no other, so
+/// confidence hovering around the threshold doesn't churn a position.
+pub const DEFAULT_SIGNAL_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Strategy settings that may change while the bot runs: per-group
+/// thresholds and position caps, the symbols it must not trade, and how
+/// often it may trade each one.
+/// Symbols are kept under their current names, after `SYMBOL_ALIASES`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyConfig {
+    /// `SYMBOL_GROUPS` and `GROUP_RISK`.
+    pub groups: SymbolGroups,
+    /// `STRATEGY_DISABLED_SYMBOLS`: comma-separated, lowercase. Unlike the
+    /// runtime pause, these symbols are still recorded.
+    pub disabled_symbols: BTreeSet<String>,
+    /// `STRATEGY_COOLDOWN_SECS`: no BUY or SELL for a symbol until this long
+    /// after its last one. Zero acts on every prediction over the threshold.
+    pub cooldown: Duration,
+    /// `STRATEGY_SINGLE_POSITION` (`true`): a symbol holding a position gets
+    /// no further BUY until a SELL has closed it. Without, every BUY adds to
+    /// the position and the SELL closes all of it.
+    pub single_position: bool,
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        Self {
+            groups: SymbolGroups::default(),
+            disabled_symbols: BTreeSet::new(),
+            cooldown: DEFAULT_SIGNAL_COOLDOWN,
+            single_position: true,
+        }
+    }
+}
+
+impl StrategyConfig {
+    /// Symbols are renamed with `aliases`, the `SYMBOL_ALIASES` read once at
+    /// startup into `common::config::Config`.
+    pub fn from_env(default_groups: &str, aliases: &SymbolAliases) -> Result<Self, GroupError> {
+        Self::from_vars(default_groups, aliases, |key| env::var(key).ok())
+    }
+
+    /// Like `from_env`, but looks every variable up with `var`.
+    pub fn from_vars(
+        default_groups: &str,
+        aliases: &SymbolAliases,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, GroupError> {
+        let groups = SymbolGroups::from_vars(default_groups, &var)?.renamed(aliases)?;
+        let disabled_symbols = var("STRATEGY_DISABLED_SYMBOLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| aliases.canonical(&s.to_lowercase()).into_owned())
+            .collect();
+        let cooldown = match var("STRATEGY_COOLDOWN_SECS") {
+            Some(secs) => {
+                parse_secs("STRATEGY_COOLDOWN_SECS", &secs).map_err(GroupError::Setting)?
+            }
+            None => DEFAULT_SIGNAL_COOLDOWN,
+        };
+        let single_position = match var("STRATEGY_SINGLE_POSITION") {
+            Some(value) => {
+                parse_bool("STRATEGY_SINGLE_POSITION", &value).map_err(GroupError::Setting)?
+            }
+            None => true,
+        };
+        Ok(Self {
+            groups,
+            disabled_symbols,
+            cooldown,
+            single_position,
+        })
+    }
+
+    pub fn is_disabled(&self, symbol: &str) -> bool {
+        !self.disabled_symbols.is_empty() && self.disabled_symbols.contains(&symbol.to_lowercase())
+    }
+
+    /// Human-readable differences from `self` to `other`, one per line.
+    pub fn changes(&self, other: &Self) -> Vec<String> {
+        let mut changes = self.groups.changes(&other.groups);
+        for symbol in other.disabled_symbols.difference(&self.disabled_symbols) {
+            changes.push(format!("{} disabled", symbol));
+        }
+        for symbol in self.disabled_symbols.difference(&other.disabled_symbols) {
+            changes.push(format!("{} enabled", symbol));
+        }
+        if self.cooldown != other.cooldown {
+            changes.push(format!(
+                "cooldown {:?} -> {:?}",
+                self.cooldown, other.cooldown
+            ));
+        }
+        if self.single_position != other.single_position {
+            changes.push(format!(
+                "single position {} -> {}",
+                self.single_position, other.single_position
+            ));
+        }
+        changes
+    }
+}
+
+/// The live `StrategyConfig`, swapped whole on reload so readers never see
+/// half of an update.
+///
+/// Cloning shares the same config.
+#[derive(Debug, Clone, Default)]
+pub struct SharedStrategyConfig(Arc<RwLock<Arc<StrategyConfig>>>);
+
+impl SharedStrategyConfig {
+    pub fn new(config: StrategyConfig) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    /// The current config. It stays the same for as long as it is held,
+    /// whatever reloads happen meanwhile.
+    pub fn load(&self) -> Arc<StrategyConfig> {
+        self.0.read().expect("strategy config poisoned").clone()
+    }
+
+    /// Replaces the config, returning what changed.
+    pub fn store(&self, config: StrategyConfig) -> Vec<String> {
+        let mut current = self.0.write().expect("strategy config poisoned");
+        let changes = current.changes(&config);
+        *current = Arc::new(config);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_swaps_config_and_reports_changes() {
+        let vars = |risk: &'static str, disabled: &'static str| {
+            move |key: &str| match key {
+                "GROUP_RISK" => Some(risk.to_string()),
+                "STRATEGY_DISABLED_SYMBOLS" => Some(disabled.to_string()),
+                _ => None,
+            }
+        };
+        let shared = SharedStrategyConfig::new(
+            StrategyConfig::from_vars(
+                "core=btcusdt",
+                &SymbolAliases::default(),
+                vars("", "dogeusdt"),
+            )
+            .unwrap(),
+        );
+        let before = shared.load();
+
+        let changes = shared.store(
+            StrategyConfig::from_vars(
+                "core=btcusdt",
+                &SymbolAliases::default(),
+                vars("core=threshold:0.7", "SOLUSDT"),
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            changes,
+            vec![
+                "core threshold 0.6 -> 0.7",
+                "solusdt disabled",
+                "dogeusdt enabled"
+            ]
+        );
+        assert!(before.is_disabled("dogeusdt"));
+        assert!(shared.load().is_disabled("SOLUSDT"));
+        assert_eq!(shared.load().groups.risk("btcusdt").threshold, 0.7);
+    }
+
+    #[test]
+    fn test_cooldown_and_single_position_reload() {
+        let vars = |cooldown: &'static str, single: &'static str| {
+            move |key: &str| match key {
+                "STRATEGY_COOLDOWN_SECS" => Some(cooldown.to_string()),
+                "STRATEGY_SINGLE_POSITION" => Some(single.to_string()),
+                _ => None,
+            }
+        };
+        assert_eq!(
+            StrategyConfig::from_vars("", &SymbolAliases::default(), |_| None).unwrap(),
+            StrategyConfig::default()
+        );
+        let shared = SharedStrategyConfig::new(StrategyConfig::default());
+
+        let changes = shared.store(
+            StrategyConfig::from_vars("", &SymbolAliases::default(), vars("0", "false")).unwrap(),
+        );
+        assert_eq!(
+            changes,
+            vec!["cooldown 60s -> 0ns", "single position true -> false"]
+        );
+        assert_eq!(shared.load().cooldown, Duration::ZERO);
+
+        assert!(matches!(
+            StrategyConfig::from_vars("", &SymbolAliases::default(), vars("1m", "true")),
+            Err(GroupError::Setting(_))
+        ));
+        assert!(matches!(
+            StrategyConfig::from_vars("", &SymbolAliases::default(), vars("0", "yes")),
+            Err(GroupError::Setting(_))
+        ));
+    }
+}
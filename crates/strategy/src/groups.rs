@@ -0,0 +1,305 @@
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+
+use common::symbols::SymbolAliases;
+use thiserror::Error;
+
+/// Confidence above which a prediction is acted on when a group sets none.
+pub const DEFAULT_THRESHOLD: f32 = 0.60;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum GroupError {
+    #[error("Expected 'name=value,...', got '{0}'")]
+    Syntax(String),
+    #[error("{symbol} is in both '{first}' and '{second}'")]
+    DuplicateSymbol {
+        symbol: String,
+        first: String,
+        second: String,
+    },
+    #[error("Risk settings for unknown group '{0}'")]
+    UnknownGroup(String),
+    #[error("Invalid risk setting '{0}'")]
+    InvalidRisk(String),
+    /// A strategy setting outside the groups that doesn't parse.
+    #[error("{0}")]
+    Setting(String),
+}
+
+/// Risk parameters shared by every symbol of a group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupRisk {
+    /// Minimum model confidence to open or close a position.
+    pub threshold: f32,
+    /// Largest position, in quote currency. `None` leaves the calibrated
+    /// per-symbol quantity uncapped.
+    pub max_notional: Option<f64>,
+}
+
+impl Default for GroupRisk {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_THRESHOLD,
+            max_notional: None,
+        }
+    }
+}
+
+/// Symbol -> group mapping (e.g. Core/Alpha/Macro) with per-group risk.
+///
+/// Symbols outside every group use `GroupRisk::default()`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolGroups {
+    /// Symbol (lowercase) -> group name.
+    members: HashMap<String, String>,
+    risk: HashMap<String, GroupRisk>,
+}
+
+impl SymbolGroups {
+    /// Reads `SYMBOL_GROUPS` (falling back to `default_spec`) and `GROUP_RISK`, e.g.
+    /// `core=btcusdt,ethusdt;alpha=dogeusdt` and
+    /// `core=threshold:0.6,max_notional:50;alpha=threshold:0.7`.
+    pub fn from_env(default_spec: &str) -> Result<Self, GroupError> {
+        Self::from_vars(default_spec, |key| env::var(key).ok())
+    }
+
+    /// Like `from_env`, but looks the two variables up with `var`.
+    pub fn from_vars(
+        default_spec: &str,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, GroupError> {
+        let spec = var("SYMBOL_GROUPS").unwrap_or_else(|| default_spec.to_string());
+        let groups = Self::parse(&spec)?;
+        match var("GROUP_RISK") {
+            Some(risk) => groups.with_risk(&risk),
+            None => Ok(groups),
+        }
+    }
+
+    /// Parses `group=symbol,...;...`. A symbol listed in two groups is an error.
+    pub fn parse(spec: &str) -> Result<Self, GroupError> {
+        let mut groups = Self::default();
+        for (group, symbols) in entries(spec)? {
+            groups.risk.entry(group.clone()).or_default();
+            for symbol in symbols.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                let symbol = symbol.to_lowercase();
+                if let Some(first) = groups.members.get(&symbol) {
+                    return Err(GroupError::DuplicateSymbol {
+                        symbol,
+                        first: first.clone(),
+                        second: group,
+                    });
+                }
+                groups.members.insert(symbol, group.clone());
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Applies `group=threshold:x,max_notional:y;...` on top of the defaults.
+    pub fn with_risk(mut self, spec: &str) -> Result<Self, GroupError> {
+        for (group, settings) in entries(spec)? {
+            let risk = self
+                .risk
+                .get_mut(&group)
+                .ok_or_else(|| GroupError::UnknownGroup(group.clone()))?;
+            for setting in settings.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                let invalid = || GroupError::InvalidRisk(setting.to_string());
+                let (key, value) = setting.split_once(':').ok_or_else(invalid)?;
+                let value = value.trim().parse::<f64>().map_err(|_| invalid())?;
+                match key.trim() {
+                    "threshold" if (0.0..=1.0).contains(&value) => risk.threshold = value as f32,
+                    "max_notional" if value > 0.0 => risk.max_notional = Some(value),
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// The same groups with every aliased symbol under its current name.
+    pub fn renamed(self, aliases: &SymbolAliases) -> Result<Self, GroupError> {
+        let mut groups = Self {
+            members: HashMap::new(),
+            risk: self.risk,
+        };
+        for (symbol, group) in self.members {
+            let symbol = aliases.canonical(&symbol).into_owned();
+            match groups.members.get(&symbol) {
+                Some(first) if *first != group => {
+                    return Err(GroupError::DuplicateSymbol {
+                        symbol,
+                        first: first.clone(),
+                        second: group,
+                    });
+                }
+                _ => {
+                    groups.members.insert(symbol, group);
+                }
+            }
+        }
+        Ok(groups)
+    }
+
+    pub fn group(&self, symbol: &str) -> Option<&str> {
+        self.members.get(&symbol.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn risk(&self, symbol: &str) -> GroupRisk {
+        self.group(symbol)
+            .and_then(|group| self.risk.get(group))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Configured symbols that belong to no group.
+    pub fn ungrouped<'a>(&self, symbols: &[&'a str]) -> Vec<&'a str> {
+        symbols
+            .iter()
+            .copied()
+            .filter(|symbol| self.group(symbol).is_none())
+            .collect()
+    }
+
+    /// One line per group setting or symbol membership that differs in
+    /// `other`, e.g. `core threshold 0.6 -> 0.55`.
+    pub fn changes(&self, other: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        let groups: BTreeSet<&String> = self.risk.keys().chain(other.risk.keys()).collect();
+        for group in groups {
+            match (self.risk.get(group), other.risk.get(group)) {
+                (Some(old), Some(new)) => {
+                    if old.threshold != new.threshold {
+                        changes.push(format!(
+                            "{} threshold {} -> {}",
+                            group, old.threshold, new.threshold
+                        ));
+                    }
+                    if old.max_notional != new.max_notional {
+                        changes.push(format!(
+                            "{} max_notional {} -> {}",
+                            group,
+                            notional(old.max_notional),
+                            notional(new.max_notional)
+                        ));
+                    }
+                }
+                (None, Some(_)) => changes.push(format!("group {} added", group)),
+                (Some(_), None) => changes.push(format!("group {} removed", group)),
+                (None, None) => {}
+            }
+        }
+        let symbols: BTreeSet<&String> = self.members.keys().chain(other.members.keys()).collect();
+        for symbol in symbols {
+            let (old, new) = (self.members.get(symbol), other.members.get(symbol));
+            if old != new {
+                changes.push(format!(
+                    "{} group {} -> {}",
+                    symbol,
+                    old.map_or("(none)", String::as_str),
+                    new.map_or("(none)", String::as_str)
+                ));
+            }
+        }
+        changes
+    }
+}
+
+fn notional(max_notional: Option<f64>) -> String {
+    max_notional.map_or_else(|| "none".to_string(), |n| n.to_string())
+}
+
+/// Splits `name=value;name=value` into lowercase names and raw values.
+fn entries(spec: &str) -> Result<Vec<(String, &str)>, GroupError> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .split_once('=')
+                .map(|(name, value)| (name.trim().to_lowercase(), value))
+                .ok_or_else(|| GroupError::Syntax(entry.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_groups_and_risk() {
+        let groups = SymbolGroups::parse("core=BTCUSDT,ethusdt; alpha=dogeusdt")
+            .unwrap()
+            .with_risk("core=threshold:0.55,max_notional:50")
+            .unwrap();
+
+        assert_eq!(groups.group("btcusdt"), Some("core"));
+        assert_eq!(
+            groups.risk("ETHUSDT"),
+            GroupRisk {
+                threshold: 0.55,
+                max_notional: Some(50.0)
+            }
+        );
+        assert_eq!(groups.risk("dogeusdt"), GroupRisk::default());
+        assert_eq!(groups.ungrouped(&["btcusdt", "xrpusdt"]), vec!["xrpusdt"]);
+    }
+
+    #[test]
+    fn test_rejects_bad_config() {
+        assert_eq!(
+            SymbolGroups::parse("core=btcusdt;alpha=btcusdt"),
+            Err(GroupError::DuplicateSymbol {
+                symbol: "btcusdt".to_string(),
+                first: "core".to_string(),
+                second: "alpha".to_string(),
+            })
+        );
+        assert!(SymbolGroups::parse("btcusdt").is_err());
+
+        let groups = SymbolGroups::parse("core=btcusdt").unwrap();
+        assert_eq!(
+            groups.clone().with_risk("macro=threshold:0.7"),
+            Err(GroupError::UnknownGroup("macro".to_string()))
+        );
+        assert!(groups.clone().with_risk("core=threshold:1.5").is_err());
+        assert!(groups.with_risk("core=size:1").is_err());
+    }
+
+    #[test]
+    fn test_renamed_moves_members_to_current_names() {
+        let aliases = SymbolAliases::parse("maticusdt=polusdt").unwrap();
+        let groups = SymbolGroups::parse("core=btcusdt,maticusdt")
+            .unwrap()
+            .renamed(&aliases)
+            .unwrap();
+        assert_eq!(groups.group("POLUSDT"), Some("core"));
+        assert_eq!(groups.group("maticusdt"), None);
+
+        let split = SymbolGroups::parse("core=maticusdt;alpha=polusdt").unwrap();
+        assert!(matches!(
+            split.renamed(&aliases),
+            Err(GroupError::DuplicateSymbol { .. })
+        ));
+    }
+
+    #[test]
+    fn test_changes_lists_risk_and_membership() {
+        let old = SymbolGroups::parse("core=btcusdt,ethusdt;alpha=dogeusdt").unwrap();
+        let new = SymbolGroups::parse("core=btcusdt;alpha=dogeusdt,ethusdt")
+            .unwrap()
+            .with_risk("alpha=threshold:0.7,max_notional:20")
+            .unwrap();
+
+        assert_eq!(
+            old.changes(&new),
+            vec![
+                "alpha threshold 0.6 -> 0.7",
+                "alpha max_notional none -> 20",
+                "ethusdt group core -> alpha",
+            ]
+        );
+        assert!(new.changes(&new).is_empty());
+    }
+}
@@ -1,17 +1,80 @@
 use std::path::Path;
 use std::sync::Arc;
+use thiserror::Error;
+use tracing::{error, info, warn};
 use tract_onnx::prelude::*;
-use tracing::{debug, error, info, warn};
+
+pub mod scaler;
+
+pub use scaler::{FeatureScaler, ScalerError};
 
 type RunnableModel = SimplePlan<TypedFact, Box<dyn TypedOp>, Graph<TypedFact, Box<dyn TypedOp>>>;
 
+/// Default `MODEL_SCORE_THRESHOLD` for single-output models.
+pub const DEFAULT_SCORE_THRESHOLD: f32 = 0.5;
+
+/// Softmax temperature that leaves the model's probabilities as trained.
+pub const DEFAULT_TEMPERATURE: f32 = 1.0;
+
+/// A model output that can't be turned into a prediction.
+#[derive(Error, Debug, PartialEq)]
+pub enum OutputError {
+    #[error("Model returned an empty output")]
+    Empty,
+    #[error("Model output {index} is {value}")]
+    NotFinite { index: usize, value: f32 },
+}
+
+/// How the model's output is turned into Hold/Buy/Sell, chosen from its width.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    /// `[1, 3]` logits: Hold, Buy, Sell.
+    HoldBuySell,
+    /// `[1, 2]` logits: Buy, Sell.
+    BuySell,
+    /// `[1, 1]` score in 0..1 (probability of an up-move). Buy at or above
+    /// `threshold`, Sell at or below `1 - threshold`, Hold in between.
+    Score { threshold: f32 },
+}
+
+impl OutputMode {
+    fn from_width(width: usize, threshold: f32) -> Option<Self> {
+        match width {
+            3 => Some(Self::HoldBuySell),
+            2 => Some(Self::BuySell),
+            1 => Some(Self::Score { threshold }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct InferenceEngine {
     model: Option<Arc<RunnableModel>>,
+    scaler: Option<FeatureScaler>,
+    sequence: bool,
+    // `None` when the output width is only known at run time.
+    output_mode: Option<OutputMode>,
+    score_threshold: f32,
+    temperature: f32,
 }
 
 impl InferenceEngine {
-    pub fn new(model_path: &str) -> Self {
+    /// Loads the model at `model_path`. Logits are divided by `temperature`
+    /// before the softmax: above 1 flattens the class probabilities, below 1
+    /// sharpens them. It has no effect on single-score models.
+    pub fn new(model_path: &str, temperature: f32) -> Self {
+        let engine = Self::load(model_path, temperature);
+        if engine.is_simulation() {
+            warn!(
+                "==== SIMULATION MODE: no model loaded from {}. Every prediction is a zero-confidence Hold and no signal will be traded. ====",
+                model_path
+            );
+        }
+        engine
+    }
+
+    fn load(model_path: &str, temperature: f32) -> Self {
         let path = Path::new(model_path);
         let model = if path.exists() {
             info!("Loading ONNX model from {:?}", path);
@@ -23,11 +86,128 @@ impl InferenceEngine {
                 }
             }
         } else {
-            warn!("ONNX model not found at {:?}. Running in SIMULATION mode (Dummy Predictions).", path);
+            warn!(
+                "ONNX model not found at {:?}. Running in SIMULATION mode (Dummy Predictions).",
+                path
+            );
+            None
+        };
+
+        let temperature = if temperature.is_finite() && temperature > 0.0 {
+            temperature
+        } else {
+            warn!(
+                "Ignoring softmax temperature {}, using {}",
+                temperature, DEFAULT_TEMPERATURE
+            );
+            DEFAULT_TEMPERATURE
+        };
+
+        // Optional `<model>.scaler.json` next to the model. Models that normalize
+        // inside the graph (like the bundled one) don't ship one.
+        let scaler_path = path.with_extension("scaler.json");
+        let scaler = if scaler_path.exists() {
+            match FeatureScaler::load(&scaler_path) {
+                Ok(scaler) => {
+                    info!(
+                        "Loaded {}-feature scaler from {:?}",
+                        scaler.len(),
+                        scaler_path
+                    );
+                    Some(scaler)
+                }
+                Err(e) => {
+                    // Unscaled features would silently skew every prediction.
+                    error!(
+                        "Failed to load scaler {:?}: {}. Running in SIMULATION mode.",
+                        scaler_path, e
+                    );
+                    return Self::simulation();
+                }
+            }
+        } else {
             None
         };
 
-        Self { model }
+        if let (Some(plan), Some(scaler)) = (&model, &scaler)
+            && let Some(width) = Self::input_width(plan)
+            && width != scaler.len()
+        {
+            error!(
+                "Scaler has {} features but the model takes {}. Running in SIMULATION mode.",
+                scaler.len(),
+                width
+            );
+            return Self::simulation();
+        }
+
+        let sequence = model.as_deref().and_then(Self::input_rank) == Some(3);
+        if sequence {
+            info!("Model takes a (1, window, N) sequence input");
+        }
+
+        let mut output_mode = None;
+        if let Some(width) = model.as_deref().and_then(Self::output_width) {
+            match OutputMode::from_width(width, DEFAULT_SCORE_THRESHOLD) {
+                Some(mode) => {
+                    info!("Model output width {} -> {:?}", width, mode);
+                    output_mode = Some(mode);
+                }
+                None => {
+                    error!(
+                        "Unsupported model output width {} (expected 1, 2 or 3). Running in SIMULATION mode.",
+                        width
+                    );
+                    return Self::simulation();
+                }
+            }
+        }
+
+        Self {
+            model,
+            scaler,
+            sequence,
+            output_mode,
+            score_threshold: DEFAULT_SCORE_THRESHOLD,
+            temperature,
+        }
+    }
+
+    /// A single-score model buys at or above `threshold` and sells at or
+    /// below `1 - threshold`. Models with 2 or 3 outputs ignore it.
+    pub fn with_score_threshold(mut self, threshold: f32) -> Self {
+        self.score_threshold = threshold;
+        if let Some(OutputMode::Score { .. }) = self.output_mode {
+            self.output_mode = Some(OutputMode::Score { threshold });
+        }
+        self
+    }
+
+    /// No model: every prediction is a zero-confidence Hold.
+    fn simulation() -> Self {
+        Self {
+            model: None,
+            scaler: None,
+            sequence: false,
+            output_mode: None,
+            score_threshold: DEFAULT_SCORE_THRESHOLD,
+            temperature: DEFAULT_TEMPERATURE,
+        }
+    }
+
+    fn output_width(plan: &RunnableModel) -> Option<usize> {
+        let fact = plan.model().output_fact(0).ok()?;
+        fact.shape.iter().last()?.to_i64().ok().map(|n| n as usize)
+    }
+
+    fn input_rank(plan: &RunnableModel) -> Option<usize> {
+        Some(plan.model().input_fact(0).ok()?.shape.len())
+    }
+
+    /// Size of the model's last input dimension, when it is fixed.
+    fn input_width(plan: &RunnableModel) -> Option<usize> {
+        let fact = plan.model().input_fact(0).ok()?;
+        fact.shape.iter().last()?.to_i64().ok().map(|n| n as usize)
     }
 
     fn load_model(path: &str) -> TractResult<RunnableModel> {
@@ -38,40 +218,165 @@ impl InferenceEngine {
         Ok(model)
     }
 
-    pub fn predict(&self, features: &[f32]) -> Result<InferenceResult, Box<dyn std::error::Error + Send + Sync>> {
+    /// True when no model is loaded (missing, unreadable or incompatible
+    /// with its scaler), so `predict` only returns dummy Holds.
+    pub fn is_simulation(&self) -> bool {
+        self.model.is_none()
+    }
+
+    /// Features per step the loaded model takes, when its input has a fixed
+    /// width.
+    pub fn feature_width(&self) -> Option<usize> {
+        self.model.as_deref().and_then(Self::input_width)
+    }
+
+    /// True when the model takes a `(1, window, N)` sequence rather than a
+    /// single `(1, N)` feature vector.
+    pub fn is_sequence_model(&self) -> bool {
+        self.sequence
+    }
+
+    pub fn predict(
+        &self,
+        features: &[f32],
+    ) -> Result<InferenceResult, Box<dyn std::error::Error + Send + Sync>> {
         if let Some(model) = &self.model {
-            // Create input tensor (1, N)
-            let tensor = tract_ndarray::Array::from_shape_vec((1, features.len()), features.to_vec())?
-                .into_tensor();
+            let features = self.scale(features)?;
 
-            let result = model.run(tvec!(tensor.into()))?;
-            
-            // Output is [1, 3] Logits (Hold, Buy, Sell)
-            let logits = result[0].to_array_view::<f32>()?;
-            let logits_slice = logits.as_slice().ok_or("Failed to get logits slice")?;
-
-            // Softmax
-            let max_logit = logits_slice.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
-            let exp_sum: f32 = logits_slice.iter().map(|&x| (x - max_logit).exp()).sum();
-            let probs: Vec<f32> = logits_slice.iter().map(|&x| (x - max_logit).exp() / exp_sum).collect();
-
-            // ArgMax
-            let mut max_index = 0;
-            let mut max_prob = 0.0;
-            for (i, &prob) in probs.iter().enumerate() {
-                if prob > max_prob {
-                    max_prob = prob;
-                    max_index = i;
-                }
-            }
+            // Create input tensor (1, N)
+            let tensor =
+                tract_ndarray::Array::from_shape_vec((1, features.len()), features)?.into_tensor();
 
+            self.classify(model, tensor)
+        } else {
+            // Dummy logic for simulation
             Ok(InferenceResult {
-                class: max_index,
-                confidence: max_prob,
+                class: 0,
+                confidence: 0.0,
+                outputs: Vec::new(),
             })
+        }
+    }
+
+    /// Runs a sequence model on `window`, oldest step first.
+    pub fn predict_sequence<T: AsRef<[f32]>>(
+        &self,
+        window: &[T],
+    ) -> Result<InferenceResult, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(model) = &self.model {
+            let width = window.first().map_or(0, |step| step.as_ref().len());
+            let mut steps = Vec::with_capacity(window.len() * width);
+            for step in window {
+                steps.extend(self.scale(step.as_ref())?);
+            }
+
+            // Create input tensor (1, window, N)
+            let tensor = tract_ndarray::Array::from_shape_vec((1, window.len(), width), steps)?
+                .into_tensor();
+
+            self.classify(model, tensor)
         } else {
             // Dummy logic for simulation
-            Ok(InferenceResult { class: 0, confidence: 0.0 })
+            Ok(InferenceResult {
+                class: 0,
+                confidence: 0.0,
+                outputs: Vec::new(),
+            })
+        }
+    }
+
+    fn scale(&self, features: &[f32]) -> Result<Vec<f32>, ScalerError> {
+        match &self.scaler {
+            Some(scaler) => scaler.transform(features),
+            None => Ok(features.to_vec()),
+        }
+    }
+
+    fn classify(
+        &self,
+        model: &RunnableModel,
+        tensor: Tensor,
+    ) -> Result<InferenceResult, Box<dyn std::error::Error + Send + Sync>> {
+        let result = model.run(tvec!(tensor.into()))?;
+
+        let logits = result[0].to_array_view::<f32>()?;
+        let logits_slice = logits.as_slice().ok_or("Failed to get logits slice")?;
+        Self::check_outputs(logits_slice)?;
+
+        let mode = match self.output_mode {
+            Some(mode) => mode,
+            None => OutputMode::from_width(logits_slice.len(), self.score_threshold)
+                .ok_or_else(|| format!("Unsupported model output width {}", logits_slice.len()))?,
+        };
+
+        match mode {
+            OutputMode::HoldBuySell => Ok(Self::argmax(logits_slice, &[0, 1, 2], self.temperature)),
+            OutputMode::BuySell => Ok(Self::argmax(logits_slice, &[1, 2], self.temperature)),
+            OutputMode::Score { threshold } => {
+                let score = logits_slice[0];
+                let (class, confidence) = if score >= threshold {
+                    (1, score)
+                } else if score <= 1.0 - threshold {
+                    (2, 1.0 - score)
+                } else {
+                    (0, 1.0 - (score - 0.5).abs() * 2.0)
+                };
+                Ok(InferenceResult {
+                    class,
+                    confidence,
+                    outputs: vec![score],
+                })
+            }
+        }
+    }
+
+    /// Rejects an empty output or one holding NaN or an infinity, which would
+    /// otherwise come out as a confident-looking class 0.
+    fn check_outputs(outputs: &[f32]) -> Result<(), OutputError> {
+        if outputs.is_empty() {
+            return Err(OutputError::Empty);
+        }
+        match outputs.iter().position(|x| !x.is_finite()) {
+            Some(index) => Err(OutputError::NotFinite {
+                index,
+                value: outputs[index],
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Softmax over `logits / temperature`, then picks the most likely one,
+    /// the lowest index on a tie. `classes[i]` is the Hold/Buy/Sell class of
+    /// logit `i`; `logits_slice` has passed `check_outputs`.
+    fn argmax(logits_slice: &[f32], classes: &[usize], temperature: f32) -> InferenceResult {
+        // Softmax
+        let max_logit = logits_slice
+            .iter()
+            .fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+        let exp_sum: f32 = logits_slice
+            .iter()
+            .map(|&x| ((x - max_logit) / temperature).exp())
+            .sum();
+        let probs: Vec<f32> = logits_slice
+            .iter()
+            .map(|&x| ((x - max_logit) / temperature).exp() / exp_sum)
+            .collect();
+
+        // ArgMax, starting from the first class so only a strictly larger
+        // probability moves it.
+        let mut max_index = 0;
+        let mut max_prob = probs[0];
+        for (i, &prob) in probs.iter().enumerate().skip(1) {
+            if prob > max_prob {
+                max_prob = prob;
+                max_index = i;
+            }
+        }
+
+        InferenceResult {
+            class: classes[max_index],
+            confidence: max_prob,
+            outputs: probs,
         }
     }
 }
@@ -80,4 +385,77 @@ impl InferenceEngine {
 pub struct InferenceResult {
     pub class: usize, // 0=Hold, 1=Buy, 2=Sell
     pub confidence: f32,
+    /// Class probabilities in the model's own order, or the raw score for a
+    /// single-output model.
+    pub outputs: Vec<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNDLED_MODEL: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../models/strategy.onnx");
+
+    #[test]
+    fn test_bundled_model_is_a_single_score() {
+        let engine = InferenceEngine::new(BUNDLED_MODEL, DEFAULT_TEMPERATURE);
+        assert!(!engine.is_simulation());
+        assert!(!engine.is_sequence_model());
+        assert_eq!(
+            engine.output_mode,
+            Some(OutputMode::Score {
+                threshold: DEFAULT_SCORE_THRESHOLD
+            })
+        );
+
+        let result = engine.predict(&[50.0, 0.1, -0.2, 3.0]).unwrap();
+        assert_eq!(result.outputs.len(), 1);
+        assert!(result.confidence >= 0.0 && result.confidence <= 1.0);
+
+        let engine = engine.with_score_threshold(0.7);
+        assert_eq!(
+            engine.output_mode,
+            Some(OutputMode::Score { threshold: 0.7 })
+        );
+    }
+
+    #[test]
+    fn test_missing_model_runs_in_simulation() {
+        let engine = InferenceEngine::load("/nonexistent/model.onnx", DEFAULT_TEMPERATURE);
+        assert!(engine.is_simulation());
+        assert_eq!(engine.predict(&[50.0, 0.1, -0.2, 3.0]).unwrap().class, 0);
+    }
+
+    #[test]
+    fn test_two_class_output_maps_to_buy_sell() {
+        let result = InferenceEngine::argmax(&[0.1, 2.0], &[1, 2], DEFAULT_TEMPERATURE);
+        assert_eq!(result.class, 2);
+        assert!(result.confidence > 0.8);
+    }
+
+    #[test]
+    fn test_ties_pick_the_lowest_index_and_bad_outputs_are_errors() {
+        let tie = InferenceEngine::argmax(&[0.0, 1.0, 1.0], &[0, 1, 2], DEFAULT_TEMPERATURE);
+        assert_eq!(tie.class, 1);
+        let flat = InferenceEngine::argmax(&[3.0, 3.0], &[1, 2], DEFAULT_TEMPERATURE);
+        assert_eq!((flat.class, flat.confidence), (1, 0.5));
+
+        assert_eq!(InferenceEngine::check_outputs(&[]), Err(OutputError::Empty));
+        assert!(matches!(
+            InferenceEngine::check_outputs(&[0.2, f32::NAN, 0.1]),
+            Err(OutputError::NotFinite { index: 1, .. })
+        ));
+        assert_eq!(InferenceEngine::check_outputs(&[0.2, -0.1]), Ok(()));
+    }
+
+    #[test]
+    fn test_temperature_softens_confidence_but_keeps_the_class() {
+        let logits = [0.5, 2.5, 0.0];
+        let sharp = InferenceEngine::argmax(&logits, &[0, 1, 2], DEFAULT_TEMPERATURE);
+        let soft = InferenceEngine::argmax(&logits, &[0, 1, 2], 2.0);
+        assert_eq!((sharp.class, soft.class), (1, 1));
+        assert!(sharp.confidence > 0.8);
+        assert!(soft.confidence < 0.65);
+        assert!((soft.outputs.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    }
 }
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ScalerError {
+    #[error("Failed to read scaler file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid scaler file: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Scaler parameters disagree in length ({0} vs {1})")]
+    Shape(usize, usize),
+    #[error("Scaler expects {expected} features, got {got}")]
+    Mismatch { expected: usize, got: usize },
+}
+
+/// Per-feature preprocessing applied before inference, so live features are on
+/// the same scale the model was trained on.
+///
+/// Loaded from JSON, e.g. `{"kind":"zscore","mean":[..],"std":[..]}` or
+/// `{"kind":"minmax","min":[..],"max":[..]}`, one entry per feature.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum FeatureScaler {
+    /// `(x - mean) / std`
+    Zscore { mean: Vec<f32>, std: Vec<f32> },
+    /// `(x - min) / (max - min)`
+    Minmax { min: Vec<f32>, max: Vec<f32> },
+}
+
+impl FeatureScaler {
+    pub fn load(path: &Path) -> Result<Self, ScalerError> {
+        let scaler: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let (a, b) = scaler.params();
+        if a.len() != b.len() {
+            return Err(ScalerError::Shape(a.len(), b.len()));
+        }
+        Ok(scaler)
+    }
+
+    /// Number of features the scaler was fitted on.
+    pub fn len(&self) -> usize {
+        self.params().0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn transform(&self, features: &[f32]) -> Result<Vec<f32>, ScalerError> {
+        if features.len() != self.len() {
+            return Err(ScalerError::Mismatch {
+                expected: self.len(),
+                got: features.len(),
+            });
+        }
+
+        // A constant training feature has zero spread; map it to 0 instead of NaN.
+        let scale = |x: f32, offset: f32, spread: f32| {
+            if spread == 0.0 {
+                0.0
+            } else {
+                (x - offset) / spread
+            }
+        };
+
+        Ok(match self {
+            Self::Zscore { mean, std } => features
+                .iter()
+                .zip(mean.iter().zip(std))
+                .map(|(&x, (&m, &s))| scale(x, m, s))
+                .collect(),
+            Self::Minmax { min, max } => features
+                .iter()
+                .zip(min.iter().zip(max))
+                .map(|(&x, (&lo, &hi))| scale(x, lo, hi - lo))
+                .collect(),
+        })
+    }
+
+    fn params(&self) -> (&[f32], &[f32]) {
+        match self {
+            Self::Zscore { mean, std } => (mean, std),
+            Self::Minmax { min, max } => (min, max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zscore_and_minmax() {
+        let zscore: FeatureScaler =
+            serde_json::from_str(r#"{"kind":"zscore","mean":[50.0,0.0],"std":[10.0,0.0]}"#)
+                .unwrap();
+        assert_eq!(zscore.transform(&[70.0, 0.3]).unwrap(), vec![2.0, 0.0]);
+
+        let minmax: FeatureScaler =
+            serde_json::from_str(r#"{"kind":"minmax","min":[0.0],"max":[100.0]}"#).unwrap();
+        assert_eq!(minmax.transform(&[25.0]).unwrap(), vec![0.25]);
+
+        assert!(matches!(
+            minmax.transform(&[1.0, 2.0]),
+            Err(ScalerError::Mismatch {
+                expected: 1,
+                got: 2
+            })
+        ));
+    }
+}
@@ -1,2 +1,4 @@
+pub mod config;
+pub mod groups;
 pub mod inference;
 pub mod services;
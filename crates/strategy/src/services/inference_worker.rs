@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::inference::{InferenceEngine, InferenceResult};
+
+/// Features per tick: [RSI, OBI, TFI, Volatility].
+pub const FEATURE_COUNT: usize = 4;
+
+/// Features per tick with the futures ones appended: the base four, then the
+/// open interest change and the funding rate.
+pub const FUTURES_FEATURE_COUNT: usize = 6;
+
+/// How often (in dropped jobs) the queue reports its running total.
+const DROP_REPORT_EVERY: u64 = 1000;
+
+/// One tick's worth of model input.
+pub struct InferenceJob {
+    pub symbol: String,
+    pub price: f64,
+    /// This tick's features; also what gets logged next to the prediction.
+    pub features: Vec<f32>,
+    /// The full window, oldest first, for sequence models.
+    pub window: Option<Vec<Vec<f32>>>,
+}
+
+pub struct InferenceOutcome {
+    pub job: InferenceJob,
+    pub result: Result<InferenceResult, String>,
+}
+
+/// Bounded FIFO between the strategy loop and the inference worker.
+///
+/// Pushing never blocks: when the worker falls behind, the oldest pending job
+/// is dropped, since a fresher tick supersedes it. A single worker consumes in
+/// order, so per-symbol ordering is preserved.
+pub struct InferenceQueue {
+    capacity: usize,
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+struct QueueState {
+    jobs: VecDeque<InferenceJob>,
+    closed: bool,
+    dropped: u64,
+}
+
+impl InferenceQueue {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(QueueState {
+                jobs: VecDeque::with_capacity(capacity),
+                closed: false,
+                dropped: 0,
+            }),
+            ready: Condvar::new(),
+        })
+    }
+
+    pub fn push(&self, job: InferenceJob) {
+        let mut state = self.state.lock().expect("inference queue poisoned");
+        if state.jobs.len() == self.capacity {
+            state.jobs.pop_front();
+            state.dropped += 1;
+            if state.dropped == 1 || state.dropped.is_multiple_of(DROP_REPORT_EVERY) {
+                warn!(
+                    "Inference worker behind, {} stale feature vectors dropped so far",
+                    state.dropped
+                );
+            }
+        }
+        state.jobs.push_back(job);
+        self.ready.notify_one();
+    }
+
+    /// Wakes the worker and makes it exit once the queue is drained.
+    pub fn close(&self) {
+        self.state.lock().expect("inference queue poisoned").closed = true;
+        self.ready.notify_all();
+    }
+
+    /// Blocks until a job is available. `None` once closed and empty.
+    fn pop(&self) -> Option<InferenceJob> {
+        let mut state = self.state.lock().expect("inference queue poisoned");
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                return Some(job);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.ready.wait(state).expect("inference queue poisoned");
+        }
+    }
+}
+
+/// Runs the model on a blocking thread so a slow model never stalls the
+/// strategy's select loop. Outcomes come back on `results` in queue order.
+pub fn spawn_inference_worker(
+    engine: InferenceEngine,
+    queue: Arc<InferenceQueue>,
+    results: mpsc::Sender<InferenceOutcome>,
+) -> JoinHandle<()> {
+    tokio::task::spawn_blocking(move || {
+        while let Some(job) = queue.pop() {
+            let result = match &job.window {
+                Some(window) => engine.predict_sequence(window),
+                None => engine.predict(&job.features),
+            }
+            .map_err(|e| e.to_string());
+
+            if results
+                .blocking_send(InferenceOutcome { job, result })
+                .is_err()
+            {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(price: f64) -> InferenceJob {
+        InferenceJob {
+            symbol: "btcusdt".to_string(),
+            price,
+            features: vec![0.0; FEATURE_COUNT],
+            window: None,
+        }
+    }
+
+    #[test]
+    fn test_full_queue_drops_oldest_and_drains_after_close() {
+        let queue = InferenceQueue::new(2);
+        queue.push(job(1.0));
+        queue.push(job(2.0));
+        queue.push(job(3.0));
+        queue.close();
+
+        assert_eq!(queue.pop().map(|j| j.price), Some(2.0));
+        assert_eq!(queue.pop().map(|j| j.price), Some(3.0));
+        assert!(queue.pop().is_none());
+    }
+}
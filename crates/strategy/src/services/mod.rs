@@ -1 +1,2 @@
+pub mod inference_worker;
 pub mod strategy_service;
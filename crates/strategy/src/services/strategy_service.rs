@@ -1,13 +1,47 @@
+use crate::config::SharedStrategyConfig;
+use crate::groups::GroupRisk;
 use crate::inference::{InferenceEngine, InferenceResult};
-use common::models::{AggTradeInsert, OrderBookInsert, TradeSignal};
-use std::collections::HashMap;
+use crate::services::inference_worker::{
+    FEATURE_COUNT, FUTURES_FEATURE_COUNT, InferenceJob, InferenceOutcome, InferenceQueue,
+    spawn_inference_worker,
+};
+use common::clock::{Clock, SystemClock};
+use common::events::{EventReceiver, MarketEvent};
+use common::models::{
+    AggTradeInsert, MarkPriceInsert, OpenInterestInsert, OrderBookInsert, Side, TradeSignal,
+};
+use common::symbols::{DisabledSymbols, SymbolAssets};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::Duration;
 use ta::Next;
 use ta::indicators::{
     BollingerBands, ExponentialMovingAverage, RelativeStrengthIndex, StandardDeviation,
 };
-use tokio::sync::broadcast;
-use tracing::{debug, error, info, warn};
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, info, warn};
+
+/// Feature vectors waiting for the inference worker before the oldest is dropped.
+const INFERENCE_QUEUE_CAPACITY: usize = 1024;
+
+/// Trades whose exchange time is further behind the clock than this are not
+/// used for signals.
+pub const DEFAULT_MAX_EVENT_AGE: Duration = Duration::from_secs(5);
+
+/// Book levels per side the OBI feature is computed over. `depth20`
+/// snapshots have 20, so this is the whole book the bundled model was
+/// trained on.
+pub const DEFAULT_OBI_LEVELS: usize = 20;
+
+/// Order size, in units of the quote asset, for symbols without a calibrated
+/// quantity. Pairs quoted in anything else are not traded.
+pub const DEFAULT_QUOTE_NOTIONAL: &[(&str, f64)] = &[
+    ("USDT", 10.0),
+    ("FDUSD", 10.0),
+    ("USDC", 10.0),
+    ("BTC", 0.0002),
+];
 
 struct SymbolState {
     rsi: RelativeStrengthIndex,
@@ -16,11 +50,22 @@ struct SymbolState {
     buy_vol_ema: ExponentialMovingAverage,
     sell_vol_ema: ExponentialMovingAverage,
     order_book_imbalance: f64,
+    // Last polled open interest, and its change from the poll before in percent.
+    open_interest: Option<f64>,
+    oi_change: f64,
+    // Latest funding rate from the mark price stream, in basis points.
+    funding_rate: f64,
     has_position: bool,
+    // Quantity bought when the position was opened, so the SELL closes it exactly.
+    position_qty: f64,
+    // Clock time of the last BUY or SELL, for the cooldown.
+    last_signal_ms: Option<u64>,
+    // Last `window_size` feature vectors, oldest first, for sequence models.
+    window: VecDeque<Vec<f32>>,
 }
 
 impl SymbolState {
-    fn new() -> Self {
+    fn new(window_size: usize) -> Self {
         Self {
             // Standard RSI(14)
             rsi: RelativeStrengthIndex::new(14).unwrap(),
@@ -32,7 +77,45 @@ impl SymbolState {
             buy_vol_ema: ExponentialMovingAverage::new(100).unwrap(),
             sell_vol_ema: ExponentialMovingAverage::new(100).unwrap(),
             order_book_imbalance: 0.0,
+            open_interest: None,
+            oi_change: 0.0,
+            funding_rate: 0.0,
             has_position: false,
+            position_qty: 0.0,
+            last_signal_ms: None,
+            window: VecDeque::with_capacity(window_size),
+        }
+    }
+}
+
+/// A feature vector width other than `FEATURE_COUNT` or
+/// `FUTURES_FEATURE_COUNT`.
+#[derive(Debug, Error)]
+#[error("feature count must be {FEATURE_COUNT} or {FUTURES_FEATURE_COUNT}, got {0}")]
+pub struct FeatureCountError(pub usize);
+
+/// The strategy's subscriptions, one per event kind it reads, so it isn't
+/// woken for klines, depth diffs or liquidations. Subscribe each to the
+/// event router's sender of that kind.
+pub struct StrategyFeeds {
+    pub trades: EventReceiver,
+    pub books: EventReceiver,
+    pub open_interest: EventReceiver,
+    pub mark_prices: EventReceiver,
+}
+
+impl StrategyFeeds {
+    /// Next event of a secondary feed, skipping lag. `None` once it closes,
+    /// which disables its `select!` arm; only the trade feed ends the run.
+    async fn next(rx: &mut EventReceiver, label: &str) -> Option<Arc<MarketEvent>> {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Strategy {} lag: {}", label, n)
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
         }
     }
 }
@@ -41,25 +124,51 @@ pub struct StrategyService {
     // Map symbol (lowercase) -> State
     states: HashMap<String, SymbolState>,
     engine: InferenceEngine,
+    window_size: usize,
+    inference_queue: Arc<InferenceQueue>,
     notification_tx: Option<broadcast::Sender<String>>,
     execution_tx: Option<broadcast::Sender<TradeSignal>>,
+    disabled_symbols: DisabledSymbols,
+    config: SharedStrategyConfig,
+    max_event_age: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    // Symbol (lowercase) -> quote asset, for sizing uncalibrated symbols.
+    quote_assets: HashMap<String, String>,
+    // Trades skipped as stale since the last status line.
+    stale_trades: u64,
+    obi_levels: usize,
+    feature_count: usize,
 }
 
 impl StrategyService {
-    pub fn new(symbols: &[&str], _window_size: usize, model_path: &str) -> Self {
+    /// `window_size` is how many ticks a sequence model sees at once; models
+    /// taking a single feature vector ignore it. `temperature` calibrates the
+    /// model's confidence before it is compared with the group thresholds.
+    pub fn new(symbols: &[&str], window_size: usize, model_path: &str, temperature: f32) -> Self {
+        let window_size = window_size.max(1);
         let mut states = HashMap::new();
         for s in symbols {
-            states.insert(s.to_lowercase(), SymbolState::new());
+            states.insert(s.to_lowercase(), SymbolState::new(window_size));
         }
 
         // Initialize AI Inference Engine
-        let engine = InferenceEngine::new(model_path);
+        let engine = InferenceEngine::new(model_path, temperature);
 
         Self {
             states,
             engine,
+            window_size,
+            inference_queue: InferenceQueue::new(INFERENCE_QUEUE_CAPACITY),
             notification_tx: None,
             execution_tx: None,
+            disabled_symbols: DisabledSymbols::new(),
+            config: SharedStrategyConfig::default(),
+            max_event_age: Some(DEFAULT_MAX_EVENT_AGE),
+            clock: Arc::new(SystemClock),
+            quote_assets: HashMap::new(),
+            stale_trades: 0,
+            obi_levels: DEFAULT_OBI_LEVELS,
+            feature_count: FEATURE_COUNT,
         }
     }
 
@@ -73,39 +182,121 @@ impl StrategyService {
         self
     }
 
-    pub async fn start(
-        mut self,
-        mut trade_rx: broadcast::Receiver<Arc<AggTradeInsert>>,
-        mut order_rx: broadcast::Receiver<Arc<OrderBookInsert>>,
-    ) {
+    /// Per-group confidence threshold and position cap, symbols not to
+    /// trade, the signal cooldown and whether positions may be added to.
+    /// Read afresh for every prediction, so a reload applies at once.
+    pub fn with_config(mut self, config: SharedStrategyConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Paused symbols get no orders; positions are left as they are.
+    pub fn with_disabled_symbols(mut self, disabled_symbols: DisabledSymbols) -> Self {
+        self.disabled_symbols = disabled_symbols;
+        self
+    }
+
+    /// Trades older than `max_age` by exchange time don't feed the
+    /// indicators or the model, so the backlog that arrives after a long
+    /// reconnect isn't traded on. `None` uses every trade. The recording
+    /// services subscribe separately and still store them.
+    pub fn with_max_event_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_event_age = max_age;
+        self
+    }
+
+    /// How many of the best levels on each side the OBI feature sums: 1 or 5
+    /// for pressure at the touch, 20 for the whole book. Must match what the
+    /// model was trained on (see `orderbook_features.obi_1`/`obi_5`/`obi`).
+    pub fn with_obi_levels(mut self, levels: usize) -> Self {
+        self.obi_levels = levels.max(1);
+        self
+    }
+
+    /// `FUTURES_FEATURE_COUNT` appends the open interest change and the
+    /// funding rate to every feature vector; `FEATURE_COUNT`, the default,
+    /// feeds only the four the bundled model was trained on. Any other count
+    /// is refused.
+    pub fn with_feature_count(mut self, count: usize) -> Result<Self, FeatureCountError> {
+        if count != FEATURE_COUNT && count != FUTURES_FEATURE_COUNT {
+            return Err(FeatureCountError(count));
+        }
+        self.feature_count = count;
+        Ok(self)
+    }
+
+    /// Score at or above which a single-output model buys, and at or below
+    /// `1 - threshold` sells. See `InferenceEngine::with_score_threshold`.
+    pub fn with_score_threshold(mut self, threshold: f32) -> Self {
+        self.engine = self.engine.with_score_threshold(threshold);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Base and quote assets from `exchangeInfo`, by ticker. Symbols without
+    /// a calibrated quantity are sized from `DEFAULT_QUOTE_NOTIONAL` by their
+    /// quote asset.
+    pub fn with_symbol_assets(mut self, assets: &HashMap<String, SymbolAssets>) -> Self {
+        self.quote_assets = assets
+            .iter()
+            .map(|(ticker, assets)| (ticker.to_lowercase(), assets.quote.clone()))
+            .collect();
+        self
+    }
+
+    /// Runs until the trade feed closes: trades and order books feed the base
+    /// features, open interest and mark prices the futures ones.
+    pub async fn start(mut self, mut feeds: StrategyFeeds) {
         info!("Starting Strategy Engine for {} symbols", self.states.len());
+        if let Some(width) = self.engine.feature_width()
+            && width != self.feature_count
+        {
+            warn!(
+                "Model takes {} features per tick but the strategy sends {}",
+                width, self.feature_count
+            );
+        }
+        if self.engine.is_simulation() {
+            self.notify(
+                "Strategy started in SIMULATION mode: no model loaded, no signals will be traded.",
+            );
+        }
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
 
+        let (result_tx, mut result_rx) = mpsc::channel(256);
+        let worker =
+            spawn_inference_worker(self.engine.clone(), self.inference_queue.clone(), result_tx);
+
         loop {
             tokio::select! {
-                trade_res = trade_rx.recv() => {
-                    match trade_res {
-                        Ok(trade) => self.process_tick(&trade),
+                event_res = feeds.trades.recv() => {
+                    match event_res {
+                        Ok(event) => self.on_event(&event),
                         Err(broadcast::error::RecvError::Lagged(n)) => warn!("Strategy trade lag: {}", n),
                         Err(_) => break,
                     }
                 }
-                order_res = order_rx.recv() => {
-                    match order_res {
-                        Ok(order) => self.process_orderbook(&order),
-                        Err(broadcast::error::RecvError::Lagged(n)) => warn!("Strategy order lag: {}", n),
-                        Err(_) => break,
-                    }
+                Some(event) = StrategyFeeds::next(&mut feeds.books, "order book") => self.on_event(&event),
+                Some(event) = StrategyFeeds::next(&mut feeds.open_interest, "open interest") => self.on_event(&event),
+                Some(event) = StrategyFeeds::next(&mut feeds.mark_prices, "mark price") => self.on_event(&event),
+                Some(outcome) = result_rx.recv() => {
+                    self.apply_prediction(outcome);
                 }
                 _ = interval.tick() => {
                     self.log_status();
                 }
             }
         }
+        self.inference_queue.close();
+        let _ = worker.await;
         info!("Strategy Engine stopped.");
     }
 
-    fn log_status(&self) {
+    fn log_status(&mut self) {
         // Log a brief summary for a few key symbols to prove liveness
         let keys = ["btcusdt", "ethusdt", "solusdt", "dogeusdt"];
         let mut summary = String::from("STATUS: ");
@@ -124,7 +315,37 @@ impl StrategyService {
                 ));
             }
         }
+        if self.stale_trades > 0 {
+            summary.push_str(&format!(
+                "({} stale trades skipped)",
+                std::mem::take(&mut self.stale_trades)
+            ));
+        }
         info!("{}", summary);
+        if self.engine.is_simulation() {
+            warn!("Strategy: SIMULATION mode, no model loaded, no signals are traded.");
+        }
+    }
+
+    fn on_event(&mut self, event: &MarketEvent) {
+        match event {
+            MarketEvent::AggTrade(trade) => self.on_trade(trade),
+            MarketEvent::OrderBook(order) => self.process_orderbook(order),
+            MarketEvent::OpenInterest(oi) => self.process_open_interest(oi),
+            MarketEvent::MarkPrice(mark) => self.process_mark_price(mark),
+            _ => {}
+        }
+    }
+
+    fn on_trade(&mut self, trade: &AggTradeInsert) {
+        if let Some(max_age) = self.max_event_age {
+            let age_secs = self.clock.now_millis() as f64 / 1000.0 - trade.trade_time;
+            if age_secs > max_age.as_secs_f64() {
+                self.stale_trades += 1;
+                return;
+            }
+        }
+        self.process_tick(trade);
     }
 
     fn process_tick(&mut self, trade: &AggTradeInsert) {
@@ -132,8 +353,6 @@ impl StrategyService {
         let price = trade.price;
         let quantity = trade.quantity;
 
-        let mut pending_action = None;
-
         if let Some(state) = self.states.get_mut(&symbol) {
             // 1. RSI & Volatility
             let rsi_val = state.rsi.next(price);
@@ -160,40 +379,25 @@ impl StrategyService {
             };
 
             // AI Inference
-            // Feature Vector: [RSI, OBI, TFI, Volatility]
-            let features = vec![rsi_val as f32, obi as f32, tfi as f32, vol_val as f32];
-            match self.engine.predict(&features) {
-                Ok(result) => {
-                    let InferenceResult { class, confidence } = result;
-
-                    // Log every prediction for visibility during testing
-                    info!(
-                        "AI Prediction for {}: Class={} Conf={:.4} (RSI={:.1} OBI={:.2} TFI={:.2} Vol={:.2})",
-                        symbol, class, confidence, rsi_val, obi, tfi, vol_val
-                    );
-
-                    // Threshold for action
-                    let threshold = 0.60; // Lowered slightly as multi-class is harder
+            // Feature Vector: [RSI, OBI, TFI, Volatility(, OI change, Funding)]
+            let mut features = vec![rsi_val as f32, obi as f32, tfi as f32, vol_val as f32];
+            if self.feature_count == FUTURES_FEATURE_COUNT {
+                features.extend([state.oi_change as f32, state.funding_rate as f32]);
+            }
+            if state.window.len() == self.window_size {
+                state.window.pop_front();
+            }
+            state.window.push_back(features.clone());
 
-                    if confidence > threshold {
-                        match class {
-                            1 => { // BUY
-                                if !state.has_position {
-                                    state.has_position = true;
-                                    pending_action = Some(("BUY", confidence));
-                                }
-                            }
-                            2 => { // SELL
-                                if state.has_position {
-                                    state.has_position = false;
-                                    pending_action = Some(("SELL", confidence));
-                                }
-                            }
-                            _ => {} // HOLD
-                        }
-                    }
-                }
-                Err(e) => warn!("AI Inference Error: {}", e),
+            let sequence = self.engine.is_sequence_model();
+            // Sequence models wait until the window has filled.
+            if !sequence || state.window.len() == self.window_size {
+                self.inference_queue.push(InferenceJob {
+                    symbol: symbol.clone(),
+                    price,
+                    features,
+                    window: sequence.then(|| state.window.iter().cloned().collect()),
+                });
             }
 
             // Legacy Rule-Based Signal Logging (for comparison)
@@ -209,43 +413,119 @@ impl StrategyService {
                 );
             }
         }
+    }
+
+    /// Acts on a prediction from the inference worker.
+    fn apply_prediction(&mut self, outcome: InferenceOutcome) {
+        let InferenceOutcome { job, result } = outcome;
+        let InferenceJob {
+            symbol,
+            price,
+            features,
+            ..
+        } = job;
+        let (rsi_val, obi, tfi, vol_val) = (features[0], features[1], features[2], features[3]);
+        let futures = match features.get(FEATURE_COUNT..) {
+            Some(&[oi_change, funding]) => {
+                format!(" OI={:+.2}% Funding={:.2}bp", oi_change, funding)
+            }
+            _ => String::new(),
+        };
+
+        let mut pending_action = None;
+        let config = self.config.load();
+        let risk = config.groups.risk(&symbol);
+        let buy_qty = self.order_quantity(&symbol, price, risk);
+        let now_ms = self.clock.now_millis();
+        let cooldown_ms = config.cooldown.as_millis() as u64;
+        let single_position = config.single_position;
+
+        if let Some(state) = self.states.get_mut(&symbol) {
+            match result {
+                Ok(result) => {
+                    let InferenceResult {
+                        class, confidence, ..
+                    } = result;
+
+                    // Log every prediction for visibility during testing
+                    info!(
+                        "AI Prediction for {}: Class={} Conf={:.4} (RSI={:.1} OBI={:.2} TFI={:.2} Vol={:.2}{})",
+                        symbol, class, confidence, rsi_val, obi, tfi, vol_val, futures
+                    );
+
+                    // Threshold for action, set per symbol group
+                    if confidence > risk.threshold
+                        && !self.disabled_symbols.is_disabled(&symbol)
+                        && !config.is_disabled(&symbol)
+                    {
+                        let cooling = state
+                            .last_signal_ms
+                            .is_some_and(|last| now_ms < last + cooldown_ms);
+                        match class {
+                            1 | 2 if cooling => {
+                                debug!("{} signal suppressed, still cooling down", symbol);
+                            }
+                            // BUY
+                            1 if !state.has_position || !single_position => {
+                                state.has_position = true;
+                                state.position_qty += buy_qty;
+                                pending_action = Some((Side::Buy, confidence, buy_qty));
+                            }
+                            // SELL
+                            2 if state.has_position => {
+                                state.has_position = false;
+                                let qty = std::mem::take(&mut state.position_qty);
+                                pending_action = Some((Side::Sell, confidence, qty));
+                            }
+                            _ => {} // HOLD
+                        }
+                        if pending_action.is_some() {
+                            state.last_signal_ms = Some(now_ms);
+                        }
+                    }
+                }
+                Err(e) => warn!("AI Inference Error: {}", e),
+            }
+        }
 
         // Execute pending action after mutable borrow is dropped
-        if let Some((side, prob)) = pending_action {
+        if let Some((side, prob, quantity)) = pending_action {
             let msg = format!(
                 "AI STRONG {} ({:.2}) for {}: Price={:.2}",
                 side, prob, symbol, price
             );
             info!("{}", msg);
             self.notify(&msg);
-            self.execute(&symbol, side, prob);
+            self.execute(&symbol, side, prob, quantity);
         }
     }
 
     fn process_orderbook(&mut self, order: &OrderBookInsert) {
         let symbol = order.symbol.to_lowercase();
-        if let Some(state) = self.states.get_mut(&symbol) {
-            let bid_vol = Self::calculate_volume(&order.bids);
-            let ask_vol = Self::calculate_volume(&order.asks);
+        if let Some(state) = self.states.get_mut(&symbol)
+            && let Some(obi) = order.depth_imbalance(self.obi_levels)
+        {
+            state.order_book_imbalance = obi;
+        }
+    }
 
-            // OBI Formula: (Bid - Ask) / (Bid + Ask)
-            let total = bid_vol + ask_vol;
-            if total > 0.0 {
-                state.order_book_imbalance = (bid_vol - ask_vol) / total;
+    fn process_open_interest(&mut self, oi: &OpenInterestInsert) {
+        let symbol = oi.symbol.to_lowercase();
+        if let Some(state) = self.states.get_mut(&symbol) {
+            if let Some(previous) = state.open_interest
+                && previous > 0.0
+            {
+                state.oi_change = (oi.oi_value - previous) / previous * 100.0;
             }
+            state.open_interest = Some(oi.oi_value);
         }
     }
 
-    fn calculate_volume(data: &[u8]) -> f64 {
-        // Data is packed as [Price(f32), Qty(f32)] in little endian
-        let mut total_vol = 0.0;
-        for chunk in data.chunks_exact(8) {
-            // We care about Quantity, which is the 2nd f32 (bytes 4..8)
-            let qty_bytes: [u8; 4] = chunk[4..8].try_into().unwrap_or([0; 4]);
-            let qty = f32::from_le_bytes(qty_bytes) as f64;
-            total_vol += qty;
+    fn process_mark_price(&mut self, mark: &MarkPriceInsert) {
+        let symbol = mark.symbol.to_lowercase();
+        if let Some(state) = self.states.get_mut(&symbol) {
+            state.funding_rate = mark.funding_rate * 10_000.0;
         }
-        total_vol
     }
 
     fn notify(&self, msg: &str) {
@@ -254,31 +534,220 @@ impl StrategyService {
         }
     }
 
-    fn execute(&self, symbol: &str, side: &str, confidence: f32) {
-        if let Some(ref tx) = self.execution_tx {
-            let quantity = match symbol.to_uppercase().as_str() {
-                "BTCUSDT" => 0.0002,
-                "ETHUSDT" => 0.005,
-                "SOLUSDT" => 0.1,
-                "DOGEUSDT" => 50.0,
-                "BNBUSDT" => 0.05,
-                _ => 0.0, // Safety: Don't trade symbols we haven't calibrated
-            };
+    /// Calibrated order size for `symbol`, or its quote asset's default
+    /// notional at `price`, capped by its group's `max_notional`.
+    fn order_quantity(&self, symbol: &str, price: f64, risk: GroupRisk) -> f64 {
+        let calibrated = match symbol.to_uppercase().as_str() {
+            "BTCUSDT" => Some(0.0002),
+            "ETHUSDT" => Some(0.005),
+            "SOLUSDT" => Some(0.1),
+            "DOGEUSDT" => Some(50.0),
+            "BNBUSDT" => Some(0.05),
+            _ => None,
+        };
+        let quantity: f64 = match calibrated {
+            Some(quantity) => quantity,
+            None => {
+                let notional = self
+                    .quote_assets
+                    .get(&symbol.to_lowercase())
+                    .and_then(|quote| DEFAULT_QUOTE_NOTIONAL.iter().find(|(q, _)| q == quote))
+                    .map(|&(_, notional)| notional);
+                match notional {
+                    Some(notional) if price > 0.0 => notional / price,
+                    _ => 0.0, // Safety: Don't trade what we can't size
+                }
+            }
+        };
 
+        match risk.max_notional {
+            Some(max_notional) if price > 0.0 => quantity.min(max_notional / price),
+            _ => quantity,
+        }
+    }
+
+    fn execute(&self, symbol: &str, side: Side, confidence: f32, quantity: f64) {
+        if self.disabled_symbols.is_disabled(symbol) {
+            info!("{} is disabled, not sending {}", symbol, side);
+            return;
+        }
+        if let Some(ref tx) = self.execution_tx {
             if quantity > 0.0 {
                 let signal = TradeSignal {
                     symbol: symbol.to_uppercase(),
-                    side: side.to_string(),
+                    side,
                     quantity,
-                    reason: format!("AI_CONFIDENCE_{:.2}", confidence),
+                    confidence,
                 };
                 let _ = tx.send(signal);
             } else {
                 warn!(
-                    "Signal generated for {} but no quantity config found. Skipping execution.",
+                    "Signal generated for {} but it has no calibrated quantity and its quote asset no default notional. Skipping execution.",
                     symbol
                 );
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StrategyConfig;
+    use crate::inference::DEFAULT_TEMPERATURE;
+    use common::clock::FixedClock;
+    use common::events::event_channel;
+
+    fn trade(trade_time: f64) -> AggTradeInsert {
+        AggTradeInsert {
+            agg_trade_id: 1,
+            time: 100.0,
+            trade_time,
+            symbol: "BTCUSDT".to_string(),
+            price: 50_000.0,
+            quantity: 0.1,
+            is_buyer_maker: false,
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn test_skips_trades_older_than_max_event_age() {
+        let mut service =
+            StrategyService::new(&["btcusdt"], 10, "missing.onnx", DEFAULT_TEMPERATURE)
+                .with_max_event_age(Some(Duration::from_millis(500)))
+                .with_clock(Arc::new(FixedClock::from_millis(100_000)));
+
+        service.on_trade(&trade(99.0));
+        assert_eq!(service.stale_trades, 1);
+        assert!(service.states["btcusdt"].window.is_empty());
+
+        service.on_trade(&trade(99.8));
+        assert_eq!(service.stale_trades, 1);
+        assert_eq!(service.states["btcusdt"].window.len(), 1);
+    }
+
+    #[test]
+    fn test_cooldown_and_single_position_limit_signals() {
+        let mut service =
+            StrategyService::new(&["btcusdt"], 10, "missing.onnx", DEFAULT_TEMPERATURE)
+                .with_clock(Arc::new(FixedClock::from_millis(100_000)));
+        let predict = |class| InferenceOutcome {
+            job: InferenceJob {
+                symbol: "btcusdt".to_string(),
+                price: 50_000.0,
+                features: vec![50.0, 0.0, 0.0, 1.0],
+                window: None,
+            },
+            result: Ok(InferenceResult {
+                class,
+                confidence: 0.99,
+                outputs: Vec::new(),
+            }),
+        };
+
+        service.apply_prediction(predict(1));
+        assert!(service.states["btcusdt"].has_position);
+        // Within the cooldown: the SELL is ignored.
+        service.apply_prediction(predict(2));
+        assert!(service.states["btcusdt"].has_position);
+
+        // Past it, a second BUY is still refused while the position is open.
+        service.states.get_mut("btcusdt").unwrap().last_signal_ms = Some(0);
+        service.apply_prediction(predict(1));
+        assert_eq!(service.states["btcusdt"].position_qty, 0.0002);
+        assert_eq!(service.states["btcusdt"].last_signal_ms, Some(0));
+
+        service.config.store(StrategyConfig {
+            single_position: false,
+            ..StrategyConfig::default()
+        });
+        service.apply_prediction(predict(1));
+        assert_eq!(service.states["btcusdt"].position_qty, 0.0004);
+    }
+
+    #[test]
+    fn test_appends_futures_features_when_configured() {
+        let mut service =
+            StrategyService::new(&["btcusdt"], 10, "missing.onnx", DEFAULT_TEMPERATURE)
+                .with_max_event_age(None)
+                .with_feature_count(FUTURES_FEATURE_COUNT)
+                .unwrap();
+        let oi = |oi_value| {
+            MarketEvent::OpenInterest(OpenInterestInsert {
+                time: 1.0,
+                symbol: "BTCUSDT".to_string(),
+                oi_value,
+                seq: None,
+            })
+        };
+        service.on_event(&oi(200.0));
+        service.on_event(&oi(210.0));
+        service.on_event(&MarketEvent::MarkPrice(MarkPriceInsert {
+            time: 1.0,
+            symbol: "BTCUSDT".to_string(),
+            mark_price: 50_000.0,
+            index_price: 50_000.0,
+            funding_rate: 0.0001,
+            next_funding_time: None,
+            seq: None,
+        }));
+        service.on_event(&MarketEvent::AggTrade(trade(99.0)));
+
+        let features = &service.states["btcusdt"].window[0];
+        assert_eq!(features.len(), FUTURES_FEATURE_COUNT);
+        assert!((features[4] - 5.0).abs() < 1e-4, "OI up 5%");
+        assert!((features[5] - 1.0).abs() < 1e-4, "1bp funding");
+
+        service.feature_count = FEATURE_COUNT;
+        service.on_event(&MarketEvent::AggTrade(trade(99.0)));
+        assert_eq!(service.states["btcusdt"].window[1].len(), FEATURE_COUNT);
+
+        assert!(matches!(
+            service.with_feature_count(5),
+            Err(FeatureCountError(5))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_alerts_when_started_without_a_model() {
+        let (notify_tx, mut notify_rx) = broadcast::channel(4);
+        let service = StrategyService::new(&["btcusdt"], 10, "missing.onnx", DEFAULT_TEMPERATURE)
+            .with_notifier(notify_tx);
+        let feed = || event_channel(1).1;
+
+        // Every sender is already gone, so it stops as soon as it starts.
+        service
+            .start(StrategyFeeds {
+                trades: feed(),
+                books: feed(),
+                open_interest: feed(),
+                mark_prices: feed(),
+            })
+            .await;
+        assert!(notify_rx.try_recv().unwrap().contains("SIMULATION"));
+    }
+
+    #[test]
+    fn test_sizes_uncalibrated_pairs_by_quote_asset() {
+        let assets = HashMap::from([(
+            "ETHBTC".to_string(),
+            SymbolAssets {
+                base: "ETH".to_string(),
+                quote: "BTC".to_string(),
+            },
+        )]);
+        let service = StrategyService::new(
+            &["ethbtc", "btcusdt", "xyzeur"],
+            10,
+            "missing.onnx",
+            DEFAULT_TEMPERATURE,
+        )
+        .with_symbol_assets(&assets);
+        let risk = GroupRisk::default();
+
+        assert_eq!(service.order_quantity("btcusdt", 50_000.0, risk), 0.0002);
+        assert!((service.order_quantity("ethbtc", 0.04, risk) - 0.005).abs() < 1e-12);
+        assert_eq!(service.order_quantity("xyzeur", 1.0, risk), 0.0);
+    }
+}
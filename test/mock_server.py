@@ -61,6 +61,16 @@ class BinanceMockHandler(http.server.SimpleHTTPRequestHandler):
             self.wfile.write(json.dumps(response).encode('utf-8'))
             
     def do_GET(self):
+        # Handle /api/v3/ping and /api/v3/time (startup preflight)
+        if self.path.startswith("/api/v3/ping") or self.path.startswith("/api/v3/time"):
+            response = {} if self.path.startswith("/api/v3/ping") else {"serverTime": int(time.time() * 1000)}
+
+            self.send_response(200)
+            self.send_header('Content-type', 'application/json')
+            self.end_headers()
+            self.wfile.write(json.dumps(response).encode('utf-8'))
+            return
+
         # Handle /api/v3/account (Balance check)
         if self.path.startswith("/api/v3/account"):
             print("\n[MOCK BINANCE] RECEIVED ACCOUNT INFO REQUEST")
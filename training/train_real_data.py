@@ -70,9 +70,9 @@ def load_and_decompress_db(zstd_path, output_db_path="temp_db.sqlite"):
 def fetch_data_from_db(db_path):
     conn = sqlite3.connect(db_path)
     print("Fetching agg_trades data...")
-    aggtrade_df = pd.read_sql_query("SELECT time, symbol, price, quantity, is_buyer_maker FROM agg_trades ORDER BY time;", conn)
+    aggtrade_df = pd.read_sql_query("SELECT time, symbol, price, quantity, is_buyer_maker FROM agg_trades_v ORDER BY time;", conn)
     print("Fetching order_books data...")
-    orderbook_df = pd.read_sql_query("SELECT time, symbol, bids, asks FROM order_books ORDER BY time;", conn)
+    orderbook_df = pd.read_sql_query("SELECT time, symbol, bids, asks FROM order_books_v ORDER BY time;", conn)
     conn.close()
 
     if 'time' in aggtrade_df.columns: